@@ -0,0 +1,224 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+
+use crate::{
+    common::{
+        DeltaItem, Duration, Event, SaveData, SimpleTime,
+        error::{Source, TaskitResult, With},
+    },
+    report::{format_duration, print_table},
+    util::DateRange,
+};
+
+/// Something that happened outside of taskit, suggesting that time was being spent
+#[derive(Clone, Debug)]
+pub struct Activity {
+    pub start: Option<SimpleTime>,
+    pub end: Option<SimpleTime>,
+    pub description: String,
+    pub kind: ActivityKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActivityKind {
+    Commit,
+    Calendar,
+}
+
+pub type ActivityLog = BTreeMap<NaiveDate, Vec<Activity>>;
+
+/// A VEVENT whose properties are still being read
+#[derive(Default)]
+struct PendingCalendarEvent {
+    start: Option<(NaiveDate, Option<NaiveTime>)>,
+    end: Option<NaiveTime>,
+    summary: String,
+}
+
+impl std::fmt::Display for Activity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => write!(f, "{start}-{end} ")?,
+            (Some(start), None) => write!(f, "{start} ")?,
+            _ => {}
+        }
+        let kind = match self.kind {
+            ActivityKind::Commit => "commit",
+            ActivityKind::Calendar => "calendar",
+        };
+        write!(f, "{kind}: {}", self.description)
+    }
+}
+
+/// Reads the commits in a git repository (on any branch), keyed by the local date they were
+/// authored on
+fn git_activity(repo: &Path, log: &mut ActivityLog) -> TaskitResult<()> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["log", "--all", "--format=%aI%x09%s"])
+        .output()
+        .with(Source::GatheringActivity)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(stderr.trim().to_owned()).with(Source::GatheringActivity).into());
+    }
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some((date, subject)) = line.split_once('\t') else {
+            continue;
+        };
+        let Ok(datetime) = DateTime::parse_from_rfc3339(date) else {
+            continue;
+        };
+        let datetime = datetime.with_timezone(&Local);
+        log.entry(datetime.date_naive()).or_default().push(Activity {
+            start: Some(datetime.time().into()),
+            end: None,
+            description: subject.to_owned(),
+            kind: ActivityKind::Commit,
+        });
+    }
+    Ok(())
+}
+
+/// Parses an iCalendar date or date-time value. Times in UTC are converted to local time, and
+/// times with any other time zone are taken as-is.
+fn parse_ics_datetime(value: &str) -> Option<(NaiveDate, Option<NaiveTime>)> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        let datetime = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        let datetime = DateTime::<Utc>::from_naive_utc_and_offset(datetime, Utc).with_timezone(&Local);
+        Some((datetime.date_naive(), Some(datetime.time())))
+    } else if let Ok(datetime) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        Some((datetime.date(), Some(datetime.time())))
+    } else {
+        Some((NaiveDate::parse_from_str(value, "%Y%m%d").ok()?, None))
+    }
+}
+
+/// Reads the events in an iCalendar (.ics) file, keyed by the date they start on. All-day events
+/// are included without times.
+fn calendar_activity(path: &Path, log: &mut ActivityLog) -> TaskitResult<()> {
+    let text = fs::read_to_string(path).with(Source::GatheringActivity)?;
+    // long lines are folded by starting the continuation with a space or tab
+    let unfolded = text.replace("\r\n", "\n").replace("\n ", "").replace("\n\t", "");
+    let mut current: Option<PendingCalendarEvent> = None;
+    for line in unfolded.lines() {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        // drop parameters like `;TZID=Europe/London`
+        let name = name.split(';').next().unwrap_or(name);
+        match (name, current.as_mut()) {
+            ("BEGIN", None) if value == "VEVENT" => current = Some(Default::default()),
+            ("DTSTART", Some(event)) => event.start = parse_ics_datetime(value),
+            ("DTEND", Some(event)) => event.end = parse_ics_datetime(value).and_then(|(_, t)| t),
+            ("SUMMARY", Some(event)) => {
+                event.summary = value.replace("\\,", ",").replace("\\;", ";").replace("\\n", " ")
+            }
+            ("END", Some(_)) if value == "VEVENT" => {
+                if let Some(PendingCalendarEvent { start: Some((date, start)), end, summary }) = current.take() {
+                    log.entry(date).or_default().push(Activity {
+                        start: start.map(Into::into),
+                        end: end.filter(|_| start.is_some()).map(Into::into),
+                        description: summary,
+                        kind: ActivityKind::Calendar,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Reads activity from all of the given repositories and calendars, sorted by time within each day
+pub fn gather_activity(git: &[PathBuf], calendar: &[PathBuf]) -> TaskitResult<ActivityLog> {
+    let mut log = ActivityLog::new();
+    for repo in git {
+        git_activity(repo, &mut log)?;
+    }
+    for file in calendar {
+        calendar_activity(file, &mut log)?;
+    }
+    for activity in log.values_mut() {
+        activity.sort_by_key(|a| a.start);
+    }
+    Ok(log)
+}
+
+pub fn audit_main(
+    save_data: SaveData,
+    git: Vec<PathBuf>,
+    calendar: Vec<PathBuf>,
+    range: Option<DateRange>,
+) -> TaskitResult<Vec<DeltaItem>> {
+    let log = gather_activity(&git, &calendar)?;
+    let range = range.unwrap_or_else(|| {
+        let today = Local::now().date_naive();
+        DateRange {
+            start: today - Days::new(29),
+            end: today,
+        }
+    });
+
+    let mut rows = vec![vec![
+        "Date".to_owned(),
+        "Commits".to_owned(),
+        "Calendar".to_owned(),
+        "Tracked".to_owned(),
+        "Untracked activity".to_owned(),
+    ]];
+    let mut empty_days = vec![];
+    for (date, activity) in log.range(range.start..=range.end) {
+        let events: Vec<&Event> = save_data.events.iter().filter(|ev| ev.date == *date).collect();
+        let tracked: Duration = events.iter().map(|ev| ev.duration()).sum();
+        let count = |kind| activity.iter().filter(|a| a.kind == kind).count().to_string();
+        let uncovered = activity
+            .iter()
+            .filter(|a| a.start.is_some_and(|t| !events.iter().any(|ev| ev.covers(t))))
+            .count();
+        let flag = if let Some(reason) = save_data.skipped_days.get(date)
+            && events.is_empty()
+        {
+            if reason.is_empty() { "skipped".to_owned() } else { format!("skipped ({reason})") }
+        } else if events.is_empty() {
+            empty_days.push((date, activity));
+            "nothing tracked".to_owned()
+        } else if uncovered > 0 {
+            format!("{uncovered} outside tracked time")
+        } else {
+            String::new()
+        };
+        rows.push(vec![
+            date.format("%a %Y-%m-%d").to_string(),
+            count(ActivityKind::Commit),
+            count(ActivityKind::Calendar),
+            format_duration(&tracked),
+            flag,
+        ]);
+    }
+    if rows.len() == 1 {
+        println!("No outside activity found between {} and {}.", range.start, range.end);
+        return Ok(vec![]);
+    }
+    print_table(&rows);
+    if !empty_days.is_empty() {
+        println!(
+            "\n{} day(s) with activity but no tracked time (`taskit reconstruct <date>` can help fill them in):",
+            empty_days.len()
+        );
+        for (date, activity) in empty_days {
+            println!("\n{}", date.format("%A %Y-%m-%d"));
+            for item in activity {
+                println!("  {item}");
+            }
+        }
+    }
+    Ok(vec![])
+}
@@ -0,0 +1,161 @@
+//! Copies of the save file taken each time before it's overwritten, kept in a `backups` directory
+//! next to it, and rolling back to one of them
+
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Local, NaiveDateTime};
+use inquire::{Confirm, Select};
+
+use crate::{
+    common::{
+        DeltaItem, SaveData,
+        config::CONFIG,
+        error::{Kind, Source, TaskitResult, With},
+    },
+    diff::{load_save, print_diff},
+    journal,
+};
+
+/// How many backups are kept if the config doesn't say
+const DEFAULT_KEEP: usize = 10;
+
+/// Goes between the save file's name and its extension in each backup's name. Colons would be
+/// nicer, but aren't allowed in file names on Windows.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H-%M-%S%.3f";
+
+/// The backups of every save file in a directory go in the same place, told apart by name
+pub fn backups_dir(save_path: &Path) -> PathBuf {
+    save_path.parent().unwrap_or(Path::new("")).join("backups")
+}
+
+fn save_stem(save_path: &Path) -> String {
+    save_path.file_stem().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+/// Backups keep the save file's extension, which says how to read them
+fn save_extension(save_path: &Path) -> String {
+    save_path.extension().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+struct Backup {
+    path: PathBuf,
+    time: NaiveDateTime,
+}
+
+/// The backups of `save_path`, oldest first. Files that don't look like one are left alone.
+fn list(save_path: &Path) -> io::Result<Vec<Backup>> {
+    let prefix = format!("{}.", save_stem(save_path));
+    let suffix = format!(".{}", save_extension(save_path));
+    let entries = match fs::read_dir(backups_dir(save_path)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let mut backups = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if let Some(time) = name
+            .strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix(&suffix))
+            .and_then(|time| NaiveDateTime::parse_from_str(time, TIMESTAMP_FORMAT).ok())
+        {
+            backups.push(Backup { path, time });
+        }
+    }
+    backups.sort_by_key(|backup| backup.time);
+    Ok(backups)
+}
+
+/// Copies the save file as it is now into the backups, then drops the oldest ones past the number
+/// to keep. Does nothing if there's no save file yet or backups are turned off.
+pub fn back_up(save_path: &Path) -> io::Result<()> {
+    match CONFIG.backups.keep.unwrap_or(DEFAULT_KEEP) {
+        0 => Ok(()),
+        keep => back_up_keeping(save_path, keep),
+    }
+}
+
+fn back_up_keeping(save_path: &Path, keep: usize) -> io::Result<()> {
+    if !save_path.exists() {
+        return Ok(());
+    }
+    let dir = backups_dir(save_path);
+    fs::create_dir_all(&dir)?;
+    let name = format!(
+        "{}.{}.{}",
+        save_stem(save_path),
+        Local::now().naive_local().format(TIMESTAMP_FORMAT),
+        save_extension(save_path)
+    );
+    fs::copy(save_path, dir.join(name))?;
+    let backups = list(save_path)?;
+    for backup in &backups[..backups.len().saturating_sub(keep)] {
+        fs::remove_file(&backup.path)?;
+    }
+    Ok(())
+}
+
+/// A backup as it's listed to pick from
+struct BackupOption {
+    backup: Backup,
+    /// None if it couldn't be read
+    save_data: Option<SaveData>,
+}
+
+impl Display for BackupOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.backup.time.format("%Y-%m-%d %H:%M:%S"))?;
+        match &self.save_data {
+            Some(save_data) => write!(f, " ({} events)", save_data.events.len()),
+            None => write!(f, " (can't be read)"),
+        }
+    }
+}
+
+/// Replaces the save file with a backup picked from a list, after showing what would change. The
+/// save file as it was is backed up first, so restoring can itself be rolled back.
+pub fn restore_main(save_data: SaveData, save_path: &Path) -> TaskitResult<Vec<DeltaItem>> {
+    let options: Vec<BackupOption> = list(save_path)
+        .with(Source::Restoring)?
+        .into_iter()
+        .rev()
+        .map(|backup| {
+            let save_data = load_save(&backup.path, Source::Restoring).ok();
+            BackupOption { backup, save_data }
+        })
+        .collect();
+    if options.is_empty() {
+        return Err(Kind::NoBackups.with(Source::Restoring));
+    }
+    let picked = Select::new("Restore which backup?", options)
+        .prompt()
+        .with(Source::Restoring)?;
+    // checked again for the error, now that it's the one that matters
+    let backup = load_save(&picked.backup.path, Source::Restoring)?;
+    print_diff(&save_data, &backup);
+    if !Confirm::new("Replace the save file with this backup?")
+        .with_default(false)
+        .with_help_message("the save file as it is now is backed up first")
+        .prompt()
+        .with(Source::Restoring)?
+    {
+        return Err(Kind::Cancelled.with(Source::Restoring));
+    }
+    // read first, since backing up the save file could drop the oldest backups, this one included
+    let restored = fs::read(&picked.backup.path).with(Source::Restoring)?;
+    // even with backups turned off, since the confirmation says so
+    back_up_keeping(save_path, CONFIG.backups.keep.unwrap_or(DEFAULT_KEEP).max(1)).with(Source::Restoring)?;
+    // through a temporary file like any other save, so an interrupted restore can't leave half of one
+    let temp_path = save_path.with_extension("tmp");
+    fs::write(&temp_path, restored).with(Source::Restoring)?;
+    fs::rename(&temp_path, save_path).with(Source::Restoring)?;
+    // the journal's changes undo changes made to the save file that was just replaced
+    journal::retire(&journal::journal_path(save_path)).with(Source::Restoring)?;
+    println!("Restored the backup from {}.", picked.backup.time.format("%Y-%m-%d %H:%M:%S"));
+    Ok(vec![])
+}
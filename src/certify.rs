@@ -0,0 +1,195 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use chrono::Local;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    common::{
+        DeltaItem, Event, SaveData,
+        config::CONFIG,
+        error::{Kind, Source, TaskitResult, With},
+    },
+    export::event_json,
+    util::DateRange,
+};
+
+/// Identifies the header line of a certified export
+const FORMAT: &str = "taskit-certified";
+/// What the header line links back to, as there's nothing before it
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// The SHA-256 of a line, in the compact form with sorted keys that it's written in, without its
+/// own `hash`
+fn hash(entry: &Map<String, Value>) -> String {
+    format!("{:x}", Sha256::digest(Value::Object(entry.clone()).to_string()))
+}
+
+/// Links a line to the one before it, returning its hash
+fn link(lines: &mut String, mut entry: Map<String, Value>, prev: &str) -> String {
+    entry.insert("prev".to_owned(), prev.into());
+    let hash = hash(&entry);
+    entry.insert("hash".to_owned(), hash.clone().into());
+    lines.push_str(&Value::Object(entry).to_string());
+    lines.push('\n');
+    hash
+}
+
+fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".minisig");
+    name.into()
+}
+
+/// Signs an export with minisign, which asks for the key's password itself. The chain's head
+/// goes in the signed trusted comment, so it shows up when the signature is checked.
+fn sign(path: &Path, head: &str) -> TaskitResult<PathBuf> {
+    let Some(key) = &CONFIG.certify.secret_key else {
+        return Err(Kind::SigningNotConfigured.with(Source::Certifying));
+    };
+    let signature = signature_path(path);
+    let status = Command::new("minisign")
+        .arg("-S")
+        .arg("-s")
+        .arg(key)
+        .arg("-m")
+        .arg(path)
+        .arg("-x")
+        .arg(&signature)
+        .arg("-t")
+        .arg(format!("taskit certified export, chain head {head}"))
+        .status()
+        .with(Source::Certifying)?;
+    if !status.success() {
+        return Err(io::Error::other(format!("minisign failed ({status})")).with(Source::Certifying).into());
+    }
+    Ok(signature)
+}
+
+/// Writes events as JSON Lines, after a header line, with each line holding the hash of the one
+/// before it and its own. Changing, removing, or reordering any line breaks the chain from there
+/// on, and the signature over the whole file stops anyone from simply rebuilding it.
+pub fn export_certified_main(
+    save_data: SaveData,
+    range: Option<DateRange>,
+    out: PathBuf,
+    unsigned: bool,
+    include_private: bool,
+) -> TaskitResult<Vec<DeltaItem>> {
+    if !unsigned && CONFIG.certify.secret_key.is_none() {
+        return Err(Kind::SigningNotConfigured.with(Source::Certifying));
+    }
+    let mut events: Vec<&Event> = save_data
+        .events
+        .iter()
+        .filter(|ev| range.is_none_or(|range| range.contains(ev.date)))
+        .filter(|ev| include_private || !save_data.private_categories.contains(&ev.category))
+        .collect();
+    events.sort_by_key(|ev| (ev.date, ev.start_time, ev.id));
+
+    let mut lines = String::new();
+    let header = Map::from_iter([
+        ("format".to_owned(), FORMAT.into()),
+        ("version".to_owned(), 1.into()),
+        ("generated".to_owned(), Local::now().naive_local().format("%Y-%m-%dT%H:%M:%S").to_string().into()),
+        ("range".to_owned(), range.map_or(Value::Null, |range| range.to_string().into())),
+        ("events".to_owned(), events.len().into()),
+    ]);
+    let mut head = link(&mut lines, header, GENESIS);
+    for ev in &events {
+        let mut entry = event_json(ev);
+        entry.insert("id".to_owned(), ev.id.to_string().into());
+        head = link(&mut lines, entry, &head);
+    }
+    fs::write(&out, lines).with(Source::Certifying)?;
+    println!("Wrote {} events to {}, with chain head {head}", events.len(), out.display());
+    if !unsigned {
+        let signature = sign(&out, &head)?;
+        println!("Signed it to {}", signature.display());
+    }
+    Ok(vec![])
+}
+
+/// Checks the hash chain of a certified export, returning how many events it holds and the hash
+/// of its last line
+fn verify_chain(lines: &str) -> Result<(usize, String), String> {
+    let mut prev = GENESIS.to_owned();
+    let mut count = 0;
+    for (n, line) in lines.lines().enumerate() {
+        let line_number = n + 1;
+        let Ok(Value::Object(mut entry)) = serde_json::from_str(line) else {
+            return Err(format!("line {line_number} isn't a JSON object"));
+        };
+        let Some(Value::String(claimed)) = entry.remove("hash") else {
+            return Err(format!("line {line_number} has no hash"));
+        };
+        if entry.get("prev").and_then(Value::as_str) != Some(&prev) {
+            return Err(format!("line {line_number} doesn't follow on from the line before it"));
+        }
+        if hash(&entry) != claimed {
+            return Err(format!("line {line_number} was changed after it was exported"));
+        }
+        if n == 0 {
+            if entry.get("format").and_then(Value::as_str) != Some(FORMAT) {
+                return Err("it doesn't start with a certified export header".to_owned());
+            }
+            count = entry.get("events").and_then(Value::as_u64).unwrap_or_default() as usize;
+        }
+        prev = claimed;
+    }
+    match lines.lines().count() {
+        0 => Err("it's empty".to_owned()),
+        n if n - 1 != count => Err(format!("the header lists {count} events, but {} follow it", n - 1)),
+        _ => Ok((count, prev)),
+    }
+}
+
+/// Checks a certified export's hash chain, then its signature against a public key. Without
+/// either, the export only passes with `unsigned`.
+pub fn verify_export_main(
+    _save_data: SaveData,
+    file: &Path,
+    public_key: Option<PathBuf>,
+    unsigned: bool,
+) -> TaskitResult<Vec<DeltaItem>> {
+    let lines = fs::read_to_string(file).with(Source::Certifying)?;
+    let (count, head) = verify_chain(&lines).map_err(|e| Kind::TamperedExport(e).with(Source::Certifying))?;
+    println!("Hash chain intact: {count} events, chain head {head}");
+
+    let signature = signature_path(file);
+    let (unchecked, fix) = if !signature.exists() {
+        (format!("there's no signature at {}", signature.display()), "pass")
+    } else if let Some(key) = public_key.or_else(|| CONFIG.certify.public_key.clone()) {
+        return check_signature(file, &signature, &key);
+    } else {
+        ("there's no public key to check its signature against".to_owned(), "pass `--public-key`, or")
+    };
+    if !unsigned {
+        // the hash chain alone can be rebuilt by whoever changed the events
+        return Err(Kind::TamperedExport(format!("{unchecked}; {fix} `--unsigned` to only check the hash chain"))
+            .with(Source::Certifying));
+    }
+    println!("Only the hash chain was checked, as {unchecked}.");
+    Ok(vec![])
+}
+
+fn check_signature(file: &Path, signature: &Path, key: &Path) -> TaskitResult<Vec<DeltaItem>> {
+    let status = Command::new("minisign")
+        .arg("-V")
+        .arg("-p")
+        .arg(key)
+        .arg("-m")
+        .arg(file)
+        .arg("-x")
+        .arg(signature)
+        .status()
+        .with(Source::Certifying)?;
+    if !status.success() {
+        return Err(Kind::TamperedExport("the signature doesn't match".to_owned()).with(Source::Certifying));
+    }
+    Ok(vec![])
+}
@@ -0,0 +1,205 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use chrono::{Days, NaiveDate};
+use plotters::{
+    coord::Shift,
+    prelude::*,
+    style::text_anchor::{HPos, Pos, VPos},
+};
+
+use crate::{
+    common::{
+        DeltaItem, Duration, SaveData,
+        error::{Kind, Source, TaskitResult, With},
+    },
+    report::{category_totals, daily_totals, format_duration},
+};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum ChartType {
+    /// Share of time per category
+    Pie,
+    /// Total time per category
+    Bar,
+    /// Total time per day
+    Line,
+}
+
+/// The data behind a chart, gathered before picking the backend to draw it with
+enum Chart {
+    Pie(Vec<(String, Duration)>),
+    Bar(Vec<(String, Duration)>),
+    Line(BTreeMap<NaiveDate, Duration>),
+}
+
+const SIZE: (u32, u32) = (800, 500);
+const FONT: &str = "sans-serif";
+const PALETTE: &[RGBColor] = &[
+    RGBColor(0x4e, 0x79, 0xa7), RGBColor(0xf2, 0x8e, 0x2b), RGBColor(0xe1, 0x57, 0x59),
+    RGBColor(0x76, 0xb7, 0xb2), RGBColor(0x59, 0xa1, 0x4f), RGBColor(0xed, 0xc9, 0x48),
+    RGBColor(0xb0, 0x7a, 0xa1), RGBColor(0xff, 0x9d, 0xa7), RGBColor(0x9c, 0x75, 0x5f),
+    RGBColor(0xba, 0xb0, 0xac),
+];
+
+type DrawResult<DB> = Result<(), DrawingAreaErrorKind<<DB as DrawingBackend>::ErrorType>>;
+
+fn hours(duration: &Duration) -> f64 {
+    duration.num_seconds() as f64 / 3600.0
+}
+
+/// Labels an axis in hours with the time it stands for, like "1h30m"
+fn hours_label(hours: &f64) -> String {
+    format_duration(&Duration::minutes((hours * 60.0).round() as i64))
+}
+
+fn color(i: usize) -> RGBColor {
+    PALETTE[i % PALETTE.len()]
+}
+
+fn pie<DB: DrawingBackend>(area: &DrawingArea<DB, Shift>, totals: &[(String, Duration)]) -> DrawResult<DB> {
+    // a slice with no time would only add an overlapping label
+    let totals: Vec<&(String, Duration)> = totals.iter().filter(|(_, d)| *d > Duration::ZERO).collect();
+    if totals.is_empty() {
+        return Ok(());
+    }
+    let (width, height) = area.dim_in_pixel();
+    let center = (width as i32 / 2, height as i32 / 2);
+    let radius = f64::from(height) * 0.35;
+    let sizes: Vec<f64> = totals.iter().map(|(_, d)| hours(d)).collect();
+    let colors: Vec<RGBColor> = (0..totals.len()).map(color).collect();
+    let labels: Vec<&str> = totals.iter().map(|(name, _)| name.as_str()).collect();
+    let mut pie = Pie::new(&center, &radius, &sizes, &colors, &labels);
+    pie.start_angle(-90.0);
+    pie.label_style((FONT, 16).into_font());
+    pie.percentages((FONT, 14).into_font().color(&WHITE));
+    area.draw(&pie)
+}
+
+fn bar<DB: DrawingBackend>(area: &DrawingArea<DB, Shift>, totals: &[(String, Duration)]) -> DrawResult<DB> {
+    // the y axis counts up from the bottom, and the biggest category should be on top
+    let row = |i: usize| (totals.len() - 1 - i) as f64;
+    let max = totals.iter().map(|(_, d)| hours(d)).fold(1.0, f64::max);
+    let mut chart = ChartBuilder::on(area)
+        .margin(20)
+        .x_label_area_size(30)
+        .y_label_area_size(160)
+        .build_cartesian_2d(0.0..max * 1.15, -0.5..totals.len() as f64 - 0.5)?;
+    // the categories are drawn next to their bars below, rather than as the axis' own labels,
+    // which would land on round numbers instead of rows
+    chart
+        .configure_mesh()
+        .disable_y_mesh()
+        .light_line_style(WHITE)
+        .y_labels(0)
+        .x_label_formatter(&hours_label)
+        .label_style((FONT, 14))
+        .draw()?;
+    chart.draw_series(totals.iter().enumerate().map(|(i, (_, time))| {
+        Rectangle::new([(0.0, row(i) - 0.4), (hours(time), row(i) + 0.4)], color(i).filled())
+    }))?;
+    chart.draw_series(totals.iter().enumerate().map(|(i, (_, time))| {
+        EmptyElement::at((hours(time), row(i)))
+            + Text::new(format_duration(time), (6, 0), TextStyle::from((FONT, 14).into_font()).pos(Pos::new(HPos::Left, VPos::Center)))
+    }))?;
+    let base = area.get_base_pixel();
+    for (i, (name, _)) in totals.iter().enumerate() {
+        let (x, y) = chart.plotting_area().map_coordinate(&(0.0, row(i)));
+        let style = TextStyle::from((FONT, 14).into_font()).pos(Pos::new(HPos::Right, VPos::Center));
+        area.draw(&Text::new(name.as_str(), (x - base.0 - 8, y - base.1), style))?;
+    }
+    Ok(())
+}
+
+fn line<DB: DrawingBackend>(area: &DrawingArea<DB, Shift>, totals: &BTreeMap<NaiveDate, Duration>) -> DrawResult<DB> {
+    let (Some(&first), Some(&last)) = (totals.keys().next(), totals.keys().next_back()) else {
+        return Ok(());
+    };
+    let points: Vec<(NaiveDate, f64)> = first
+        .iter_days()
+        .take_while(|d| *d <= last)
+        .map(|d| (d, totals.get(&d).map_or(0.0, hours)))
+        .collect();
+    let max_hours = points.iter().map(|(_, h)| *h).fold(1.0, f64::max).ceil();
+    let mut chart = ChartBuilder::on(area)
+        .margin(20)
+        .margin_right(50)
+        .x_label_area_size(30)
+        .y_label_area_size(60)
+        .build_cartesian_2d(first..last, 0.0..max_hours)?;
+    chart
+        .configure_mesh()
+        .light_line_style(WHITE)
+        .x_labels(8)
+        .x_label_formatter(&|d| d.to_string())
+        .y_label_formatter(&hours_label)
+        .label_style((FONT, 14))
+        .draw()?;
+    chart.draw_series(LineSeries::new(points, color(0).stroke_width(2)))?;
+    Ok(())
+}
+
+/// Draws the chart with any of plotters' backends, and writes it out
+fn draw<DB: DrawingBackend>(backend: DB, title: &str, chart: &Chart) -> TaskitResult<()>
+where
+    DB::ErrorType: 'static,
+{
+    let drawn = (|| {
+        let root = backend.into_drawing_area();
+        root.fill(&WHITE)?;
+        let area = root.titled(title, (FONT, 20))?;
+        match chart {
+            Chart::Pie(totals) => pie(&area, totals)?,
+            Chart::Bar(totals) => bar(&area, totals)?,
+            Chart::Line(totals) => line(&area, totals)?,
+        }
+        root.present()
+    })();
+    drawn.map_err(|e| Kind::Other(Box::new(e)).with(Source::Exporting))
+}
+
+pub fn chart_main(
+    save_data: SaveData,
+    chart_type: ChartType,
+    out: PathBuf,
+    include_private: bool,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> TaskitResult<Vec<DeltaItem>> {
+    let extension = out.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase);
+    if !matches!(extension.as_deref(), Some("svg" | "png")) {
+        return Err(Kind::UnsupportedFormat(out.display().to_string()).with(Source::Exporting));
+    }
+    let events = save_data
+        .events
+        .iter()
+        .filter(|ev| include_private || !save_data.private_categories.contains(&ev.category))
+        .filter(|ev| from.is_none_or(|from| ev.date >= from) && to.is_none_or(|to| ev.date <= to));
+    let range = match (from, to) {
+        (Some(from), Some(to)) => format!(", {from} to {to}"),
+        (Some(from), None) => format!(", since {from}"),
+        (None, Some(to)) => format!(", until {to}"),
+        (None, None) => String::new(),
+    };
+    let (title, chart) = match chart_type {
+        ChartType::Pie => (format!("Time per category{range}"), Chart::Pie(category_totals(events))),
+        ChartType::Bar => (format!("Time per category{range}"), Chart::Bar(category_totals(events))),
+        ChartType::Line => {
+            let mut totals = daily_totals(events);
+            // make sure the line spans the whole requested range, even if the ends are empty
+            for date in from.into_iter().chain(to) {
+                totals.entry(date).or_insert(Duration::ZERO);
+            }
+            if totals.len() == 1 {
+                let only = *totals.keys().next().expect("has one element");
+                totals.insert(only + Days::new(1), Duration::ZERO);
+            }
+            (format!("Time per day{range}"), Chart::Line(totals))
+        }
+    };
+    match extension.as_deref() {
+        Some("png") => draw(BitMapBackend::new(&out, SIZE), &title, &chart)?,
+        _ => draw(SVGBackend::new(&out, SIZE), &title, &chart)?,
+    }
+    println!("Wrote chart to {}", out.display());
+    Ok(vec![])
+}
@@ -1,35 +1,117 @@
 pub mod invariants;
 pub mod error;
+pub mod index;
+pub mod duration;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
 
-use chrono::{Local, NaiveDate, TimeDelta, Timelike};
+use chrono::{Datelike, Days, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Weekday};
 use inquire::{
     Autocomplete,
     validator::{ErrorMessage, StringValidator, Validation},
 };
+#[cfg(feature = "tui")]
+use ratatui::style::Color;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::{cell::LazyCell, collections::{HashMap, HashSet}, fmt::Display, ops::Sub, str::FromStr};
+use std::{cell::LazyCell, collections::{BTreeMap, BTreeSet, HashMap, HashSet}, fmt::Display, fs, io, ops::Sub, path::{Path, PathBuf}, str::FromStr};
 
-use error::TaskitResult;
+use error::{Kind, Source, TaskitResult, With};
 
 use crate::{common::{config::CONFIG, invariants::{Category, Opaque, Tag}}, util::SetVec};
 
-pub use invariants::{SaveData, Event};
+pub use invariants::{SaveData, Event, EventId};
+pub use duration::Duration;
 
 
 pub mod config {
-    use std::sync::{LazyLock, OnceLock};
+    #[cfg(feature = "push")]
+    use std::collections::BTreeMap;
+    use std::{
+        collections::{HashMap, HashSet},
+        env,
+        path::PathBuf,
+        ops::Deref,
+        sync::RwLock,
+    };
 
+    use chrono::format::{Item, StrftimeItems};
     use serde::Deserialize;
 
-    pub static CONFIG_WRITE: OnceLock<Config> = OnceLock::new();
+    use crate::common::{Event, FieldValue, invariants::Tag};
+
+    static CONFIG_WRITE: RwLock<Option<&'static Config>> = RwLock::new(None);
     // stupid fucking hack so i don't have to unwrap every time i query CONFIG
-    pub static CONFIG: LazyLock<&Config> = LazyLock::new(|| CONFIG_WRITE.get().unwrap());
+    pub static CONFIG: ConfigRef = ConfigRef;
+
+    /// The config in use, set by `set_config`
+    pub struct ConfigRef;
+
+    impl Deref for ConfigRef {
+        type Target = Config;
+
+        fn deref(&self) -> &Config {
+            CONFIG_WRITE.read().unwrap().expect("the config is set before it's used")
+        }
+    }
+
+    /// Makes `config` the one in use. It's set once at startup, and again when the dashboard
+    /// switches to a profile with a config of its own; each one set is leaked, so that references
+    /// to the one before stay valid.
+    pub fn set_config(config: Config) {
+        *CONFIG_WRITE.write().unwrap() = Some(Box::leak(Box::new(config)));
+    }
 
     #[derive(Deserialize, Default, Debug)]
     pub struct Config {
-        #[serde(rename = "preferences")]
-        pub prefs: Preferences
+        #[serde(rename = "preferences", default)]
+        pub prefs: Preferences,
+        #[serde(default)]
+        pub aggregation: Aggregation,
+        #[serde(default)]
+        pub record: Record,
+        #[serde(default)]
+        pub stopwatch: Stopwatch,
+        #[serde(default)]
+        pub close_month: CloseMonth,
+        #[serde(default)]
+        pub tidy: Tidy,
+        #[serde(default)]
+        pub backups: Backups,
+        #[serde(default)]
+        pub workdays: Workdays,
+        #[serde(default)]
+        pub icons: Icons,
+        #[serde(default)]
+        pub expenses: Expenses,
+        #[serde(default)]
+        pub reports: Reports,
+        #[serde(default)]
+        pub invoice: Invoice,
+        #[serde(default)]
+        pub capacity: Capacity,
+        #[cfg(feature = "push")]
+        #[serde(default)]
+        pub push: Push,
+        #[cfg(feature = "certify")]
+        #[serde(default)]
+        pub certify: Certify,
+        #[cfg(feature = "tui")]
+        #[serde(default)]
+        pub keys: Keys,
+        #[cfg(feature = "tui")]
+        #[serde(default)]
+        pub theme: Theme,
+        /// Styles for the events in `taskit show` that match each rule
+        #[cfg(feature = "tui")]
+        #[serde(default)]
+        pub highlights: Vec<Highlight>,
+        /// Named sequences of actions, run with `taskit do` or from the command palette
+        #[serde(default)]
+        pub macros: Vec<Macro>,
+        /// User-defined fields that can be filled in on each event
+        #[serde(default)]
+        pub fields: Vec<CustomField>,
     }
 
     #[derive(Deserialize, Default, Debug)]
@@ -42,6 +124,486 @@ pub mod config {
         /// When 12hr time is disabled, this does nothing
         #[serde(default)]
         pub guess_am_pm: bool,
+        /// Start `taskit show` in the large layout: one full-width panel, extra spacing, and no
+        /// dimmed text. It can still be toggled with `L` either way
+        #[cfg(feature = "tui")]
+        #[serde(default)]
+        pub large_layout: bool,
+        /// Write event notes in your editor ($EDITOR or $VISUAL) rather than on a single line, for
+        /// notes that need several lines
+        #[serde(default)]
+        pub notes_in_editor: bool,
+        /// How dates are shown in the TUI, date prompts, and reports, as a strftime format
+        /// (`DEFAULT_DATE_FORMAT` if unset)
+        pub date_format: Option<String>,
+        /// Write the save file indented over many lines rather than on one, so it can be read and
+        /// diffed, e.g. when it's kept in git
+        #[serde(default)]
+        pub pretty_save: bool,
+    }
+
+    /// Dates with their weekday, e.g. 2024-06-01 Sat, so a wrong date stands out
+    pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d %a";
+
+    #[derive(Deserialize, Default, Debug)]
+    pub struct Aggregation {
+        /// Categories whose time doesn't count towards totals, though their events are still shown
+        #[serde(default)]
+        pub exclude_categories: Vec<String>,
+        /// Tags whose time doesn't count towards totals, though their events are still shown
+        #[serde(default)]
+        pub exclude_tags: Vec<String>,
+    }
+
+    #[derive(Deserialize, Default, Debug)]
+    pub struct Record {
+        /// What `taskit record` does when an event starts later than the previous one ended
+        #[serde(default)]
+        pub gap_fill: GapFill,
+        /// Category for events filling gaps, like a break. Without one, gaps can only be closed by
+        /// moving the start time.
+        pub filler_category: Option<String>,
+        /// Minutes an event in each category usually lasts, which the end time defaults to
+        #[serde(default)]
+        pub default_durations: HashMap<String, u32>,
+        /// Events longer than this many hours are asked about before they're saved (12 if unset)
+        pub warn_over_hours: Option<u32>,
+        /// Events dated more than this many days ahead are asked about before they're saved (1 if
+        /// unset)
+        pub warn_days_ahead: Option<u32>,
+    }
+
+    #[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+    #[serde(rename_all = "lowercase")]
+    pub enum GapFill {
+        /// Ask whether to leave the gap, start at the previous end, or fill the gap
+        #[default]
+        Ask,
+        /// Always start at the previous end, without asking
+        Snap,
+        /// Always fill the gap with an event in the filler category, without asking
+        Fill,
+        Off,
+    }
+
+    #[derive(Deserialize, Default, Debug)]
+    pub struct Stopwatch {
+        /// While the stopwatch runs, its state is kept written to this file (e.g. for use as a
+        /// text source in OBS)
+        pub overlay_file: Option<PathBuf>,
+    }
+
+    #[derive(Deserialize, Default, Debug)]
+    pub struct CloseMonth {
+        /// Steps that `taskit close-month` asks you to confirm before it locks the month
+        #[serde(default)]
+        pub checklist: Vec<String>,
+    }
+
+    #[derive(Deserialize, Default, Debug)]
+    pub struct Tidy {
+        /// Events shorter than this many minutes are offered up by `taskit tidy` (2 if unset)
+        pub min_minutes: Option<u32>,
+    }
+
+    #[derive(Deserialize, Default, Debug)]
+    pub struct Backups {
+        /// How many copies of the save file from before it was overwritten are kept, 10 if unset.
+        /// 0 turns backups off
+        pub keep: Option<usize>,
+    }
+
+    #[derive(Deserialize, Default, Debug)]
+    pub struct Workdays {
+        /// Default columns for `taskit export workdays`
+        #[serde(default)]
+        pub columns: Vec<String>,
+    }
+
+    /// Keys for each action in `taskit show`. Each action can have several keys, written like "j",
+    /// "down", or "ctrl+d".
+    #[cfg(feature = "tui")]
+    #[derive(Deserialize, Debug)]
+    #[serde(default)]
+    pub struct Keys {
+        pub quit: Vec<String>,
+        pub scroll_down: Vec<String>,
+        pub scroll_up: Vec<String>,
+        pub tab_left: Vec<String>,
+        pub tab_right: Vec<String>,
+        pub select: Vec<String>,
+        pub next_event: Vec<String>,
+        pub previous_event: Vec<String>,
+        pub next_day: Vec<String>,
+        pub previous_day: Vec<String>,
+        pub switch_compact_tab: Vec<String>,
+        pub annotate: Vec<String>,
+        pub edit: Vec<String>,
+        pub delete: Vec<String>,
+        pub filters: Vec<String>,
+        pub clear_filters: Vec<String>,
+        pub negate_filter: Vec<String>,
+        pub or_filter: Vec<String>,
+        pub percentages: Vec<String>,
+        pub sparkline: Vec<String>,
+        pub chart: Vec<String>,
+        pub large_layout: Vec<String>,
+        pub week_grouping: Vec<String>,
+        pub export: Vec<String>,
+        pub palette: Vec<String>,
+        pub switch_profile: Vec<String>,
+    }
+
+    #[cfg(feature = "tui")]
+    impl Default for Keys {
+        fn default() -> Self {
+            let keys = |keys: &[&str]| keys.iter().map(|&key| key.to_owned()).collect();
+            Self {
+                quit: keys(&["q"]),
+                scroll_down: keys(&["down"]),
+                scroll_up: keys(&["up"]),
+                tab_left: keys(&["left"]),
+                tab_right: keys(&["right"]),
+                select: keys(&["enter"]),
+                next_event: keys(&["j"]),
+                previous_event: keys(&["k"]),
+                next_day: keys(&["]"]),
+                previous_day: keys(&["["]),
+                switch_compact_tab: keys(&["tab"]),
+                annotate: keys(&["a"]),
+                edit: keys(&["E"]),
+                delete: keys(&["d"]),
+                filters: keys(&["f"]),
+                clear_filters: keys(&["X"]),
+                negate_filter: keys(&["!"]),
+                or_filter: keys(&["o"]),
+                percentages: keys(&["p"]),
+                sparkline: keys(&["s"]),
+                chart: keys(&["c"]),
+                large_layout: keys(&["L"]),
+                week_grouping: keys(&["w"]),
+                export: keys(&["e"]),
+                palette: keys(&[":"]),
+                switch_profile: keys(&["P"]),
+            }
+        }
+    }
+
+    /// Colors and text styles of the TUIs, as changes to one of the built-in themes. Each style is
+    /// a list of words like "bold yellow" or "black on white"; styles that can't be parsed are left
+    /// as the theme has them.
+    #[cfg(feature = "tui")]
+    #[derive(Deserialize, Default, Debug)]
+    #[serde(default)]
+    pub struct Theme {
+        pub base: ThemeBase,
+        /// Categories without a color of their own
+        pub category: Option<String>,
+        pub tag: Option<String>,
+        /// Panel headings like "Aggregated durations"
+        pub heading: Option<String>,
+        /// The date at the top of each day
+        pub date: Option<String>,
+        /// Durations and percentages
+        pub duration: Option<String>,
+        /// Daily notes
+        pub note: Option<String>,
+        /// Text matched by filters, and pinned events' stars
+        pub highlight: Option<String>,
+        /// Whatever is under the cursor
+        pub selected: Option<String>,
+        /// The total of all categories
+        pub total: Option<String>,
+        /// Goals that have been met
+        pub good: Option<String>,
+        /// Goals that haven't been met yet
+        pub warning: Option<String>,
+        /// Maximums that have been passed, and invalid input
+        pub error: Option<String>,
+        /// Messages at the bottom, like unsaved changes
+        pub status: Option<String>,
+        /// Whether secondary text, like each event's duration, is dimmed
+        pub dim: Option<bool>,
+        /// Whether categories are shown in their own colors (see `taskit describe-category`)
+        pub category_colors: Option<bool>,
+    }
+
+    #[cfg(feature = "tui")]
+    #[derive(Deserialize, Default, Debug, Clone, Copy)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum ThemeBase {
+        #[default]
+        Default,
+        /// Bright colors and no dimmed text
+        HighContrast,
+        /// Only bold, italic, underlined, and reversed text, for terminals without color or for
+        /// anyone who'd rather not have it
+        NoColor,
+    }
+
+    /// A style for the events matching all of the conditions that are set, e.g. events mentioning
+    /// "URGENT" in red. When several rules match an event, later ones are applied over earlier ones.
+    #[cfg(feature = "tui")]
+    #[derive(Deserialize, Debug)]
+    pub struct Highlight {
+        /// Text that the description contains, matching case
+        pub contains: Option<String>,
+        /// A regex matching part of the description
+        pub regex: Option<String>,
+        pub category: Option<String>,
+        /// Events lasting longer than this many hours
+        pub over_hours: Option<f64>,
+        /// In the same words as the styles of `[theme]`
+        pub style: String,
+    }
+
+    #[derive(Deserialize, Default, Debug)]
+    pub struct Icons {
+        /// Show each icon's ASCII fallback instead of the icon. If unset, this is decided by
+        /// whether the locale is UTF-8
+        pub ascii: Option<bool>,
+        /// Icon for each category, by name
+        #[serde(default)]
+        pub categories: HashMap<String, Icon>,
+    }
+
+    /// Either just the icon, or the icon along with what to show instead where emoji can't be
+    #[derive(Deserialize, Debug)]
+    #[serde(untagged)]
+    pub enum Icon {
+        Plain(String),
+        WithFallback { icon: String, ascii: String },
+    }
+
+    #[derive(Deserialize, Default, Debug)]
+    pub struct Reports {
+        /// Where `taskit report --run-scheduled` writes scheduled reports
+        pub output_dir: Option<PathBuf>,
+    }
+
+    #[derive(Deserialize, Default, Debug)]
+    pub struct Invoice {
+        /// Each event's time is rounded up to a multiple of this many minutes before it's billed
+        pub round_to_minutes: Option<u32>,
+        /// Bool field that can mark events in billed categories as not billable, by being false
+        pub billable_field: Option<String>,
+    }
+
+    #[derive(Deserialize, Default, Debug)]
+    pub struct Capacity {
+        /// Hours committed to each category every week, by name, for `taskit capacity`
+        #[serde(default)]
+        pub weekly_hours: HashMap<String, f64>,
+    }
+
+    #[cfg(feature = "push")]
+    #[derive(Deserialize, Default, Debug)]
+    pub struct Push {
+        /// Where each new event is sent. Nothing is pushed while this is unset
+        pub url: Option<String>,
+        /// The HTTP method, POST if unset
+        pub method: Option<String>,
+        #[serde(default)]
+        pub headers: BTreeMap<String, String>,
+        /// The request body, a JSON object with the event's fields if unset
+        pub body: Option<String>,
+        /// Filled in wherever `{token}` appears, e.g. in an Authorization header
+        pub token: Option<String>,
+        /// Only push events in these categories, or in any category if empty
+        #[serde(default)]
+        pub categories: Vec<String>,
+        /// How many more times to try a push that fails before giving up on it, 2 if unset
+        pub retries: Option<u32>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct Macro {
+        pub name: String,
+        pub actions: Vec<MacroAction>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[serde(rename_all = "snake_case")]
+    pub enum MacroAction {
+        /// Adds an event lasting `minutes` that ends now, or that starts when today's last event
+        /// ended if `after_previous` is set
+        Log {
+            category: String,
+            minutes: u32,
+            #[serde(default)]
+            notes: String,
+            #[serde(default)]
+            after_previous: bool,
+        },
+        /// Adds a line to today's daily note
+        Note(String),
+    }
+
+    impl Config {
+        pub fn macro_named(&self, name: &str) -> Option<&Macro> {
+            self.macros.iter().find(|m| m.name == name)
+        }
+    }
+
+    #[cfg(feature = "certify")]
+    #[derive(Deserialize, Default, Debug)]
+    pub struct Certify {
+        /// The minisign secret key that certified exports are signed with
+        pub secret_key: Option<PathBuf>,
+        /// The matching public key, which `taskit verify-export` checks signatures against
+        pub public_key: Option<PathBuf>,
+    }
+
+    #[derive(Deserialize, Default, Debug)]
+    pub struct Expenses {
+        /// Number field holding how much was spent alongside an event, to be reimbursed
+        pub amount_field: Option<String>,
+        /// String field holding the path to the receipt for an event's expense
+        pub receipt_field: Option<String>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct CustomField {
+        pub name: String,
+        #[serde(rename = "type", default)]
+        pub kind: FieldKind,
+        /// Bounds for number fields, e.g. 1 and 5 for a mood score
+        pub min: Option<f64>,
+        pub max: Option<f64>,
+        /// If set, the only values a string field can take, picked from a list instead of typed
+        #[serde(default)]
+        pub choices: Vec<String>,
+    }
+
+    #[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+    #[serde(rename_all = "lowercase")]
+    pub enum FieldKind {
+        #[default]
+        String,
+        Number,
+        Bool,
+    }
+
+    impl FieldKind {
+        /// Parses user input as a value of this kind. Booleans accept yes/no, y/n, and true/false.
+        pub fn parse(self, input: &str) -> Option<FieldValue> {
+            let input = input.trim();
+            match self {
+                FieldKind::String => Some(FieldValue::String(input.to_owned())),
+                FieldKind::Number => input.parse().ok().map(FieldValue::Number),
+                FieldKind::Bool => match input.to_lowercase().as_str() {
+                    "y" | "yes" | "true" => Some(FieldValue::Bool(true)),
+                    "n" | "no" | "false" => Some(FieldValue::Bool(false)),
+                    _ => None,
+                },
+            }
+        }
+    }
+
+    impl CustomField {
+        /// Parses user input as a value of this field, rejecting numbers outside its bounds and
+        /// strings that aren't one of its choices
+        pub fn parse(&self, input: &str) -> Option<FieldValue> {
+            self.kind.parse(input).and_then(|value| match value {
+                FieldValue::Number(n) => (self.min.is_none_or(|min| n >= min)
+                    && self.max.is_none_or(|max| n <= max))
+                .then_some(value),
+                // choices are matched loosely, but stored as written in the config
+                FieldValue::String(s) if !self.choices.is_empty() => self
+                    .choices
+                    .iter()
+                    .find(|c| c.eq_ignore_ascii_case(&s))
+                    .map(|c| FieldValue::String(c.clone())),
+                _ => Some(value),
+            })
+        }
+
+        /// A short description of what the field accepts, for prompts and error messages
+        pub fn expected(&self) -> String {
+            match (self.kind, self.min, self.max) {
+                (FieldKind::String, ..) if !self.choices.is_empty() => {
+                    format!("one of {}", self.choices.join(", "))
+                }
+                (FieldKind::String, ..) => "any text".to_owned(),
+                (FieldKind::Bool, ..) => "y or n".to_owned(),
+                (FieldKind::Number, Some(min), Some(max)) => format!("a number from {min} to {max}"),
+                (FieldKind::Number, Some(min), None) => format!("a number of at least {min}"),
+                (FieldKind::Number, None, Some(max)) => format!("a number of at most {max}"),
+                (FieldKind::Number, None, None) => "a number".to_owned(),
+            }
+        }
+    }
+
+    impl Config {
+        pub fn field(&self, name: &str) -> Option<&CustomField> {
+            self.fields.iter().find(|f| f.name == name)
+        }
+    }
+
+    impl Aggregation {
+        /// Whether an event should be left out of totals. `category_tags` are the tags its category
+        /// has in the tag map.
+        pub fn excludes(&self, ev: &Event, category_tags: Option<&HashSet<Tag>>) -> bool {
+            self.exclude_categories.iter().any(|c| c == ev.category.inner())
+                || ev
+                    .tags
+                    .iter()
+                    .chain(category_tags.into_iter().flatten())
+                    .any(|t| self.exclude_tags.iter().any(|excluded| excluded == t.inner()))
+        }
+
+        #[cfg(feature = "tui")]
+        pub fn excludes_category(&self, category: &str) -> bool {
+            self.exclude_categories.iter().any(|c| c == category)
+        }
+
+        #[cfg(feature = "tui")]
+        pub fn excludes_tag(&self, tag: &Tag) -> bool {
+            self.exclude_tags.iter().any(|t| t == tag.inner())
+        }
+    }
+
+    impl Preferences {
+        /// The format to show dates in, falling back to the default if `date_format` isn't a
+        /// valid format
+        pub fn date_format(&self) -> &str {
+            self.date_format
+                .as_deref()
+                .filter(|format| StrftimeItems::new(format).all(|item| !matches!(item, Item::Error)))
+                .unwrap_or(DEFAULT_DATE_FORMAT)
+        }
+    }
+
+    impl Icons {
+        fn ascii_only(&self) -> bool {
+            self.ascii.unwrap_or_else(|| {
+                // the first of these that's set decides the character encoding
+                ["LC_ALL", "LC_CTYPE", "LANG"]
+                    .into_iter()
+                    .find_map(|var| env::var(var).ok().filter(|v| !v.is_empty()))
+                    .is_some_and(|locale| {
+                        let locale = locale.to_lowercase();
+                        !locale.contains("utf-8") && !locale.contains("utf8")
+                    })
+            })
+        }
+
+        /// The icon to show for a category, if it has one that can be shown
+        pub fn get(&self, category: &str) -> Option<&str> {
+            match self.categories.get(category)? {
+                Icon::Plain(icon) if self.ascii_only() && !icon.is_ascii() => None,
+                Icon::Plain(icon) | Icon::WithFallback { icon, .. } if !self.ascii_only() => Some(icon),
+                Icon::Plain(icon) | Icon::WithFallback { ascii: icon, .. } => Some(icon),
+            }
+        }
+
+        /// The category's name, preceded by its icon if it has one
+        pub fn label(&self, category: &str) -> String {
+            match self.get(category) {
+                Some(icon) => format!("{icon} {category}"),
+                None => category.to_owned(),
+            }
+        }
     }
 }
 
@@ -50,16 +612,79 @@ struct Categories {
     options: Vec<String>,
 }
 
+/// Completes and validates active categories, showing their descriptions in the suggestions
 #[derive(Clone)]
-pub struct CategoriesCompleter<'a>(pub &'a SetVec<Category>);
+pub struct CategoriesCompleter<'a>(pub &'a SetVec<Category>, pub &'a HashMap<Category, CategoryDetails>);
 
+/// Like CategoriesCompleter, for active and archived categories
 #[derive(Clone)]
-pub struct CategoriesPair<'a, 'b>(pub &'a [Category], pub &'b [Category]);
+pub struct CategoriesPair<'a, 'b>(pub &'a [Category], pub &'b [Category], pub &'a HashMap<Category, CategoryDetails>);
+
+/// How a category is suggested when choosing one: its name, followed by its description if it has
+/// one
+fn category_suggestion(category: &Category, details: &HashMap<Category, CategoryDetails>) -> String {
+    match details.get(category).and_then(|d| d.description.as_deref()) {
+        Some(description) => format!("{category} — {description}"),
+        None => category.own(),
+    }
+}
+
+/// Turns an answer to a prompt using CategoriesCompleter or CategoriesPair back into the name of a
+/// category. Choosing a suggestion answers with the whole suggestion, description and all.
+pub fn category_answer(answer: String, details: &HashMap<Category, CategoryDetails>) -> String {
+    details
+        .keys()
+        .find(|category| category_suggestion(category, details) == answer)
+        .map(Category::own)
+        .unwrap_or(answer)
+}
+
+/// The tags an event's notes mention, as `#tag`
+pub fn get_description_tags(description: &str) -> HashSet<String> {
+    description
+        .split_whitespace()
+        .filter_map(|s| s.strip_prefix('#'))
+        .map(str::to_owned)
+        .collect()
+}
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct SimpleTime {
     pub hour: u8,
     pub minute: u8,
+    /// Usually only set for times read off a clock, like the stopwatch's. Left out of the save
+    /// file when zero, which is also what older saves without it read as.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub second: u8,
+}
+
+fn is_zero(n: &u8) -> bool {
+    *n == 0
+}
+
+/// How an event came to be recorded, so that events from a misbehaving importer or tool can be
+/// found later. Stored as a string like `stopwatch` or `import:toggl`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(into = "String", from = "String")]
+pub enum EventSource {
+    /// Recorded before sources were tracked
+    Unknown,
+    Record,
+    Stopwatch,
+    Reconstruct,
+    /// Brought in by an importer, named by the string
+    Import(String),
+    /// Logged by the macro named by the string
+    Macro(String),
+}
+
+/// The value of a user-defined field on an event
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum FieldValue {
+    Bool(bool),
+    Number(f64),
+    String(String),
 }
 
 /// Represents one change in the save file. Note the use of Opaque, which is a private type used to
@@ -71,7 +696,7 @@ pub struct SimpleTime {
 /// Tag object, which we achieve by only constructing those variants through a function that also
 /// constructs a category/tag
 #[allow(private_interfaces)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum DeltaItem {
     AddCategory(Opaque<Category>),
     RenameCategory {
@@ -80,8 +705,9 @@ pub enum DeltaItem {
     },
     ArchiveCategory(Category),
     AddEvent(Event),
+    /// Replaces the event with this id, which the new event keeps
     ChangeEvent {
-        index: usize,
+        id: EventId,
         new_event: Event,
     },
     AddTag(Opaque<Tag>),
@@ -90,10 +716,312 @@ pub enum DeltaItem {
     /// category, tag
     UntagCategory(Category, Tag),
     SetDailyNote(NaiveDate, String),
-    DeleteEvent(usize),
+    DeleteEvent(EventId),
     /// Assumes category is already archived
     DeleteCategory(Category),
     DeleteTag(Tag),
+    /// event, whether it should be pinned
+    SetPinned(EventId, bool),
+    /// category, whether it should be private
+    SetCategoryPrivate(Category, bool),
+    /// Prevents changes to events and notes on or before the date, or allows them again if None
+    LockUntil(Option<NaiveDate>),
+    /// Marks a day as intentionally untracked with a reason, or unmarks it if None
+    SetSkipped(NaiveDate, Option<String>),
+    /// Appends a line to an event's description. Tags are those mentioned in the new line.
+    AnnotateEvent {
+        id: EventId,
+        annotation: String,
+        tags: HashSet<Tag>,
+    },
+    /// Moves an archived category back to its old position among the active ones. Only used to
+    /// undo `ArchiveCategory`.
+    UnarchiveCategory {
+        index: usize,
+        category: Category,
+    },
+    /// Puts an event back at its old position. Only used to undo `DeleteEvent`.
+    RestoreEvent {
+        index: usize,
+        event: Event,
+    },
+    /// Puts a deleted category back at its old position among the archived ones. Only used to undo
+    /// `DeleteCategory`.
+    RestoreCategory {
+        index: usize,
+        category: Opaque<Category>,
+    },
+    /// Only used to undo `SetDailyNote` on a day that had no note
+    RemoveDailyNote(NaiveDate),
+    /// Saves a report under a name, replacing any report by that name, or deletes it if None
+    SetReport(String, Option<ReportSpec>),
+    /// Replaces a category's color and description. Empty details remove them.
+    SetCategoryDetails(Category, CategoryDetails),
+    /// category, its hourly rate, or None if it isn't billed
+    SetCategoryRate(Category, Option<f64>),
+    /// category, its weekly goal, or None to remove it
+    SetCategoryGoal(Category, Option<Goal>),
+    /// tag, its weekly goal, or None to remove it
+    SetTagGoal(Tag, Option<Goal>),
+}
+
+/// A `DeltaItem` as written to the undo journal (see `taskit undo`), with categories, tags, and
+/// events referred to by name. It's checked against the save data before being turned back into a
+/// `DeltaItem`, as the save might have changed since it was written.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum UnverifiedDelta {
+    AddCategory(String),
+    RenameCategory { old: String, new: String },
+    ArchiveCategory(String),
+    AddEvent(UnverifiedEventV15),
+    ChangeEvent { id: u64, new_event: UnverifiedEventV15 },
+    AddTag(String),
+    TagCategory(String, String),
+    UntagCategory(String, String),
+    SetDailyNote(NaiveDate, String),
+    DeleteEvent(u64),
+    DeleteCategory(String),
+    DeleteTag(String),
+    SetPinned(u64, bool),
+    SetCategoryPrivate(String, bool),
+    LockUntil(Option<NaiveDate>),
+    SetSkipped(NaiveDate, Option<String>),
+    AnnotateEvent { id: u64, annotation: String, tags: HashSet<String> },
+    UnarchiveCategory { index: usize, category: String },
+    RestoreEvent { index: usize, event: UnverifiedEventV15 },
+    RestoreCategory { index: usize, category: String },
+    RemoveDailyNote(NaiveDate),
+    SetReport(String, Option<ReportSpec>),
+    SetCategoryDetails(String, CategoryDetails),
+    SetCategoryRate(String, Option<f64>),
+    SetCategoryGoal(String, Option<Goal>),
+    SetTagGoal(String, Option<Goal>),
+}
+
+/// How `taskit report` groups events into rows
+#[derive(Serialize, Deserialize, clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportGrouping {
+    #[default]
+    Category,
+    /// Events count towards each of their own tags and their category's tags
+    Tag,
+    Day,
+    /// ISO weeks, starting on Monday
+    Week,
+    Month,
+}
+
+impl Display for ReportGrouping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Self::Category => "category",
+            Self::Tag => "tag",
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+        })
+    }
+}
+
+/// What `taskit report` writes its rows as
+#[derive(Serialize, Deserialize, clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    /// An aligned table, for reading in the terminal
+    #[default]
+    Table,
+    Csv,
+    Markdown,
+    /// One JSON object per line
+    Jsonl,
+}
+
+impl Display for ReportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Self::Table => "table",
+            Self::Csv => "csv",
+            Self::Markdown => "markdown",
+            Self::Jsonl => "jsonl",
+        })
+    }
+}
+
+/// Optional extra information about a category (see `taskit describe-category`)
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CategoryDetails {
+    /// Used for the category's name in the TUI. Anything ratatui can parse, like `red`,
+    /// `light-blue`, or `#ff8800`.
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Shown next to the category's name when choosing a category
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl CategoryDetails {
+    pub fn is_empty(&self) -> bool {
+        self.color.is_none() && self.description.is_none()
+    }
+
+    /// The color to show the category in, if it has one that can be parsed
+    #[cfg(feature = "tui")]
+    pub fn color(&self) -> Option<Color> {
+        self.color.as_deref().and_then(|c| Color::from_str(c).ok())
+    }
+}
+
+/// A weekly target for the time spent in a category or on a tag (see `taskit goal`)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Goal {
+    /// Hours per week, Monday to Sunday
+    pub hours: f64,
+    /// Whether this is a limit to stay under, rather than an amount to reach
+    #[serde(default)]
+    pub max: bool,
+}
+
+impl Goal {
+    pub fn weekly(&self) -> Duration {
+        Duration::seconds((self.hours * 60.0 * 60.0).round() as i64)
+    }
+
+    /// Whether a week with this much time is on the right side of the goal
+    pub fn met(&self, time: Duration) -> bool {
+        if self.max { time <= self.weekly() } else { time >= self.weekly() }
+    }
+}
+
+impl Display for Goal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.max {
+            write!(f, "max ")?;
+        }
+        write!(f, "{}/week", self.weekly())
+    }
+}
+
+/// The choices that make up a `taskit report`, which can be saved under a name to run again later
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReportSpec {
+    /// Kept as typed (like `last-week`), so that relative ranges stay relative. All time if None.
+    #[serde(default)]
+    pub range: Option<String>,
+    #[serde(default)]
+    pub group_by: ReportGrouping,
+    /// Only events in these categories, or in any category if empty
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// Only events with at least one of these tags (directly or through their category), or any
+    /// events if empty
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Only events whose description contains this text, ignoring case
+    #[serde(default)]
+    pub contains: Option<String>,
+    #[serde(default)]
+    pub include_private: bool,
+    #[serde(default)]
+    pub format: ReportFormat,
+    /// When the report runs by itself, if ever (see `taskit report --run-scheduled`)
+    #[serde(default)]
+    pub schedule: Option<ReportSchedule>,
+}
+
+/// How often a scheduled report runs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScheduleEvery {
+    Day,
+    Week(Weekday),
+    /// On this day of the month, or the last day of months that are too short
+    Month(u32),
+}
+
+/// When a saved report runs by itself, written like `daily 18:00`, `weekly mon 08:00`, or
+/// `monthly 1 09:00`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct ReportSchedule {
+    pub every: ScheduleEvery,
+    pub at: NaiveTime,
+}
+
+impl ReportSchedule {
+    /// The latest time the report was meant to run at or before `now`
+    pub fn latest(&self, now: NaiveDateTime) -> NaiveDateTime {
+        let today = now.date();
+        match self.every {
+            ScheduleEvery::Day => {
+                let time = today.and_time(self.at);
+                if time <= now { time } else { time - Days::new(1) }
+            }
+            ScheduleEvery::Week(weekday) => {
+                let days_since = (7 + today.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+                let time = (today - Days::new(days_since.into())).and_time(self.at);
+                if time <= now { time } else { time - Days::new(7) }
+            }
+            ScheduleEvery::Month(day) => {
+                let in_month = |month: NaiveDate| {
+                    // the first of the month plus up to a month's worth of days, stopping at its end
+                    let first = month.with_day(1).expect("every month has a first day");
+                    let last = first + Months::new(1) - Days::new(1);
+                    (first + Days::new(u64::from(day.max(1)) - 1)).min(last).and_time(self.at)
+                };
+                let time = in_month(today);
+                if time <= now { time } else { in_month(today - Months::new(1)) }
+            }
+        }
+    }
+}
+
+impl FromStr for ReportSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let parse_time = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| format!("invalid time {time:?}, expected HH:MM"))
+        };
+        let (every, time) = match words[..] {
+            ["daily", time] => (ScheduleEvery::Day, time),
+            ["weekly", weekday, time] => (
+                ScheduleEvery::Week(weekday.parse().map_err(|_| format!("invalid weekday {weekday:?}"))?),
+                time,
+            ),
+            ["monthly", day, time] => match day.parse() {
+                Ok(day @ 1..=31) => (ScheduleEvery::Month(day), time),
+                _ => return Err(format!("invalid day of the month {day:?}")),
+            },
+            _ => return Err(format!("invalid schedule {s:?}, expected e.g. `daily 18:00`, `weekly mon 08:00`, or `monthly 1 09:00`")),
+        };
+        Ok(Self { every, at: parse_time(time)? })
+    }
+}
+
+impl TryFrom<String> for ReportSchedule {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ReportSchedule> for String {
+    fn from(value: ReportSchedule) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for ReportSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let at = self.at.format("%H:%M");
+        match self.every {
+            ScheduleEvery::Day => write!(f, "daily {at}"),
+            ScheduleEvery::Week(weekday) => write!(f, "weekly {} {at}", weekday.to_string().to_lowercase()),
+            ScheduleEvery::Month(day) => write!(f, "monthly {day} {at}"),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -102,7 +1030,7 @@ pub struct TagCompleter<'a>(pub &'a SetVec<Tag>);
 impl SimpleTime {
     pub fn try_new(hour: u8, minute: u8) -> Option<Self> {
         if hour < 24 && minute < 60 {
-            Some(Self { hour, minute })
+            Some(Self { hour, minute, second: 0 })
         } else {
             None
         }
@@ -113,7 +1041,7 @@ impl SimpleTime {
                    else if hour == 12 { 0 }
                    else { hour };
         if hour < 12 && minute < 60 {
-            Some(Self { hour: hour + if pm {12} else {0}, minute })
+            Some(Self { hour: hour + if pm {12} else {0}, minute, second: 0 })
         } else {
             None
         }
@@ -124,8 +1052,13 @@ impl SimpleTime {
         Self {
             hour: now.hour() as u8,
             minute: now.minute() as u8,
+            second: 0,
         }
     }
+
+    fn with_second(self, second: u8) -> Option<Self> {
+        (second < 60).then_some(Self { second, ..self })
+    }
 }
 
 impl FromStr for SimpleTime {
@@ -134,11 +1067,12 @@ impl FromStr for SimpleTime {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         // is this actually any better than just using lazylock
         thread_local! {
-            static RE: LazyCell<Regex> = LazyCell::new(|| Regex::new(r"^(?:(?<hour>\d\d?):(?<minute>\d\d)|(?<hour2>\d\d?)(?<minute2>\d\d))\s*(?:(?<ap>[apAP])[mM]?)?$").expect("regex should compile"));
+            static RE: LazyCell<Regex> = LazyCell::new(|| Regex::new(r"^(?:(?<hour>\d\d?):(?<minute>\d\d)(?::(?<second>\d\d))?|(?<hour2>\d\d?)(?<minute2>\d\d))\s*(?:(?<ap>[apAP])[mM]?)?$").expect("regex should compile"));
         }
         let captures = RE.with(|re| re.captures(s)).ok_or(())?;
         let hour = captures.name("hour").or_else(|| captures.name("hour2")).ok_or(())?.as_str().parse().ok().ok_or(())?;
         let minute = captures.name("minute").or_else(|| captures.name("minute2")).ok_or(())?.as_str().parse().ok().ok_or(())?;
+        let second = captures.name("second").map_or(Ok(0), |s| s.as_str().parse().map_err(|_| ()))?;
 
         // AM/PM logic:
         // Under 24hr time, fail if we see AM or PM at all
@@ -152,22 +1086,24 @@ impl FromStr for SimpleTime {
             } else if CONFIG.prefs.guess_am_pm {
                 let am_option = Self::try_new_12hr(hour, minute, false).ok_or(())?;
                 let now = Self::now();
-                now - am_option > TimeDelta::hours(6) && now - am_option < TimeDelta::hours(18)
+                now - am_option > Duration::hours(6) && now - am_option < Duration::hours(18)
             } else {
                 Err(())?
             };
-            Self::try_new_12hr(hour, minute, pm).ok_or(())
+            Self::try_new_12hr(hour, minute, pm).and_then(|t| t.with_second(second)).ok_or(())
         } else {
             if captures.name("ap").is_some() {
                 Err(())?
             }
-            Self::try_new(hour, minute).ok_or(())
+            Self::try_new(hour, minute).and_then(|t| t.with_second(second)).ok_or(())
         }
     }
 }
 
 pub trait Apply<T> {
     fn apply(&mut self, delta: T) -> TaskitResult<()>;
+    /// Like `apply`, but also returns the changes that would undo `delta` if applied right after it
+    fn apply_reversibly(&mut self, delta: T) -> TaskitResult<Vec<DeltaItem>>;
 }
 
 impl Apply<Vec<DeltaItem>> for SaveData {
@@ -177,6 +1113,15 @@ impl Apply<Vec<DeltaItem>> for SaveData {
         }
         Ok(())
     }
+
+    fn apply_reversibly(&mut self, delta: Vec<DeltaItem>) -> TaskitResult<Vec<DeltaItem>> {
+        let mut inverses = vec![];
+        for delta in delta {
+            inverses.push(self.apply_reversibly(delta)?);
+        }
+        // later changes have to be undone first
+        Ok(inverses.into_iter().rev().flatten().collect())
+    }
 }
 
 impl<'a, 'b> Autocomplete for CategoriesPair<'a, 'b> {
@@ -186,7 +1131,7 @@ impl<'a, 'b> Autocomplete for CategoriesPair<'a, 'b> {
             .iter()
             .chain(self.1.iter())
             .filter(|c| c.inner().starts_with(input))
-            .map(Category::own)
+            .map(|c| category_suggestion(c, self.2))
             .collect())
     }
 
@@ -208,7 +1153,7 @@ impl<'a> Autocomplete for CategoriesCompleter<'a> {
             .0
             .iter()
             .filter(|c| c.inner().starts_with(input))
-            .map(Category::own)
+            .map(|c| category_suggestion(c, self.1))
             .collect())
     }
 
@@ -254,6 +1199,7 @@ impl<'a, 'b> StringValidator for CategoriesPair<'a, 'b> {
         &self,
         input: &str,
     ) -> Result<inquire::validator::Validation, inquire::CustomUserError> {
+        let input = category_answer(input.to_owned(), self.2);
         if self
             .0
             .iter()
@@ -272,7 +1218,7 @@ impl<'a> StringValidator for CategoriesCompleter<'a> {
         &self,
         input: &str,
     ) -> Result<inquire::validator::Validation, inquire::CustomUserError> {
-        if self.0.contains_match(input) {
+        if self.0.contains_match(category_answer(input.to_owned(), self.1).as_str()) {
             Ok(Validation::Valid)
         } else {
             Ok(Validation::Invalid(ErrorMessage::Default))
@@ -296,6 +1242,7 @@ impl From<chrono::NaiveTime> for SimpleTime {
         Self {
             hour: value.hour() as u8,
             minute: value.minute() as u8,
+            second: value.second() as u8,
         }
     }
 }
@@ -303,41 +1250,93 @@ impl From<chrono::NaiveTime> for SimpleTime {
 impl TryFrom<SimpleTime> for chrono::NaiveTime {
     type Error = ();
     fn try_from(value: SimpleTime) -> Result<Self, ()> {
-        chrono::NaiveTime::from_hms_opt(value.hour as u32, value.minute as u32, 0).ok_or(())
+        chrono::NaiveTime::from_hms_opt(value.hour as u32, value.minute as u32, value.second as u32).ok_or(())
     }
 }
 
-impl Display for SimpleTime {
+impl Display for FieldValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if CONFIG.prefs.use_12hr_time {
-            let hour = self.hour % 12;
-            write!(f, "{:02}:{:02} {}", if hour == 0 {12} else {hour}, self.minute, if self.hour >= 12 {"pm"} else {"am"})
-        } else {
-            write!(f, "{:02}:{:02}", self.hour, self.minute)
+        match self {
+            FieldValue::Bool(b) => write!(f, "{}", if *b { "yes" } else { "no" }),
+            FieldValue::Number(n) => write!(f, "{n}"),
+            FieldValue::String(s) => write!(f, "{s}"),
         }
     }
 }
 
-// Note that this implementation is slightly unusual in that e.g. 01:00 - 23:00 = 2 hr
-impl Sub for SimpleTime {
-    type Output = TimeDelta;
+impl Display for EventSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EventSource::Unknown => write!(f, "unknown"),
+            EventSource::Record => write!(f, "record"),
+            EventSource::Stopwatch => write!(f, "stopwatch"),
+            EventSource::Reconstruct => write!(f, "reconstruct"),
+            EventSource::Import(importer) => write!(f, "import:{importer}"),
+            EventSource::Macro(name) => write!(f, "macro:{name}"),
+        }
+    }
+}
 
-    fn sub(self, rhs: Self) -> Self::Output {
-        let lhs_minute = (self.hour as i64) * 60 + self.minute as i64;
-        let rhs_minute = (rhs.hour as i64) * 60 + rhs.minute as i64;
-        let mut minutes = lhs_minute - rhs_minute;
-        if minutes < 0 {
-            minutes += 60 * 24;
+impl From<String> for EventSource {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "record" => Self::Record,
+            "stopwatch" => Self::Stopwatch,
+            "reconstruct" => Self::Reconstruct,
+            other => match (other.strip_prefix("import:"), other.strip_prefix("macro:")) {
+                (Some(importer), _) => Self::Import(importer.to_owned()),
+                (_, Some(name)) => Self::Macro(name.to_owned()),
+                _ => Self::Unknown,
+            },
         }
-        TimeDelta::minutes(minutes)
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct UnverifiedEventV1 {
-    pub start_time: SimpleTime,
-    pub end_time: SimpleTime, // if end_time before start_time: counts as that time on date + 1
-    pub date: NaiveDate,
+impl From<EventSource> for String {
+    fn from(value: EventSource) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for SimpleTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if CONFIG.prefs.use_12hr_time {
+            let hour = self.hour % 12;
+            write!(f, "{:02}:{:02}", if hour == 0 {12} else {hour}, self.minute)?;
+            if self.second != 0 {
+                write!(f, ":{:02}", self.second)?;
+            }
+            write!(f, " {}", if self.hour >= 12 {"pm"} else {"am"})
+        } else {
+            write!(f, "{:02}:{:02}", self.hour, self.minute)?;
+            if self.second != 0 {
+                write!(f, ":{:02}", self.second)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+// Note that this implementation is slightly unusual in that e.g. 01:00 - 23:00 = 2 hr
+impl Sub for SimpleTime {
+    type Output = Duration;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        let lhs_second = ((self.hour as i64) * 60 + self.minute as i64) * 60 + self.second as i64;
+        let rhs_second = ((rhs.hour as i64) * 60 + rhs.minute as i64) * 60 + rhs.second as i64;
+        let mut seconds = lhs_second - rhs_second;
+        if seconds < 0 {
+            seconds += 60 * 60 * 24;
+        }
+        Duration::seconds(seconds)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnverifiedEventV1 {
+    pub start_time: SimpleTime,
+    pub end_time: SimpleTime, // if end_time before start_time: counts as that time on date + 1
+    pub date: NaiveDate,
     pub category: String,
     pub comments: String,
 }
@@ -353,6 +1352,82 @@ pub struct UnverifiedEventV5 {
     pub tags: HashSet<String>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnverifiedEventV8 {
+    pub start_time: SimpleTime,
+    pub end_time: SimpleTime, // if end_time before start_time: counts as that time on date + 1
+    pub date: NaiveDate,
+    pub category: String,
+    #[serde(rename = "comments")]
+    pub description: String,
+    pub tags: HashSet<String>,
+    pub pinned: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnverifiedEventV10 {
+    pub start_time: SimpleTime,
+    pub end_time: SimpleTime, // if end_time before start_time: counts as that time on date + 1
+    pub date: NaiveDate,
+    pub category: String,
+    #[serde(rename = "comments")]
+    pub description: String,
+    pub tags: HashSet<String>,
+    pub pinned: bool,
+    pub source: EventSource,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnverifiedEventV11 {
+    pub start_time: SimpleTime,
+    pub end_time: SimpleTime, // if end_time before start_time: counts as that time on date + 1
+    pub date: NaiveDate,
+    pub category: String,
+    #[serde(rename = "comments")]
+    pub description: String,
+    pub tags: HashSet<String>,
+    pub pinned: bool,
+    pub source: EventSource,
+    /// The id the event has in the system it was imported from, if any
+    pub external_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnverifiedEventV13 {
+    pub start_time: SimpleTime,
+    pub end_time: SimpleTime, // if end_time before start_time: counts as that time on date + 1
+    pub date: NaiveDate,
+    pub category: String,
+    #[serde(rename = "comments")]
+    pub description: String,
+    pub tags: HashSet<String>,
+    pub pinned: bool,
+    pub source: EventSource,
+    /// The id the event has in the system it was imported from, if any
+    pub external_id: Option<String>,
+    /// Values of user-defined fields (see `fields` in the config), by field name
+    pub fields: BTreeMap<String, FieldValue>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnverifiedEventV15 {
+    /// Never reused, even once the event is deleted
+    pub id: u64,
+    pub start_time: SimpleTime,
+    pub end_time: SimpleTime, // if end_time before start_time: counts as that time on date + 1
+    pub date: NaiveDate,
+    pub category: String,
+    #[serde(rename = "comments")]
+    pub description: String,
+    pub tags: BTreeSet<String>,
+    pub pinned: bool,
+    pub source: EventSource,
+    /// The id the event has in the system it was imported from, if any
+    pub external_id: Option<String>,
+    /// Values of user-defined fields (see `fields` in the config), by field name
+    pub fields: BTreeMap<String, FieldValue>,
+}
+
 trait Upgrade {
     type Next;
     fn upgrade(self) -> Self::Next;
@@ -457,7 +1532,221 @@ pub struct UnverifiedSaveDataV7 {
     daily_notes: HashMap<NaiveDate, String>,
 }
 
-pub type UnverifiedSaveDataLatest = UnverifiedSaveDataV7;
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct UnverifiedSaveDataV8 {
+    categories: Vec<String>,
+    archived_categories: Vec<String>,
+    tags: Vec<String>,
+    /// Maps from category name to tags
+    tag_map: HashMap<String, Vec<String>>,
+    events: Vec<UnverifiedEventV8>,
+    daily_notes: HashMap<NaiveDate, String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct UnverifiedSaveDataV9 {
+    categories: Vec<String>,
+    archived_categories: Vec<String>,
+    /// Categories (active or archived) whose events are left out of shared output
+    private_categories: Vec<String>,
+    tags: Vec<String>,
+    /// Maps from category name to tags
+    tag_map: HashMap<String, Vec<String>>,
+    events: Vec<UnverifiedEventV8>,
+    daily_notes: HashMap<NaiveDate, String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct UnverifiedSaveDataV10 {
+    categories: Vec<String>,
+    archived_categories: Vec<String>,
+    /// Categories (active or archived) whose events are left out of shared output
+    private_categories: Vec<String>,
+    tags: Vec<String>,
+    /// Maps from category name to tags
+    tag_map: HashMap<String, Vec<String>>,
+    events: Vec<UnverifiedEventV10>,
+    daily_notes: HashMap<NaiveDate, String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct UnverifiedSaveDataV11 {
+    categories: Vec<String>,
+    archived_categories: Vec<String>,
+    /// Categories (active or archived) whose events are left out of shared output
+    private_categories: Vec<String>,
+    tags: Vec<String>,
+    /// Maps from category name to tags
+    tag_map: HashMap<String, Vec<String>>,
+    events: Vec<UnverifiedEventV11>,
+    daily_notes: HashMap<NaiveDate, String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct UnverifiedSaveDataV12 {
+    categories: Vec<String>,
+    archived_categories: Vec<String>,
+    /// Categories (active or archived) whose events are left out of shared output
+    private_categories: Vec<String>,
+    tags: Vec<String>,
+    /// Maps from category name to tags
+    tag_map: HashMap<String, Vec<String>>,
+    events: Vec<UnverifiedEventV11>,
+    daily_notes: HashMap<NaiveDate, String>,
+    /// Events and notes on or before this date can no longer be changed
+    locked_until: Option<NaiveDate>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct UnverifiedSaveDataV13 {
+    categories: Vec<String>,
+    archived_categories: Vec<String>,
+    /// Categories (active or archived) whose events are left out of shared output
+    private_categories: Vec<String>,
+    tags: Vec<String>,
+    /// Maps from category name to tags
+    tag_map: HashMap<String, Vec<String>>,
+    events: Vec<UnverifiedEventV13>,
+    daily_notes: HashMap<NaiveDate, String>,
+    /// Events and notes on or before this date can no longer be changed
+    locked_until: Option<NaiveDate>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct UnverifiedSaveDataV14 {
+    categories: Vec<String>,
+    archived_categories: Vec<String>,
+    /// Categories (active or archived) whose events are left out of shared output
+    private_categories: Vec<String>,
+    tags: Vec<String>,
+    /// Maps from category name to tags
+    tag_map: HashMap<String, Vec<String>>,
+    events: Vec<UnverifiedEventV13>,
+    daily_notes: HashMap<NaiveDate, String>,
+    /// Events and notes on or before this date can no longer be changed
+    locked_until: Option<NaiveDate>,
+    /// Days intentionally left untracked, with the reason (which may be empty)
+    skipped_days: BTreeMap<NaiveDate, String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct UnverifiedSaveDataV15 {
+    categories: Vec<String>,
+    archived_categories: Vec<String>,
+    /// Categories (active or archived) whose events are left out of shared output
+    private_categories: Vec<String>,
+    tags: Vec<String>,
+    /// Maps from category name to tags
+    tag_map: HashMap<String, Vec<String>>,
+    events: Vec<UnverifiedEventV15>,
+    /// The id the next event to be added will get
+    next_event_id: u64,
+    daily_notes: HashMap<NaiveDate, String>,
+    /// Events and notes on or before this date can no longer be changed
+    locked_until: Option<NaiveDate>,
+    /// Days intentionally left untracked, with the reason (which may be empty)
+    skipped_days: BTreeMap<NaiveDate, String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct UnverifiedSaveDataV16 {
+    categories: Vec<String>,
+    archived_categories: Vec<String>,
+    /// Categories (active or archived) whose events are left out of shared output
+    private_categories: Vec<String>,
+    tags: Vec<String>,
+    /// Maps from category name to tags
+    tag_map: HashMap<String, Vec<String>>,
+    events: Vec<UnverifiedEventV15>,
+    /// The id the next event to be added will get
+    next_event_id: u64,
+    daily_notes: HashMap<NaiveDate, String>,
+    /// Events and notes on or before this date can no longer be changed
+    locked_until: Option<NaiveDate>,
+    /// Days intentionally left untracked, with the reason (which may be empty)
+    skipped_days: BTreeMap<NaiveDate, String>,
+    /// Reports saved from `taskit report`, by name
+    reports: BTreeMap<String, ReportSpec>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct UnverifiedSaveDataV17 {
+    categories: Vec<String>,
+    archived_categories: Vec<String>,
+    /// Categories (active or archived) whose events are left out of shared output
+    private_categories: Vec<String>,
+    /// Colors and descriptions of categories (active or archived), by name
+    category_details: HashMap<String, CategoryDetails>,
+    tags: Vec<String>,
+    /// Maps from category name to tags
+    tag_map: HashMap<String, Vec<String>>,
+    events: Vec<UnverifiedEventV15>,
+    /// The id the next event to be added will get
+    next_event_id: u64,
+    daily_notes: HashMap<NaiveDate, String>,
+    /// Events and notes on or before this date can no longer be changed
+    locked_until: Option<NaiveDate>,
+    /// Days intentionally left untracked, with the reason (which may be empty)
+    skipped_days: BTreeMap<NaiveDate, String>,
+    /// Reports saved from `taskit report`, by name
+    reports: BTreeMap<String, ReportSpec>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct UnverifiedSaveDataV18 {
+    categories: Vec<String>,
+    archived_categories: Vec<String>,
+    /// Categories (active or archived) whose events are left out of shared output
+    private_categories: Vec<String>,
+    /// Colors and descriptions of categories (active or archived), by name
+    category_details: HashMap<String, CategoryDetails>,
+    /// Hourly rates of categories (active or archived) that are billed, by name
+    category_rates: HashMap<String, f64>,
+    tags: Vec<String>,
+    /// Maps from category name to tags
+    tag_map: HashMap<String, Vec<String>>,
+    events: Vec<UnverifiedEventV15>,
+    /// The id the next event to be added will get
+    next_event_id: u64,
+    daily_notes: HashMap<NaiveDate, String>,
+    /// Events and notes on or before this date can no longer be changed
+    locked_until: Option<NaiveDate>,
+    /// Days intentionally left untracked, with the reason (which may be empty)
+    skipped_days: BTreeMap<NaiveDate, String>,
+    /// Reports saved from `taskit report`, by name
+    reports: BTreeMap<String, ReportSpec>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct UnverifiedSaveDataV19 {
+    categories: Vec<String>,
+    archived_categories: Vec<String>,
+    /// Categories (active or archived) whose events are left out of shared output
+    private_categories: Vec<String>,
+    /// Colors and descriptions of categories (active or archived), by name
+    category_details: BTreeMap<String, CategoryDetails>,
+    /// Hourly rates of categories (active or archived) that are billed, by name
+    category_rates: BTreeMap<String, f64>,
+    /// Weekly goals of categories (active or archived), by name
+    category_goals: BTreeMap<String, Goal>,
+    tags: Vec<String>,
+    /// Maps from category name to tags
+    tag_map: BTreeMap<String, Vec<String>>,
+    /// Weekly goals of tags, by name
+    tag_goals: BTreeMap<String, Goal>,
+    events: Vec<UnverifiedEventV15>,
+    /// The id the next event to be added will get
+    next_event_id: u64,
+    daily_notes: BTreeMap<NaiveDate, String>,
+    /// Events and notes on or before this date can no longer be changed
+    locked_until: Option<NaiveDate>,
+    /// Days intentionally left untracked, with the reason (which may be empty)
+    skipped_days: BTreeMap<NaiveDate, String>,
+    /// Reports saved from `taskit report`, by name
+    reports: BTreeMap<String, ReportSpec>,
+}
+
+pub type UnverifiedSaveDataLatest = UnverifiedSaveDataV19;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum UnverifiedSaveDataVersioned {
@@ -468,24 +1757,36 @@ pub enum UnverifiedSaveDataVersioned {
     V5(UnverifiedSaveDataV5),
     V6(UnverifiedSaveDataV6),
     V7(UnverifiedSaveDataV7),
+    V8(UnverifiedSaveDataV8),
+    V9(UnverifiedSaveDataV9),
+    V10(UnverifiedSaveDataV10),
+    V11(UnverifiedSaveDataV11),
+    V12(UnverifiedSaveDataV12),
+    V13(UnverifiedSaveDataV13),
+    V14(UnverifiedSaveDataV14),
+    V15(UnverifiedSaveDataV15),
+    V16(UnverifiedSaveDataV16),
+    V17(UnverifiedSaveDataV17),
+    V18(UnverifiedSaveDataV18),
+    V19(UnverifiedSaveDataV19),
 }
 
 impl Default for UnverifiedSaveDataVersioned {
     fn default() -> Self {
-        Self::V7(Default::default())
+        Self::V19(Default::default())
     }
 }
 
 impl From<UnverifiedSaveDataLatest> for UnverifiedSaveDataVersioned {
     fn from(value: UnverifiedSaveDataLatest) -> Self {
-        Self::V7(value)
+        Self::V19(value)
     }
 }
 
 impl UnverifiedSaveDataVersioned {
     /// Returns the latest version of SaveData, and a bool that is true iff the format was upgraded
     pub fn extract(self) -> (UnverifiedSaveDataLatest, bool) {
-        if let Self::V7(data) = self {
+        if let Self::V19(data) = self {
             (data, false)
         } else {
             (self.upgrade_once().extract().0, true)
@@ -500,11 +1801,129 @@ impl UnverifiedSaveDataVersioned {
             Self::V4(data) => Self::V5(data.upgrade()),
             Self::V5(data) => Self::V6(data.upgrade()),
             Self::V6(data) => Self::V7(data.upgrade()),
-            Self::V7(_) => panic!(),
+            Self::V7(data) => Self::V8(data.upgrade()),
+            Self::V8(data) => Self::V9(data.upgrade()),
+            Self::V9(data) => Self::V10(data.upgrade()),
+            Self::V10(data) => Self::V11(data.upgrade()),
+            Self::V11(data) => Self::V12(data.upgrade()),
+            Self::V12(data) => Self::V13(data.upgrade()),
+            Self::V13(data) => Self::V14(data.upgrade()),
+            Self::V14(data) => Self::V15(data.upgrade()),
+            Self::V15(data) => Self::V16(data.upgrade()),
+            Self::V16(data) => Self::V17(data.upgrade()),
+            Self::V17(data) => Self::V18(data.upgrade()),
+            Self::V18(data) => Self::V19(data.upgrade()),
+            Self::V19(_) => panic!(),
+        }
+    }
+}
+
+/// Somewhere a save is kept. A JSON file can only be read and written whole, but storage that can
+/// do better, like a database, only reads the events a command asks for and only writes the ones
+/// that changed.
+pub trait Storage {
+    /// Returns None if there's no save there yet
+    fn read(&self) -> TaskitResult<Option<UnverifiedSaveDataVersioned>>;
+    /// Like `read`, but the events and daily notes outside `from` to `to` (either open if None) can
+    /// be left out, for commands that only look at a range of days. Reads the whole save unless
+    /// the storage can do better.
+    fn read_between(&self, from: Option<NaiveDate>, to: Option<NaiveDate>) -> TaskitResult<Option<UnverifiedSaveDataVersioned>> {
+        let _ = (from, to);
+        self.read()
+    }
+    /// Replaces the save in one go, so that an interrupted write leaves the old one as it was
+    fn write(&self, save: UnverifiedSaveDataLatest) -> TaskitResult<()>;
+    /// Like `write`, for a save that `changes` were just applied to after it was read from here, so
+    /// that only what they touched has to be written. Writes the whole save unless the storage can
+    /// do better.
+    fn write_changes(&self, save: UnverifiedSaveDataLatest, changes: &[DeltaItem]) -> TaskitResult<()> {
+        let _ = changes;
+        self.write(save)
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StorageKind {
+    /// One JSON file, tagged with the version of the save format
+    Json,
+    /// A SQLite database, with a row per event
+    Sqlite,
+}
+
+impl StorageKind {
+    /// SQLite for `.sqlite` and `.db` files, JSON for anything else
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("sqlite" | "db") => Self::Sqlite,
+            _ => Self::Json,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Sqlite => "sqlite",
         }
     }
 }
 
+/// The storage for the save file at `path`, going by its extension
+pub fn storage(path: &Path) -> TaskitResult<Box<dyn Storage>> {
+    match StorageKind::of(path) {
+        StorageKind::Json => Ok(Box::new(JsonStorage { path: path.to_owned() })),
+        #[cfg(feature = "sqlite")]
+        StorageKind::Sqlite => Ok(Box::new(sqlite::SqliteStorage::new(path))),
+        #[cfg(not(feature = "sqlite"))]
+        StorageKind::Sqlite => Err(Kind::UnsupportedFormat(path.display().to_string()).with(Source::ReadingSave)),
+    }
+}
+
+/// The save file kept in `dir`, like the data directory or a profile's: `save.sqlite` once it's
+/// been migrated to SQLite, `save.json` otherwise
+pub fn save_in(dir: &Path) -> PathBuf {
+    let sqlite = dir.join("save").with_extension(StorageKind::Sqlite.extension());
+    if sqlite.exists() {
+        sqlite
+    } else {
+        dir.join("save").with_extension(StorageKind::Json.extension())
+    }
+}
+
+pub struct JsonStorage {
+    path: PathBuf,
+}
+
+impl Storage for JsonStorage {
+    fn read(&self) -> TaskitResult<Option<UnverifiedSaveDataVersioned>> {
+        let json = match fs::read_to_string(&self.path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.with(Source::ReadingSave).into()),
+        };
+        serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| Kind::InvalidSave(format!("{}: {e}", self.path.display())).with(Source::ReadingSave))
+    }
+
+    fn write(&self, save: UnverifiedSaveDataLatest) -> TaskitResult<()> {
+        let versioned = UnverifiedSaveDataVersioned::from(save);
+        let json = if CONFIG.prefs.pretty_save {
+            // ending in a newline, like a file written by hand, keeps git from flagging the last line
+            serde_json::to_vec_pretty(&versioned).map(|mut json| {
+                json.push(b'\n');
+                json
+            })
+        } else {
+            serde_json::to_vec(&versioned)
+        };
+        let json = json.expect("save data always serializes");
+        let temp_path = self.path.with_extension("tmp");
+        fs::write(&temp_path, json).with(Source::SavingChanges)?;
+        fs::rename(&temp_path, &self.path).with(Source::SavingChanges)?;
+        Ok(())
+    }
+}
+
 impl Upgrade for UnverifiedSaveDataV1 {
     type Next = UnverifiedSaveDataV2;
     fn upgrade(self) -> Self::Next {
@@ -570,9 +1989,9 @@ impl Upgrade for UnverifiedSaveDataV4 {
                         description: comments.clone(),
                         tags: comments
                             .split(' ')
-                            .filter(|s| s.starts_with('#'))
-                            .filter(|s| tags.iter().any(|tag| tag.as_str() == &s[1..]))
-                            .map(|s| s[1..].to_owned())
+                            .filter_map(|s| s.strip_prefix('#'))
+                            .filter(|s| tags.iter().any(|tag| tag.as_str() == *s))
+                            .map(str::to_owned)
                             .collect(),
                     },
                 )
@@ -631,4 +2050,538 @@ impl Upgrade for UnverifiedSaveDataV6 {
     }
 }
 
+impl Upgrade for UnverifiedSaveDataV7 {
+    type Next = UnverifiedSaveDataV8;
+    fn upgrade(self) -> Self::Next {
+        let UnverifiedSaveDataV7 {
+            categories,
+            archived_categories,
+            tags,
+            tag_map,
+            events,
+            daily_notes,
+        } = self;
+        UnverifiedSaveDataV8 {
+            categories,
+            archived_categories,
+            tags,
+            tag_map,
+            events: events
+                .into_iter()
+                .map(
+                    |UnverifiedEventV5 {
+                         start_time,
+                         end_time,
+                         date,
+                         category,
+                         description,
+                         tags,
+                     }| UnverifiedEventV8 {
+                        start_time,
+                        end_time,
+                        date,
+                        category,
+                        description,
+                        tags,
+                        pinned: false,
+                    },
+                )
+                .collect(),
+            daily_notes,
+        }
+    }
+}
+
+impl Upgrade for UnverifiedSaveDataV8 {
+    type Next = UnverifiedSaveDataV9;
+    fn upgrade(self) -> Self::Next {
+        let UnverifiedSaveDataV8 {
+            categories,
+            archived_categories,
+            tags,
+            tag_map,
+            events,
+            daily_notes,
+        } = self;
+        UnverifiedSaveDataV9 {
+            categories,
+            archived_categories,
+            private_categories: Default::default(),
+            tags,
+            tag_map,
+            events,
+            daily_notes,
+        }
+    }
+}
+
+impl Upgrade for UnverifiedSaveDataV9 {
+    type Next = UnverifiedSaveDataV10;
+    fn upgrade(self) -> Self::Next {
+        let UnverifiedSaveDataV9 {
+            categories,
+            archived_categories,
+            private_categories,
+            tags,
+            tag_map,
+            events,
+            daily_notes,
+        } = self;
+        UnverifiedSaveDataV10 {
+            categories,
+            archived_categories,
+            private_categories,
+            tags,
+            tag_map,
+            events: events
+                .into_iter()
+                .map(
+                    |UnverifiedEventV8 {
+                         start_time,
+                         end_time,
+                         date,
+                         category,
+                         description,
+                         tags,
+                         pinned,
+                     }| UnverifiedEventV10 {
+                        start_time,
+                        end_time,
+                        date,
+                        category,
+                        description,
+                        tags,
+                        pinned,
+                        source: EventSource::Unknown,
+                    },
+                )
+                .collect(),
+            daily_notes,
+        }
+    }
+}
+
+impl Upgrade for UnverifiedSaveDataV10 {
+    type Next = UnverifiedSaveDataV11;
+    fn upgrade(self) -> Self::Next {
+        let UnverifiedSaveDataV10 {
+            categories,
+            archived_categories,
+            private_categories,
+            tags,
+            tag_map,
+            events,
+            daily_notes,
+        } = self;
+        UnverifiedSaveDataV11 {
+            categories,
+            archived_categories,
+            private_categories,
+            tags,
+            tag_map,
+            events: events
+                .into_iter()
+                .map(
+                    |UnverifiedEventV10 {
+                         start_time,
+                         end_time,
+                         date,
+                         category,
+                         description,
+                         tags,
+                         pinned,
+                         source,
+                     }| UnverifiedEventV11 {
+                        start_time,
+                        end_time,
+                        date,
+                        category,
+                        description,
+                        tags,
+                        pinned,
+                        source,
+                        external_id: None,
+                    },
+                )
+                .collect(),
+            daily_notes,
+        }
+    }
+}
+
+impl Upgrade for UnverifiedSaveDataV11 {
+    type Next = UnverifiedSaveDataV12;
+    fn upgrade(self) -> Self::Next {
+        let UnverifiedSaveDataV11 {
+            categories,
+            archived_categories,
+            private_categories,
+            tags,
+            tag_map,
+            events,
+            daily_notes,
+        } = self;
+        UnverifiedSaveDataV12 {
+            categories,
+            archived_categories,
+            private_categories,
+            tags,
+            tag_map,
+            events,
+            daily_notes,
+            locked_until: None,
+        }
+    }
+}
+
+impl Upgrade for UnverifiedSaveDataV12 {
+    type Next = UnverifiedSaveDataV13;
+    fn upgrade(self) -> Self::Next {
+        let UnverifiedSaveDataV12 {
+            categories,
+            archived_categories,
+            private_categories,
+            tags,
+            tag_map,
+            events,
+            daily_notes,
+            locked_until,
+        } = self;
+        UnverifiedSaveDataV13 {
+            categories,
+            archived_categories,
+            private_categories,
+            tags,
+            tag_map,
+            events: events
+                .into_iter()
+                .map(
+                    |UnverifiedEventV11 {
+                         start_time,
+                         end_time,
+                         date,
+                         category,
+                         description,
+                         tags,
+                         pinned,
+                         source,
+                         external_id,
+                     }| UnverifiedEventV13 {
+                        start_time,
+                        end_time,
+                        date,
+                        category,
+                        description,
+                        tags,
+                        pinned,
+                        source,
+                        external_id,
+                        fields: Default::default(),
+                    },
+                )
+                .collect(),
+            daily_notes,
+            locked_until,
+        }
+    }
+}
+
+impl Upgrade for UnverifiedSaveDataV13 {
+    type Next = UnverifiedSaveDataV14;
+    fn upgrade(self) -> Self::Next {
+        let UnverifiedSaveDataV13 {
+            categories,
+            archived_categories,
+            private_categories,
+            tags,
+            tag_map,
+            events,
+            daily_notes,
+            locked_until,
+        } = self;
+        UnverifiedSaveDataV14 {
+            categories,
+            archived_categories,
+            private_categories,
+            tags,
+            tag_map,
+            events,
+            daily_notes,
+            locked_until,
+            skipped_days: BTreeMap::new(),
+        }
+    }
+}
+
+impl Upgrade for UnverifiedSaveDataV14 {
+    type Next = UnverifiedSaveDataV15;
+    fn upgrade(self) -> Self::Next {
+        let UnverifiedSaveDataV14 {
+            categories,
+            archived_categories,
+            private_categories,
+            tags,
+            tag_map,
+            events,
+            daily_notes,
+            locked_until,
+            skipped_days,
+        } = self;
+        UnverifiedSaveDataV15 {
+            categories,
+            archived_categories,
+            private_categories,
+            tags,
+            tag_map,
+            next_event_id: events.len() as u64 + 1,
+            events: events
+                .into_iter()
+                .zip(1..)
+                .map(
+                    |(
+                        UnverifiedEventV13 {
+                            start_time,
+                            end_time,
+                            date,
+                            category,
+                            description,
+                            tags,
+                            pinned,
+                            source,
+                            external_id,
+                            fields,
+                        },
+                        id,
+                    )| UnverifiedEventV15 {
+                        id,
+                        start_time,
+                        end_time,
+                        date,
+                        category,
+                        description,
+                        tags: tags.into_iter().collect(),
+                        pinned,
+                        source,
+                        external_id,
+                        fields,
+                    },
+                )
+                .collect(),
+            daily_notes,
+            locked_until,
+            skipped_days,
+        }
+    }
+}
+
+impl Upgrade for UnverifiedSaveDataV15 {
+    type Next = UnverifiedSaveDataV16;
+    fn upgrade(self) -> Self::Next {
+        let UnverifiedSaveDataV15 {
+            categories,
+            archived_categories,
+            private_categories,
+            tags,
+            tag_map,
+            events,
+            next_event_id,
+            daily_notes,
+            locked_until,
+            skipped_days,
+        } = self;
+        UnverifiedSaveDataV16 {
+            categories,
+            archived_categories,
+            private_categories,
+            tags,
+            tag_map,
+            events,
+            next_event_id,
+            daily_notes,
+            locked_until,
+            skipped_days,
+            reports: BTreeMap::new(),
+        }
+    }
+}
+
+impl Upgrade for UnverifiedSaveDataV16 {
+    type Next = UnverifiedSaveDataV17;
+    fn upgrade(self) -> Self::Next {
+        let UnverifiedSaveDataV16 {
+            categories,
+            archived_categories,
+            private_categories,
+            tags,
+            tag_map,
+            events,
+            next_event_id,
+            daily_notes,
+            locked_until,
+            skipped_days,
+            reports,
+        } = self;
+        UnverifiedSaveDataV17 {
+            categories,
+            archived_categories,
+            private_categories,
+            category_details: HashMap::new(),
+            tags,
+            tag_map,
+            events,
+            next_event_id,
+            daily_notes,
+            locked_until,
+            skipped_days,
+            reports,
+        }
+    }
+}
+
+impl Upgrade for UnverifiedSaveDataV17 {
+    type Next = UnverifiedSaveDataV18;
+    fn upgrade(self) -> Self::Next {
+        let UnverifiedSaveDataV17 {
+            categories,
+            archived_categories,
+            private_categories,
+            category_details,
+            tags,
+            tag_map,
+            events,
+            next_event_id,
+            daily_notes,
+            locked_until,
+            skipped_days,
+            reports,
+        } = self;
+        UnverifiedSaveDataV18 {
+            categories,
+            archived_categories,
+            private_categories,
+            category_details,
+            category_rates: HashMap::new(),
+            tags,
+            tag_map,
+            events,
+            next_event_id,
+            daily_notes,
+            locked_until,
+            skipped_days,
+            reports,
+        }
+    }
+}
+
+impl Upgrade for UnverifiedSaveDataV18 {
+    type Next = UnverifiedSaveDataV19;
+    fn upgrade(self) -> Self::Next {
+        let UnverifiedSaveDataV18 {
+            categories,
+            archived_categories,
+            private_categories,
+            category_details,
+            category_rates,
+            tags,
+            tag_map,
+            events,
+            next_event_id,
+            daily_notes,
+            locked_until,
+            skipped_days,
+            reports,
+        } = self;
+        UnverifiedSaveDataV19 {
+            categories,
+            archived_categories,
+            private_categories,
+            category_details: category_details.into_iter().collect(),
+            category_rates: category_rates.into_iter().collect(),
+            category_goals: BTreeMap::new(),
+            tags,
+            tag_map: tag_map.into_iter().collect(),
+            tag_goals: BTreeMap::new(),
+            events,
+            next_event_id,
+            daily_notes: daily_notes.into_iter().collect(),
+            locked_until,
+            skipped_days,
+            reports,
+        }
+    }
+}
+
 // ================================= END VERSIONING WORK =================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{date} {time}"), "%Y-%m-%d %H:%M").expect("test times should parse")
+    }
+
+    fn schedule(s: &str) -> ReportSchedule {
+        s.parse().expect("test schedules should parse")
+    }
+
+    #[test]
+    fn parse_schedule() {
+        assert_eq!(schedule("daily 18:00"), ReportSchedule { every: ScheduleEvery::Day, at: NaiveTime::from_hms_opt(18, 0, 0).unwrap() });
+        assert_eq!(schedule("weekly mon 08:00").every, ScheduleEvery::Week(Weekday::Mon));
+        assert_eq!(schedule("monthly 31 09:00").every, ScheduleEvery::Month(31));
+        for s in ["daily 18:00", "weekly fri 17:30", "monthly 1 09:00"] {
+            assert_eq!(schedule(s).to_string(), s);
+        }
+        assert!("monthly 0 09:00".parse::<ReportSchedule>().is_err());
+        assert!("monthly 32 09:00".parse::<ReportSchedule>().is_err());
+        assert!("weekly someday 09:00".parse::<ReportSchedule>().is_err());
+        assert!("daily 25:00".parse::<ReportSchedule>().is_err());
+        assert!("hourly".parse::<ReportSchedule>().is_err());
+    }
+
+    #[test]
+    fn latest_daily() {
+        let daily = schedule("daily 18:00");
+        assert_eq!(daily.latest(at("2024-05-08", "18:00")), at("2024-05-08", "18:00"));
+        assert_eq!(daily.latest(at("2024-05-08", "23:59")), at("2024-05-08", "18:00"));
+        // not yet due today, so yesterday's run is the latest
+        assert_eq!(daily.latest(at("2024-05-08", "17:59")), at("2024-05-07", "18:00"));
+        assert_eq!(daily.latest(at("2024-03-01", "09:00")), at("2024-02-29", "18:00"));
+    }
+
+    #[test]
+    fn latest_weekly() {
+        // 2024-05-06 is a Monday
+        let weekly = schedule("weekly mon 08:00");
+        assert_eq!(weekly.latest(at("2024-05-06", "08:00")), at("2024-05-06", "08:00"));
+        assert_eq!(weekly.latest(at("2024-05-09", "12:00")), at("2024-05-06", "08:00"));
+        assert_eq!(weekly.latest(at("2024-05-12", "23:59")), at("2024-05-06", "08:00"));
+        // due on a Monday, but not yet
+        assert_eq!(weekly.latest(at("2024-05-06", "07:59")), at("2024-04-29", "08:00"));
+        let friday = schedule("weekly fri 17:00");
+        assert_eq!(friday.latest(at("2024-05-06", "08:00")), at("2024-05-03", "17:00"));
+    }
+
+    #[test]
+    fn latest_monthly() {
+        let monthly = schedule("monthly 15 09:00");
+        assert_eq!(monthly.latest(at("2024-05-15", "09:00")), at("2024-05-15", "09:00"));
+        assert_eq!(monthly.latest(at("2024-05-20", "12:00")), at("2024-05-15", "09:00"));
+        assert_eq!(monthly.latest(at("2024-05-15", "08:59")), at("2024-04-15", "09:00"));
+        assert_eq!(monthly.latest(at("2024-05-01", "12:00")), at("2024-04-15", "09:00"));
+        assert_eq!(monthly.latest(at("2024-01-10", "12:00")), at("2023-12-15", "09:00"));
+    }
+
+    #[test]
+    fn latest_monthly_clamps_to_month_end() {
+        let monthly = schedule("monthly 31 09:00");
+        assert_eq!(monthly.latest(at("2024-01-31", "10:00")), at("2024-01-31", "09:00"));
+        assert_eq!(monthly.latest(at("2024-02-29", "09:00")), at("2024-02-29", "09:00"));
+        assert_eq!(monthly.latest(at("2023-02-28", "09:00")), at("2023-02-28", "09:00"));
+        assert_eq!(monthly.latest(at("2024-04-30", "12:00")), at("2024-04-30", "09:00"));
+        // not yet due on the last day of April, so the run at the end of March is the latest
+        assert_eq!(monthly.latest(at("2024-04-30", "08:00")), at("2024-03-31", "09:00"));
+        assert_eq!(monthly.latest(at("2024-03-15", "12:00")), at("2024-02-29", "09:00"));
+    }
+}
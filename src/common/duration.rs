@@ -0,0 +1,215 @@
+use std::{
+    fmt::Display,
+    iter::Sum,
+    ops::{Add, AddAssign, Div, Neg, Sub, SubAssign},
+};
+
+use chrono::TimeDelta;
+
+/// An amount of tracked time, in whole seconds. Every total Taskit reports is a sum of these, so
+/// arithmetic saturates instead of overflowing or panicking, and division rounds towards zero the
+/// same way everywhere.
+///
+/// Wall-clock arithmetic on dates and instants still uses chrono's TimeDelta; convert with `From`
+/// at the boundary.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Duration {
+    seconds: i64,
+}
+
+impl Duration {
+    pub const ZERO: Duration = Duration { seconds: 0 };
+
+    pub const fn seconds(seconds: i64) -> Self {
+        Self { seconds }
+    }
+
+    pub const fn minutes(minutes: i64) -> Self {
+        Self { seconds: minutes.saturating_mul(60) }
+    }
+
+    pub const fn hours(hours: i64) -> Self {
+        Self { seconds: hours.saturating_mul(60 * 60) }
+    }
+
+    pub fn num_seconds(self) -> i64 {
+        self.seconds
+    }
+
+    /// Whole minutes, rounded towards zero
+    pub fn num_minutes(self) -> i64 {
+        self.seconds / 60
+    }
+
+    /// Whole hours, rounded towards zero
+    pub fn num_hours(self) -> i64 {
+        self.seconds / (60 * 60)
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.seconds == 0
+    }
+
+    /// How much of `whole` this is, as a fraction (so 0.5 for half). Nothing is a fraction of
+    /// nothing, so this is 0 when `whole` is zero.
+    pub fn fraction_of(self, whole: Duration) -> f64 {
+        if whole.is_zero() {
+            0.0
+        } else {
+            self.seconds as f64 / whole.seconds as f64
+        }
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self { seconds: self.seconds.saturating_add(rhs.seconds) }
+    }
+}
+
+impl AddAssign for Duration {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Duration {
+    type Output = Duration;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self { seconds: self.seconds.saturating_sub(rhs.seconds) }
+    }
+}
+
+impl SubAssign for Duration {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl Neg for Duration {
+    type Output = Duration;
+
+    fn neg(self) -> Self::Output {
+        Self { seconds: self.seconds.saturating_neg() }
+    }
+}
+
+/// Splits a duration into `rhs` equal parts, like for an average. Dividing by zero gives zero,
+/// since an average over no days or no events is best shown as nothing.
+impl Div<i64> for Duration {
+    type Output = Duration;
+
+    fn div(self, rhs: i64) -> Self::Output {
+        Self { seconds: self.seconds.checked_div(rhs).unwrap_or(0) }
+    }
+}
+
+impl Sum for Duration {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Duration> for Duration {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+impl From<TimeDelta> for Duration {
+    /// Drops anything shorter than a second
+    fn from(delta: TimeDelta) -> Self {
+        Self { seconds: delta.num_seconds() }
+    }
+}
+
+impl From<Duration> for TimeDelta {
+    fn from(duration: Duration) -> Self {
+        TimeDelta::try_seconds(duration.seconds).unwrap_or(if duration.seconds < 0 {
+            TimeDelta::MIN
+        } else {
+            TimeDelta::MAX
+        })
+    }
+}
+
+/// Formats a duration like `1h30m`. Zero durations are formatted as an empty string, and seconds
+/// are only shown for durations too short to have any whole minutes.
+impl Display for Duration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.num_hours() != 0 {
+            write!(f, "{}h", self.num_hours())?;
+        }
+        if self.num_minutes() % 60 != 0 {
+            write!(f, "{}m", self.num_minutes() % 60)?;
+        }
+        if self.num_minutes() == 0 && self.seconds != 0 {
+            write!(f, "{}s", self.seconds)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_saturate() {
+        let durations = [Duration::seconds(i64::MAX), Duration::minutes(1), Duration::hours(1)];
+        assert_eq!(durations.iter().sum::<Duration>(), Duration::seconds(i64::MAX));
+        assert_eq!(Duration::seconds(i64::MIN) - Duration::seconds(1), Duration::seconds(i64::MIN));
+        assert_eq!(Duration::minutes(i64::MAX), Duration::seconds(i64::MAX));
+        assert_eq!(-Duration::seconds(i64::MIN), Duration::seconds(i64::MAX));
+    }
+
+    #[test]
+    fn sums_keep_seconds() {
+        // three 20 second stopwatch runs make a minute, even though none of them has a whole minute
+        let total: Duration = [Duration::seconds(20); 3].into_iter().sum();
+        assert_eq!(total, Duration::minutes(1));
+        assert_eq!(total.num_minutes(), 1);
+    }
+
+    #[test]
+    fn division() {
+        assert_eq!(Duration::minutes(10) / 4, Duration::seconds(150));
+        assert_eq!(Duration::seconds(7) / 2, Duration::seconds(3));
+        assert_eq!(Duration::hours(1) / 0, Duration::ZERO);
+        assert_eq!(Duration::seconds(i64::MIN) / -1, Duration::ZERO);
+    }
+
+    #[test]
+    fn fractions() {
+        assert_eq!(Duration::minutes(15).fraction_of(Duration::hours(1)), 0.25);
+        assert_eq!(Duration::minutes(15).fraction_of(Duration::ZERO), 0.0);
+    }
+
+    #[test]
+    fn whole_units_round_towards_zero() {
+        assert_eq!(Duration::seconds(119).num_minutes(), 1);
+        assert_eq!(Duration::seconds(-119).num_minutes(), -1);
+        assert_eq!(Duration::minutes(179).num_hours(), 2);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Duration::ZERO.to_string(), "");
+        assert_eq!(Duration::seconds(45).to_string(), "45s");
+        assert_eq!(Duration::seconds(61).to_string(), "1m");
+        assert_eq!(Duration::minutes(90).to_string(), "1h30m");
+        assert_eq!(Duration::hours(2).to_string(), "2h");
+        assert_eq!(Duration::minutes(-90).to_string(), "-1h-30m");
+    }
+
+    #[test]
+    fn time_delta_round_trip() {
+        let delta = TimeDelta::milliseconds(90_500);
+        assert_eq!(Duration::from(delta), Duration::seconds(90));
+        assert_eq!(TimeDelta::from(Duration::seconds(90)), TimeDelta::seconds(90));
+        assert_eq!(TimeDelta::from(Duration::seconds(i64::MAX)), TimeDelta::MAX);
+    }
+}
@@ -1,5 +1,6 @@
 use std::{error::Error, fmt::Display, io};
 
+use chrono::NaiveDate;
 use inquire::InquireError;
 
 #[derive(Debug)]
@@ -13,14 +14,61 @@ pub enum Kind {
     Cancelled,
     CategoryArchived(String),
     NoSuchCategory(String),
+    NoSuchTag(String),
     DuplicateCategory(String),
     CategoryNotEmpty(String),
-    Other(Box<dyn Error>),
+    Other(Box<dyn Error + Send + Sync>),
     NoSpaceInTag,
+    #[cfg(feature = "push")]
+    InvalidTemplate(String),
+    #[cfg(feature = "tui")]
+    InvalidRegex(String),
+    UnsupportedFormat(String),
+    InvalidImport(String),
+    PeriodLocked(NaiveDate),
+    NoSuchField(String),
+    InvalidYear(i32),
+    NothingToUndo,
+    NoSuchHistoryEntry(usize),
+    NoSuchReport(String),
+    NoSuchMacro(String),
+    /// The command being redone ran, but failed
+    RedoFailed(String),
+    NoStopwatchToResume,
+    NoMatchingEvents,
+    ExpensesNotConfigured,
+    ReportsNotConfigured,
+    CapacityNotConfigured,
+    #[cfg(feature = "push")]
+    PushNotConfigured,
+    /// A `[push]` header whose name or value, once filled in, has a line break or other control
+    /// character, which could end the header early and start another
+    #[cfg(feature = "push")]
+    InvalidHeader(String),
+    #[cfg(feature = "certify")]
+    SigningNotConfigured,
+    /// A certified export whose hash chain or signature doesn't check out
+    #[cfg(feature = "certify")]
+    TamperedExport(String),
+    /// The undo journal couldn't be read or no longer matches the save file
+    InvalidJournal(String),
+    /// A save file other than the current one couldn't be read
+    InvalidSave(String),
+    NoSuchProfile(String),
+    DuplicateProfile(String),
+    /// A profile name that can't be a directory name
+    InvalidProfileName(String),
+    /// The profile being deleted is the one taskit was run with
+    ProfileInUse(String),
+    /// The passphrase given for a profile isn't the one it was set up with
+    WrongPassphrase(String),
+    /// The save is already in the kind of storage it was asked to move to, named by extension
+    AlreadyStoredAs(&'static str),
+    NoBackups,
 }
 
 /// Used in TaskitError for "error occurred while attempting to [...]"
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Source {
     CreatingTag,
     CreatingEntry,
@@ -33,10 +81,33 @@ pub enum Source {
     EditingNote,
     UpdatingCategory,
     DrawingTui,
+    #[cfg(feature = "tui")]
     SettingFilter,
     ConfirmingDelete,
     DeletingCategory,
     DeletingTag,
+    Exporting,
+    GatheringActivity,
+    Importing,
+    SavingChanges,
+    ClosingMonth,
+    Reporting,
+    SkippingDays,
+    Undoing,
+    Redoing,
+    Diffing,
+    ViewingPast,
+    #[cfg(feature = "push")]
+    Pushing,
+    #[cfg(feature = "certify")]
+    Certifying,
+    RunningMacro,
+    Tidying,
+    ManagingProfiles,
+    OpeningProfile,
+    Restoring,
+    ReadingSave,
+    MigratingStorage,
 }
 
 pub type TaskitResult<T> = Result<T, TaskitError>;
@@ -55,10 +126,33 @@ impl Source {
             Source::EditingNote => "editing a daily note",
             Source::UpdatingCategory => "updating a category",
             Source::DrawingTui => "performing TUI operations",
+            #[cfg(feature = "tui")]
             Source::SettingFilter => "setting a filter",
             Source::ConfirmingDelete => "confirming deletion",
             Source::DeletingCategory => "deleting a category",
             Source::DeletingTag => "deleting a tag",
+            Source::Exporting => "exporting data",
+            Source::GatheringActivity => "reading activity from git or a calendar",
+            Source::Importing => "importing events",
+            Source::SavingChanges => "saving changes",
+            Source::ClosingMonth => "closing a month",
+            Source::Reporting => "building a report",
+            Source::SkippingDays => "marking days as skipped",
+            Source::Undoing => "undoing the last change",
+            Source::Redoing => "re-running a command",
+            Source::Diffing => "comparing save files",
+            Source::ViewingPast => "rebuilding past data from the journal",
+            #[cfg(feature = "push")]
+            Source::Pushing => "pushing events",
+            #[cfg(feature = "certify")]
+            Source::Certifying => "certifying an export",
+            Source::RunningMacro => "running a macro",
+            Source::Tidying => "tidying up short events",
+            Source::ManagingProfiles => "managing profiles",
+            Source::OpeningProfile => "opening a profile",
+            Source::Restoring => "restoring a backup",
+            Source::ReadingSave => "reading the save file",
+            Source::MigratingStorage => "moving the save to another kind of storage",
         }
     }
 }
@@ -71,9 +165,46 @@ impl Display for TaskitError {
             Kind::Cancelled => write!(f, "User cancelled while {activity}."),
             Kind::CategoryArchived(c) => write!(f, "Operation ({activity}) could not be completed because category {c} is archived."),
             Kind::NoSuchCategory(c) => write!(f, "While {activity}, tried to use category '{c}', which doesn't exist."),
+            Kind::NoSuchTag(t) => write!(f, "While {activity}, tried to use tag '#{t}', which doesn't exist."),
             Kind::DuplicateCategory(c) => write!(f, "While {activity}, tried to create category '{c}', which already exists."),
             Kind::CategoryNotEmpty(c) => write!(f, "Category {c} was not empty while {activity}."),
             Kind::NoSpaceInTag => write!(f, "Spaces aren't allowed in tags. Occurred while {activity}."),
+            Kind::UnsupportedFormat(file) => write!(f, "While {activity}, tried to use an unsupported file format ({file})."),
+            Kind::PeriodLocked(date) => write!(f, "While {activity}, tried to change something on or before {date}, which has been closed (`taskit close-month --reopen` unlocks it)."),
+            Kind::InvalidYear(year) => write!(f, "While {activity}, got year {year}, which is out of range."),
+            Kind::NoSuchField(name) => write!(f, "While {activity}, tried to use field '{name}', which isn't a built-in field or declared in the config."),
+            Kind::NoMatchingEvents => write!(f, "No events matched while {activity}."),
+            Kind::NoStopwatchToResume => write!(f, "While {activity}, found no interrupted stopwatch to resume."),
+            Kind::ExpensesNotConfigured => write!(f, "While {activity}, found no `amount_field` under `[expenses]` in the config to read expenses from."),
+            Kind::ReportsNotConfigured => write!(f, "While {activity}, found no `output_dir` under `[reports]` in the config to write scheduled reports to."),
+            Kind::CapacityNotConfigured => write!(f, "While {activity}, found no `weekly_hours` under `[capacity]` in the config to compare against."),
+            #[cfg(feature = "push")]
+            Kind::PushNotConfigured => write!(f, "While {activity}, found no `url` under `[push]` in the config to push to."),
+            #[cfg(feature = "push")]
+            Kind::InvalidHeader(name) => write!(f, "While {activity}, found a line break or other control character in the {name:?} header, so it wasn't sent."),
+            #[cfg(feature = "certify")]
+            Kind::SigningNotConfigured => write!(f, "While {activity}, found no `secret_key` under `[certify]` in the config to sign with. Pass `--unsigned` to export without a signature."),
+            #[cfg(feature = "certify")]
+            Kind::TamperedExport(e) => write!(f, "While {activity}, found that the export doesn't verify: {e}."),
+            Kind::NothingToUndo => write!(f, "There was nothing to undo while {activity}."),
+            Kind::NoSuchHistoryEntry(n) => write!(f, "While {activity}, found no command number {n} in the history (see `taskit history`)."),
+            Kind::NoSuchReport(name) => write!(f, "While {activity}, found no saved report named '{name}'."),
+            Kind::NoSuchMacro(name) => write!(f, "While {activity}, found no macro named '{name}' in the config."),
+            Kind::RedoFailed(command) => write!(f, "While {activity}, `taskit {command}` failed."),
+            Kind::InvalidJournal(e) => write!(f, "While {activity}, found that the undo journal can't be used ({e})."),
+            Kind::InvalidSave(e) => write!(f, "While {activity}, found a save file that can't be used ({e})."),
+            Kind::NoSuchProfile(name) => write!(f, "While {activity}, found no profile named '{name}' (see `taskit profile list`)."),
+            Kind::DuplicateProfile(name) => write!(f, "While {activity}, tried to create profile '{name}', which already exists."),
+            Kind::InvalidProfileName(name) => write!(f, "While {activity}, got profile name '{name}', which can only have letters, digits, '-', and '_'."),
+            Kind::ProfileInUse(name) => write!(f, "While {activity}, tried to delete profile '{name}', which is the one in use."),
+            Kind::WrongPassphrase(name) => write!(f, "While {activity}, got the wrong passphrase for profile '{name}'."),
+            Kind::AlreadyStoredAs(kind) => write!(f, "While {activity}, found the save already stored as {kind}."),
+            Kind::NoBackups => write!(f, "While {activity}, found no backups of the save file."),
+            Kind::InvalidImport(e) => write!(f, "While {activity}, found an invalid entry ({e})."),
+            #[cfg(feature = "push")]
+            Kind::InvalidTemplate(placeholder) => write!(f, "While {activity}, found {placeholder} in a template, which isn't a known placeholder."),
+            #[cfg(feature = "tui")]
+            Kind::InvalidRegex(e) => write!(f, "While {activity}, got an invalid regular expression:\n{e}\n"),
             Kind::Other(error) => write!(f, "An external error occurred while {activity}: {error}"),
         }
     }
@@ -114,6 +245,22 @@ impl With<Source> for io::Error {
     }
 }
 
+#[cfg(feature = "sqlite")]
+impl From<(rusqlite::Error, Source)> for TaskitError {
+    fn from((value, source): (rusqlite::Error, Source)) -> Self {
+        Self { kind: Kind::Other(Box::new(value)), source }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl With<Source> for rusqlite::Error {
+    type Joined = (Self, Source);
+
+    fn with(self, source: Source) -> Self::Joined {
+        (self, source)
+    }
+}
+
 impl With<Source> for InquireError {
     type Joined = (Self, Source);
 
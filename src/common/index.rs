@@ -0,0 +1,191 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use chrono::NaiveDate;
+use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::common::{Event, EventId, SaveData};
+
+/// A trigram index over a set of keyed strings. Looking up a query returns a superset of the keys
+/// whose text contains the query (ignoring case), so callers still need to check each candidate,
+/// but the number of candidates is usually tiny compared to the full list.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(bound(serialize = "K: Serialize", deserialize = "K: Deserialize<'de> + Ord"))]
+pub struct TrigramIndex<K> {
+    postings: BTreeMap<String, BTreeSet<K>>,
+}
+
+/// Indexes event descriptions by event id and daily notes by date, for `taskit search` and the
+/// dashboard's description filter. It's kept in a file next to the save file, written again
+/// whenever the save is, along with a hash of the save so that one changed some other way (by a
+/// restore, or by hand) is noticed and indexed again.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TextIndex {
+    save_hash: String,
+    pub events: TrigramIndex<EventId>,
+    pub notes: TrigramIndex<NaiveDate>,
+}
+
+static MATCHER: LazyLock<SkimMatcherV2> = LazyLock::new(|| SkimMatcherV2::default().smart_case());
+
+/// Skim-style fuzzy match of `pattern` against `text`. Returns the score (higher is better) and the
+/// char indices in `text` that were matched, or None if it doesn't match at all.
+pub fn fuzzy_match(text: &str, pattern: &str) -> Option<(i64, Vec<usize>)> {
+    MATCHER.fuzzy_indices(text, pattern)
+}
+
+fn trigrams(s: &str) -> impl Iterator<Item = String> {
+    let chars: Vec<char> = s.to_lowercase().chars().collect();
+    (0..chars.len().saturating_sub(2)).map(move |i| chars[i..i + 3].iter().collect())
+}
+
+impl<K> Default for TrigramIndex<K> {
+    fn default() -> Self {
+        Self {
+            postings: BTreeMap::new(),
+        }
+    }
+}
+
+impl<K: Ord + Copy> TrigramIndex<K> {
+    pub fn insert(&mut self, key: K, text: &str) {
+        for trigram in trigrams(text) {
+            self.postings.entry(trigram).or_default().insert(key);
+        }
+    }
+
+    /// Takes out a key that was inserted with `text`
+    #[cfg(feature = "tui")]
+    pub fn remove(&mut self, key: K, text: &str) {
+        for trigram in trigrams(text) {
+            if let Some(keys) = self.postings.get_mut(&trigram) {
+                keys.remove(&key);
+                if keys.is_empty() {
+                    self.postings.remove(&trigram);
+                }
+            }
+        }
+    }
+
+    /// Returns None if the query is too short to narrow anything down, in which case every key is
+    /// a candidate.
+    pub fn candidates(&self, query: &str) -> Option<BTreeSet<K>> {
+        let mut trigrams = trigrams(query).peekable();
+        trigrams.peek()?;
+        let mut out: Option<BTreeSet<K>> = None;
+        for trigram in trigrams {
+            let Some(keys) = self.postings.get(&trigram) else {
+                return Some(BTreeSet::new());
+            };
+            out = Some(match out {
+                Some(set) => set.intersection(keys).copied().collect(),
+                None => keys.clone(),
+            });
+        }
+        out
+    }
+}
+
+/// The index sits next to the save file, like the undo journal
+pub fn index_path(save_path: &Path) -> PathBuf {
+    save_path.with_extension("index.json")
+}
+
+fn hash(save: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(save))
+}
+
+impl TextIndex {
+    pub fn new(save_data: &SaveData) -> Self {
+        let mut index = Self {
+            save_hash: String::new(),
+            events: TrigramIndex::default(),
+            notes: TrigramIndex::default(),
+        };
+        for ev in &save_data.events {
+            index.insert_event(ev);
+        }
+        for (date, note) in &save_data.daily_notes {
+            index.notes.insert(*date, note);
+        }
+        index
+    }
+
+    /// Reads the index of the save file at `save_path`, which `save_data` was read from. If the
+    /// index is missing or was made for a different version of the save, it's built again and, if
+    /// possible, written for next time.
+    pub fn load(save_path: &Path, save_data: &SaveData) -> Self {
+        let save = fs::read(save_path).unwrap_or_default();
+        let stored = fs::read(index_path(save_path))
+            .ok()
+            .and_then(|index| serde_json::from_slice::<TextIndex>(&index).ok())
+            .filter(|index| index.save_hash == hash(&save));
+        stored.unwrap_or_else(|| {
+            let mut index = TextIndex::new(save_data);
+            if let Err(e) = index.write(save_path, &save) {
+                eprintln!("Warning: the search index couldn't be written: {e}");
+            }
+            index
+        })
+    }
+
+    /// Writes the index next to the save file at `save_path`, whose contents are `save`
+    pub fn write(&mut self, save_path: &Path, save: &[u8]) -> io::Result<()> {
+        self.save_hash = hash(save);
+        let path = index_path(save_path);
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_vec(self)?)?;
+        fs::rename(temp_path, path)
+    }
+
+    pub fn insert_event(&mut self, ev: &Event) {
+        self.events.insert(ev.id, &ev.description);
+    }
+
+    /// Takes out an event, as it was when it was indexed
+    #[cfg(feature = "tui")]
+    pub fn remove_event(&mut self, ev: &Event) {
+        self.events.remove(ev.id, &ev.description);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> TrigramIndex<u32> {
+        let mut index = TrigramIndex::default();
+        index.insert(1, "Parser work");
+        index.insert(2, "Standup");
+        index.insert(3, "Reviewing the parser");
+        index
+    }
+
+    #[test]
+    fn candidates_ignore_case() {
+        assert_eq!(index().candidates("PARSER"), Some(BTreeSet::from([1, 3])));
+        assert_eq!(index().candidates("stand"), Some(BTreeSet::from([2])));
+        assert_eq!(index().candidates("nothing"), Some(BTreeSet::new()));
+    }
+
+    #[test]
+    fn short_queries_match_everything() {
+        assert_eq!(index().candidates("pa"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "tui")]
+    fn removed_keys_are_not_candidates() {
+        let mut index = index();
+        index.remove(1, "Parser work");
+        assert_eq!(index.candidates("parser"), Some(BTreeSet::from([3])));
+        assert_eq!(index.candidates("work"), Some(BTreeSet::new()));
+    }
+}
@@ -1,8 +1,10 @@
-use std::{collections::{HashMap, HashSet}, fmt::Display, ops::Deref, sync::Arc};
+use std::{collections::{BTreeMap, HashMap, HashSet}, fmt::Display, iter, ops::Deref, sync::Arc};
 
-use chrono::NaiveDate;
+use chrono::{Local, NaiveDate};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
-use crate::{common::{Apply, DeltaItem, SimpleTime, UnverifiedEventV5, UnverifiedSaveDataLatest, UnverifiedSaveDataVersioned, error::TaskitResult}, input::get_description_tags, util::SetVec};
+use crate::{common::{Apply, CategoryDetails, DeltaItem, Duration, Goal, ReportSpec, SimpleTime, EventSource, FieldValue, UnverifiedDelta, UnverifiedEventV15, UnverifiedSaveDataLatest, UnverifiedSaveDataVersioned, error::{Kind, Source, TaskitResult, With}, get_description_tags}, util::SetVec};
 
 /// Each of these represents an invariant for the SaveData struct.
 #[allow(unused)] // for now we need this because the fields are only used for Debug impl
@@ -25,11 +27,26 @@ pub enum VerificationError {
     EventInvalidCategory(String),
     /// each element of `event.tags` should be an element of `tags`
     EventInvalidTag(String),
+    /// every element of `private_categories` should be an element of `categories` U
+    /// `archived_categories`
+    PrivateInvalidCategory(String),
+    /// every key of `category_details` should be an element of `categories` U `archived_categories`
+    DetailsInvalidCategory(String),
+    /// every key of `category_rates` should be an element of `categories` U `archived_categories`
+    RateInvalidCategory(String),
+    /// every key of `category_goals` should be an element of `categories` U `archived_categories`
+    GoalInvalidCategory(String),
+    /// every key of `tag_goals` should be an element of `tags`
+    GoalInvalidTag(String),
     /// `event.tags` should equal the list of words prefixed with `#` in event.description
     EventTagsMismatch {
         in_string: HashSet<String>,
         in_vec: HashSet<String>,
     },
+    /// each event's id should be unique
+    NonUniqueEventIds(u64),
+    /// each event's id should be at least 1 and less than `next_event_id`
+    EventIdOutOfRange(u64),
 }
 
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
@@ -53,24 +70,72 @@ impl Tag {
     }
 }
 
+/// Identifies an event for as long as it exists, however the events around it change
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct EventId(u64);
+
+impl EventId {
+    /// The id of an event that hasn't been added yet. Adding it gives it a real one.
+    pub const NEW: EventId = EventId(0);
+}
+
+impl Display for EventId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<EventId> for u64 {
+    fn from(id: EventId) -> Self {
+        id.0
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Event {
+    pub id: EventId,
     pub start_time: SimpleTime,
     pub end_time: SimpleTime, // if end_time before start_time: counts as that time on date + 1
     pub date: NaiveDate,
     pub category: Category,
     pub description: String,
     pub tags: HashSet<Tag>,
+    /// Pinned events are milestones that should be easy to find later
+    pub pinned: bool,
+    pub source: EventSource,
+    /// The id the event has in the system it was imported from, if any. Together with `source`,
+    /// this lets re-running an import update events instead of duplicating them.
+    pub external_id: Option<String>,
+    /// Values of user-defined fields, by field name
+    pub fields: BTreeMap<String, FieldValue>,
 }
 
 #[derive(Clone, Debug)]
 pub struct SaveData {
     pub categories: SetVec<Category>,
     pub archived_categories: SetVec<Category>,
+    /// Categories whose events are left out of shared output (like `taskit share`)
+    pub private_categories: HashSet<Category>,
+    /// Colors and descriptions of categories that have them
+    pub category_details: HashMap<Category, CategoryDetails>,
+    /// Hourly rates of categories that are billed (see `taskit invoice`)
+    pub category_rates: HashMap<Category, f64>,
+    /// Weekly goals of categories that have them (see `taskit goals`)
+    pub category_goals: HashMap<Category, Goal>,
     pub tags: SetVec<Tag>,
     pub tag_map: HashMap<Category, HashSet<Tag>>,
+    /// Weekly goals of tags that have them
+    pub tag_goals: HashMap<Tag, Goal>,
     pub events: Vec<Event>,
+    /// The id the next event to be added will get
+    next_event_id: u64,
     pub daily_notes: HashMap<NaiveDate, String>,
+    /// Events and notes on or before this date can no longer be changed (see `taskit close-month`)
+    pub locked_until: Option<NaiveDate>,
+    /// Days intentionally left untracked (see `taskit skip`), with the reason, which may be empty
+    pub skipped_days: BTreeMap<NaiveDate, String>,
+    /// Reports saved from `taskit report`, by name
+    pub reports: BTreeMap<String, ReportSpec>,
 }
 
 
@@ -100,6 +165,30 @@ impl UnverifiedSaveDataLatest {
                     .map_err(|s| VerificationError::NonUniqueCategories(s.0.to_string(), false))
             )?;
 
+        // VerificationError::PrivateInvalidCategory
+        let private_categories = self.private_categories
+            .into_iter()
+            .map(|name| categories.iter().chain(archived_categories.iter()).find(|cat| cat.inner() == name).cloned().ok_or(VerificationError::PrivateInvalidCategory(name)))
+            .collect::<Result<_, _>>()?;
+
+        // VerificationError::DetailsInvalidCategory
+        let category_details = self.category_details
+            .into_iter()
+            .map(|(name, details)| categories.iter().chain(archived_categories.iter()).find(|cat| cat.inner() == name).map(|cat| (cat.clone(), details)).ok_or(VerificationError::DetailsInvalidCategory(name)))
+            .collect::<Result<_, _>>()?;
+
+        // VerificationError::RateInvalidCategory
+        let category_rates = self.category_rates
+            .into_iter()
+            .map(|(name, rate)| categories.iter().chain(archived_categories.iter()).find(|cat| cat.inner() == name).map(|cat| (cat.clone(), rate)).ok_or(VerificationError::RateInvalidCategory(name)))
+            .collect::<Result<_, _>>()?;
+
+        // VerificationError::GoalInvalidCategory
+        let category_goals = self.category_goals
+            .into_iter()
+            .map(|(name, goal)| categories.iter().chain(archived_categories.iter()).find(|cat| cat.inner() == name).map(|cat| (cat.clone(), goal)).ok_or(VerificationError::GoalInvalidCategory(name)))
+            .collect::<Result<_, _>>()?;
+
         // VerificationError::NonUniqueTags
         let mut tags = SetVec::new();
         for i in self.tags {
@@ -115,6 +204,12 @@ impl UnverifiedSaveDataLatest {
             }
         }
 
+        // VerificationError::GoalInvalidTag
+        let tag_goals = self.tag_goals
+            .into_iter()
+            .map(|(name, goal)| tags.find(&name).map(|tag| (tag.clone(), goal)).ok_or(VerificationError::GoalInvalidTag(name)))
+            .collect::<Result<_, _>>()?;
+
         // VerificationError::TagMapInvalidCategory & VerificationError::TagMapInvalidTag
         let mut tag_map = HashMap::new();
         for (cat, map_tags) in self.tag_map {
@@ -132,12 +227,21 @@ impl UnverifiedSaveDataLatest {
 
         // event errors
         let mut events = Vec::new();
+        let mut ids = HashSet::new();
         for event in self.events {
+            // VerificationError::NonUniqueEventIds & VerificationError::EventIdOutOfRange
+            if !ids.insert(event.id) {
+                return Err(VerificationError::NonUniqueEventIds(event.id));
+            }
+            if event.id == 0 || event.id >= self.next_event_id {
+                return Err(VerificationError::EventIdOutOfRange(event.id));
+            }
+
             // VerificationError::EventInvalidCategory
             let category = categories.iter().chain(archived_categories.iter()).find(|cat| cat.inner() == event.category).ok_or_else(|| VerificationError::EventInvalidCategory(event.category))?.clone();
 
             // VerificationError::EventInvalidTag
-            let tags = event.tags.into_iter().map(|tag|
+            let tags = event.tags.into_iter().map(|tag: String|
                 tags
                 .find(&tag)
                 .cloned()
@@ -153,22 +257,36 @@ impl UnverifiedSaveDataLatest {
                 });
             }
             events.push(Event {
+                id: EventId(event.id),
                 start_time: event.start_time, 
                 end_time: event.end_time, 
                 date: event.date, 
                 category, 
                 description: event.description, 
-                tags
+                tags,
+                pinned: event.pinned,
+                source: event.source,
+                external_id: event.external_id,
+                fields: event.fields,
             })
         }
 
         Ok(SaveData {
             categories,
             archived_categories,
+            private_categories,
+            category_details,
+            category_rates,
+            category_goals,
             tags,
             tag_map,
+            tag_goals,
             events,
-            daily_notes: self.daily_notes,
+            next_event_id: self.next_event_id,
+            daily_notes: self.daily_notes.into_iter().collect(),
+            locked_until: self.locked_until,
+            skipped_days: self.skipped_days,
+            reports: self.reports,
         })
     }
 
@@ -185,6 +303,30 @@ impl UnverifiedSaveDataLatest {
         let categories: SetVec<_> = self.categories.into_iter().map(Category::new).collect();
         let archived_categories: SetVec<_> = self.archived_categories.into_iter().map(Category::new).collect();
 
+        // VerificationError::PrivateInvalidCategory
+        let private_categories = self.private_categories
+            .into_iter()
+            .filter_map(|name| categories.iter().chain(archived_categories.iter()).find(|cat| cat.inner() == name).cloned())
+            .collect();
+
+        // VerificationError::DetailsInvalidCategory
+        let category_details = self.category_details
+            .into_iter()
+            .filter_map(|(name, details)| categories.iter().chain(archived_categories.iter()).find(|cat| cat.inner() == name).map(|cat| (cat.clone(), details)))
+            .collect();
+
+        // VerificationError::RateInvalidCategory
+        let category_rates = self.category_rates
+            .into_iter()
+            .filter_map(|(name, rate)| categories.iter().chain(archived_categories.iter()).find(|cat| cat.inner() == name).map(|cat| (cat.clone(), rate)))
+            .collect();
+
+        // VerificationError::GoalInvalidCategory
+        let category_goals = self.category_goals
+            .into_iter()
+            .filter_map(|(name, goal)| categories.iter().chain(archived_categories.iter()).find(|cat| cat.inner() == name).map(|cat| (cat.clone(), goal)))
+            .collect();
+
         // VerificationError::TagWithSpace
         let mut changes = Vec::new();
         for (i, tag) in self.tags.iter().enumerate() {
@@ -206,6 +348,12 @@ impl UnverifiedSaveDataLatest {
         // TODO: why does the below line compile? (does it actually? does it also for ordinary Vec?)
         // tags.iter_mut().map(|e| *e = Tag(String::new()));
 
+        // VerificationError::GoalInvalidTag
+        let tag_goals = self.tag_goals
+            .into_iter()
+            .filter_map(|(name, goal)| tags.find(&name).map(|tag| (tag.clone(), goal)))
+            .collect();
+
         // VerificationError::TagMapInvalidCategory & VerificationError::TagMapInvalidTag
         self.tag_map.retain(|cat, _| categories.contains_match(cat));
         self.tag_map.iter_mut().for_each(|(_, map_tags)| map_tags.retain(|tag| tags.contains_match(tag)));
@@ -217,6 +365,16 @@ impl UnverifiedSaveDataLatest {
                 ).collect())))
             .collect();
 
+        // VerificationError::NonUniqueEventIds & VerificationError::EventIdOutOfRange
+        let mut next_event_id = self.events.iter().map(|ev| ev.id + 1).max().unwrap_or(1).max(self.next_event_id);
+        let mut ids = HashSet::new();
+        for event in self.events.iter_mut() {
+            if event.id == 0 || !ids.insert(event.id) {
+                event.id = next_event_id;
+                next_event_id += 1;
+            }
+        }
+
         // event errors
         let mut events = Vec::new();
         for event in self.events {
@@ -230,35 +388,54 @@ impl UnverifiedSaveDataLatest {
             let tags = event_tags.into_iter().map(|tag| tags.find(&tag).ok_or(VerificationError::EventInvalidTag(tag)).cloned()).collect::<Result<_, _>>()?;
 
             events.push(Event {
+                id: EventId(event.id),
                 start_time: event.start_time,
                 end_time: event.end_time,
                 date: event.date,
                 category,
                 description: event.description,
                 tags,
+                pinned: event.pinned,
+                source: event.source,
+                external_id: event.external_id,
+                fields: event.fields,
             })
         }
 
         Ok(SaveData {
             categories,
             archived_categories,
+            private_categories,
+            category_details,
+            category_rates,
+            category_goals,
             tags,
             tag_map,
+            tag_goals,
             events,
-            daily_notes: self.daily_notes,
+            next_event_id,
+            daily_notes: self.daily_notes.into_iter().collect(),
+            locked_until: self.locked_until,
+            skipped_days: self.skipped_days,
+            reports: self.reports,
         })
     }
 }
 
-impl From<Event> for UnverifiedEventV5 {
+impl From<Event> for UnverifiedEventV15 {
     fn from(value: Event) -> Self {
         Self {
+            id: value.id.0,
             start_time: value.start_time,
             end_time: value.end_time,
             date: value.date,
             category: value.category.own(),
             description: value.description,
             tags: value.tags.into_iter().map(Tag::own).collect(),
+            pinned: value.pinned,
+            source: value.source,
+            external_id: value.external_id,
+            fields: value.fields,
         }
     }
 }
@@ -268,10 +445,20 @@ impl From<SaveData> for UnverifiedSaveDataLatest {
         UnverifiedSaveDataLatest {
             categories: value.categories.iter().map(Category::own).collect(),
             archived_categories: value.archived_categories.iter().map(Category::own).collect(),
+            private_categories: value.private_categories.iter().map(Category::own).sorted().collect(),
+            category_details: value.category_details.into_iter().map(|(k, v)| (k.own(), v)).collect(),
+            category_rates: value.category_rates.into_iter().map(|(k, v)| (k.own(), v)).collect(),
+            category_goals: value.category_goals.into_iter().map(|(k, v)| (k.own(), v)).collect(),
             tags: value.tags.into_iter().map(Tag::own).collect(),
-            tag_map: value.tag_map.into_iter().map(|(k, v)| (k.own(), v.into_iter().map(Tag::own).collect())).collect(),
+            // untagging a category can leave it with no tags, which is the same as not being in the map
+            tag_map: value.tag_map.into_iter().filter(|(_, v)| !v.is_empty()).map(|(k, v)| (k.own(), v.into_iter().map(Tag::own).sorted().collect())).collect(),
+            tag_goals: value.tag_goals.into_iter().map(|(k, v)| (k.own(), v)).collect(),
             events: value.events.into_iter().map(Into::into).collect(),
-            daily_notes: value.daily_notes,
+            next_event_id: value.next_event_id,
+            daily_notes: value.daily_notes.into_iter().collect(),
+            locked_until: value.locked_until,
+            skipped_days: value.skipped_days,
+            reports: value.reports,
         }
     }
 }
@@ -335,11 +522,51 @@ impl Display for Event {
         write!(
             f,
             "{}: {} ({}, {}-{})",
-            self.category.inner(), self.description, self.date, self.start_time, self.end_time
+            self.category.inner(),
+            self.description.lines().collect::<Vec<_>>().join(" | "),
+            self.date,
+            self.start_time,
+            self.end_time
         )
     }
 }
 
+impl Event {
+    pub fn duration(&self) -> Duration {
+        self.end_time - self.start_time
+    }
+
+    /// Whether `time` falls within this event. SimpleTime subtraction wraps around midnight, so
+    /// this also handles events that end the next day.
+    pub fn covers(&self, time: SimpleTime) -> bool {
+        time - self.start_time < self.duration()
+    }
+
+    /// Appends an already-timestamped annotation line to the description, along with the tags it
+    /// mentions
+    pub fn annotate(&mut self, annotation: &str, tags: HashSet<Tag>) {
+        self.description.push('\n');
+        self.description.push_str(annotation);
+        self.tags.extend(tags);
+    }
+
+    /// Whether this event passes a field filter, typed as `name=value` to match a value (ignoring
+    /// case) or just `name` to match any event that has the field set. An empty filter matches
+    /// everything.
+    // without the dashboard, only the fuzz targets' library uses it, not the binary
+    #[cfg(any(feature = "tui", feature = "fuzzing"))]
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
+    pub fn matches_field_filter(&self, filter: &str) -> bool {
+        match filter.split_once('=') {
+            Some((name, value)) => self
+                .fields
+                .get(name.trim())
+                .is_some_and(|v| v.to_string().eq_ignore_ascii_case(value.trim())),
+            None => filter.trim().is_empty() || self.fields.contains_key(filter.trim()),
+        }
+    }
+}
+
 /// Constructs an AddTag DeltaItem and returns the Tag that will be generated, assuming it's added to
 /// the list
 pub fn add_tag(s: String) -> (DeltaItem, Tag) {
@@ -362,8 +589,171 @@ pub fn rename_category(pre: Category, post: String) -> (DeltaItem, Category) {
 }
 
 
+/// Constructs an AnnotateEvent DeltaItem, stamping the annotation with the current time
+pub fn annotate_event(id: EventId, text: &str, tags: HashSet<Tag>) -> DeltaItem {
+    DeltaItem::AnnotateEvent {
+        id,
+        annotation: format!("[{}] {text}", Local::now().format("%Y-%m-%d %H:%M")),
+        tags,
+    }
+}
+
+impl SaveData {
+    /// Where the event with this id currently is in `events`
+    pub fn event_index(&self, id: EventId) -> Option<usize> {
+        self.events.iter().position(|ev| ev.id == id)
+    }
+
+    /// Like `event_index`, for changes, which must only refer to events that exist
+    fn index_of(&self, id: EventId) -> usize {
+        self.event_index(id).expect("changes must only refer to existing events")
+    }
+
+    fn check_unlocked(&self, date: NaiveDate) -> TaskitResult<()> {
+        match self.locked_until {
+            Some(locked_until) if date <= locked_until => {
+                Err(Kind::PeriodLocked(locked_until).with(Source::SavingChanges))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The changes that would undo `delta`, which must not have been applied yet
+    fn inverse(&self, delta: &DeltaItem) -> Vec<DeltaItem> {
+        match delta {
+            DeltaItem::AddCategory(Opaque(category)) => {
+                vec![DeltaItem::ArchiveCategory(category.clone()), DeltaItem::DeleteCategory(category.clone())]
+            }
+            DeltaItem::RenameCategory { old, new: Opaque(new) } => {
+                vec![DeltaItem::RenameCategory { old: new.clone(), new: Opaque(old.clone()) }]
+            }
+            // archiving drops the category's tags, so they have to be put back too
+            DeltaItem::ArchiveCategory(category) => iter::once(DeltaItem::UnarchiveCategory {
+                index: self.categories.index_of(category).expect("only active categories can be archived"),
+                category: category.clone(),
+            })
+                .chain(
+                    self.tag_map
+                        .get(category)
+                        .into_iter()
+                        .flatten()
+                        .map(|tag| DeltaItem::TagCategory(category.clone(), tag.clone())),
+                )
+                .collect(),
+            DeltaItem::AddEvent(_) => vec![DeltaItem::DeleteEvent(EventId(self.next_event_id))],
+            DeltaItem::ChangeEvent { id, .. } | DeltaItem::AnnotateEvent { id, .. } => {
+                vec![DeltaItem::ChangeEvent { id: *id, new_event: self.events[self.index_of(*id)].clone() }]
+            }
+            DeltaItem::AddTag(Opaque(tag)) => vec![DeltaItem::DeleteTag(tag.clone())],
+            DeltaItem::TagCategory(category, tag) => {
+                if self.tag_map.get(category).is_some_and(|tags| tags.contains(tag)) {
+                    vec![]
+                } else {
+                    vec![DeltaItem::UntagCategory(category.clone(), tag.clone())]
+                }
+            }
+            DeltaItem::UntagCategory(category, tag) => {
+                if self.tag_map.get(category).is_some_and(|tags| tags.contains(tag)) {
+                    vec![DeltaItem::TagCategory(category.clone(), tag.clone())]
+                } else {
+                    vec![]
+                }
+            }
+            DeltaItem::SetDailyNote(date, _) | DeltaItem::RemoveDailyNote(date) => match self.daily_notes.get(date) {
+                Some(note) => vec![DeltaItem::SetDailyNote(*date, note.clone())],
+                None => vec![DeltaItem::RemoveDailyNote(*date)],
+            },
+            DeltaItem::DeleteEvent(id) => {
+                let index = self.index_of(*id);
+                vec![DeltaItem::RestoreEvent { index, event: self.events[index].clone() }]
+            }
+            DeltaItem::DeleteCategory(category) => {
+                let index = self.archived_categories.index_of(category).expect("only archived categories are deleted");
+                let mut inverse = vec![DeltaItem::RestoreCategory { index, category: Opaque(category.clone()) }];
+                if self.private_categories.contains(category) {
+                    inverse.push(DeltaItem::SetCategoryPrivate(category.clone(), true));
+                }
+                if let Some(details) = self.category_details.get(category) {
+                    inverse.push(DeltaItem::SetCategoryDetails(category.clone(), details.clone()));
+                }
+                if let Some(rate) = self.category_rates.get(category) {
+                    inverse.push(DeltaItem::SetCategoryRate(category.clone(), Some(*rate)));
+                }
+                if let Some(goal) = self.category_goals.get(category) {
+                    inverse.push(DeltaItem::SetCategoryGoal(category.clone(), Some(*goal)));
+                }
+                inverse
+            }
+            DeltaItem::DeleteTag(tag) => iter::once(DeltaItem::AddTag(Opaque(tag.clone())))
+                .chain(
+                    self.tag_map
+                        .iter()
+                        .filter(|(_, tags)| tags.contains(tag))
+                        .map(|(category, _)| DeltaItem::TagCategory(category.clone(), tag.clone())),
+                )
+                .chain(self.tag_goals.get(tag).map(|goal| DeltaItem::SetTagGoal(tag.clone(), Some(*goal))))
+                .collect(),
+            DeltaItem::SetPinned(id, _) => vec![DeltaItem::SetPinned(*id, self.events[self.index_of(*id)].pinned)],
+            DeltaItem::SetCategoryPrivate(category, _) => {
+                vec![DeltaItem::SetCategoryPrivate(category.clone(), self.private_categories.contains(category))]
+            }
+            DeltaItem::LockUntil(_) => vec![DeltaItem::LockUntil(self.locked_until)],
+            DeltaItem::SetSkipped(date, _) => vec![DeltaItem::SetSkipped(*date, self.skipped_days.get(date).cloned())],
+            DeltaItem::UnarchiveCategory { category, .. } => vec![DeltaItem::ArchiveCategory(category.clone())],
+            DeltaItem::RestoreEvent { event, .. } => vec![DeltaItem::DeleteEvent(event.id)],
+            DeltaItem::RestoreCategory { category: Opaque(category), .. } => vec![DeltaItem::DeleteCategory(category.clone())],
+            DeltaItem::SetReport(name, _) => vec![DeltaItem::SetReport(name.clone(), self.reports.get(name).cloned())],
+            DeltaItem::SetCategoryDetails(category, _) => vec![DeltaItem::SetCategoryDetails(
+                category.clone(),
+                self.category_details.get(category).cloned().unwrap_or_default(),
+            )],
+            DeltaItem::SetCategoryRate(category, _) => {
+                vec![DeltaItem::SetCategoryRate(category.clone(), self.category_rates.get(category).copied())]
+            }
+            DeltaItem::SetCategoryGoal(category, _) => {
+                vec![DeltaItem::SetCategoryGoal(category.clone(), self.category_goals.get(category).copied())]
+            }
+            DeltaItem::SetTagGoal(tag, _) => vec![DeltaItem::SetTagGoal(tag.clone(), self.tag_goals.get(tag).copied())],
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl DeltaItem {
+    /// The tag this change creates, if it creates one
+    pub fn added_tag(&self) -> Option<Tag> {
+        match self {
+            DeltaItem::AddTag(Opaque(tag)) => Some(tag.clone()),
+            _ => None,
+        }
+    }
+
+    /// The category this change creates, if it creates one
+    pub fn added_category(&self) -> Option<Category> {
+        match self {
+            DeltaItem::AddCategory(Opaque(category)) => Some(category.clone()),
+            _ => None,
+        }
+    }
+}
+
 impl Apply<DeltaItem> for SaveData {
     fn apply(&mut self, delta: DeltaItem) -> TaskitResult<()> {
+        match &delta {
+            DeltaItem::AddEvent(event) => self.check_unlocked(event.date)?,
+            DeltaItem::ChangeEvent { id, new_event } => {
+                self.check_unlocked(self.events[self.index_of(*id)].date)?;
+                self.check_unlocked(new_event.date)?;
+            }
+            DeltaItem::DeleteEvent(id) | DeltaItem::AnnotateEvent { id, .. } => {
+                self.check_unlocked(self.events[self.index_of(*id)].date)?
+            }
+            DeltaItem::RestoreEvent { event, .. } => self.check_unlocked(event.date)?,
+            DeltaItem::SetDailyNote(date, _) | DeltaItem::SetSkipped(date, _) | DeltaItem::RemoveDailyNote(date) => {
+                self.check_unlocked(*date)?
+            }
+            _ => {}
+        }
         match delta {
             DeltaItem::AddCategory(Opaque(category)) => {
                 assert!(self.categories.push(category).is_ok());
@@ -375,28 +765,51 @@ impl Apply<DeltaItem> for SaveData {
                         || (self.archived_categories.contains(&old)
                             && !self.archived_categories.contains(&new))
                 );
-                if self.categories.remove(&old).is_some() {
-                    self.categories.push(new.clone()).expect("category name must be previously uninhabited");
+                // in place, so that the categories stay in the same order
+                if let Some(index) = self.categories.index_of(&old) {
+                    self.categories.remove(&old);
+                    self.categories.insert(index, new.clone()).expect("category name must be previously uninhabited");
                 }
-                if self.archived_categories.remove(&old).is_some() {
-                    self.archived_categories.push(new.clone()).expect("archived category name must be previously uninhabited");
+                if let Some(index) = self.archived_categories.index_of(&old) {
+                    self.archived_categories.remove(&old);
+                    self.archived_categories.insert(index, new.clone()).expect("archived category name must be previously uninhabited");
                 }
                 self.events.iter_mut().for_each(|ev| {
                     if ev.category == old {
                         ev.category = new.clone();
                     }
                 });
+                if self.private_categories.remove(&old) {
+                    self.private_categories.insert(new.clone());
+                }
+                if let Some(details) = self.category_details.remove(&old) {
+                    self.category_details.insert(new.clone(), details);
+                }
+                if let Some(rate) = self.category_rates.remove(&old) {
+                    self.category_rates.insert(new.clone(), rate);
+                }
+                if let Some(goal) = self.category_goals.remove(&old) {
+                    self.category_goals.insert(new.clone(), goal);
+                }
+                self.reports
+                    .values_mut()
+                    .flat_map(|report| report.categories.iter_mut())
+                    .filter(|name| **name == *old)
+                    .for_each(|name| *name = new.own());
                 self.tag_map
                     .remove(&old)
                     .and_then(|v| self.tag_map.insert(new, v));
             }
-            DeltaItem::AddEvent(event) => {
+            DeltaItem::AddEvent(mut event) => {
                 assert!(self.categories.contains(&event.category));
                 assert!(event.tags.iter().all(|tag| self.tags.contains(tag)));
+                event.id = EventId(self.next_event_id);
+                self.next_event_id += 1;
                 self.events.push(event);
             }
-            DeltaItem::ChangeEvent { index, new_event } => {
-                assert!(index < self.events.len());
+            DeltaItem::ChangeEvent { id, mut new_event } => {
+                let index = self.index_of(id);
+                new_event.id = id;
                 self.events[index] = new_event;
             }
             DeltaItem::ArchiveCategory(category) => {
@@ -423,15 +836,99 @@ impl Apply<DeltaItem> for SaveData {
             DeltaItem::SetDailyNote(date, note) => {
                 self.daily_notes.insert(date, note);
             }
-            DeltaItem::DeleteEvent(index) => {
-                assert!(self.events.len() > index);
+            DeltaItem::DeleteEvent(id) => {
+                let index = self.index_of(id);
                 self.events.remove(index);
             }
-            DeltaItem::DeleteCategory(c) => self.archived_categories.retain(|x| x != &c),
+            DeltaItem::SetPinned(id, pinned) => {
+                let index = self.index_of(id);
+                self.events[index].pinned = pinned;
+            }
+            DeltaItem::AnnotateEvent { id, annotation, tags } => {
+                let index = self.index_of(id);
+                assert!(tags.iter().all(|tag| self.tags.contains(tag)));
+                self.events[index].annotate(&annotation, tags);
+            }
+            DeltaItem::SetCategoryPrivate(category, private) => {
+                assert!(self.categories.contains(&category) || self.archived_categories.contains(&category));
+                if private {
+                    self.private_categories.insert(category);
+                } else {
+                    self.private_categories.remove(&category);
+                }
+            }
+            DeltaItem::LockUntil(date) => self.locked_until = date,
+            DeltaItem::SetSkipped(date, Some(reason)) => {
+                self.skipped_days.insert(date, reason);
+            }
+            DeltaItem::SetSkipped(date, None) => {
+                self.skipped_days.remove(&date);
+            }
+            DeltaItem::SetReport(name, Some(report)) => {
+                self.reports.insert(name, report);
+            }
+            DeltaItem::SetReport(name, None) => {
+                self.reports.remove(&name);
+            }
+            DeltaItem::SetCategoryRate(category, rate) => {
+                assert!(self.categories.contains(&category) || self.archived_categories.contains(&category));
+                match rate {
+                    Some(rate) => self.category_rates.insert(category, rate),
+                    None => self.category_rates.remove(&category),
+                };
+            }
+            DeltaItem::SetCategoryGoal(category, goal) => {
+                assert!(self.categories.contains(&category) || self.archived_categories.contains(&category));
+                match goal {
+                    Some(goal) => self.category_goals.insert(category, goal),
+                    None => self.category_goals.remove(&category),
+                };
+            }
+            DeltaItem::SetTagGoal(tag, goal) => {
+                assert!(self.tags.contains(&tag));
+                match goal {
+                    Some(goal) => self.tag_goals.insert(tag, goal),
+                    None => self.tag_goals.remove(&tag),
+                };
+            }
+            DeltaItem::SetCategoryDetails(category, details) => {
+                assert!(self.categories.contains(&category) || self.archived_categories.contains(&category));
+                if details.is_empty() {
+                    self.category_details.remove(&category);
+                } else {
+                    self.category_details.insert(category, details);
+                }
+            }
+            DeltaItem::UnarchiveCategory { index, category } => {
+                assert!(self.archived_categories.remove(&category).is_some());
+                assert!(self.categories.insert(index, category).is_ok());
+            }
+            DeltaItem::RestoreEvent { index, event } => {
+                assert!(index <= self.events.len());
+                assert!(self.event_index(event.id).is_none() && event.id.0 < self.next_event_id);
+                assert!(self.categories.contains(&event.category) || self.archived_categories.contains(&event.category));
+                assert!(event.tags.iter().all(|tag| self.tags.contains(tag)));
+                self.events.insert(index, event);
+            }
+            DeltaItem::RemoveDailyNote(date) => {
+                self.daily_notes.remove(&date);
+            }
+            DeltaItem::RestoreCategory { index, category: Opaque(category) } => {
+                assert!(!self.categories.contains(&category));
+                assert!(self.archived_categories.insert(index, category).is_ok());
+            }
+            DeltaItem::DeleteCategory(c) => {
+                self.private_categories.remove(&c);
+                self.category_details.remove(&c);
+                self.category_rates.remove(&c);
+                self.category_goals.remove(&c);
+                self.archived_categories.retain(|x| x != &c);
+            }
             DeltaItem::DeleteTag(t) => {
                 assert!(self.tags.contains(&t));
                 assert!(self.events.iter().all(|ev| !ev.tags.contains(&t)));
                 self.tags.retain(|x| x != &t);
+                self.tag_goals.remove(&t);
                 self.tag_map
                     .iter_mut()
                     .for_each(|(_, v)| v.retain(|x| x != &t));
@@ -442,6 +939,165 @@ impl Apply<DeltaItem> for SaveData {
         }
         Ok(())
     }
+
+    fn apply_reversibly(&mut self, delta: DeltaItem) -> TaskitResult<Vec<DeltaItem>> {
+        let inverse = self.inverse(&delta);
+        self.apply(delta)?;
+        Ok(inverse)
+    }
+}
+
+impl From<DeltaItem> for UnverifiedDelta {
+    fn from(value: DeltaItem) -> Self {
+        match value {
+            DeltaItem::AddCategory(Opaque(category)) => Self::AddCategory(category.own()),
+            DeltaItem::RenameCategory { old, new: Opaque(new) } => Self::RenameCategory { old: old.own(), new: new.own() },
+            DeltaItem::ArchiveCategory(category) => Self::ArchiveCategory(category.own()),
+            DeltaItem::AddEvent(event) => Self::AddEvent(event.into()),
+            DeltaItem::ChangeEvent { id, new_event } => Self::ChangeEvent { id: id.0, new_event: new_event.into() },
+            DeltaItem::AddTag(Opaque(tag)) => Self::AddTag(tag.own()),
+            DeltaItem::TagCategory(category, tag) => Self::TagCategory(category.own(), tag.own()),
+            DeltaItem::UntagCategory(category, tag) => Self::UntagCategory(category.own(), tag.own()),
+            DeltaItem::SetDailyNote(date, note) => Self::SetDailyNote(date, note),
+            DeltaItem::DeleteEvent(id) => Self::DeleteEvent(id.0),
+            DeltaItem::DeleteCategory(category) => Self::DeleteCategory(category.own()),
+            DeltaItem::DeleteTag(tag) => Self::DeleteTag(tag.own()),
+            DeltaItem::SetPinned(id, pinned) => Self::SetPinned(id.0, pinned),
+            DeltaItem::SetCategoryPrivate(category, private) => Self::SetCategoryPrivate(category.own(), private),
+            DeltaItem::LockUntil(date) => Self::LockUntil(date),
+            DeltaItem::SetSkipped(date, reason) => Self::SetSkipped(date, reason),
+            DeltaItem::SetReport(name, report) => Self::SetReport(name, report),
+            DeltaItem::SetCategoryDetails(category, details) => Self::SetCategoryDetails(category.own(), details),
+            DeltaItem::SetCategoryRate(category, rate) => Self::SetCategoryRate(category.own(), rate),
+            DeltaItem::SetCategoryGoal(category, goal) => Self::SetCategoryGoal(category.own(), goal),
+            DeltaItem::SetTagGoal(tag, goal) => Self::SetTagGoal(tag.own(), goal),
+            DeltaItem::AnnotateEvent { id, annotation, tags } => Self::AnnotateEvent {
+                id: id.0,
+                annotation,
+                tags: tags.into_iter().map(Tag::own).collect(),
+            },
+            DeltaItem::UnarchiveCategory { index, category } => Self::UnarchiveCategory { index, category: category.own() },
+            DeltaItem::RestoreEvent { index, event } => Self::RestoreEvent { index, event: event.into() },
+            DeltaItem::RestoreCategory { index, category: Opaque(category) } => {
+                Self::RestoreCategory { index, category: category.own() }
+            }
+            DeltaItem::RemoveDailyNote(date) => Self::RemoveDailyNote(date),
+        }
+    }
+}
+
+impl UnverifiedDelta {
+    /// Checks that this change can still be applied to `save_data` without breaking any invariants,
+    /// and turns it back into a `DeltaItem`. If it can't, describes why.
+    pub fn verify(self, save_data: &SaveData) -> Result<DeltaItem, String> {
+        let active = |name: &String| {
+            save_data.categories.find(name).cloned().ok_or_else(|| format!("category {name} isn't active"))
+        };
+        let archived = |name: &String| {
+            save_data.archived_categories.find(name).cloned().ok_or_else(|| format!("category {name} isn't archived"))
+        };
+        let any = |name: &String| active(name).or_else(|_| archived(name)).map_err(|_| format!("no category {name}"));
+        let new_name = |name: &String| match any(name) {
+            Ok(_) => Err(format!("category {name} already exists")),
+            Err(_) => Ok(Category::new(name.clone())),
+        };
+        let tag = |name: &String| save_data.tags.find(name).cloned().ok_or_else(|| format!("no tag #{name}"));
+        let event_with = |id: u64| {
+            save_data.event_index(EventId(id)).map(|_| EventId(id)).ok_or_else(|| format!("no event with id {id}"))
+        };
+        let event = |event: UnverifiedEventV15| -> Result<Event, String> {
+            let tags = event.tags.iter().map(tag).collect::<Result<HashSet<_>, _>>()?;
+            if tags != get_description_tags(&event.description).into_iter().map(Tag::new).collect() {
+                return Err("an event's tags don't match its description".to_owned());
+            }
+            Ok(Event {
+                id: EventId(event.id),
+                start_time: event.start_time,
+                end_time: event.end_time,
+                date: event.date,
+                category: any(&event.category)?,
+                description: event.description,
+                tags,
+                pinned: event.pinned,
+                source: event.source,
+                external_id: event.external_id,
+                fields: event.fields,
+            })
+        };
+        let unused = |used: &dyn Fn(&Event) -> bool, what: String| {
+            if save_data.events.iter().any(used) { Err(format!("{what} still has events")) } else { Ok(()) }
+        };
+        Ok(match self {
+            Self::AddCategory(name) => DeltaItem::AddCategory(Opaque(new_name(&name)?)),
+            Self::RenameCategory { old, new } => DeltaItem::RenameCategory { old: any(&old)?, new: Opaque(new_name(&new)?) },
+            Self::ArchiveCategory(name) => DeltaItem::ArchiveCategory(active(&name)?),
+            Self::AddEvent(new_event) => {
+                let new_event = event(new_event)?;
+                active(&new_event.category.own())?;
+                DeltaItem::AddEvent(new_event)
+            }
+            Self::ChangeEvent { id, new_event } => DeltaItem::ChangeEvent { id: event_with(id)?, new_event: event(new_event)? },
+            Self::AddTag(name) => {
+                if tag(&name).is_ok() {
+                    return Err(format!("tag #{name} already exists"));
+                }
+                if name.contains(char::is_whitespace) {
+                    return Err(format!("tag #{name} contains a space"));
+                }
+                DeltaItem::AddTag(Opaque(Tag::new(name)))
+            }
+            Self::TagCategory(category, name) => DeltaItem::TagCategory(active(&category)?, tag(&name)?),
+            Self::UntagCategory(category, name) => DeltaItem::UntagCategory(active(&category)?, tag(&name)?),
+            Self::SetDailyNote(date, note) => DeltaItem::SetDailyNote(date, note),
+            Self::DeleteEvent(id) => DeltaItem::DeleteEvent(event_with(id)?),
+            Self::DeleteCategory(name) => {
+                let category = archived(&name)?;
+                unused(&|ev| ev.category == category, format!("category {name}"))?;
+                DeltaItem::DeleteCategory(category)
+            }
+            Self::DeleteTag(name) => {
+                let tag = tag(&name)?;
+                unused(&|ev| ev.tags.contains(&tag), format!("tag #{name}"))?;
+                DeltaItem::DeleteTag(tag)
+            }
+            Self::SetPinned(id, pinned) => DeltaItem::SetPinned(event_with(id)?, pinned),
+            Self::SetCategoryPrivate(name, private) => DeltaItem::SetCategoryPrivate(any(&name)?, private),
+            Self::LockUntil(date) => DeltaItem::LockUntil(date),
+            Self::SetSkipped(date, reason) => DeltaItem::SetSkipped(date, reason),
+            Self::SetReport(name, report) => DeltaItem::SetReport(name, report),
+            Self::SetCategoryDetails(name, details) => DeltaItem::SetCategoryDetails(any(&name)?, details),
+            Self::SetCategoryRate(name, rate) => DeltaItem::SetCategoryRate(any(&name)?, rate),
+            Self::SetCategoryGoal(name, goal) => DeltaItem::SetCategoryGoal(any(&name)?, goal),
+            Self::SetTagGoal(name, goal) => DeltaItem::SetTagGoal(tag(&name)?, goal),
+            Self::AnnotateEvent { id, annotation, tags } => DeltaItem::AnnotateEvent {
+                id: event_with(id)?,
+                annotation,
+                tags: tags.iter().map(tag).collect::<Result<_, _>>()?,
+            },
+            Self::UnarchiveCategory { index, category: name } => {
+                if index > save_data.categories.len() {
+                    return Err(format!("no position {index} to put category '{name}' back at"));
+                }
+                DeltaItem::UnarchiveCategory { index, category: archived(&name)? }
+            }
+            Self::RestoreEvent { index, event: restored } => {
+                if index > save_data.events.len() {
+                    return Err(format!("no position {index} to put an event back at"));
+                }
+                if event_with(restored.id).is_ok() || restored.id >= save_data.next_event_id {
+                    return Err(format!("event id {} is already taken", restored.id));
+                }
+                DeltaItem::RestoreEvent { index, event: event(restored)? }
+            }
+            Self::RemoveDailyNote(date) => DeltaItem::RemoveDailyNote(date),
+            Self::RestoreCategory { index, category: name } => {
+                if index > save_data.archived_categories.len() {
+                    return Err(format!("no position {index} to put category '{name}' back at"));
+                }
+                DeltaItem::RestoreCategory { index, category: Opaque(new_name(&name)?) }
+            }
+        })
+    }
 }
 
 /// There's gotta be a better way to do this...
@@ -451,5 +1107,155 @@ impl Apply<DeltaItem> for SaveData {
 /// we don't want to allow constructing it from existing categories. The solution is to instead wrap
 /// it in `Opaque`, so the only way to construct an instance is if you have access to the Opaque
 /// constructor, which only we do.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(super) struct Opaque<T>(T);
+
+#[cfg(feature = "sqlite")]
+impl<T> Opaque<T> {
+    /// Looks at what's wrapped, which doesn't give a way to make one
+    pub fn get(&self) -> &T {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Everything that gets saved, in a form where equal saves compare equal
+    fn snapshot(save_data: &SaveData) -> serde_json::Value {
+        serde_json::to_value(UnverifiedSaveDataLatest::from(save_data.clone())).expect("save data always serializes")
+    }
+
+    fn event(category: &Category, description: &str, tags: &[&Tag]) -> Event {
+        Event {
+            id: EventId::NEW,
+            start_time: SimpleTime::try_new(9, 0).unwrap(),
+            end_time: SimpleTime::try_new(10, 30).unwrap(),
+            date: NaiveDate::from_ymd_opt(2024, 5, 6).unwrap(),
+            category: category.clone(),
+            description: description.to_owned(),
+            tags: tags.iter().map(|&tag| tag.clone()).collect(),
+            pinned: false,
+            source: EventSource::Record,
+            external_id: None,
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// Three categories, the first tagged, with an event in each of the first two
+    fn save_data() -> (SaveData, [Category; 3], Tag) {
+        let mut save_data = UnverifiedSaveDataLatest::default().fix_and_verify().unwrap();
+        let (add_coding, coding) = add_category("coding".to_owned());
+        let (add_meetings, meetings) = add_category("meetings".to_owned());
+        let (add_personal, personal) = add_category("personal".to_owned());
+        let (add_client, client) = add_tag("client".to_owned());
+        save_data
+            .apply(vec![
+                add_coding,
+                add_meetings,
+                add_personal,
+                add_client,
+                DeltaItem::TagCategory(coding.clone(), client.clone()),
+                DeltaItem::AddEvent(event(&coding, "Parser work #client", &[&client])),
+                DeltaItem::AddEvent(event(&meetings, "Standup", &[])),
+            ])
+            .unwrap();
+        (save_data, [coding, meetings, personal], client)
+    }
+
+    /// Applies `delta`, then the inverse it came with, and checks that the save is as it was
+    /// before. The change has to have done something, or the check would pass trivially.
+    fn assert_undone(save_data: &SaveData, delta: DeltaItem) -> SaveData {
+        let before = snapshot(save_data);
+        let mut changed = save_data.clone();
+        let inverse = changed.apply_reversibly(delta).unwrap();
+        assert_ne!(snapshot(&changed), before);
+        changed.apply(inverse).unwrap();
+        assert_eq!(snapshot(&changed), before);
+        changed
+    }
+
+    #[test]
+    fn inverse_of_add_event() {
+        let (save_data, [coding, ..], client) = save_data();
+        let before = snapshot(&save_data);
+        let mut changed = save_data.clone();
+        let inverse = changed
+            .apply_reversibly(DeltaItem::AddEvent(event(&coding, "More #client work", &[&client])))
+            .unwrap();
+        assert_eq!(changed.events.len(), save_data.events.len() + 1);
+        changed.apply(inverse).unwrap();
+        // ids are never reused, so the deleted event's stays taken
+        assert_eq!(changed.next_event_id, save_data.next_event_id + 1);
+        changed.next_event_id = save_data.next_event_id;
+        assert_eq!(snapshot(&changed), before);
+    }
+
+    #[test]
+    fn inverse_of_change_event() {
+        let (save_data, [_, meetings, _], _) = save_data();
+        let id = save_data.events[0].id;
+        assert_undone(&save_data, DeltaItem::ChangeEvent { id, new_event: event(&meetings, "Planning", &[]) });
+    }
+
+    #[test]
+    fn inverse_of_annotate_event() {
+        let (save_data, _, client) = save_data();
+        let id = save_data.events[1].id;
+        let annotation = "[2024-05-06 10:00] moved to #client".to_owned();
+        assert_undone(&save_data, DeltaItem::AnnotateEvent { id, annotation, tags: HashSet::from([client]) });
+    }
+
+    #[test]
+    fn inverse_of_delete_event() {
+        let (save_data, ..) = save_data();
+        // the first event, so that putting it back at the end wouldn't pass
+        assert_undone(&save_data, DeltaItem::DeleteEvent(save_data.events[0].id));
+    }
+
+    #[test]
+    fn inverse_of_archive_category() {
+        let (save_data, [coding, ..], _) = save_data();
+        // the first category, with a tag, so that both its place and its tags have to come back
+        assert_undone(&save_data, DeltaItem::ArchiveCategory(coding));
+    }
+
+    #[test]
+    fn inverse_of_delete_category() {
+        let (mut save_data, ..) = save_data();
+        let (add_old, old) = add_category("old-project".to_owned());
+        let (add_older, _) = add_category("older-project".to_owned());
+        save_data
+            .apply(vec![add_old, add_older, DeltaItem::ArchiveCategory(old.clone())])
+            .unwrap();
+        let older = save_data.categories.find("older-project").unwrap().clone();
+        save_data.apply(DeltaItem::ArchiveCategory(older)).unwrap();
+        // the first archived category, so that putting it back at the end wouldn't pass
+        assert_undone(&save_data, DeltaItem::DeleteCategory(old));
+    }
+
+    #[test]
+    fn inverse_of_rename_category() {
+        let (save_data, [coding, ..], _) = save_data();
+        let (rename, _) = rename_category(coding, "programming".to_owned());
+        let renamed = assert_undone(&save_data, rename);
+        assert_eq!(renamed.events[0].category.inner(), "coding");
+        assert!(renamed.tag_map.keys().any(|category| category.inner() == "coding"));
+    }
+
+    #[test]
+    fn inverse_of_tag_category() {
+        let (save_data, [_, meetings, _], client) = save_data();
+        assert_undone(&save_data, DeltaItem::TagCategory(meetings, client));
+    }
+
+    #[test]
+    fn inverse_of_tagging_an_already_tagged_category() {
+        let (save_data, [coding, ..], client) = save_data();
+        let mut changed = save_data.clone();
+        let inverse = changed.apply_reversibly(DeltaItem::TagCategory(coding, client)).unwrap();
+        assert!(inverse.is_empty());
+        assert_eq!(snapshot(&changed), snapshot(&save_data));
+    }
+}
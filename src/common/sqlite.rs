@@ -0,0 +1,424 @@
+//! Keeping the save in a SQLite database instead of a JSON file. Events get a row each, with their
+//! tags and the daily notes in tables of their own, so they can be queried by date and written one
+//! at a time; the rest of the save is small and kept as JSON.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
+    mem,
+    path::{Path, PathBuf},
+};
+
+use chrono::NaiveDate;
+use rusqlite::{Connection, Row, Statement, Transaction, params, types::Type};
+use serde_json::Value;
+
+use crate::common::{
+    DeltaItem, SimpleTime, Storage, UnverifiedEventV15, UnverifiedSaveDataLatest, UnverifiedSaveDataVersioned,
+    error::{Kind, Source, TaskitError, TaskitResult, With},
+};
+
+/// The version of the save format the database holds, kept as its `user_version`. Databases with
+/// an older one are read as that version and upgraded like JSON saves are. The events table
+/// follows `UnverifiedEventV15`, and has to gain columns if a later version changes events.
+const FORMAT_VERSION: i64 = 19;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY,
+    date TEXT NOT NULL,
+    -- seconds since midnight; an end before the start is on the next day
+    start_time INTEGER NOT NULL,
+    end_time INTEGER NOT NULL,
+    category TEXT NOT NULL,
+    description TEXT NOT NULL,
+    pinned INTEGER NOT NULL,
+    source TEXT NOT NULL,
+    external_id TEXT,
+    -- a JSON object of the event's custom fields
+    fields TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS events_by_date ON events (date);
+CREATE INDEX IF NOT EXISTS events_by_category ON events (category);
+CREATE TABLE IF NOT EXISTS event_tags (
+    event_id INTEGER NOT NULL REFERENCES events (id),
+    tag TEXT NOT NULL,
+    PRIMARY KEY (event_id, tag)
+);
+CREATE TABLE IF NOT EXISTS daily_notes (
+    date TEXT PRIMARY KEY,
+    note TEXT NOT NULL
+);
+-- everything else in the save, as in a JSON save
+CREATE TABLE IF NOT EXISTS save (
+    json TEXT NOT NULL
+);
+";
+
+/// Picks out the events and notes between two dates, either open if NULL
+const IN_RANGE: &str = "(?1 IS NULL OR date >= ?1) AND (?2 IS NULL OR date <= ?2)";
+
+pub struct SqliteStorage {
+    path: PathBuf,
+}
+
+impl SqliteStorage {
+    pub fn new(path: &Path) -> Self {
+        Self { path: path.to_owned() }
+    }
+
+    fn invalid(&self, reason: impl Display) -> TaskitError {
+        Kind::InvalidSave(format!("{}: {reason}", self.path.display())).with(Source::ReadingSave)
+    }
+
+    /// Opens the database and checks that this version of taskit can read it, returning the version
+    /// of the save format it holds
+    fn open(&self) -> TaskitResult<(Connection, i64)> {
+        let db = Connection::open(&self.path).with(Source::ReadingSave)?;
+        let version: i64 = db
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .with(Source::ReadingSave)?;
+        if !(1..=FORMAT_VERSION).contains(&version) {
+            return Err(self.invalid(format!("it holds version {version} of the save format, which this taskit can't read")));
+        }
+        Ok((db, version))
+    }
+
+    fn read_range(&self, from: Option<NaiveDate>, to: Option<NaiveDate>) -> TaskitResult<Option<UnverifiedSaveDataVersioned>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let (db, version) = self.open()?;
+        let (rest, events, daily_notes) = read_tables(&db, from, to).with(Source::ReadingSave)?;
+        if version == FORMAT_VERSION {
+            let save: UnverifiedSaveDataLatest = serde_json::from_str(&rest).map_err(|e| self.invalid(e))?;
+            return Ok(Some(UnverifiedSaveDataLatest { events, daily_notes, ..save }.into()));
+        }
+        // put together as a JSON save of the same version would be, to be upgraded like one
+        let mut save: Value = serde_json::from_str(&rest).map_err(|e| self.invalid(e))?;
+        let Value::Object(fields) = &mut save else {
+            return Err(self.invalid("the rest of the save isn't a JSON object"));
+        };
+        fields.insert("events".to_owned(), serde_json::to_value(events).expect("events always serialize"));
+        fields.insert("daily_notes".to_owned(), serde_json::to_value(daily_notes).expect("notes always serialize"));
+        let versioned = Value::Object([(format!("V{version}"), save)].into_iter().collect());
+        serde_json::from_value(versioned).map(Some).map_err(|e| self.invalid(e))
+    }
+}
+
+fn seconds(time: SimpleTime) -> i64 {
+    i64::from(time.hour) * 3600 + i64::from(time.minute) * 60 + i64::from(time.second)
+}
+
+fn time(row: &Row, column: &str) -> rusqlite::Result<SimpleTime> {
+    let seconds: i64 = row.get(column)?;
+    Ok(SimpleTime {
+        hour: (seconds / 3600) as u8,
+        minute: (seconds / 60 % 60) as u8,
+        second: (seconds % 60) as u8,
+    })
+}
+
+/// An event's row, with the tags looked up separately
+fn event(row: &Row, tags: &mut HashMap<i64, BTreeSet<String>>) -> rusqlite::Result<UnverifiedEventV15> {
+    let id: i64 = row.get("id")?;
+    let fields: String = row.get("fields")?;
+    let fields = serde_json::from_str(&fields).map_err(|e| {
+        match row.as_ref().column_index("fields") {
+            Ok(column) => rusqlite::Error::FromSqlConversionFailure(column, Type::Text, Box::new(e)),
+            Err(e) => e,
+        }
+    })?;
+    Ok(UnverifiedEventV15 {
+        id: id as u64,
+        start_time: time(row, "start_time")?,
+        end_time: time(row, "end_time")?,
+        date: row.get("date")?,
+        category: row.get("category")?,
+        description: row.get("description")?,
+        tags: tags.remove(&id).unwrap_or_default(),
+        pinned: row.get("pinned")?,
+        source: row.get::<_, String>("source")?.into(),
+        external_id: row.get("external_id")?,
+        fields,
+    })
+}
+
+/// The rest of the save as JSON, the events, and the daily notes
+type Tables = (String, Vec<UnverifiedEventV15>, BTreeMap<NaiveDate, String>);
+
+/// Reads the save, with only the events and notes from `from` to `to`
+fn read_tables(db: &Connection, from: Option<NaiveDate>, to: Option<NaiveDate>) -> rusqlite::Result<Tables> {
+    let rest = db.query_row("SELECT json FROM save", [], |row| row.get(0))?;
+    let mut tags: HashMap<i64, BTreeSet<String>> = HashMap::new();
+    let mut statement = db.prepare(&format!(
+        "SELECT event_id, tag FROM event_tags WHERE event_id IN (SELECT id FROM events WHERE {IN_RANGE})"
+    ))?;
+    let mut rows = statement.query(params![from, to])?;
+    while let Some(row) = rows.next()? {
+        tags.entry(row.get(0)?).or_default().insert(row.get(1)?);
+    }
+    let mut events = vec![];
+    let mut statement = db.prepare(&format!("SELECT * FROM events WHERE {IN_RANGE} ORDER BY id"))?;
+    let mut rows = statement.query(params![from, to])?;
+    while let Some(row) = rows.next()? {
+        events.push(event(row, &mut tags)?);
+    }
+    let mut notes = BTreeMap::new();
+    let mut statement = db.prepare(&format!("SELECT date, note FROM daily_notes WHERE {IN_RANGE}"))?;
+    let mut rows = statement.query(params![from, to])?;
+    while let Some(row) = rows.next()? {
+        notes.insert(row.get(0)?, row.get(1)?);
+    }
+    Ok((rest, events, notes))
+}
+
+/// Prepared inserts of an event's row and its tags
+struct Inserts<'a> {
+    event: Statement<'a>,
+    tag: Statement<'a>,
+    note: Statement<'a>,
+}
+
+impl<'a> Inserts<'a> {
+    fn new(transaction: &'a Transaction) -> rusqlite::Result<Self> {
+        Ok(Self {
+            event: transaction.prepare(
+                "INSERT INTO events (id, date, start_time, end_time, category, description, pinned, source, external_id, fields)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?,
+            tag: transaction.prepare("INSERT INTO event_tags (event_id, tag) VALUES (?1, ?2)")?,
+            note: transaction.prepare("INSERT INTO daily_notes (date, note) VALUES (?1, ?2)")?,
+        })
+    }
+
+    fn event(&mut self, ev: UnverifiedEventV15) -> rusqlite::Result<()> {
+        self.event.execute(params![
+            ev.id as i64,
+            ev.date,
+            seconds(ev.start_time),
+            seconds(ev.end_time),
+            ev.category,
+            ev.description,
+            ev.pinned,
+            String::from(ev.source),
+            ev.external_id,
+            serde_json::to_string(&ev.fields).expect("fields always serialize"),
+        ])?;
+        for tag in &ev.tags {
+            self.tag.execute(params![ev.id as i64, tag])?;
+        }
+        Ok(())
+    }
+}
+
+/// Replaces the rest of the save, returning the events and notes for the caller to write
+fn write_rest(
+    transaction: &Transaction,
+    mut save: UnverifiedSaveDataLatest,
+) -> rusqlite::Result<(Vec<UnverifiedEventV15>, BTreeMap<NaiveDate, String>)> {
+    let events = mem::take(&mut save.events);
+    let notes = mem::take(&mut save.daily_notes);
+    let json = serde_json::to_string(&save).expect("save data always serializes");
+    transaction.execute("DELETE FROM save", [])?;
+    transaction.execute("INSERT INTO save (json) VALUES (?1)", [json])?;
+    Ok((events, notes))
+}
+
+/// Replaces everything in the database with `save`, in one transaction
+fn write_tables(db: &mut Connection, save: UnverifiedSaveDataLatest) -> rusqlite::Result<()> {
+    let transaction = db.transaction()?;
+    transaction.execute_batch(SCHEMA)?;
+    transaction.execute_batch("DELETE FROM event_tags; DELETE FROM events; DELETE FROM daily_notes;")?;
+    let (events, notes) = write_rest(&transaction, save)?;
+    {
+        let mut inserts = Inserts::new(&transaction)?;
+        for ev in events {
+            inserts.event(ev)?;
+        }
+        for (date, note) in notes {
+            inserts.note.execute(params![date, note])?;
+        }
+    }
+    transaction.pragma_update(None, "user_version", FORMAT_VERSION)?;
+    transaction.commit()
+}
+
+/// Writes only the events and notes that `changes` touched, and the rest of the save, in one
+/// transaction. The database has to hold the current version of the save.
+fn write_changed(db: &mut Connection, save: UnverifiedSaveDataLatest, changes: &[DeltaItem]) -> rusqlite::Result<()> {
+    let transaction = db.transaction()?;
+    // ids are never reused, so added events are the ones past every event stored
+    let newest: Option<i64> = transaction.query_row("SELECT MAX(id) FROM events", [], |row| row.get(0))?;
+    let mut changed_events = BTreeSet::new();
+    let mut changed_notes = BTreeSet::new();
+    let mut added = false;
+    for change in changes {
+        match change {
+            DeltaItem::AddEvent(_) => added = true,
+            DeltaItem::ChangeEvent { id, .. }
+            | DeltaItem::DeleteEvent(id)
+            | DeltaItem::SetPinned(id, _)
+            | DeltaItem::AnnotateEvent { id, .. } => {
+                changed_events.insert(u64::from(*id));
+            }
+            DeltaItem::RestoreEvent { event, .. } => {
+                changed_events.insert(u64::from(event.id));
+            }
+            DeltaItem::RenameCategory { old, new } => {
+                transaction.execute(
+                    "UPDATE events SET category = ?2 WHERE category = ?1",
+                    params![old.inner(), new.get().inner()],
+                )?;
+            }
+            DeltaItem::DeleteTag(tag) => {
+                transaction.execute("DELETE FROM event_tags WHERE tag = ?1", [tag.inner()])?;
+            }
+            DeltaItem::SetDailyNote(date, _) | DeltaItem::RemoveDailyNote(date) => {
+                changed_notes.insert(*date);
+            }
+            _ => {}
+        }
+    }
+    let (events, mut notes) = write_rest(&transaction, save)?;
+    {
+        let mut inserts = Inserts::new(&transaction)?;
+        let mut delete_event = transaction.prepare("DELETE FROM events WHERE id = ?1")?;
+        let mut delete_tags = transaction.prepare("DELETE FROM event_tags WHERE event_id = ?1")?;
+        let mut events: BTreeMap<u64, UnverifiedEventV15> = events
+            .into_iter()
+            .filter(|ev| changed_events.contains(&ev.id) || (added && newest.is_none_or(|newest| ev.id as i64 > newest)))
+            .map(|ev| (ev.id, ev))
+            .collect();
+        changed_events.extend(events.keys().copied());
+        for id in changed_events {
+            delete_tags.execute([id as i64])?;
+            delete_event.execute([id as i64])?;
+            if let Some(ev) = events.remove(&id) {
+                inserts.event(ev)?;
+            }
+        }
+        let mut delete_note = transaction.prepare("DELETE FROM daily_notes WHERE date = ?1")?;
+        for date in changed_notes {
+            delete_note.execute([date])?;
+            if let Some(note) = notes.remove(&date) {
+                inserts.note.execute(params![date, note])?;
+            }
+        }
+    }
+    transaction.commit()
+}
+
+impl Storage for SqliteStorage {
+    fn read(&self) -> TaskitResult<Option<UnverifiedSaveDataVersioned>> {
+        self.read_range(None, None)
+    }
+
+    fn read_between(&self, from: Option<NaiveDate>, to: Option<NaiveDate>) -> TaskitResult<Option<UnverifiedSaveDataVersioned>> {
+        // an older save is upgraded and written back whole, so it has to be read whole
+        if self.path.exists() && self.open()?.1 != FORMAT_VERSION {
+            return self.read();
+        }
+        self.read_range(from, to)
+    }
+
+    fn write(&self, save: UnverifiedSaveDataLatest) -> TaskitResult<()> {
+        let mut db = Connection::open(&self.path).with(Source::SavingChanges)?;
+        write_tables(&mut db, save).with(Source::SavingChanges)?;
+        Ok(())
+    }
+
+    fn write_changes(&self, save: UnverifiedSaveDataLatest, changes: &[DeltaItem]) -> TaskitResult<()> {
+        if !self.path.exists() {
+            return self.write(save);
+        }
+        let mut db = Connection::open(&self.path).with(Source::SavingChanges)?;
+        let version: i64 = db
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .with(Source::SavingChanges)?;
+        if version == FORMAT_VERSION {
+            write_changed(&mut db, save, changes).with(Source::SavingChanges)?;
+        } else {
+            write_tables(&mut db, save).with(Source::SavingChanges)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::common::{Apply, SaveData, invariants::rename_category};
+
+    fn fixture() -> SaveData {
+        serde_json::from_str::<UnverifiedSaveDataVersioned>(include_str!("../../tests/fixtures/save.json"))
+            .unwrap()
+            .extract()
+            .0
+            .fix_and_verify()
+            .unwrap()
+    }
+
+    /// A database holding the fixture save, named after the test so tests don't share one
+    fn storage(name: &str) -> SqliteStorage {
+        let path = env::temp_dir().join(format!("taskit-sqlite-test-{}-{name}.sqlite", process::id()));
+        let _ = fs::remove_file(&path);
+        let storage = SqliteStorage::new(&path);
+        storage.write(fixture().into()).unwrap();
+        storage
+    }
+
+    fn snapshot(save: UnverifiedSaveDataVersioned) -> Value {
+        serde_json::to_value(save).expect("save data always serializes")
+    }
+
+    #[test]
+    fn changes_are_written_in_place() {
+        let storage = storage("changes");
+        let mut save_data = fixture();
+        let mut added = save_data.events[0].clone();
+        added.description = "More parser work".to_owned();
+        let mut changed = save_data.events[1].clone();
+        changed.description = "Planning".to_owned();
+        let (rename, _) = rename_category(save_data.events[0].category.clone(), "programming".to_owned());
+        let changes = vec![
+            DeltaItem::AddEvent(added),
+            DeltaItem::ChangeEvent { id: changed.id, new_event: changed },
+            DeltaItem::DeleteEvent(save_data.events[2].id),
+            DeltaItem::SetPinned(save_data.events[3].id, true),
+            DeltaItem::SetDailyNote(save_data.events[0].date, "Slow day".to_owned()),
+            rename,
+        ];
+        save_data.apply(changes.clone()).unwrap();
+        storage.write_changes(save_data.clone().into(), &changes).unwrap();
+        let expected = UnverifiedSaveDataVersioned::from(UnverifiedSaveDataLatest::from(save_data));
+        assert_eq!(snapshot(storage.read().unwrap().unwrap()), snapshot(expected));
+        fs::remove_file(&storage.path).unwrap();
+    }
+
+    #[test]
+    fn ranges_leave_out_other_days() {
+        let storage = storage("range");
+        let day = NaiveDate::from_ymd_opt(2026, 3, 3);
+        let (save, _) = storage.read_between(day, day).unwrap().unwrap().extract();
+        assert_eq!(save.events.iter().map(|ev| ev.id).collect::<Vec<_>>(), [4, 5]);
+        let (save, _) = storage.read_between(day, None).unwrap().unwrap().extract();
+        assert_eq!(save.events.len(), 3);
+        fs::remove_file(&storage.path).unwrap();
+    }
+
+    #[test]
+    fn older_versions_are_upgraded() {
+        let storage = storage("upgrade");
+        let db = Connection::open(&storage.path).unwrap();
+        db.pragma_update(None, "user_version", FORMAT_VERSION - 1).unwrap();
+        drop(db);
+        let (save, upgraded) = storage.read().unwrap().unwrap().extract();
+        assert!(upgraded);
+        let expected = UnverifiedSaveDataVersioned::from(UnverifiedSaveDataLatest::from(fixture()));
+        assert_eq!(snapshot(save.into()), snapshot(expected));
+        fs::remove_file(&storage.path).unwrap();
+    }
+}
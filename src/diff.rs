@@ -0,0 +1,148 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::Path,
+};
+
+use itertools::Itertools;
+
+use crate::common::{
+    DeltaItem, Event, EventId, SaveData,
+    error::{Kind, Source, TaskitError, TaskitResult, With},
+    storage,
+};
+
+/// Reads a save file of any version and kind of storage, upgrading it and fixing what can be
+/// fixed the same way taskit does when it starts
+pub fn load_save(path: &Path, source: Source) -> TaskitResult<SaveData> {
+    storage(path)
+        .and_then(|storage| storage.read())
+        .map_err(|e| TaskitError { source, ..e })?
+        .ok_or_else(|| Kind::InvalidSave(format!("{}: there's no save there", path.display())).with(source))?
+        .extract()
+        .0
+        .fix_and_verify()
+        .map_err(|e| Kind::InvalidSave(format!("{}: {e:?}", path.display())).with(source))
+}
+
+/// Names of the parts of an event that differ between two versions of it
+fn changed_fields(old: &Event, new: &Event) -> Vec<&'static str> {
+    [
+        ("date", old.date != new.date),
+        ("start", old.start_time != new.start_time),
+        ("end", old.end_time != new.end_time),
+        ("category", old.category != new.category),
+        ("description", old.description != new.description),
+        ("tags", old.tags != new.tags),
+        ("pinned", old.pinned != new.pinned),
+        ("fields", old.fields != new.fields),
+    ]
+    .into_iter()
+    .filter_map(|(name, changed)| changed.then_some(name))
+    .collect()
+}
+
+/// Prints a section of the diff, if it has any lines
+fn section(title: &str, lines: Vec<String>) -> bool {
+    if lines.is_empty() {
+        return false;
+    }
+    println!("{title}");
+    for line in lines {
+        println!("  {line}");
+    }
+    true
+}
+
+/// Prints what differs between two saves: categories, tags, events, and daily notes. Events are
+/// matched by their ids, which are never reused, so this is meant for two versions of the same
+/// save, like a backup and the current one.
+pub fn diff_main(save_data: SaveData, old: &Path, new: Option<&Path>) -> TaskitResult<Vec<DeltaItem>> {
+    let old = load_save(old, Source::Diffing)?;
+    let new = match new {
+        Some(path) => load_save(path, Source::Diffing)?,
+        None => save_data,
+    };
+    print_diff(&old, &new);
+    Ok(vec![])
+}
+
+/// Prints the differences between two versions of a save, section by section
+pub fn print_diff(old: &SaveData, new: &SaveData) {
+    let mut any = false;
+
+    let names = |data: &SaveData| -> BTreeSet<String> {
+        data.categories.iter().chain(data.archived_categories.iter()).map(|c| c.own()).collect()
+    };
+    let (old_names, new_names) = (names(old), names(new));
+    let archived = |data: &SaveData, name: &str| data.archived_categories.iter().any(|c| c.inner() == name);
+    let categories = new_names
+        .difference(&old_names)
+        .map(|name| format!("+ {name}"))
+        .chain(old_names.difference(&new_names).map(|name| format!("- {name}")))
+        .chain(old_names.intersection(&new_names).filter_map(|name| {
+            match (archived(old, name), archived(new, name)) {
+                (false, true) => Some(format!("~ {name} was archived")),
+                (true, false) => Some(format!("~ {name} was unarchived")),
+                _ => None,
+            }
+        }))
+        .collect();
+    any |= section("Categories", categories);
+
+    let old_tags: BTreeSet<&str> = old.tags.iter().map(|t| t.inner()).collect();
+    let new_tags: BTreeSet<&str> = new.tags.iter().map(|t| t.inner()).collect();
+    let tags = new_tags
+        .difference(&old_tags)
+        .map(|tag| format!("+ #{tag}"))
+        .chain(old_tags.difference(&new_tags).map(|tag| format!("- #{tag}")))
+        .collect();
+    any |= section("Tags", tags);
+
+    let old_events: HashMap<EventId, &Event> = old.events.iter().map(|ev| (ev.id, ev)).collect();
+    let new_events: HashMap<EventId, &Event> = new.events.iter().map(|ev| (ev.id, ev)).collect();
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    let mut events = vec![];
+    for ev in old.events.iter().chain(new.events.iter().filter(|ev| !old_events.contains_key(&ev.id))).sorted_by_key(|ev| (ev.date, ev.start_time, ev.id)) {
+        match (old_events.get(&ev.id), new_events.get(&ev.id)) {
+            (Some(_), None) => {
+                removed += 1;
+                events.push(format!("- {ev}"));
+            }
+            (None, Some(_)) => {
+                added += 1;
+                events.push(format!("+ {ev}"));
+            }
+            (Some(old_ev), Some(new_ev)) => {
+                let fields = changed_fields(old_ev, new_ev);
+                if !fields.is_empty() {
+                    changed += 1;
+                    events.push(format!("~ {old_ev}"));
+                    events.push(format!("  now {new_ev} (changed: {})", fields.join(", ")));
+                }
+            }
+            (None, None) => unreachable!("every event is from one of the saves"),
+        }
+    }
+    any |= section("Events", events);
+
+    let first_line = |note: &str| note.lines().next().unwrap_or_default().to_owned();
+    let dates: BTreeSet<_> = old.daily_notes.keys().chain(new.daily_notes.keys()).collect();
+    let notes = dates
+        .into_iter()
+        .filter_map(|date| match (old.daily_notes.get(date), new.daily_notes.get(date)) {
+            (None, Some(note)) => Some(format!("+ {date}: {}", first_line(note))),
+            (Some(note), None) => Some(format!("- {date}: {}", first_line(note))),
+            (Some(old_note), Some(new_note)) if old_note != new_note => {
+                Some(format!("~ {date}: {}", first_line(new_note)))
+            }
+            _ => None,
+        })
+        .collect();
+    any |= section("Daily notes", notes);
+
+    if any {
+        println!("\n{added} event(s) added, {removed} removed, {changed} changed.");
+    } else {
+        println!("No differences.");
+    }
+}
@@ -0,0 +1,658 @@
+#[cfg(feature = "tui")]
+use std::{
+    io,
+    process::{Command, Stdio},
+};
+use std::{collections::BTreeMap, fs, io::Write, path::PathBuf, str::FromStr};
+
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use clap::ValueEnum;
+use itertools::Itertools;
+use serde_json::{Map, Value};
+
+use crate::{
+    common::{
+        DeltaItem, Duration, Event, FieldValue, ReportGrouping, SaveData, SimpleTime,
+        config::CONFIG,
+        error::{Kind, Source, TaskitResult, With},
+    },
+    report::{ReportRow, format_duration},
+    util::DateRange,
+};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Markdown,
+    /// One JSON object per line
+    Jsonl,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Date,
+    Start,
+    End,
+    /// Formatted like `1h30m`, except in JSON Lines, where it's a number of minutes
+    Duration,
+    Category,
+    Description,
+    Tags,
+    Pinned,
+    /// How the event was recorded, e.g. `stopwatch` or `import:toggl`
+    Source,
+}
+
+/// An exported column: either one of the built-in fields or a custom field from the config
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Column {
+    Builtin(Field),
+    Custom(String),
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // names that aren't built in are checked against the config once it's loaded
+        Ok(Field::from_str(s, true).map_or_else(|_| Column::Custom(s.to_owned()), Column::Builtin))
+    }
+}
+
+impl ExportFormat {
+    #[cfg(feature = "tui")]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Csv => "CSV",
+            Self::Markdown => "Markdown",
+            Self::Jsonl => "JSON Lines",
+        }
+    }
+
+    /// The fields exported when `--fields` isn't given. CSV and Markdown tables usually end up in
+    /// front of clients, so they leave descriptions and custom fields out unless asked for.
+    fn default_columns(self) -> Vec<Column> {
+        match self {
+            Self::Csv | Self::Markdown => {
+                [Field::Date, Field::Start, Field::End, Field::Duration, Field::Category]
+                    .into_iter()
+                    .map(Column::Builtin)
+                    .collect()
+            }
+            Self::Jsonl => [
+                Field::Date,
+                Field::Start,
+                Field::End,
+                Field::Duration,
+                Field::Category,
+                Field::Description,
+                Field::Tags,
+                Field::Pinned,
+                Field::Source,
+            ]
+            .into_iter()
+            .map(Column::Builtin)
+            .chain(CONFIG.fields.iter().map(|f| Column::Custom(f.name.clone())))
+            .collect(),
+        }
+    }
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Date => "date",
+            Self::Start => "start",
+            Self::End => "end",
+            Self::Duration => "duration",
+            Self::Category => "category",
+            Self::Description => "description",
+            Self::Tags => "tags",
+            Self::Pinned => "pinned",
+            Self::Source => "source",
+        }
+    }
+
+    fn text(self, ev: &Event) -> String {
+        match self {
+            Self::Date => ev.date.to_string(),
+            Self::Start => clock_time(ev.start_time),
+            Self::End => clock_time(ev.end_time),
+            Self::Duration => format_duration(&ev.duration()),
+            Self::Category => ev.category.own(),
+            Self::Description => ev.description.clone(),
+            Self::Tags => ev.tags.iter().map(|t| t.inner()).sorted().join(" "),
+            Self::Pinned => ev.pinned.to_string(),
+            Self::Source => ev.source.to_string(),
+        }
+    }
+
+    fn json(self, ev: &Event) -> Value {
+        match self {
+            Self::Duration => ev.duration().num_minutes().into(),
+            Self::Tags => ev.tags.iter().map(|t| t.inner()).sorted().collect(),
+            Self::Pinned => ev.pinned.into(),
+            _ => self.text(ev).into(),
+        }
+    }
+}
+
+/// A column that can be exported for each item of type `T`
+trait ExportColumn<T> {
+    fn name(&self) -> &str;
+    fn text(&self, item: &T) -> String;
+    fn json(&self, item: &T) -> Value;
+}
+
+impl ExportColumn<&Event> for Column {
+    fn name(&self) -> &str {
+        match self {
+            Column::Builtin(field) => field.name(),
+            Column::Custom(name) => name,
+        }
+    }
+
+    fn text(&self, ev: &&Event) -> String {
+        match self {
+            Column::Builtin(field) => field.text(ev),
+            Column::Custom(name) => ev.fields.get(name).map(|v| v.to_string()).unwrap_or_default(),
+        }
+    }
+
+    fn json(&self, ev: &&Event) -> Value {
+        match self {
+            Column::Builtin(field) => field.json(ev),
+            Column::Custom(name) => match ev.fields.get(name) {
+                Some(FieldValue::Bool(b)) => (*b).into(),
+                Some(FieldValue::Number(n)) => (*n).into(),
+                Some(FieldValue::String(s)) => s.clone().into(),
+                None => Value::Null,
+            },
+        }
+    }
+}
+
+/// A time as 24-hour `HH:MM`, with `:SS` only if it has seconds, whatever the display preferences
+pub fn clock_time(time: SimpleTime) -> String {
+    match time.second {
+        0 => format!("{:02}:{:02}", time.hour, time.minute),
+        second => format!("{:02}:{:02}:{second:02}", time.hour, time.minute),
+    }
+}
+
+fn csv_cell(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+fn markdown_cell(s: &str) -> String {
+    s.replace('|', "\\|").lines().join("<br>")
+}
+
+fn render<T>(items: &[T], format: ExportFormat, fields: &[impl ExportColumn<T>]) -> String {
+    let mut out = String::new();
+    match format {
+        ExportFormat::Csv => {
+            out.push_str(&fields.iter().map(|f| f.name()).join(","));
+            out.push('\n');
+            for ev in items {
+                out.push_str(&fields.iter().map(|f| csv_cell(&f.text(ev))).join(","));
+                out.push('\n');
+            }
+        }
+        ExportFormat::Markdown => {
+            out.push_str(&format!("| {} |\n", fields.iter().map(|f| f.name()).join(" | ")));
+            out.push_str(&format!("|{}\n", "---|".repeat(fields.len())));
+            for ev in items {
+                out.push_str(&format!(
+                    "| {} |\n",
+                    fields.iter().map(|f| markdown_cell(&f.text(ev))).join(" | ")
+                ));
+            }
+        }
+        ExportFormat::Jsonl => {
+            for ev in items {
+                let object: Map<String, Value> = fields
+                    .iter()
+                    .map(|f| (f.name().to_owned(), f.json(ev)))
+                    .collect();
+                out.push_str(&Value::Object(object).to_string());
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// An event as a JSON object with every field, as in a JSON Lines export
+#[cfg(feature = "certify")]
+pub fn event_json(ev: &Event) -> Map<String, Value> {
+    ExportFormat::Jsonl
+        .default_columns()
+        .iter()
+        .map(|c| (c.name().to_owned(), c.json(&ev)))
+        .collect()
+}
+
+/// Renders events with the format's default fields, in chronological order
+#[cfg(feature = "tui")]
+pub fn render_events(mut events: Vec<&Event>, format: ExportFormat) -> String {
+    events.sort_by_key(|ev| (ev.date, ev.start_time));
+    render(&events, format, &format.default_columns())
+}
+
+/// Copies text to the system clipboard using whichever clipboard tool is installed
+#[cfg(feature = "tui")]
+pub fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    const TOOLS: [(&str, &[&str]); 5] = [
+        ("pbcopy", &[]),
+        ("wl-copy", &[]),
+        ("xclip", &["-selection", "clipboard"]),
+        ("xsel", &["--clipboard", "--input"]),
+        ("clip.exe", &[]),
+    ];
+    for (program, args) in TOOLS {
+        let Ok(mut child) = Command::new(program).args(args).stdin(Stdio::piped()).spawn() else {
+            continue;
+        };
+        child
+            .stdin
+            .take()
+            .expect("stdin was piped")
+            .write_all(text.as_bytes())?;
+        if child.wait()?.success() {
+            return Ok(());
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "no clipboard tool found (tried pbcopy, wl-copy, xclip, xsel, and clip.exe)",
+    ))
+}
+
+pub fn export_main(
+    save_data: SaveData,
+    format: ExportFormat,
+    fields: Vec<Column>,
+    range: Option<DateRange>,
+    out: Option<PathBuf>,
+    include_private: bool,
+) -> TaskitResult<Vec<DeltaItem>> {
+    if let Some(Column::Custom(name)) = fields
+        .iter()
+        .find(|c| matches!(c, Column::Custom(name) if CONFIG.field(name).is_none()))
+    {
+        return Err(Kind::NoSuchField(name.clone()).with(Source::Exporting));
+    }
+    let fields = if fields.is_empty() { format.default_columns() } else { fields };
+    let mut events: Vec<&Event> = save_data
+        .events
+        .iter()
+        .filter(|ev| range.is_none_or(|range| range.contains(ev.date)))
+        .filter(|ev| include_private || !save_data.private_categories.contains(&ev.category))
+        .collect();
+    events.sort_by_key(|ev| (ev.date, ev.start_time));
+    let document = render(&events, format, &fields);
+    write_document(&document, out, &format!("{} events", events.len()))?;
+    Ok(vec![])
+}
+
+/// Writes an export to `out`, or to standard output if it's not given. `what` describes what was
+/// written, e.g. "12 events".
+pub fn write_document(document: &str, out: Option<PathBuf>, what: &str) -> TaskitResult<()> {
+    match out {
+        Some(path) => {
+            fs::write(&path, document).with(Source::Exporting)?;
+            println!("Wrote {what} to {}", path.display());
+        }
+        None => std::io::stdout()
+            .write_all(document.as_bytes())
+            .with(Source::Exporting)?,
+    }
+    Ok(())
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum ExportKind {
+    /// One row per day with tracked time, e.g. for a home-office or tax declaration
+    Workdays {
+        /// Only include this calendar year
+        #[arg(long, conflicts_with = "range")]
+        year: Option<i32>,
+        /// Only include days in this range (see `taskit share --help` for the accepted forms)
+        #[arg(long)]
+        range: Option<DateRange>,
+        /// Comma-separated list of columns, in order: date, weekday, hours, duration, start, end,
+        /// events, category, or the name of a custom field. Category and custom fields show
+        /// whichever value took up the most time that day. Defaults to `columns` under
+        /// `[workdays]` in the config, or date, hours, and category.
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<WorkdayColumn>,
+        #[arg(long, value_enum, default_value = "csv")]
+        format: ExportFormat,
+        /// Write to this file instead of standard output
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Include events in private categories
+        #[arg(long)]
+        include_private: bool,
+    },
+    /// Events as an iCalendar (.ics) file, e.g. to overlay tracked time on a calendar app
+    Ics {
+        /// Only include events in this range (see `taskit share --help` for the accepted forms)
+        #[arg(long)]
+        range: Option<DateRange>,
+        /// Write to this file instead of standard output
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Include events in private categories
+        #[arg(long)]
+        include_private: bool,
+    },
+    #[cfg(feature = "certify")]
+    /// Events as JSON Lines linked by a hash chain and signed with the minisign key under
+    /// `[certify]` in the config, so whoever receives them can check they weren't altered (see
+    /// `taskit verify-export`)
+    Certified {
+        /// Only include events in this range (see `taskit share --help` for the accepted forms)
+        #[arg(long)]
+        range: Option<DateRange>,
+        /// Where to write the export. The signature is written next to it, with `.minisig` added
+        #[arg(long)]
+        out: PathBuf,
+        /// Write the hash chain without signing it
+        #[arg(long)]
+        unsigned: bool,
+        /// Include events in private categories
+        #[arg(long)]
+        include_private: bool,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkdayField {
+    Date,
+    Weekday,
+    /// Decimal hours, e.g. `7.50`
+    Hours,
+    Duration,
+    /// When the first event of the day started
+    Start,
+    /// When the last event of the day ended
+    End,
+    Events,
+    Category,
+}
+
+/// A workday column: either one of the built-in ones or the dominant value of a custom field
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WorkdayColumn {
+    Builtin(WorkdayField),
+    Custom(String),
+}
+
+impl FromStr for WorkdayColumn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(WorkdayField::from_str(s, true).map_or_else(|_| Self::Custom(s.to_owned()), Self::Builtin))
+    }
+}
+
+/// Everything tracked on one day
+struct Workday<'a> {
+    date: NaiveDate,
+    events: Vec<&'a Event>,
+}
+
+impl Workday<'_> {
+    fn total(&self) -> Duration {
+        self.events.iter().map(|ev| ev.duration()).sum()
+    }
+
+    /// Whichever value (as given by `value`) the most time was spent on
+    fn dominant(&self, value: impl Fn(&Event) -> Option<String>) -> String {
+        let mut totals: BTreeMap<String, Duration> = BTreeMap::new();
+        for ev in &self.events {
+            if let Some(v) = value(ev) {
+                *totals.entry(v).or_default() += ev.duration();
+            }
+        }
+        totals.into_iter().max_by_key(|(_, time)| *time).map(|(v, _)| v).unwrap_or_default()
+    }
+
+    fn hours(&self) -> f64 {
+        self.total().num_minutes() as f64 / 60.0
+    }
+}
+
+impl ExportColumn<Workday<'_>> for WorkdayColumn {
+    fn name(&self) -> &str {
+        match self {
+            WorkdayColumn::Builtin(field) => match field {
+                WorkdayField::Date => "date",
+                WorkdayField::Weekday => "weekday",
+                WorkdayField::Hours => "hours",
+                WorkdayField::Duration => "duration",
+                WorkdayField::Start => "start",
+                WorkdayField::End => "end",
+                WorkdayField::Events => "events",
+                WorkdayField::Category => "category",
+            },
+            WorkdayColumn::Custom(name) => name,
+        }
+    }
+
+    fn text(&self, day: &Workday) -> String {
+        match self {
+            WorkdayColumn::Builtin(field) => match field {
+                WorkdayField::Date => day.date.to_string(),
+                WorkdayField::Weekday => day.date.format("%A").to_string(),
+                WorkdayField::Hours => format!("{:.2}", day.hours()),
+                WorkdayField::Duration => format_duration(&day.total()),
+                // events are sorted by start time
+                WorkdayField::Start => day.events.first().map(|ev| clock_time(ev.start_time)).unwrap_or_default(),
+                WorkdayField::End => day
+                    .events
+                    .iter()
+                    .map(|ev| ev.end_time)
+                    .max()
+                    .map(clock_time)
+                    .unwrap_or_default(),
+                WorkdayField::Events => day.events.len().to_string(),
+                WorkdayField::Category => day.dominant(|ev| Some(ev.category.own())),
+            },
+            WorkdayColumn::Custom(name) => day.dominant(|ev| ev.fields.get(name).map(|v| v.to_string())),
+        }
+    }
+
+    fn json(&self, day: &Workday) -> Value {
+        match self {
+            WorkdayColumn::Builtin(WorkdayField::Hours) => ((day.hours() * 100.0).round() / 100.0).into(),
+            WorkdayColumn::Builtin(WorkdayField::Events) => day.events.len().into(),
+            _ => self.text(day).into(),
+        }
+    }
+}
+
+pub fn export_workdays_main(
+    save_data: SaveData,
+    year: Option<i32>,
+    range: Option<DateRange>,
+    columns: Vec<WorkdayColumn>,
+    format: ExportFormat,
+    out: Option<PathBuf>,
+    include_private: bool,
+) -> TaskitResult<Vec<DeltaItem>> {
+    let range = match year {
+        Some(year) => Some(DateRange::year(year).ok_or_else(|| Kind::InvalidYear(year).with(Source::Exporting))?),
+        None => range,
+    };
+    let columns = if !columns.is_empty() {
+        columns
+    } else if !CONFIG.workdays.columns.is_empty() {
+        CONFIG
+            .workdays
+            .columns
+            .iter()
+            .map(|c| WorkdayColumn::from_str(c).expect("parsing a column never fails"))
+            .collect()
+    } else {
+        [WorkdayField::Date, WorkdayField::Hours, WorkdayField::Category]
+            .into_iter()
+            .map(WorkdayColumn::Builtin)
+            .collect()
+    };
+    if let Some(WorkdayColumn::Custom(name)) = columns
+        .iter()
+        .find(|c| matches!(c, WorkdayColumn::Custom(name) if CONFIG.field(name).is_none()))
+    {
+        return Err(Kind::NoSuchField(name.clone()).with(Source::Exporting));
+    }
+
+    let mut days: BTreeMap<NaiveDate, Vec<&Event>> = BTreeMap::new();
+    for ev in save_data
+        .events
+        .iter()
+        .filter(|ev| range.is_none_or(|range| range.contains(ev.date)))
+        .filter(|ev| include_private || !save_data.private_categories.contains(&ev.category))
+        .filter(|ev| !CONFIG.aggregation.excludes(ev, save_data.tag_map.get(&ev.category)))
+    {
+        days.entry(ev.date).or_default().push(ev);
+    }
+    let days: Vec<Workday> = days
+        .into_iter()
+        .map(|(date, mut events)| {
+            events.sort_by_key(|ev| ev.start_time);
+            Workday { date, events }
+        })
+        .collect();
+    let document = render(&days, format, &columns);
+    write_document(&document, out, &format!("{} days", days.len()))?;
+    Ok(vec![])
+}
+
+/// A column of `taskit report` output
+enum ReportColumn {
+    /// Whatever the rows are grouped by, named after the grouping
+    Group(ReportGrouping),
+    Duration,
+    /// Percentage of the report's total
+    Share,
+    Events,
+}
+
+impl ExportColumn<ReportRow> for ReportColumn {
+    fn name(&self) -> &str {
+        match self {
+            ReportColumn::Group(ReportGrouping::Category) => "category",
+            ReportColumn::Group(ReportGrouping::Tag) => "tag",
+            ReportColumn::Group(ReportGrouping::Day) => "date",
+            ReportColumn::Group(ReportGrouping::Week) => "week",
+            ReportColumn::Group(ReportGrouping::Month) => "month",
+            ReportColumn::Duration => "duration",
+            ReportColumn::Share => "share",
+            ReportColumn::Events => "events",
+        }
+    }
+
+    fn text(&self, row: &ReportRow) -> String {
+        match self {
+            ReportColumn::Group(_) => row.group.clone(),
+            ReportColumn::Duration => format_duration(&row.time),
+            ReportColumn::Share => format!("{:.0}%", row.share),
+            ReportColumn::Events => row.events.to_string(),
+        }
+    }
+
+    fn json(&self, row: &ReportRow) -> Value {
+        match self {
+            // minutes, like events' durations
+            ReportColumn::Duration => row.time.num_minutes().into(),
+            ReportColumn::Share => ((row.share * 10.0).round() / 10.0).into(),
+            ReportColumn::Events => row.events.into(),
+            ReportColumn::Group(_) => self.text(row).into(),
+        }
+    }
+}
+
+/// Renders the rows of a `taskit report` grouped by `grouping`
+pub fn render_report(rows: &[ReportRow], grouping: ReportGrouping, format: ExportFormat) -> String {
+    let columns = [ReportColumn::Group(grouping), ReportColumn::Duration, ReportColumn::Share, ReportColumn::Events];
+    render(rows, format, &columns)
+}
+
+/// Escapes text for an iCalendar property value
+fn ics_text(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Folds an iCalendar content line so that no line is longer than 75 bytes, as the spec requires.
+/// Continuation lines start with a space.
+fn ics_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn render_ics(events: &[&Event]) -> String {
+    let mut out = String::new();
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ");
+    ics_line(&mut out, "BEGIN:VCALENDAR");
+    ics_line(&mut out, "VERSION:2.0");
+    ics_line(&mut out, "PRODID:-//taskit//taskit-tracker//EN");
+    for ev in events {
+        let start = NaiveDateTime::new(ev.date, ev.start_time.try_into().expect("saved times are valid"));
+        // events that end before they start run past midnight
+        let end = start + TimeDelta::from(ev.duration());
+        let summary = match ev.description.lines().next() {
+            Some(first) if !first.is_empty() => format!("{}: {first}", CONFIG.icons.label(&ev.category)),
+            _ => CONFIG.icons.label(&ev.category),
+        };
+        ics_line(&mut out, "BEGIN:VEVENT");
+        // stable across exports, so calendar apps update edited events instead of duplicating them
+        ics_line(&mut out, &format!("UID:event-{}@taskit", ev.id));
+        ics_line(&mut out, &format!("DTSTAMP:{stamp}"));
+        // floating times, so they show up at the same wall-clock time they were tracked at
+        ics_line(&mut out, &format!("DTSTART:{}", start.format("%Y%m%dT%H%M%S")));
+        ics_line(&mut out, &format!("DTEND:{}", end.format("%Y%m%dT%H%M%S")));
+        ics_line(&mut out, &format!("SUMMARY:{}", ics_text(&summary)));
+        if !ev.description.is_empty() {
+            ics_line(&mut out, &format!("DESCRIPTION:{}", ics_text(&ev.description)));
+        }
+        ics_line(&mut out, &format!("CATEGORIES:{}", ics_text(ev.category.inner())));
+        ics_line(&mut out, "END:VEVENT");
+    }
+    ics_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+pub fn export_ics_main(
+    save_data: SaveData,
+    range: Option<DateRange>,
+    out: Option<PathBuf>,
+    include_private: bool,
+) -> TaskitResult<Vec<DeltaItem>> {
+    let mut events: Vec<&Event> = save_data
+        .events
+        .iter()
+        .filter(|ev| range.is_none_or(|range| range.contains(ev.date)))
+        .filter(|ev| include_private || !save_data.private_categories.contains(&ev.category))
+        .collect();
+    events.sort_by_key(|ev| (ev.date, ev.start_time));
+    write_document(&render_ics(&events), out, &format!("{} events", events.len()))?;
+    Ok(vec![])
+}
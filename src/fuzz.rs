@@ -0,0 +1,77 @@
+//! Entry points for the fuzz targets in `fuzz/`. Each one feeds arbitrary text to a parser that
+//! can see input from outside of inquire's prompts, and panics if the parser panics or breaks one
+//! of its own guarantees.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    str::FromStr,
+    sync::Once,
+};
+
+use chrono::NaiveDate;
+
+use crate::{
+    common::{
+        Event, EventId, EventSource, FieldValue, SimpleTime,
+        config::{Config, set_config},
+        invariants::add_category,
+    },
+    import,
+    util::DateRange,
+};
+
+/// The parsers read the config, which the binary would have loaded from disk by now
+fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        set_config(Config::default());
+    });
+}
+
+/// Times that parse display as text that parses back to the same time
+pub fn simple_time(text: &str) {
+    init();
+    if let Ok(time) = SimpleTime::from_str(text) {
+        let shown = time.to_string();
+        assert_eq!(SimpleTime::from_str(&shown), Ok(time), "{text:?} parsed as {shown:?}");
+    }
+}
+
+/// Ranges that parse never end before they start
+pub fn date_range(text: &str) {
+    init();
+    if let Ok(range) = DateRange::from_str(text) {
+        assert!(range.start <= range.end, "{text:?} parsed as {range}");
+    }
+}
+
+/// Field filters, as typed into the filter bar of `taskit show`
+pub fn field_filter(text: &str) {
+    init();
+    let (_, category) = add_category("fuzz".to_owned());
+    let event = Event {
+        id: EventId::NEW,
+        start_time: SimpleTime::try_new(9, 0).expect("09:00 is a valid time"),
+        end_time: SimpleTime::try_new(10, 30).expect("10:30 is a valid time"),
+        date: NaiveDate::from_ymd_opt(2026, 1, 1).expect("2026-01-01 is a valid date"),
+        category,
+        description: "fuzzing #ünïcödé 日本語".to_owned(),
+        tags: HashSet::new(),
+        pinned: false,
+        source: EventSource::Record,
+        external_id: None,
+        fields: BTreeMap::from([
+            ("location".to_owned(), FieldValue::String("Zürich".to_owned())),
+            ("billable".to_owned(), FieldValue::Bool(true)),
+            ("hours".to_owned(), FieldValue::Number(1.5)),
+        ]),
+    };
+    event.matches_field_filter(text);
+}
+
+/// Files given to `taskit import`, which may have come from anywhere
+pub fn import(text: &str) {
+    init();
+    let _ = import::parse_csv(text);
+    let _ = import::parse_jsonl(text);
+}
@@ -0,0 +1,116 @@
+use std::{
+    env,
+    fs::{self, File, OpenOptions, rename},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::common::{
+    DeltaItem, SaveData,
+    error::{Kind, Source, TaskitResult, With},
+};
+
+/// How many commands are kept. Older ones are dropped as new ones are recorded.
+const HISTORY_LENGTH: usize = 100;
+
+/// One successful run of taskit
+#[derive(Serialize, Deserialize)]
+struct HistoryEntry {
+    time: NaiveDateTime,
+    /// The arguments taskit was run with, kept separate so that redoing doesn't depend on quoting
+    args: Vec<String>,
+    /// Where taskit was run from, so that relative paths in the arguments mean the same thing
+    dir: PathBuf,
+}
+
+impl HistoryEntry {
+    /// The arguments as they could be typed into a shell
+    fn command(&self) -> String {
+        self.args
+            .iter()
+            .map(|arg| {
+                if !arg.is_empty() && arg.chars().all(|c| c.is_alphanumeric() || "-_./=:,@#".contains(c)) {
+                    arg.clone()
+                } else {
+                    format!("'{}'", arg.replace('\'', r"'\''"))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Like the undo journal, the history sits next to the save file
+pub fn history_path(save_path: &Path) -> PathBuf {
+    save_path.with_extension("history.jsonl")
+}
+
+/// Reads the recorded commands, oldest first. The history is only a convenience, so a missing file
+/// or lines that can't be read are skipped rather than treated as errors.
+fn read_entries(path: &Path) -> Vec<HistoryEntry> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect()
+}
+
+/// Appends the command taskit is currently running with, dropping the oldest commands once there
+/// are more than HISTORY_LENGTH
+pub fn record(path: &Path) -> io::Result<()> {
+    let entry = HistoryEntry {
+        time: Local::now().naive_local(),
+        args: env::args().skip(1).collect(),
+        dir: env::current_dir()?,
+    };
+    let mut entries = read_entries(path);
+    if entries.len() < HISTORY_LENGTH {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        return writeln!(file, "{}", serde_json::to_string(&entry).map_err(io::Error::other)?);
+    }
+    entries.push(entry);
+    let temp_path = path.with_extension("tmp");
+    {
+        let mut file = File::create(&temp_path)?;
+        for entry in &entries[entries.len() - HISTORY_LENGTH..] {
+            writeln!(file, "{}", serde_json::to_string(entry).map_err(io::Error::other)?)?;
+        }
+    }
+    rename(temp_path, path)
+}
+
+/// Lists the most recent commands, numbered the way `taskit redo` expects
+pub fn history_main(_save_data: SaveData, history: &Path, limit: usize) -> TaskitResult<Vec<DeltaItem>> {
+    let entries = read_entries(history);
+    if entries.is_empty() {
+        println!("No commands recorded yet.");
+    }
+    for (n, entry) in entries.iter().rev().enumerate().take(limit) {
+        println!("{:>3}  {}  taskit {}", n + 1, entry.time.format("%Y-%m-%d %H:%M"), entry.command());
+    }
+    Ok(vec![])
+}
+
+/// Runs the `n`th most recent command again. It's run as a separate process, exactly as it was
+/// typed the first time, so it saves its own changes and shows up in the history again.
+pub fn redo_main(_save_data: SaveData, history: &Path, n: usize) -> TaskitResult<Vec<DeltaItem>> {
+    let entries = read_entries(history);
+    let Some(entry) = n.checked_sub(1).and_then(|i| entries.iter().rev().nth(i)) else {
+        return Err(Kind::NoSuchHistoryEntry(n).with(Source::Redoing));
+    };
+    let command = entry.command();
+    println!("Running `taskit {command}`");
+    let status = Command::new(env::current_exe().with(Source::Redoing)?)
+        .args(&entry.args)
+        .current_dir(&entry.dir)
+        .status()
+        .with(Source::Redoing)?;
+    if !status.success() {
+        return Err(Kind::RedoFailed(command).with(Source::Redoing));
+    }
+    Ok(vec![])
+}
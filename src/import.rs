@@ -0,0 +1,304 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::PathBuf,
+};
+
+use chrono::{NaiveDate, NaiveTime};
+use inquire::Confirm;
+use serde_json::Value;
+
+use crate::{
+    common::{
+        DeltaItem, Event, EventId, EventSource, FieldValue, SaveData, SimpleTime,
+        config::CONFIG,
+        error::{Kind, Source, TaskitResult, With},
+        get_description_tags,
+        invariants::{Category, Tag, add_category, add_tag},
+    },
+};
+
+/// One event as read from an import file, before it's checked against the save
+pub struct ImportedEvent {
+    /// Where the event starts in the file, for errors
+    line: usize,
+    date: NaiveDate,
+    start_time: SimpleTime,
+    end_time: SimpleTime,
+    category: String,
+    description: String,
+    id: Option<String>,
+    fields: BTreeMap<String, FieldValue>,
+}
+
+fn invalid(line: usize, message: impl std::fmt::Display) -> crate::common::error::TaskitError {
+    Kind::InvalidImport(format!("line {line}: {message}")).with(Source::Importing)
+}
+
+impl ImportedEvent {
+    /// Builds an event from named fields, using the same names as `taskit export`, plus an
+    /// optional `id` and any custom fields from the config
+    fn from_fields(line: usize, get: impl Fn(&str) -> Option<String>) -> TaskitResult<Self> {
+        let required = |name: &str| get(name).ok_or_else(|| invalid(line, format!("missing {name}")));
+        let time = |name: &str| -> TaskitResult<SimpleTime> {
+            let text = required(name)?;
+            NaiveTime::parse_from_str(&text, "%H:%M")
+                .or_else(|_| NaiveTime::parse_from_str(&text, "%H:%M:%S"))
+                .map(Into::into)
+                .map_err(|_| invalid(line, format!("invalid {name} time {text:?} (expected HH:MM)")))
+        };
+        let date = required("date")?;
+        let mut fields = BTreeMap::new();
+        for field in &CONFIG.fields {
+            let Some(text) = get(&field.name).filter(|t| !t.trim().is_empty()) else {
+                continue;
+            };
+            let value = field.parse(&text).ok_or_else(|| {
+                invalid(line, format!("invalid {} {text:?} (expected {})", field.name, field.expected()))
+            })?;
+            fields.insert(field.name.clone(), value);
+        }
+        Ok(Self {
+            line,
+            date: NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                .map_err(|_| invalid(line, format!("invalid date {date:?} (expected YYYY-MM-DD)")))?,
+            start_time: time("start")?,
+            end_time: time("end")?,
+            category: required("category")?,
+            description: get("description").unwrap_or_default(),
+            id: get("id").filter(|id| !id.is_empty()),
+            fields,
+        })
+    }
+}
+
+pub fn parse_jsonl(text: &str) -> TaskitResult<Vec<ImportedEvent>> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| {
+            let value: Value = serde_json::from_str(l).map_err(|e| invalid(i + 1, e))?;
+            ImportedEvent::from_fields(i + 1, |name| match value.get(name)? {
+                Value::String(s) => Some(s.clone()),
+                Value::Null => None,
+                other => Some(other.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Splits CSV text into records, handling quoted fields (which may contain commas, doubled quotes,
+/// and newlines). Each record comes with the line it starts on.
+fn csv_records(text: &str) -> Vec<(usize, Vec<String>)> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let (mut line, mut record_line) = (1, 1);
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => record.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                record.push(std::mem::take(&mut field));
+                records.push((record_line, std::mem::take(&mut record)));
+                line += 1;
+                record_line = line;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((record_line, record));
+    }
+    records.retain(|(_, r)| !(r.len() == 1 && r[0].trim().is_empty()));
+    records
+}
+
+pub fn parse_csv(text: &str) -> TaskitResult<Vec<ImportedEvent>> {
+    let mut records = csv_records(text).into_iter();
+    let Some((_, header)) = records.next() else {
+        return Ok(vec![]);
+    };
+    let columns: HashMap<String, usize> = header
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| (name.trim().to_lowercase(), i))
+        .collect();
+    records
+        .map(|(line, record)| {
+            ImportedEvent::from_fields(line, |name| {
+                columns.get(name).and_then(|&i| record.get(i)).cloned()
+            })
+        })
+        .collect()
+}
+
+pub fn import_main(save_data: SaveData, file: PathBuf, source: String) -> TaskitResult<Vec<DeltaItem>> {
+    let extension = file.extension().map(|e| e.to_string_lossy().to_lowercase());
+    let text = fs::read_to_string(&file).with(Source::Importing)?;
+    let imported = match extension.as_deref() {
+        Some("csv") => parse_csv(&text)?,
+        Some("jsonl" | "ndjson") => parse_jsonl(&text)?,
+        _ => return Err(Kind::UnsupportedFormat(file.display().to_string()).with(Source::Importing)),
+    };
+    let source = EventSource::Import(source);
+    // a second row with the same id would have to update an event that hasn't been added yet
+    let mut first_lines: HashMap<&str, usize> = HashMap::new();
+    for ev in &imported {
+        if let Some(id) = &ev.id
+            && let Some(first) = first_lines.insert(id, ev.line)
+        {
+            return Err(invalid(ev.line, format!("id {id:?} was already used on line {first}")));
+        }
+    }
+    let without_id = imported.iter().filter(|ev| ev.id.is_none()).count();
+    if without_id > 0 {
+        println!(
+            "{without_id} event(s) have no id, so they're matched to earlier imports by their date, times, and category. \
+             Changing any of those in the file adds them again on the next import."
+        );
+    }
+
+    let new_categories: Vec<String> = imported
+        .iter()
+        .map(|ev| ev.category.as_str())
+        .filter(|c| !save_data.categories.contains_match(c) && !save_data.archived_categories.contains_match(c))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .map(str::to_owned)
+        .collect();
+    let new_tags: Vec<String> = imported
+        .iter()
+        .flat_map(|ev| get_description_tags(&ev.description))
+        .filter(|t| !save_data.tags.contains_match(t.as_str()))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    if new_tags.iter().any(|t| t.contains(char::is_whitespace)) {
+        return Err(Kind::NoSpaceInTag.with(Source::Importing));
+    }
+    if !new_categories.is_empty() || !new_tags.is_empty() {
+        let mut message = String::from("This import will create");
+        if !new_categories.is_empty() {
+            message.push_str(&format!(" categories {}", new_categories.join(", ")));
+        }
+        if !new_tags.is_empty() {
+            if !new_categories.is_empty() {
+                message.push_str(" and");
+            }
+            let tags: Vec<String> = new_tags.iter().map(|t| format!("#{t}")).collect();
+            message.push_str(&format!(" tags {}", tags.join(", ")));
+        }
+        message.push_str(". Continue?");
+        if !Confirm::new(&message).prompt().with(Source::Importing)? {
+            return Err(Kind::Cancelled.with(Source::Importing));
+        }
+    }
+
+    let mut delta = vec![];
+    let mut categories: HashMap<String, Category> = HashMap::new();
+    for name in new_categories {
+        let (del, cat) = add_category(name.clone());
+        delta.push(del);
+        categories.insert(name, cat);
+    }
+    let mut tags: HashMap<String, Tag> = HashMap::new();
+    for name in new_tags {
+        let (del, tag) = add_tag(name.clone());
+        delta.push(del);
+        tags.insert(name, tag);
+    }
+
+    // events that came from this importer before, by their id
+    let known: HashMap<String, usize> = save_data
+        .events
+        .iter()
+        .enumerate()
+        .filter(|(_, ev)| ev.source == source)
+        .filter_map(|(i, ev)| ev.external_id.clone().map(|id| (id, i)))
+        .collect();
+    // and the ones that came without an id, by when they were and their category. Each can only be
+    // matched once, so that identical rows in one file stay separate events
+    let mut known_without_id: HashMap<(NaiveDate, SimpleTime, SimpleTime, String), Vec<usize>> = HashMap::new();
+    for (i, ev) in save_data.events.iter().enumerate() {
+        if ev.source == source && ev.external_id.is_none() {
+            known_without_id
+                .entry((ev.date, ev.start_time, ev.end_time, ev.category.inner().to_owned()))
+                .or_default()
+                .push(i);
+        }
+    }
+    let (mut added, mut updated, mut unchanged) = (0, 0, 0);
+    for ImportedEvent { date, start_time, end_time, category, description, id, fields, .. } in imported {
+        let category = match save_data.categories.find(&category) {
+            Some(cat) => cat.clone(),
+            None => match save_data.archived_categories.find(&category) {
+                Some(cat) => cat.clone(),
+                None => categories[&category].clone(),
+            },
+        };
+        let event_tags = get_description_tags(&description)
+            .into_iter()
+            .map(|t| save_data.tags.find(t.as_str()).cloned().unwrap_or_else(|| tags[&t].clone()))
+            .collect();
+        let existing = match &id {
+            Some(id) => known.get(id).copied(),
+            None => known_without_id
+                .get_mut(&(date, start_time, end_time, category.inner().to_owned()))
+                .and_then(|found| found.pop()),
+        };
+        let event = Event {
+            id: existing.map_or(EventId::NEW, |i| save_data.events[i].id),
+            start_time,
+            end_time,
+            date,
+            category,
+            description,
+            tags: event_tags,
+            pinned: existing.is_some_and(|i| save_data.events[i].pinned),
+            source: source.clone(),
+            external_id: id.clone(),
+            fields,
+        };
+        let unchanged_event = existing.is_some_and(|i| {
+            let old = &save_data.events[i];
+            old.date == event.date
+                && old.start_time == event.start_time
+                && old.end_time == event.end_time
+                && old.category == event.category
+                && old.description == event.description
+                && old.tags == event.tags
+                && old.fields == event.fields
+        });
+        if unchanged_event {
+            unchanged += 1;
+            continue;
+        }
+        // archived categories take no new time, whether it's added or moved there by an update
+        if save_data.archived_categories.contains(&event.category) {
+            return Err(Kind::CategoryArchived(event.category.own()).with(Source::Importing));
+        }
+        if existing.is_some() {
+            updated += 1;
+            delta.push(DeltaItem::ChangeEvent { id: event.id, new_event: event });
+        } else {
+            added += 1;
+            delta.push(DeltaItem::AddEvent(event));
+        }
+    }
+    println!("Added {added} events, updated {updated}, and left {unchanged} unchanged.");
+    Ok(delta)
+}
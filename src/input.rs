@@ -1,19 +1,23 @@
 use std::{
-    collections::HashSet, fmt::Display, io::{Write, stdout}, thread::sleep, time::Duration
+    collections::{BTreeMap, HashSet}, fmt::Display, fs, io::{self, Write, stdout}, path::{Path, PathBuf}, thread::sleep, time
 };
 
+use chrono::{DateTime, Datelike, Days, Local, Months, NaiveDate, NaiveTime, TimeDelta};
 use crossterm::{
     event::{self, Event as CEvent, KeyCode, KeyModifiers},
+    style::Stylize,
     terminal::{disable_raw_mode, enable_raw_mode},
 };
-use inquire::{Autocomplete, Confirm, CustomType, DateSelect, Select, Text};
+use inquire::{Autocomplete, Confirm, CustomType, DateSelect, Select, Text, validator::Validation};
+use itertools::{Either, Itertools};
+use serde::{Deserialize, Serialize};
 
-use crate::common::{
-    CategoriesCompleter, CategoriesPair, DeltaItem, Event, SaveData, SimpleTime, TagCompleter, error::{Kind, Source, TaskitResult, With}, invariants::{Tag, add_category, add_tag, rename_category}
-};
+use crate::{audit::{Activity, gather_activity}, common::{
+    Apply, CategoriesCompleter, CategoriesPair, DeltaItem, Duration, Event, EventSource, FieldValue, EventId, Goal, SaveData, SimpleTime, TagCompleter, category_answer, get_description_tags, config::{CONFIG, FieldKind, GapFill}, error::{Kind, Source, TaskitError, TaskitResult, With}, index::{TextIndex, fuzzy_match}, invariants::{Category, Tag, add_category, add_tag, annotate_event, rename_category}
+}, report::{category_totals, event_problems, format_duration, print_session_summary}, util::{DateRange, display_date, highlight_graphemes}};
 
 #[derive(Clone)]
-struct DescriptionTagsAutocomplete<'a>(&'a [Tag]);
+pub struct DescriptionTagsAutocomplete<'a>(pub &'a [Tag]);
 
 impl<'a> Autocomplete for DescriptionTagsAutocomplete<'a> {
     fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, inquire::CustomUserError> {
@@ -53,17 +57,9 @@ impl<'a> Autocomplete for DescriptionTagsAutocomplete<'a> {
     }
 }
 
-pub fn get_description_tags(description: &str) -> HashSet<String> {
-    description
-        .split(' ')
-        .filter(|s| s.starts_with('#'))
-        .map(|s| s[1..].to_owned())
-        .collect()
-}
-
 /// The purpose of this function is to add new tags in case the description included tags that don't exist.
 /// Retuns Ok((delta items required to add new tags, HashSet<Tag> of all tags from prospective), or Err if user refused
-fn validate_description_tags<'a>(
+pub fn validate_description_tags<'a>(
     prospective_tags: impl Iterator<Item=String>,
     mut valid_tags: impl Iterator<Item=&'a Tag>,
 ) -> TaskitResult<(Vec<DeltaItem>, HashSet<Tag>)> {
@@ -91,28 +87,153 @@ fn validate_description_tags<'a>(
     Ok((deltas, tags))
 }
 
-pub fn record_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
+/// Asks for each field declared in the config, starting from the event's current values. Empty
+/// answers leave a field unset. Values of fields that are no longer in the config are kept as-is.
+pub fn prompt_custom_fields(
+    current: &BTreeMap<String, FieldValue>,
+    source: Source,
+) -> TaskitResult<BTreeMap<String, FieldValue>> {
+    let mut fields = current.clone();
+    for field in &CONFIG.fields {
+        if !field.choices.is_empty() {
+            const NONE: &str = "(none)";
+            let options: Vec<&str> = std::iter::once(NONE)
+                .chain(field.choices.iter().map(String::as_str))
+                .collect();
+            let cursor = current
+                .get(&field.name)
+                .and_then(|v| options.iter().position(|o| *o == v.to_string()))
+                .unwrap_or(0);
+            let answer = Select::new(&format!("{}:", field.name), options)
+                .with_starting_cursor(cursor)
+                .prompt()
+                .with(source)?;
+            match answer {
+                NONE => fields.remove(&field.name),
+                choice => fields.insert(field.name.clone(), FieldValue::String(choice.to_owned())),
+            };
+            continue;
+        }
+        let help = match field.kind {
+            FieldKind::String => "optional".to_owned(),
+            _ => format!("optional, {}", field.expected()),
+        };
+        let initial = current.get(&field.name).map(|v| v.to_string()).unwrap_or_default();
+        let answer = Text::new(&format!("{}:", field.name))
+            .with_initial_value(&initial)
+            .with_help_message(&help)
+            .with_validator(|input: &str| {
+                Ok(if input.trim().is_empty() || field.parse(input).is_some() {
+                    Validation::Valid
+                } else {
+                    Validation::Invalid(format!("Expected {}", field.expected()).into())
+                })
+            })
+            .prompt()
+            .with(source)?;
+        match field.parse(&answer).filter(|_| !answer.trim().is_empty()) {
+            Some(value) => fields.insert(field.name.clone(), value),
+            None => fields.remove(&field.name),
+        };
+    }
+    Ok(fields)
+}
+
+/// Asks for an event's notes, on one line or in the user's editor (see `notes_in_editor` in the
+/// config). Notes that already span several lines always go to the editor so they aren't flattened.
+fn prompt_notes(save_data: &SaveData, current: Option<&str>, source: Source) -> TaskitResult<String> {
+    if CONFIG.prefs.notes_in_editor || current.is_some_and(|notes| notes.contains('\n')) {
+        let notes = inquire::Editor::new("Notes:")
+            .with_predefined_text(current.unwrap_or(""))
+            .prompt()
+            .with(source)?;
+        // editors end the file with a newline, which isn't part of the notes
+        return Ok(notes.trim_end_matches(['\n', '\r']).to_owned());
+    }
+    let mut prompt = Text::new("Notes:").with_autocomplete(DescriptionTagsAutocomplete(save_data.tags.as_ref()));
+    if let Some(current) = current {
+        prompt = prompt.with_default(current);
+    }
+    Ok(prompt.prompt().with(source)?)
+}
+
+pub fn record_main(save_data: SaveData, batch: bool) -> TaskitResult<Vec<DeltaItem>> {
+    if !batch {
+        return record_event(save_data, None);
+    }
+    // later events are recorded against a copy with the earlier ones in it, so that `prev`, gaps,
+    // and overlaps take them into account
+    let mut recorded = save_data.clone();
     let mut delta = vec![];
-    let date = DateSelect::new("Date:")
-        .prompt()
-        .with(Source::CreatingEntry)?;
-    let start_time = CustomType::<SimpleTime>::new("Start time:")
-        .prompt()
-        .with(Source::CreatingEntry)?;
+    let mut carried = None;
+    loop {
+        let event_delta = match record_event(recorded.clone(), carried) {
+            Ok(event_delta) => event_delta,
+            // cancelling one event shouldn't throw away the ones before it
+            Err(TaskitError { kind: Kind::Cancelled, .. }) if !delta.is_empty() => break,
+            Err(e) => return Err(e),
+        };
+        carried = event_delta.iter().rev().find_map(|item| match item {
+            DeltaItem::AddEvent(ev) => Some((ev.date, ev.end_time)),
+            _ => None,
+        });
+        recorded.apply(event_delta.clone())?;
+        delta.extend(event_delta);
+        if !Confirm::new("Record another event?")
+            .with_default(true)
+            .prompt()
+            .with(Source::CreatingEntry)?
+        {
+            break;
+        }
+    }
+    print_session_summary(&save_data, &delta, &[]);
+    Ok(delta)
+}
+
+/// Asks for each part of a new event. In a batch, `carried` is the date and end of the previous
+/// event, which become the defaults for this one's date and start.
+fn record_event(save_data: SaveData, carried: Option<(NaiveDate, SimpleTime)>) -> TaskitResult<Vec<DeltaItem>> {
+    let mut delta = vec![];
+    let mut date_select = DateSelect::new("Date:").with_formatter(&display_date);
+    if let Some((date, _)) = carried {
+        date_select = date_select.with_default(date);
+    }
+    let date = date_select.prompt().with(Source::CreatingEntry)?;
+    let shortcuts = TimeShortcuts {
+        prev: previous_end(&save_data, date, None, None),
+        next: None,
+    };
+    let start_default = carried.filter(|&(carried_date, _)| carried_date == date).map(|(_, end)| end);
+    let mut start_time = prompt_time("Start time:", start_default, shortcuts, Source::CreatingEntry)?;
+    let mut filler = None;
+    if let Some(gap) = close_gap(&save_data, date, start_time)? {
+        match gap {
+            Gap::Snap(previous_end) => start_time = previous_end,
+            Gap::Fill(event) => filler = Some(event),
+        }
+    }
     let category = Text::new("Select a category:")
-        .with_autocomplete(CategoriesCompleter(&save_data.categories))
-        .prompt()
-        .with(Source::CreatingEntry)?;
-    let comments = Text::new("Notes:")
-        .with_autocomplete(DescriptionTagsAutocomplete(save_data.tags.as_ref()))
-        .prompt()
-        .with(Source::CreatingEntry)?;
-    let end_time = CustomType::<SimpleTime>::new("End time:")
+        .with_autocomplete(CategoriesCompleter(&save_data.categories, &save_data.category_details))
         .prompt()
+        .map(|answer| category_answer(answer, &save_data.category_details))
         .with(Source::CreatingEntry)?;
+    let comments = prompt_notes(&save_data, None, Source::CreatingEntry)?;
+    let fields = prompt_custom_fields(&BTreeMap::new(), Source::CreatingEntry)?;
+    let shortcuts = TimeShortcuts {
+        prev: None,
+        next: next_start(&save_data, date, start_time, None),
+    };
+    let typical_end = save_data
+        .categories
+        .find(&category)
+        .and_then(|cat| CONFIG.record.default_durations.get(cat.inner()))
+        .or_else(|| CONFIG.record.default_durations.get(&category))
+        .map(|minutes| minutes_to_time((time_to_minutes(start_time) + minutes) % (24 * 60)));
+    let end_time = prompt_time("End time:", typical_end, shortcuts, Source::CreatingEntry)?;
     if save_data.archived_categories.contains_match(&category) {
         println!("Category {category} is archived. Try again!");
-        return record_main(save_data);
+        return record_event(save_data, carried);
     }
     let category = if let Some(cat) = save_data.categories.find(&category) {
         cat.clone()
@@ -128,60 +249,458 @@ pub fn record_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
             cat
         } else {
             println!("Cannot create event with nonexistent category.");
-            return record_main(save_data);
+            return record_event(save_data, carried);
         }
     };
     let tags = get_description_tags(&comments);
     let (tag_deltas, tags) = validate_description_tags(tags.into_iter(), save_data.tags.iter())?;
-    delta.extend(tag_deltas);
-    delta.push(DeltaItem::AddEvent(Event {
+    let event = Event {
+        id: EventId::NEW,
         start_time,
         end_time,
         date,
         category,
         description: comments,
         tags,
-    }));
+        pinned: false,
+        source: EventSource::Record,
+        external_id: None,
+        fields,
+    };
+    if !confirm_plausible(&save_data, &event, None, Source::CreatingEntry)? {
+        println!("Event not recorded. Try again!");
+        return record_event(save_data, carried);
+    }
+    delta.extend(tag_deltas);
+    delta.extend(filler.map(DeltaItem::AddEvent));
+    delta.push(DeltaItem::AddEvent(event));
     Ok(delta)
 }
 
-pub fn stopwatch_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
-    let mut delta = vec![];
-    let start_datetime = chrono::Local::now();
-    let date = start_datetime.date_naive();
-    let start_time: SimpleTime = start_datetime.time().into();
-    enable_raw_mode().with(Source::RunningStopwatch)?;
-    'l: loop {
-        let now: SimpleTime = chrono::Local::now().time().into();
-        let timedelta = now - start_time;
-        print!(
-            "\r{:02}:{:02} (<Enter> to finish)",
-            timedelta.num_hours(),
-            timedelta.num_minutes() % 60,
-        );
+/// Events in a category that never fell on a weekend before it are only asked about once there
+/// are this many of them, so new categories aren't flagged on their first weekend
+const WEEKDAY_ONLY_MIN_EVENTS: usize = 10;
+
+fn is_weekend(date: NaiveDate) -> bool {
+    date.weekday().num_days_from_monday() >= 5
+}
+
+/// Reasons an event about to be saved looks like a typo: it's very long, far in the future,
+/// overlaps another event (other than the one at `skip`, which it replaces), or is on a weekend
+/// when its category never has been
+fn suspicions(save_data: &SaveData, event: &Event, skip: Option<usize>) -> Vec<String> {
+    let settings = &CONFIG.record;
+    let mut suspicions = vec![];
+    let max_hours = settings.warn_over_hours.unwrap_or(12);
+    if event.duration() > Duration::hours(max_hours as i64) {
+        suspicions.push(format!("it's {} long", format_duration(&event.duration())));
+    }
+    let days_ahead = (event.date - Local::now().date_naive()).num_days();
+    if days_ahead > settings.warn_days_ahead.unwrap_or(1) as i64 {
+        suspicions.push(format!("it's {days_ahead} days from now"));
+    }
+    let others = save_data
+        .events
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| Some(i) != skip)
+        .map(|(_, ev)| ev);
+    for other in others.clone().filter(|other| other.date == event.date) {
+        if other.covers(event.start_time) || event.covers(other.start_time) {
+            suspicions.push(format!("it overlaps {other}"));
+        }
+    }
+    if is_weekend(event.date) {
+        let category_dates: Vec<NaiveDate> = others
+            .filter(|other| other.category == event.category)
+            .map(|other| other.date)
+            .collect();
+        if category_dates.len() >= WEEKDAY_ONLY_MIN_EVENTS && !category_dates.into_iter().any(is_weekend) {
+            suspicions.push(format!(
+                "it's on a {}, but {} has only ever been on weekdays",
+                event.date.format("%A"),
+                event.category
+            ));
+        }
+    }
+    suspicions
+}
+
+/// Asks whether to save an event anyway if it looks like a typo, catching mistakes while they're
+/// easy to fix rather than in a report weeks later
+fn confirm_plausible(save_data: &SaveData, event: &Event, skip: Option<usize>, source: Source) -> TaskitResult<bool> {
+    let suspicions = suspicions(save_data, event, skip);
+    if suspicions.is_empty() {
+        return Ok(true);
+    }
+    println!("This event looks unusual:");
+    for suspicion in &suspicions {
+        println!("  - {suspicion}");
+    }
+    Ok(Confirm::new("Save it anyway?").with_default(false).prompt().with(source)?)
+}
+
+/// How to close the gap between the previous event and a new one
+enum Gap {
+    /// Start the new event when the previous one ended instead
+    Snap(SimpleTime),
+    /// Add this event in the filler category to cover the gap
+    Fill(Event),
+}
+
+/// Checks whether an event starting at `start` would leave a gap after the previous event that
+/// day, and if so, how to close it (per `gap_fill` in the config, asking if need be)
+fn close_gap(save_data: &SaveData, date: NaiveDate, start: SimpleTime) -> TaskitResult<Option<Gap>> {
+    let settings = &CONFIG.record;
+    if settings.gap_fill == GapFill::Off {
+        return Ok(None);
+    }
+    let Some(previous_end) = previous_end(save_data, date, Some(start), None) else {
+        return Ok(None);
+    };
+    if previous_end == start {
+        return Ok(None);
+    }
+    let filler_category = settings
+        .filler_category
+        .as_ref()
+        .and_then(|name| save_data.categories.find(name));
+    let filler = filler_category.map(|category| Event {
+        id: EventId::NEW,
+        start_time: previous_end,
+        end_time: start,
+        date,
+        category: category.clone(),
+        description: String::new(),
+        tags: HashSet::new(),
+        pinned: false,
+        source: EventSource::Record,
+        external_id: None,
+        fields: BTreeMap::new(),
+    });
+    let gap = format_duration(&(start - previous_end));
+    let snap = format!("Start at {previous_end} instead");
+    let fill = filler_category.map(|category| format!("Add a {category} event from {previous_end} to {start}"));
+    match (settings.gap_fill, filler) {
+        (GapFill::Snap, _) => {
+            println!("Starting at {previous_end}, when the previous event ended.");
+            Ok(Some(Gap::Snap(previous_end)))
+        }
+        (GapFill::Fill, Some(filler)) => {
+            println!("Filling the {gap} gap since the previous event with a {} event.", filler.category);
+            Ok(Some(Gap::Fill(filler)))
+        }
+        (GapFill::Fill, None) => Ok(None),
+        (_, filler) => {
+            let leave = "Leave the gap".to_owned();
+            let options: Vec<&String> = [&leave, &snap].into_iter().chain(fill.as_ref()).collect();
+            let choice = Select::new(
+                &format!("There's a {gap} gap since the previous event ended at {previous_end}."),
+                options,
+            )
+            .prompt()
+            .with(Source::CreatingEntry)?;
+            Ok(if *choice == snap {
+                Some(Gap::Snap(previous_end))
+            } else if Some(choice) == fill.as_ref() {
+                filler.map(Gap::Fill)
+            } else {
+                None
+            })
+        }
+    }
+}
+
+/// When the latest event on `date` ended, ignoring the event at `skip` and any ending after `before`
+fn previous_end(
+    save_data: &SaveData,
+    date: NaiveDate,
+    before: Option<SimpleTime>,
+    skip: Option<usize>,
+) -> Option<SimpleTime> {
+    // events running past midnight end the next day, so they can't be the previous event
+    save_data
+        .events
+        .iter()
+        .enumerate()
+        .filter(|&(i, ev)| {
+            Some(i) != skip && ev.date == date && ev.start_time <= ev.end_time
+        })
+        .map(|(_, ev)| ev.end_time)
+        .filter(|&end| before.is_none_or(|before| end <= before))
+        .max()
+}
+
+/// When the first event on `date` starting after `after` starts, ignoring the event at `skip`
+fn next_start(save_data: &SaveData, date: NaiveDate, after: SimpleTime, skip: Option<usize>) -> Option<SimpleTime> {
+    save_data
+        .events
+        .iter()
+        .enumerate()
+        .filter(|&(i, ev)| Some(i) != skip && ev.date == date)
+        .map(|(_, ev)| ev.start_time)
+        .filter(|&start| start > after)
+        .min()
+}
+
+/// Times worked out from the save that can be typed into a time prompt instead of a clock time
+#[derive(Default)]
+struct TimeShortcuts {
+    /// `prev`: when the previous event ended
+    prev: Option<SimpleTime>,
+    /// `next`: when the next event starts
+    next: Option<SimpleTime>,
+}
+
+/// Asks for a time, accepting `now` and any of the shortcuts that could be resolved as well
+fn prompt_time(
+    message: &str,
+    default: Option<SimpleTime>,
+    shortcuts: TimeShortcuts,
+    source: Source,
+) -> TaskitResult<SimpleTime> {
+    let parser = |input: &str| match input.trim() {
+        "now" => Ok(SimpleTime::now()),
+        "prev" => shortcuts.prev.ok_or(()),
+        "next" => shortcuts.next.ok_or(()),
+        other => other.parse(),
+    };
+    let shortcut_names: Vec<String> = [("prev", shortcuts.prev), ("next", shortcuts.next)]
+        .into_iter()
+        .filter_map(|(name, time)| time.map(|time| format!("{name} ({time})")))
+        .chain(["now".to_owned()])
+        .collect();
+    let help = format!("or {}", shortcut_names.join(", "));
+    let error = format!("Enter a time like 9:30, or {}", shortcut_names.join(", "));
+    let mut prompt = CustomType::<SimpleTime>::new(message)
+        .with_parser(&parser)
+        .with_help_message(&help)
+        .with_error_message(&error);
+    if let Some(default) = default {
+        prompt = prompt.with_default(default);
+    }
+    Ok(prompt.prompt().with(source)?)
+}
+
+/// Keeps the configured overlay file (if any) up to date with the stopwatch's state
+fn write_overlay(category: Option<&Category>, elapsed: TimeDelta, paused: bool) -> TaskitResult<()> {
+    let Some(path) = &CONFIG.stopwatch.overlay_file else {
+        return Ok(());
+    };
+    let elapsed = format_duration(&Duration::from(elapsed));
+    let mut text = match category {
+        Some(category) => format!("{} \u{2014} {elapsed}", CONFIG.icons.label(category)),
+        None => elapsed,
+    };
+    if paused {
+        text.push_str(" (paused)");
+    }
+    fs::write(path, text).with(Source::RunningStopwatch)?;
+    Ok(())
+}
+
+fn clear_overlay() -> TaskitResult<()> {
+    if let Some(path) = &CONFIG.stopwatch.overlay_file {
+        fs::write(path, "").with(Source::RunningStopwatch)?;
+    }
+    Ok(())
+}
+
+/// What's kept on disk while the stopwatch runs, so that it can be picked back up if taskit is
+/// closed before the event is saved
+#[derive(Serialize, Deserialize)]
+struct StopwatchState {
+    started: DateTime<Local>,
+    category: Option<String>,
+    /// Set once the stopwatch has been stopped, while the event's details are being filled in
+    stopped: Option<DateTime<Local>>,
+    /// When the stopwatch was paused and resumed. The last pause has no end while it's still paused.
+    #[serde(default)]
+    pauses: Vec<(DateTime<Local>, Option<DateTime<Local>>)>,
+}
+
+impl StopwatchState {
+    fn read(path: &Path) -> Option<Self> {
+        serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+    }
+
+    fn write(&self, path: &Path) -> TaskitResult<()> {
+        let text = serde_json::to_string(self).map_err(io::Error::other).with(Source::RunningStopwatch)?;
+        fs::write(path, text).with(Source::RunningStopwatch)?;
+        Ok(())
+    }
+
+    fn is_paused(&self) -> bool {
+        self.pauses.last().is_some_and(|(_, resumed)| resumed.is_none())
+    }
+
+    fn toggle_pause(&mut self) {
+        let now = Local::now();
+        match self.pauses.last_mut() {
+            Some((_, resumed @ None)) => *resumed = Some(now),
+            _ => self.pauses.push((now, None)),
+        }
+    }
+
+    /// The stretches of time the stopwatch was running for, up to `now` if it hasn't been stopped
+    fn active_spans(&self, now: DateTime<Local>) -> Vec<(DateTime<Local>, DateTime<Local>)> {
+        let mut spans = vec![];
+        let mut start = self.started;
+        for &(paused, resumed) in &self.pauses {
+            spans.push((start, paused));
+            match resumed {
+                Some(resumed) => start = resumed,
+                None => return spans,
+            }
+        }
+        spans.push((start, self.stopped.unwrap_or(now)));
+        spans
+    }
+
+    fn paused_for(&self, now: DateTime<Local>) -> TimeDelta {
+        self.pauses
+            .iter()
+            .map(|&(paused, resumed)| resumed.or(self.stopped).unwrap_or(now) - paused)
+            .sum()
+    }
+
+    fn describe(&self) -> String {
+        let mut text = format!("started at {}", self.started.format("%H:%M on %Y-%m-%d"));
+        if let Some(category) = &self.category {
+            text.push_str(&format!(" for {category}"));
+        }
+        if let Some(stopped) = self.stopped {
+            text.push_str(&format!(" and stopped at {}", stopped.format("%H:%M")));
+        }
+        text
+    }
+}
+
+fn remove_stopwatch_state(path: &Path) -> TaskitResult<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.with(Source::RunningStopwatch).into()),
+        _ => Ok(()),
+    }
+}
+
+/// Shows the running stopwatch until it's finished with Enter, returning true, or cancelled with
+/// Ctrl-C, returning false. The terminal has to be in raw mode.
+fn run_stopwatch(state: &mut StopwatchState, category: Option<&Category>, state_path: &Path) -> TaskitResult<bool> {
+    loop {
+        let now = Local::now();
+        let active: TimeDelta = state.active_spans(now).iter().map(|(start, end)| *end - *start).sum();
+        let paused = state.paused_for(now);
+        let mut line = format!("{:02}:{:02}", active.num_hours(), active.num_minutes() % 60);
+        if !paused.is_zero() {
+            line.push_str(&format!(", paused {:02}:{:02}", paused.num_hours(), paused.num_minutes() % 60));
+        }
+        let action = if state.is_paused() { "resume" } else { "pause" };
+        line.push_str(&format!(" (<Space> to {action}, <Enter> to finish)"));
+        // padded so nothing is left over from a longer previous line
+        print!("\r{line:<72}");
         stdout().flush().with(Source::DrawingTui)?;
-        while event::poll(Duration::ZERO).with(Source::RunningStopwatch)? {
+        write_overlay(category, active, state.is_paused())?;
+        let mut toggled = false;
+        while !toggled && event::poll(time::Duration::ZERO).with(Source::RunningStopwatch)? {
             if let CEvent::Key(ev) = event::read().with(Source::RunningStopwatch)? {
                 if ev.is_press()
                     && ev.code == KeyCode::Char('c')
                     && ev.modifiers == KeyModifiers::CONTROL
                 {
-                    return Err(Kind::Cancelled.with(Source::RunningStopwatch));
+                    return Ok(false);
                 } else if ev.is_press() && ev.code == KeyCode::Enter {
-                    break 'l;
+                    return Ok(true);
+                } else if ev.is_press() && ev.code == KeyCode::Char(' ') {
+                    state.toggle_pause();
+                    state.write(state_path)?;
+                    toggled = true;
                 }
             }
         }
-        sleep(Duration::from_millis(500));
+        if !toggled {
+            sleep(time::Duration::from_millis(500));
+        }
     }
-    disable_raw_mode().with(Source::RunningStopwatch)?;
-    println!();
-    let end_datetime = chrono::Local::now();
-    let end_time: SimpleTime = end_datetime.time().into();
-    let category = loop {
+}
+
+pub fn stopwatch_main(
+    save_data: SaveData,
+    category: Option<String>,
+    resume: bool,
+    state_path: &Path,
+) -> TaskitResult<Vec<DeltaItem>> {
+    let mut delta = vec![];
+    // a stopwatch whose event never got saved, e.g. because the terminal was closed
+    let resumed = match StopwatchState::read(state_path) {
+        Some(state) if resume => Some(state),
+        Some(state) => Confirm::new(&format!("A stopwatch {} was never saved. Resume it?", state.describe()))
+            .with_default(true)
+            .prompt()
+            .with(Source::RunningStopwatch)?
+            .then_some(state),
+        None if resume => return Err(Kind::NoStopwatchToResume.with(Source::RunningStopwatch)),
+        None => None,
+    };
+    let category = match category.or_else(|| resumed.as_ref().and_then(|state| state.category.clone())) {
+        None => None,
+        Some(name) => Some(if let Some(category) = save_data.categories.find(&name) {
+            category.clone()
+        } else if save_data.archived_categories.contains_match(&name) {
+            return Err(Kind::CategoryArchived(name).with(Source::RunningStopwatch));
+        } else if Confirm::new(&format!("Category {name} does not currently exist. Create it?"))
+            .prompt()
+            .with(Source::CreatingCategory)?
+        {
+            let (del, cat) = add_category(name);
+            delta.push(del);
+            cat
+        } else {
+            return Err(Kind::Cancelled.with(Source::CreatingCategory));
+        }),
+    };
+    let mut state = resumed.unwrap_or_else(|| StopwatchState {
+        started: Local::now(),
+        category: category.as_ref().map(Category::own),
+        stopped: None,
+        pauses: vec![],
+    });
+    state.write(state_path)?;
+    if state.stopped.is_none() {
+        enable_raw_mode().with(Source::RunningStopwatch)?;
+        // checked once raw mode is off again, so a failed write doesn't leave the terminal in it
+        let finished = run_stopwatch(&mut state, category.as_ref(), state_path);
+        disable_raw_mode().with(Source::RunningStopwatch)?;
+        println!();
+        if !finished? {
+            clear_overlay()?;
+            remove_stopwatch_state(state_path)?;
+            return Err(Kind::Cancelled.with(Source::RunningStopwatch));
+        }
+        clear_overlay()?;
+        state.stopped = Some(Local::now());
+        state.write(state_path)?;
+    }
+    // with pauses, there's one event per stretch the stopwatch was running, leaving out any too
+    // short to show up in whole seconds
+    let spans: Vec<(NaiveDate, SimpleTime, SimpleTime)> = state
+        .active_spans(Local::now())
+        .into_iter()
+        .map(|(start, end)| (start.date_naive(), start.time().into(), end.time().into()))
+        .collect();
+    let spans = match spans.iter().filter(|(_, start, end)| start != end).copied().collect::<Vec<_>>() {
+        kept if kept.is_empty() => spans.into_iter().take(1).collect(),
+        kept => kept,
+    };
+    if spans.len() > 1 {
+        println!("The stopwatch ran in {} stretches, which will each be recorded as an event.", spans.len());
+    }
+    let category = if let Some(category) = category {
+        category
+    } else { loop {
         let category_selection = Text::new("Select a category:")
-            .with_autocomplete(CategoriesCompleter(&save_data.categories))
+            .with_autocomplete(CategoriesCompleter(&save_data.categories, &save_data.category_details))
             .prompt()
+            .map(|answer| category_answer(answer, &save_data.category_details))
             .with(Source::CreatingEntry)?;
         if let Some(category) = save_data.categories.find(&category_selection) {
             break category.clone();
@@ -201,47 +720,415 @@ pub fn stopwatch_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
             delta.push(del);
             break cat;
         }
-    };
-    let comments = Text::new("Notes:")
-        .with_autocomplete(DescriptionTagsAutocomplete(save_data.tags.as_ref()))
-        .prompt()
-        .with(Source::CreatingEntry)?;
+    }};
+    let comments = prompt_notes(&save_data, None, Source::CreatingEntry)?;
+    let fields = prompt_custom_fields(&BTreeMap::new(), Source::CreatingEntry)?;
     let tags = get_description_tags(&comments);
     let (del, tags) = validate_description_tags(tags.into_iter(), save_data.tags.iter())?;
     delta.extend(del);
-    delta.push(DeltaItem::AddEvent(Event {
-        start_time,
-        end_time,
-        date,
-        category,
-        tags,
-        description: comments,
+    delta.extend(spans.into_iter().map(|(date, start_time, end_time)| {
+        DeltaItem::AddEvent(Event {
+            id: EventId::NEW,
+            start_time,
+            end_time,
+            date,
+            category: category.clone(),
+            tags: tags.clone(),
+            description: comments.clone(),
+            pinned: false,
+            source: EventSource::Stopwatch,
+            external_id: None,
+            fields: fields.clone(),
+        })
     }));
+    remove_stopwatch_state(state_path)?;
+    print_session_summary(&save_data, &delta, &[]);
+    Ok(delta)
+}
+
+/// A stretch of the day being reconstructed, in minutes since midnight
+struct Slot {
+    start: u32,
+    end: u32,
+    category: String,
+    description: String,
+}
+
+fn minutes_to_time(minutes: u32) -> SimpleTime {
+    SimpleTime::try_new((minutes / 60) as u8, (minutes % 60) as u8).expect("slots stay within the day")
+}
+
+fn time_to_minutes(time: SimpleTime) -> u32 {
+    time.hour as u32 * 60 + time.minute as u32
+}
+
+/// The category most often tracked at this time of day on the same weekday before `date`
+fn usual_category(save_data: &SaveData, date: NaiveDate, time: SimpleTime) -> Option<String> {
+    save_data
+        .events
+        .iter()
+        .filter(|ev| ev.date < date && ev.date.weekday() == date.weekday() && ev.covers(time))
+        .filter(|ev| save_data.categories.contains(&ev.category))
+        .map(|ev| ev.category.inner())
+        .counts()
+        .into_iter()
+        .max_by_key(|&(_, count)| count)
+        .map(|(category, _)| category.to_owned())
+}
+
+pub fn reconstruct_main(
+    save_data: SaveData,
+    date: NaiveDate,
+    from: NaiveTime,
+    until: NaiveTime,
+    step: u32,
+    git: Vec<PathBuf>,
+    calendar: Vec<PathBuf>,
+) -> TaskitResult<Vec<DeltaItem>> {
+    let activity = gather_activity(&git, &calendar)?.remove(&date).unwrap_or_default();
+    let existing: Vec<&Event> = save_data.events.iter().filter(|ev| ev.date == date).collect();
+    let (from, until) = (time_to_minutes(from.into()), time_to_minutes(until.into()));
+    let step = step.max(1);
+
+    println!("Reconstructing {}", date.format("%A %Y-%m-%d"));
+    for ev in &existing {
+        println!("  already tracked: {ev}");
+    }
+    for item in activity.iter().filter(|a| a.start.is_none()) {
+        println!("  all day: {item}");
+    }
+
+    let archived: Vec<String> = save_data.archived_categories.iter().map(|c| c.own()).collect();
+    let mut slots: Vec<Slot> = vec![];
+    let mut start = from;
+    while start < until {
+        let end = (start + step).min(until);
+        let (start_time, end_time) = (minutes_to_time(start), minutes_to_time(end % (24 * 60)));
+        if existing.iter().any(|ev| ev.covers(start_time)) {
+            start = end;
+            continue;
+        }
+        println!("\nWhat were you doing {start_time}-{end_time}?");
+        let hints: Vec<&Activity> = activity
+            .iter()
+            .filter(|a| {
+                let Some(a_start) = a.start.map(time_to_minutes) else {
+                    return false;
+                };
+                match a.end.map(time_to_minutes) {
+                    Some(a_end) => a_start < end && (a_end > start || a_end <= a_start),
+                    None => start <= a_start && a_start < end,
+                }
+            })
+            .collect();
+        for hint in &hints {
+            println!("  {hint}");
+        }
+        let previous = slots.last().filter(|slot| slot.end == start);
+        let suggested_category = previous
+            .map(|slot| slot.category.clone())
+            .or_else(|| usual_category(&save_data, date, start_time))
+            .unwrap_or_default();
+        let archived = archived.clone();
+        let category = Text::new("Category:")
+            .with_initial_value(&suggested_category)
+            .with_help_message("leave empty to skip this time")
+            .with_autocomplete(CategoriesCompleter(&save_data.categories, &save_data.category_details))
+            .with_validator(move |input: &str| {
+                Ok(if archived.iter().any(|c| c == input.trim()) {
+                    Validation::Invalid(format!("Category {} is archived", input.trim()).into())
+                } else {
+                    Validation::Valid
+                })
+            })
+            .prompt()
+            .map(|answer| category_answer(answer, &save_data.category_details))
+            .with(Source::CreatingEntry)?;
+        let category = category.trim().to_owned();
+        if category.is_empty() {
+            start = end;
+            continue;
+        }
+        let suggested_description = match previous {
+            Some(slot) if slot.category == category => slot.description.clone(),
+            _ => hints.iter().map(|a| a.description.as_str()).join("; "),
+        };
+        let description = Text::new("Notes:")
+            .with_initial_value(&suggested_description)
+            .with_autocomplete(DescriptionTagsAutocomplete(save_data.tags.as_ref()))
+            .prompt()
+            .with(Source::CreatingEntry)?;
+        match slots.last_mut() {
+            // consecutive slots spent on the same thing become one event
+            Some(slot) if slot.end == start && slot.category == category && slot.description == description => {
+                slot.end = end;
+            }
+            _ => slots.push(Slot { start, end, category, description }),
+        }
+        start = end;
+    }
+
+    if slots.is_empty() {
+        println!("Nothing to add.");
+        return Ok(vec![]);
+    }
+    println!();
+    for slot in &slots {
+        println!(
+            "{}-{} {}: {}",
+            minutes_to_time(slot.start),
+            minutes_to_time(slot.end % (24 * 60)),
+            slot.category,
+            slot.description
+        );
+    }
+    if !Confirm::new(&format!("Add these {} events?", slots.len()))
+        .with_default(true)
+        .prompt()
+        .with(Source::CreatingEntry)?
+    {
+        return Err(Kind::Cancelled.with(Source::CreatingEntry));
+    }
+
+    let mut delta = vec![];
+    let mut new_categories: Vec<Category> = vec![];
+    let mut new_tags: Vec<Tag> = vec![];
+    for slot in slots {
+        let category = if let Some(cat) = save_data
+            .categories
+            .find(&slot.category)
+            .or_else(|| new_categories.iter().find(|c| c.inner() == slot.category))
+        {
+            cat.clone()
+        } else if Confirm::new(&format!("Category {} does not currently exist. Create it?", slot.category))
+            .prompt()
+            .with(Source::CreatingCategory)?
+        {
+            let (del, cat) = add_category(slot.category);
+            delta.push(del);
+            new_categories.push(cat.clone());
+            cat
+        } else {
+            return Err(Kind::Cancelled.with(Source::CreatingCategory));
+        };
+        let tags = get_description_tags(&slot.description);
+        let (tag_deltas, tags) =
+            validate_description_tags(tags.into_iter(), save_data.tags.iter().chain(new_tags.iter()))?;
+        delta.extend(tag_deltas);
+        new_tags.extend(tags.iter().filter(|t| !save_data.tags.contains(t)).cloned());
+        delta.push(DeltaItem::AddEvent(Event {
+            id: EventId::NEW,
+            start_time: minutes_to_time(slot.start),
+            end_time: minutes_to_time(slot.end % (24 * 60)),
+            date,
+            category,
+            description: slot.description,
+            tags,
+            pinned: false,
+            source: EventSource::Reconstruct,
+            external_id: None,
+            fields: BTreeMap::new(),
+        }));
+    }
     Ok(delta)
 }
 
+pub fn close_month_main(save_data: SaveData, month: Option<DateRange>, reopen: bool) -> TaskitResult<Vec<DeltaItem>> {
+    let month = month.unwrap_or_else(|| DateRange::month_of(Local::now().date_naive() - Months::new(1)));
+    let label = month.start.format("%Y-%m");
+    if reopen {
+        return if save_data.locked_until.is_some_and(|locked| locked >= month.start) {
+            println!("Reopened {label} and everything after it.");
+            Ok(vec![DeltaItem::LockUntil(Some(month.start - Days::new(1)))])
+        } else {
+            println!("{label} isn't closed.");
+            Ok(vec![])
+        };
+    }
+    if save_data.locked_until.is_some_and(|locked| locked >= month.end) {
+        println!("{label} is already closed.");
+        return Ok(vec![]);
+    }
+
+    let events: Vec<&Event> = save_data.events.iter().filter(|ev| month.contains(ev.date)).collect();
+    let total: Duration = events.iter().map(|ev| ev.duration()).sum();
+    println!("{label}: {} tracked in {} events", format_duration(&total), events.len());
+    for (category, duration) in category_totals(events.iter().copied()) {
+        println!("  {category}: {}", format_duration(&duration));
+    }
+
+    let mut problems = event_problems(events.iter().copied());
+    problems.extend(
+        month
+            .start
+            .iter_days()
+            .take_while(|d| *d <= month.end)
+            .filter(|d| d.weekday().num_days_from_monday() < 5)
+            .filter(|d| !events.iter().any(|ev| ev.date == *d))
+            // days marked with `taskit skip` were left untracked on purpose
+            .filter(|d| !save_data.skipped_days.contains_key(d))
+            .map(|d| (d, "weekday with nothing tracked".to_owned())),
+    );
+    problems.sort_by_key(|(date, _)| *date);
+    if !problems.is_empty() {
+        println!("\nPossible problems:");
+        for (date, problem) in &problems {
+            println!("  {date}: {problem}");
+        }
+        if !Confirm::new("Close the month anyway?")
+            .with_default(false)
+            .prompt()
+            .with(Source::ClosingMonth)?
+        {
+            return Err(Kind::Cancelled.with(Source::ClosingMonth));
+        }
+    }
+    println!();
+    for step in &CONFIG.close_month.checklist {
+        if !Confirm::new(&format!("{step}?")).prompt().with(Source::ClosingMonth)? {
+            println!("Finish that first, then run this again.");
+            return Err(Kind::Cancelled.with(Source::ClosingMonth));
+        }
+    }
+    if save_data.locked_until.is_none_or(|locked| locked < month.start - Days::new(1)) {
+        println!("Note: closing {label} also locks every earlier month.");
+    }
+    println!("Closed {label}. Events and notes up to {} can no longer be changed.", month.end);
+    Ok(vec![DeltaItem::LockUntil(Some(month.end))])
+}
+
+/// What `taskit tidy` can do with a short event
+enum TidyChoice {
+    /// Extend the event at this position in the working list to cover it
+    MergeInto(usize, String),
+    Delete,
+    Keep,
+    /// Keep this event and every one after it
+    Stop,
+}
+
+impl Display for TidyChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TidyChoice::MergeInto(_, label) => write!(f, "{label}"),
+            TidyChoice::Delete => write!(f, "Delete it"),
+            TidyChoice::Keep => write!(f, "Keep it"),
+            TidyChoice::Stop => write!(f, "Keep it and the rest"),
+        }
+    }
+}
+
+pub fn tidy_main(save_data: SaveData, under: Option<u32>) -> TaskitResult<Vec<DeltaItem>> {
+    let under = under.or(CONFIG.tidy.min_minutes).unwrap_or(2);
+    let threshold = Duration::minutes(under.into());
+    let unlocked = |ev: &Event| save_data.locked_until.is_none_or(|locked| ev.date > locked);
+    // changes are made to this copy as they're picked, so that later events are merged into their
+    // neighbors as they'll be
+    let mut events: Vec<Event> = save_data.events.clone();
+    let short: Vec<EventId> = events
+        .iter()
+        .filter(|ev| ev.duration() < threshold && unlocked(ev))
+        .sorted_by_key(|ev| (ev.date, ev.start_time))
+        .map(|ev| ev.id)
+        .collect();
+    if short.is_empty() {
+        println!("No events are shorter than {under} minute(s).");
+        return Ok(vec![]);
+    }
+    println!("{} event(s) are shorter than {under} minute(s).", short.len());
+
+    let mut deltas = vec![];
+    let (mut merged, mut deleted) = (0, 0);
+    for (n, id) in short.into_iter().enumerate() {
+        // it may have been merged into already, which makes it longer
+        let Some(pos) = events.iter().position(|ev| ev.id == id && ev.duration() < threshold) else {
+            continue;
+        };
+        let ev = &events[pos];
+        // the closest events on the same day that end before it starts and start after it ends
+        let same_day = |i: usize| i != pos && events[i].date == ev.date && events[i].start_time <= events[i].end_time;
+        let previous = (0..events.len())
+            .filter(|&i| same_day(i) && events[i].end_time <= ev.start_time && unlocked(&events[i]))
+            .max_by_key(|&i| events[i].end_time);
+        let next = (0..events.len())
+            .filter(|&i| same_day(i) && events[i].start_time >= ev.end_time && unlocked(&events[i]))
+            .min_by_key(|&i| events[i].start_time);
+        let mut options = vec![];
+        options.extend(previous.map(|i| {
+            TidyChoice::MergeInto(i, format!("Merge into the previous event, ending it at {}: {}", ev.end_time, events[i]))
+        }));
+        options.extend(next.map(|i| {
+            TidyChoice::MergeInto(i, format!("Merge into the next event, starting it at {}: {}", ev.start_time, events[i]))
+        }));
+        options.extend([TidyChoice::Delete, TidyChoice::Keep, TidyChoice::Stop]);
+        let choice = Select::new(&format!("({}) {ev}", n + 1), options)
+            .prompt()
+            .with(Source::Tidying)?;
+        match choice {
+            TidyChoice::MergeInto(i, _) => {
+                let mut new_event = events[i].clone();
+                if previous == Some(i) {
+                    new_event.end_time = ev.end_time;
+                } else {
+                    new_event.start_time = ev.start_time;
+                }
+                deltas.push(DeltaItem::DeleteEvent(id));
+                deltas.push(DeltaItem::ChangeEvent { id: new_event.id, new_event: new_event.clone() });
+                events[i] = new_event;
+                events.remove(pos);
+                merged += 1;
+            }
+            TidyChoice::Delete => {
+                deltas.push(DeltaItem::DeleteEvent(id));
+                events.remove(pos);
+                deleted += 1;
+            }
+            TidyChoice::Keep => {}
+            TidyChoice::Stop => break,
+        }
+    }
+    println!("Merged {merged} and deleted {deleted} event(s).");
+    Ok(deltas)
+}
+
 /// prompts the user to select an event. events are displayed in reverse order, and the index given
 /// is reversed (0 for last element, 1 for next to last, etc)
 fn prompt_for_reverse_index(save_data: &SaveData) -> TaskitResult<usize> {
+    prompt_for_matching_reverse_index(save_data, |_| true)
+}
+
+/// Like `prompt_for_reverse_index`, but only lists events for which `matches` is true. If only one
+/// event does, it's picked without asking.
+fn prompt_for_matching_reverse_index(
+    save_data: &SaveData,
+    matches: impl Fn(&Event) -> bool,
+) -> TaskitResult<usize> {
     struct IndexedEvent<'a>(usize, &'a Event);
     impl Display for IndexedEvent<'_> {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             write!(f, "({:02}) {}", self.0 + 1, self.1)
         }
     }
-    Ok(Select::new(
-        "select event to modify",
-        save_data
-            .events
-            .iter()
-            .rev()
-            .enumerate()
-            .map(|(n, ev)| IndexedEvent(n, ev))
-            .collect::<Vec<IndexedEvent>>(),
-    )
-    .prompt()
-    .with(Source::SelectingEntry)?
-    .0)
+    let mut options: Vec<IndexedEvent> = save_data
+        .events
+        .iter()
+        .rev()
+        .enumerate()
+        .filter(|(_, ev)| matches(ev))
+        .map(|(n, ev)| IndexedEvent(n, ev))
+        .collect();
+    match options.len() {
+        0 => Err(Kind::NoMatchingEvents.with(Source::SelectingEntry)),
+        1 => {
+            let only = options.remove(0);
+            println!("Selected the only matching event: {}", only.1);
+            Ok(only.0)
+        }
+        _ => Ok(Select::new("select event to modify", options)
+            .prompt()
+            .with(Source::SelectingEntry)?
+            .0),
+    }
 }
 
 pub fn delete_event_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
@@ -257,44 +1144,106 @@ pub fn delete_event_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
     .prompt()
     .with(Source::ConfirmingDelete)?;
     if confirm {
-        Ok(vec![DeltaItem::DeleteEvent(index)])
+        Ok(vec![DeltaItem::DeleteEvent(save_data.events[index].id)])
     } else {
         Err(Kind::Cancelled.with(Source::ConfirmingDelete))
     }
 }
 
-pub fn dispatch_amend(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
+pub fn annotate_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
     let reverse_index = prompt_for_reverse_index(&save_data)?;
+    let index = save_data.events.len() - 1 - reverse_index;
+    let text = Text::new("Annotation:")
+        .with_autocomplete(DescriptionTagsAutocomplete(save_data.tags.as_ref()))
+        .prompt()
+        .with(Source::EditingEntry)?;
+    let tags = get_description_tags(&text);
+    let (mut delta, tags) = validate_description_tags(tags.into_iter(), save_data.tags.iter())?;
+    delta.push(annotate_event(save_data.events[index].id, &text, tags));
+    Ok(delta)
+}
+
+pub fn pin_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
+    let reverse_index = prompt_for_reverse_index(&save_data)?;
+    let index = save_data.events.len() - 1 - reverse_index;
+    let pinned = !save_data.events[index].pinned;
+    println!(
+        "{} {}",
+        if pinned { "Pinned" } else { "Unpinned" },
+        save_data.events[index]
+    );
+    Ok(vec![DeltaItem::SetPinned(save_data.events[index].id, pinned)])
+}
+
+pub fn pins_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
+    let mut pinned: Vec<&Event> = save_data.events.iter().filter(|ev| ev.pinned).collect();
+    if pinned.is_empty() {
+        println!("No events are pinned. Pin one with `taskit pin`.");
+    }
+    pinned.sort_by_key(|ev| (ev.date, ev.start_time));
+    for ev in pinned {
+        println!("{ev}");
+    }
+    Ok(vec![])
+}
+
+/// Picks an event to amend from those matching all of the given filters. The list can also be
+/// narrowed down by typing.
+pub fn dispatch_amend(
+    save_data: SaveData,
+    category: Option<String>,
+    range: Option<DateRange>,
+    contains: Option<String>,
+) -> TaskitResult<Vec<DeltaItem>> {
+    let contains = contains.map(|text| text.to_lowercase());
+    let reverse_index = prompt_for_matching_reverse_index(&save_data, |ev| {
+        category.as_ref().is_none_or(|c| ev.category.inner().eq_ignore_ascii_case(c))
+            && range.is_none_or(|range| range.contains(ev.date))
+            && contains.as_ref().is_none_or(|text| ev.description.to_lowercase().contains(text))
+    })?;
     amend_main(save_data, reverse_index)
 }
 
 // reverse_index is the index of the event to be amended, counting from the end of the list
 pub fn amend_main(save_data: SaveData, reverse_index: usize) -> TaskitResult<Vec<DeltaItem>> {
+    amend_event(&save_data, save_data.events.len() - 1 - reverse_index)
+}
+
+/// Asks for new values for each part of the event at `index`, defaulting to its current ones
+pub fn amend_event(save_data: &SaveData, index: usize) -> TaskitResult<Vec<DeltaItem>> {
     let mut delta = vec![];
-    let index = save_data.events.len() - 1 - reverse_index;
 
     let date = DateSelect::new("Date:")
+        .with_formatter(&display_date)
         .with_default(save_data.events[index].date)
         .prompt()
         .with(Source::EditingEntry)?;
-    let start_time = CustomType::<SimpleTime>::new("Start time:")
-        .with_default(save_data.events[index].start_time)
-        .prompt()
-        .with(Source::EditingEntry)?;
+    let original = &save_data.events[index];
+    // if the event stays on the same day, the previous event is the one before it
+    let before = (date == original.date).then_some(original.start_time);
+    let shortcuts = TimeShortcuts {
+        prev: previous_end(save_data, date, before, Some(index)),
+        next: None,
+    };
+    let start_time = prompt_time("Start time:", Some(original.start_time), shortcuts, Source::EditingEntry)?;
     let category = Text::new("Select a category:")
-        .with_autocomplete(CategoriesCompleter(&save_data.categories))
+        .with_autocomplete(CategoriesCompleter(&save_data.categories, &save_data.category_details))
         .with_default(save_data.events[index].category.inner())
         .prompt()
+        .map(|answer| category_answer(answer, &save_data.category_details))
         .with(Source::EditingEntry)?;
-    let comments = Text::new("Notes:")
-        .with_autocomplete(DescriptionTagsAutocomplete(save_data.tags.as_ref()))
-        .with_default(&save_data.events[index].description)
-        .prompt()
-        .with(Source::EditingEntry)?;
-    let end_time = CustomType::<SimpleTime>::new("End time:")
-        .with_default(save_data.events[index].end_time)
-        .prompt()
-        .with(Source::EditingEntry)?;
+    let comments = prompt_notes(save_data, Some(&save_data.events[index].description), Source::EditingEntry)?;
+    let fields = prompt_custom_fields(&save_data.events[index].fields, Source::EditingEntry)?;
+    let shortcuts = TimeShortcuts {
+        prev: None,
+        next: next_start(save_data, date, start_time, Some(index)),
+    };
+    let end_time = prompt_time(
+        "End time:",
+        Some(save_data.events[index].end_time),
+        shortcuts,
+        Source::EditingEntry,
+    )?;
 
     if save_data.archived_categories.contains_match(&category) {
         // println!("Cannot update event with archived category {category}.");
@@ -319,18 +1268,24 @@ pub fn amend_main(save_data: SaveData, reverse_index: usize) -> TaskitResult<Vec
     };
     let tags = get_description_tags(&comments);
     let (dels, tags) = validate_description_tags(tags.into_iter(), save_data.tags.iter())?;
+    let new_event = Event {
+        id: save_data.events[index].id,
+        start_time,
+        end_time,
+        date,
+        category,
+        tags,
+        description: comments,
+        pinned: save_data.events[index].pinned,
+        source: save_data.events[index].source.clone(),
+        external_id: save_data.events[index].external_id.clone(),
+        fields,
+    };
+    if !confirm_plausible(save_data, &new_event, Some(index), Source::EditingEntry)? {
+        return Err(Kind::Cancelled.with(Source::EditingEntry));
+    }
     delta.extend(dels);
-    delta.push(DeltaItem::ChangeEvent {
-        index,
-        new_event: Event {
-            start_time,
-            end_time,
-            date,
-            category,
-            tags,
-            description: comments,
-        },
-    });
+    delta.push(DeltaItem::ChangeEvent { id: new_event.id, new_event });
     Ok(delta)
 }
 
@@ -344,12 +1299,222 @@ pub fn archive_main(save_data: SaveData, category: String) -> TaskitResult<Vec<D
     }
 }
 
+pub fn private_main(save_data: SaveData, category: String, unset: bool) -> TaskitResult<Vec<DeltaItem>> {
+    let Some(cat) = save_data.categories.find(&category).or_else(|| save_data.archived_categories.find(&category)) else {
+        return Err(Kind::NoSuchCategory(category).with(Source::UpdatingCategory));
+    };
+    if unset {
+        println!("Events in {cat} will be included in shared output again.");
+    } else {
+        println!("Events in {cat} will be left out of shared output unless --include-private is passed.");
+    }
+    Ok(vec![DeltaItem::SetCategoryPrivate(cat.clone(), !unset)])
+}
+
+/// Checks that a rate given on the command line is a number that can be billed
+pub fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate >= 0.0 => Ok(rate),
+        _ => Err("expected a number that isn't negative".to_owned()),
+    }
+}
+
+/// Sets the hourly rate `taskit invoice` bills a category at, prompting for it if it isn't given
+pub fn rate_main(save_data: SaveData, category: String, rate: Option<f64>, unset: bool) -> TaskitResult<Vec<DeltaItem>> {
+    let Some(cat) = save_data.categories.find(&category).or_else(|| save_data.archived_categories.find(&category)) else {
+        return Err(Kind::NoSuchCategory(category).with(Source::UpdatingCategory));
+    };
+    if unset {
+        println!("{cat} will no longer be billed.");
+        return Ok(vec![DeltaItem::SetCategoryRate(cat.clone(), None)]);
+    }
+    let rate = match rate {
+        Some(rate) => rate,
+        None => {
+            let message = format!("Hourly rate for {cat}:");
+            let mut prompt = CustomType::<f64>::new(&message)
+                .with_validator(|rate: &f64| {
+                    Ok(match parse_rate(&rate.to_string()) {
+                        Ok(_) => Validation::Valid,
+                        Err(e) => Validation::Invalid(e.into()),
+                    })
+                });
+            if let Some(current) = save_data.category_rates.get(cat) {
+                prompt = prompt.with_default(*current);
+            }
+            prompt.prompt().with(Source::UpdatingCategory)?
+        }
+    };
+    println!("{cat} will be billed at {rate:.2} per hour.");
+    Ok(vec![DeltaItem::SetCategoryRate(cat.clone(), Some(rate))])
+}
+
+/// Checks that a number of hours given on the command line is more than nothing
+pub fn parse_hours(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(hours) if hours.is_finite() && hours > 0.0 => Ok(hours),
+        _ => Err("expected a number of hours greater than 0".to_owned()),
+    }
+}
+
+/// Sets or removes the weekly goal of a category or tag, prompting for the hours if they aren't
+/// given
+pub fn goal_main(
+    save_data: SaveData,
+    name: String,
+    hours: Option<f64>,
+    max: bool,
+    tag: bool,
+    unset: bool,
+) -> TaskitResult<Vec<DeltaItem>> {
+    let name = name.trim_start_matches('#');
+    let target = if tag {
+        let Some(tag) = save_data.tags.find(name) else {
+            return Err(Kind::NoSuchTag(name.to_owned()).with(Source::UpdatingTag));
+        };
+        Either::Right(tag)
+    } else {
+        let Some(cat) = save_data.categories.find(name).or_else(|| save_data.archived_categories.find(name)) else {
+            return Err(Kind::NoSuchCategory(name.to_owned()).with(Source::UpdatingCategory));
+        };
+        Either::Left(cat)
+    };
+    let (label, current) = match target {
+        Either::Left(cat) => (cat.own(), save_data.category_goals.get(cat).copied()),
+        Either::Right(tag) => (format!("#{}", tag.inner()), save_data.tag_goals.get(tag).copied()),
+    };
+    let set = |goal| match target {
+        Either::Left(cat) => DeltaItem::SetCategoryGoal(cat.clone(), goal),
+        Either::Right(tag) => DeltaItem::SetTagGoal(tag.clone(), goal),
+    };
+    if unset {
+        println!("{label} no longer has a goal.");
+        return Ok(vec![set(None)]);
+    }
+    let hours = match hours {
+        Some(hours) => hours,
+        None => {
+            let message = format!("Hours per week for {label}:");
+            let mut prompt = CustomType::<f64>::new(&message).with_validator(|hours: &f64| {
+                Ok(match parse_hours(&hours.to_string()) {
+                    Ok(_) => Validation::Valid,
+                    Err(e) => Validation::Invalid(e.into()),
+                })
+            });
+            if let Some(current) = current {
+                prompt = prompt.with_default(current.hours);
+            }
+            prompt.prompt().with(if tag { Source::UpdatingTag } else { Source::UpdatingCategory })?
+        }
+    };
+    let goal = Goal { hours, max };
+    println!("Goal for {label}: {goal}.");
+    Ok(vec![set(Some(goal))])
+}
+
+/// Checks that a color given on the command line can be shown. An empty one is allowed, to remove
+/// the color.
+pub fn parse_color(s: &str) -> Result<String, String> {
+    // builds without the dashboard have nothing to show colors with, so they're kept as given
+    #[cfg(feature = "tui")]
+    if !s.trim().is_empty() && s.trim().parse::<ratatui::style::Color>().is_err() {
+        return Err("expected a color name like red or light-blue, or a hex code like #ff8800".to_owned());
+    }
+    Ok(s.trim().to_owned())
+}
+
+/// Sets a category's color and description. Whichever isn't given is prompted for, unless the
+/// other one was given, in which case it's left as it is.
+pub fn describe_category_main(
+    save_data: SaveData,
+    category: String,
+    color: Option<String>,
+    description: Option<String>,
+) -> TaskitResult<Vec<DeltaItem>> {
+    let Some(cat) = save_data.categories.find(&category).or_else(|| save_data.archived_categories.find(&category)) else {
+        return Err(Kind::NoSuchCategory(category).with(Source::UpdatingCategory));
+    };
+    let mut details = save_data.category_details.get(cat).cloned().unwrap_or_default();
+    if color.is_none() && description.is_none() {
+        let color = Text::new("Color (leave empty for none):")
+            .with_initial_value(details.color.as_deref().unwrap_or_default())
+            .with_help_message("a name like red or light-blue, or a hex code like #ff8800")
+            .with_validator(|input: &str| {
+                Ok(match parse_color(input) {
+                    Ok(_) => Validation::Valid,
+                    Err(e) => Validation::Invalid(e.into()),
+                })
+            })
+            .prompt()
+            .with(Source::UpdatingCategory)?;
+        let description = Text::new("Description (leave empty for none):")
+            .with_initial_value(details.description.as_deref().unwrap_or_default())
+            .prompt()
+            .with(Source::UpdatingCategory)?;
+        details.color = Some(color.trim().to_owned()).filter(|c| !c.is_empty());
+        details.description = Some(description.trim().to_owned()).filter(|d| !d.is_empty());
+    } else {
+        // an empty value removes it, like leaving the prompt empty
+        if let Some(color) = color {
+            details.color = Some(color.trim().to_owned()).filter(|c| !c.is_empty());
+        }
+        if let Some(description) = description {
+            details.description = Some(description.trim().to_owned()).filter(|d| !d.is_empty());
+        }
+    }
+    if save_data.category_details.get(cat).cloned().unwrap_or_default() == details {
+        println!("Nothing changed.");
+        return Ok(vec![]);
+    }
+    Ok(vec![DeltaItem::SetCategoryDetails(cat.clone(), details)])
+}
+
+pub fn skip_main(
+    save_data: SaveData,
+    days: DateRange,
+    reason: Option<String>,
+    unset: bool,
+) -> TaskitResult<Vec<DeltaItem>> {
+    let dates: Vec<NaiveDate> = days.start.iter_days().take_while(|d| *d <= days.end).collect();
+    if unset {
+        let delta: Vec<DeltaItem> = dates
+            .into_iter()
+            .filter(|d| save_data.skipped_days.contains_key(d))
+            .map(|d| DeltaItem::SetSkipped(d, None))
+            .collect();
+        println!("Unmarked {} skipped day(s).", delta.len());
+        return Ok(delta);
+    }
+    let tracked: Vec<NaiveDate> = dates
+        .iter()
+        .copied()
+        .filter(|d| save_data.events.iter().any(|ev| ev.date == *d))
+        .collect();
+    if !tracked.is_empty() {
+        let list = tracked.iter().map(NaiveDate::to_string).join(", ");
+        if !Confirm::new(&format!("Time is already tracked on {list}. Mark as skipped anyway?"))
+            .with_default(false)
+            .prompt()
+            .with(Source::SkippingDays)?
+        {
+            return Err(Kind::Cancelled.with(Source::SkippingDays));
+        }
+    }
+    let reason = reason.unwrap_or_default();
+    println!("Marked {days} as skipped. Skipped days don't count towards averages or break streaks.");
+    Ok(dates
+        .into_iter()
+        .map(|d| DeltaItem::SetSkipped(d, Some(reason.clone())))
+        .collect())
+}
+
 pub fn tag_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
     let mut delta = vec![];
     let category = Text::new("Select a category to tag:")
-        .with_autocomplete(CategoriesCompleter(&save_data.categories))
-        .with_validator(CategoriesCompleter(&save_data.categories))
+        .with_autocomplete(CategoriesCompleter(&save_data.categories, &save_data.category_details))
+        .with_validator(CategoriesCompleter(&save_data.categories, &save_data.category_details))
         .prompt()
+        .map(|answer| category_answer(answer, &save_data.category_details))
         .with(Source::UpdatingTag)?;
     let tag = Text::new("Select a tag:")
         .with_autocomplete(TagCompleter(&save_data.tags))
@@ -380,6 +1545,7 @@ pub fn tag_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
 
 pub fn note_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
     let date = DateSelect::new("Date:")
+        .with_formatter(&display_date)
         .prompt()
         .with(Source::EditingNote)?;
     let note = inquire::Editor::new("Daily Note:")
@@ -392,33 +1558,91 @@ pub fn note_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
         )
         .prompt()
         .with(Source::EditingNote)?;
+    // an unchanged copy of the last note usually means the day's note was never actually written
+    if let Some((previous_date, _)) = save_data
+        .daily_notes
+        .iter()
+        .filter(|(d, _)| **d < date)
+        .max_by_key(|(d, _)| **d)
+        .filter(|(_, previous)| nearly_identical(previous, &note))
+    {
+        let save = Confirm::new(&format!(
+            "This note is (nearly) the same as the one for {previous_date}. Save it anyway?"
+        ))
+        .with_default(false)
+        .prompt()
+        .with(Source::EditingNote)?;
+        if !save {
+            return Err(Kind::Cancelled.with(Source::EditingNote));
+        }
+    }
     Ok(vec![DeltaItem::SetDailyNote(date, note)])
 }
 
-pub fn rename_category_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
-    let category = Text::new("Select a category to rename:")
-        .with_autocomplete(CategoriesPair(
-            &save_data.categories,
-            &save_data.archived_categories,
-        ))
-        .with_validator(CategoriesPair(
-            &save_data.categories,
-            &save_data.archived_categories,
-        ))
-        .prompt()
-        .with(Source::UpdatingCategory)?;
+/// Whether two notes share nearly all of their words, ignoring case, punctuation, and layout
+fn nearly_identical(a: &str, b: &str) -> bool {
+    let words = |s: &str| -> Vec<String> {
+        s.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    };
+    let (a, b) = (words(a), words(b));
+    // an empty note has nothing to have been copied
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    let mut b_counts = b.iter().counts();
+    let shared = a
+        .iter()
+        .filter(|w| match b_counts.get_mut(w) {
+            Some(n) if *n > 0 => {
+                *n -= 1;
+                true
+            }
+            _ => false,
+        })
+        .count();
+    // allows a word or so of difference in a short note, or a line in a long one
+    shared * 10 >= a.len().max(b.len()) * 9
+}
+
+pub fn rename_category_main(
+    save_data: SaveData,
+    old: Option<String>,
+    new: Option<String>,
+) -> TaskitResult<Vec<DeltaItem>> {
+    let category = match old {
+        Some(category) => category,
+        None => Text::new("Select a category to rename:")
+            .with_autocomplete(CategoriesPair(
+                &save_data.categories,
+                &save_data.archived_categories,
+                &save_data.category_details,
+            ))
+            .with_validator(CategoriesPair(
+                &save_data.categories,
+                &save_data.archived_categories,
+                &save_data.category_details,
+            ))
+            .prompt()
+            .map(|answer| category_answer(answer, &save_data.category_details))
+            .with(Source::UpdatingCategory)?,
+    };
     let category = save_data.categories
         .iter()
         .chain(save_data.archived_categories.iter()).find(|c| c.inner() == category)
-        .expect("validator will only allow selecting already-existing categories")
+        .ok_or_else(|| Kind::NoSuchCategory(category.clone()).with(Source::UpdatingCategory))?
         .clone();
-    let new_name = Text::new("Select a new category name")
-        .prompt()
-        .with(Source::UpdatingCategory)?;
+    let new_name = match new {
+        Some(name) => name,
+        None => Text::new("Select a new category name")
+            .prompt()
+            .with(Source::UpdatingCategory)?,
+    };
     if save_data.categories.iter().chain(save_data.archived_categories.iter()).any(|c| c.inner() == new_name)
     {
-        // println!("Category {new_name} already exists!");
-        Err(Kind::DuplicateCategory(category.own()).with(Source::UpdatingCategory))
+        Err(Kind::DuplicateCategory(new_name).with(Source::UpdatingCategory))
     } else {
         Ok(vec![rename_category(category, new_name).0])
     }
@@ -430,12 +1654,15 @@ pub fn delete_category_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>>
         .with_autocomplete(CategoriesPair(
             &save_data.categories,
             &save_data.archived_categories,
+            &save_data.category_details,
         ))
         .with_validator(CategoriesPair(
             &save_data.categories,
             &save_data.archived_categories,
+            &save_data.category_details,
         ))
         .prompt()
+        .map(|answer| category_answer(answer, &save_data.category_details))
         .with(Source::DeletingCategory)?;
     let category = save_data.categories.iter()
         .chain(save_data.archived_categories.iter())
@@ -485,3 +1712,77 @@ pub fn delete_tag_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
     }
     Ok(vec![DeltaItem::DeleteTag(tag)])
 }
+
+/// Renders `text` with the characters at the given char indices highlighted
+fn highlight_indices(text: &str, indices: &[usize]) -> String {
+    highlight_graphemes(text, |i| indices.contains(&i))
+        .map(|(g, highlighted)| {
+            if highlighted {
+                g.yellow().bold().to_string()
+            } else {
+                g.to_owned()
+            }
+        })
+        .collect()
+}
+
+fn fuzzy_search(save_data: &SaveData, query: &str) {
+    enum Hit<'a> {
+        Event(&'a Event),
+        Note(&'a chrono::NaiveDate, &'a String),
+    }
+    let mut hits: Vec<(i64, Vec<usize>, Hit)> = save_data
+        .events
+        .iter()
+        .filter_map(|ev| {
+            fuzzy_match(&ev.description, query).map(|(score, indices)| (score, indices, Hit::Event(ev)))
+        })
+        .chain(save_data.daily_notes.iter().filter_map(|(date, note)| {
+            fuzzy_match(note, query).map(|(score, indices)| (score, indices, Hit::Note(date, note)))
+        }))
+        .collect();
+    hits.sort_by_key(|(score, _, _)| -score);
+    for (_, indices, hit) in hits {
+        match hit {
+            Hit::Event(ev) => println!(
+                "{}: {} ({}, {}-{})",
+                ev.category,
+                highlight_indices(&ev.description, &indices),
+                ev.date,
+                ev.start_time,
+                ev.end_time
+            ),
+            Hit::Note(date, note) => {
+                println!("{date} (daily note): {}", highlight_indices(note, &indices))
+            }
+        }
+    }
+}
+
+pub fn search_main(save_data: SaveData, save_path: &Path, query: String, fuzzy: bool) -> TaskitResult<Vec<DeltaItem>> {
+    if fuzzy {
+        fuzzy_search(&save_data, &query);
+        return Ok(vec![]);
+    }
+    let index = TextIndex::load(save_path, &save_data);
+    let lowercase_query = query.to_lowercase();
+    // only the events the index can't rule out need their descriptions checked
+    let candidates = index.events.candidates(&query);
+    let events = save_data.events.iter().filter(|ev| candidates.as_ref().is_none_or(|ids| ids.contains(&ev.id)));
+    for ev in events {
+        if ev.description.to_lowercase().contains(&lowercase_query) {
+            println!("{ev}");
+        }
+    }
+    let dates = match index.notes.candidates(&query) {
+        Some(dates) => dates,
+        None => save_data.daily_notes.keys().copied().collect(),
+    };
+    for date in dates {
+        let Some(note) = save_data.daily_notes.get(&date) else { continue };
+        if note.to_lowercase().contains(&lowercase_query) {
+            println!("{date} (daily note): {note}");
+        }
+    }
+    Ok(vec![])
+}
@@ -0,0 +1,182 @@
+use std::{
+    fs::{self, File, OpenOptions, rename},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime};
+use inquire::Confirm;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{
+        Apply, DeltaItem, SaveData, UnverifiedDelta,
+        error::{Kind, Source, TaskitResult, With},
+    },
+    diff::print_diff,
+    report::{format_duration, print_table},
+    util::DateRange,
+};
+
+/// One saved change to the save file, recorded as the changes that would reverse it
+#[derive(Serialize, Deserialize)]
+struct JournalEntry {
+    time: NaiveDateTime,
+    /// The arguments taskit was run with, to remind the user what they're undoing
+    command: String,
+    undo: Vec<UnverifiedDelta>,
+}
+
+/// The journal sits next to the save file, so each save file has its own history
+pub fn journal_path(save_path: &Path) -> PathBuf {
+    save_path.with_extension("journal.jsonl")
+}
+
+fn read_entries(path: &Path) -> TaskitResult<Vec<JournalEntry>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.with(Source::Undoing).into()),
+    };
+    text.lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| serde_json::from_str(l).map_err(|e| Kind::InvalidJournal(e.to_string()).with(Source::Undoing)))
+        .collect()
+}
+
+/// Appends a change that was just saved to the journal, given the changes that would undo it
+pub fn record(path: &Path, undo: Vec<DeltaItem>) -> io::Result<()> {
+    if undo.is_empty() {
+        return Ok(());
+    }
+    let entry = JournalEntry {
+        time: Local::now().naive_local(),
+        command: std::env::args().skip(1).collect::<Vec<_>>().join(" "),
+        undo: undo.into_iter().map(Into::into).collect(),
+    };
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(&entry).map_err(io::Error::other)?)
+}
+
+/// Drops the latest entry from the journal, once it has been undone
+pub fn pop(path: &Path) -> TaskitResult<()> {
+    let mut entries = read_entries(path)?;
+    entries.pop();
+    let temp_path = path.with_extension("tmp");
+    {
+        let mut file = File::create(&temp_path).with(Source::Undoing)?;
+        for entry in entries {
+            let line = serde_json::to_string(&entry).map_err(io::Error::other).with(Source::Undoing)?;
+            writeln!(file, "{line}").with(Source::Undoing)?;
+        }
+    }
+    rename(temp_path, path).with(Source::Undoing)?;
+    Ok(())
+}
+
+/// Moves the journal aside once the save file's format has been upgraded, as its changes are
+/// written in the old format, or once the save file has been replaced by a backup, as its changes
+/// were made to the one replaced
+pub fn retire(path: &Path) -> io::Result<()> {
+    match rename(path, path.with_extension("jsonl.upgrade_bak")) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+pub fn undo_main(save_data: SaveData, journal: &Path) -> TaskitResult<Vec<DeltaItem>> {
+    let Some(entry) = read_entries(journal)?.pop() else {
+        return Err(Kind::NothingToUndo.with(Source::Undoing));
+    };
+    // each change is checked against the save as it will be once the ones before it are applied,
+    // since they can depend on each other (e.g. re-creating a category before tagging it)
+    let mut scratch = save_data.clone();
+    let mut delta = vec![];
+    for item in entry.undo {
+        let item = item.verify(&scratch).map_err(|e| Kind::InvalidJournal(e).with(Source::Undoing))?;
+        scratch.apply(item.clone())?;
+        delta.push(item);
+    }
+    let message = format!(
+        "Undo `taskit {}` from {} ({} change{})?",
+        entry.command,
+        entry.time.format("%Y-%m-%d %H:%M"),
+        delta.len(),
+        if delta.len() == 1 { "" } else { "s" },
+    );
+    if !Confirm::new(&message).with_default(true).prompt().with(Source::Undoing)? {
+        return Err(Kind::Cancelled.with(Source::Undoing));
+    }
+    Ok(delta)
+}
+
+/// Rebuilds the save as it was at `time`, by undoing every journaled change made since, newest
+/// first. Also returns when the oldest journaled change was made: the journal is started over when
+/// the save format is upgraded, so changes from before then can't be undone, and changes that
+/// were undone with `taskit undo` are dropped from it entirely.
+pub fn as_of(save_data: SaveData, journal: &Path, time: NaiveDateTime) -> TaskitResult<(SaveData, Option<NaiveDateTime>)> {
+    let entries = read_entries(journal)?;
+    let oldest = entries.first().map(|entry| entry.time);
+    let mut past = save_data;
+    for entry in entries.into_iter().rev().take_while(|entry| entry.time > time) {
+        for item in entry.undo {
+            let item = item.verify(&past).map_err(|e| Kind::InvalidJournal(e).with(Source::ViewingPast))?;
+            past.apply(item)?;
+        }
+    }
+    Ok((past, oldest))
+}
+
+/// Lists the events in `range` as they were at the end of `date`, e.g. to check what was reported
+/// to a client at the time, and optionally what has changed since
+pub fn view_main(
+    save_data: SaveData,
+    journal: &Path,
+    date: NaiveDate,
+    range: Option<DateRange>,
+    changes: bool,
+) -> TaskitResult<Vec<DeltaItem>> {
+    let end_of_day = date.and_time(NaiveTime::from_hms_opt(23, 59, 59).expect("valid time"));
+    let (past, oldest) = as_of(save_data.clone(), journal, end_of_day)?;
+    match oldest {
+        None => println!("Note: nothing has been journaled yet, so this is the data as it is now.\n"),
+        Some(oldest) if oldest > end_of_day => println!(
+            "Note: the journal only goes back to {}, so changes made between {date} and then are already included.\n",
+            oldest.format("%Y-%m-%d %H:%M"),
+        ),
+        Some(_) => {}
+    }
+    let mut events: Vec<_> = past
+        .events
+        .iter()
+        .filter(|ev| range.is_none_or(|range| range.contains(ev.date)))
+        .collect();
+    events.sort_by_key(|ev| (ev.date, ev.start_time));
+    if events.is_empty() {
+        println!("There were no events as of {date}.");
+    } else {
+        let mut rows = vec![vec![
+            "Date".to_owned(),
+            "Time".to_owned(),
+            "Duration".to_owned(),
+            "Category".to_owned(),
+            "Description".to_owned(),
+        ]];
+        rows.extend(events.iter().map(|ev| {
+            vec![
+                ev.date.to_string(),
+                format!("{}-{}", ev.start_time, ev.end_time),
+                format_duration(&ev.duration()),
+                ev.category.own(),
+                ev.description.lines().next().unwrap_or_default().to_owned(),
+            ]
+        }));
+        println!("As of the end of {date}:");
+        print_table(&rows);
+    }
+    if changes {
+        println!("\nChanged since:");
+        print_diff(&past, &save_data);
+    }
+    Ok(vec![])
+}
@@ -0,0 +1,11 @@
+//! taskit is a binary; this library target only exists so that the fuzz targets in `fuzz/` can
+//! reach the parsers, and it's empty unless the `fuzzing` feature is enabled.
+#![cfg(feature = "fuzzing")]
+
+// Only the modules the fuzz entry points reach. They're public so that the parts of them the
+// entry points don't use aren't dead code.
+pub mod common;
+pub mod import;
+pub mod util;
+
+pub mod fuzz;
@@ -0,0 +1,111 @@
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::{Days, Local};
+
+use crate::{
+    common::{
+        Apply, DeltaItem, Event, EventId, EventSource, SaveData, SimpleTime,
+        config::{CONFIG, Macro, MacroAction},
+        error::{Kind, Source, TaskitResult, With},
+        get_description_tags,
+    },
+    report::describe_changes,
+};
+
+/// The changes one action makes. Events' tags are whichever of the tags their notes mention
+/// already exist, since there's nobody to ask whether to create the others.
+fn action_deltas(save_data: &SaveData, name: &str, action: &MacroAction) -> TaskitResult<Vec<DeltaItem>> {
+    let today = Local::now().date_naive();
+    match action {
+        MacroAction::Log { category, minutes, notes, after_previous } => {
+            let Some(category) = save_data.categories.find(category.as_str()) else {
+                return Err(Kind::NoSuchCategory(category.clone()).with(Source::RunningMacro));
+            };
+            let previous_end = after_previous
+                .then(|| {
+                    save_data
+                        .events
+                        .iter()
+                        .filter(|ev| ev.date == today && ev.start_time <= ev.end_time)
+                        .map(|ev| ev.end_time)
+                        .max()
+                })
+                .flatten();
+            let (start_time, end_time) = match previous_end {
+                Some(start) => (start, shift(start, *minutes as i64)),
+                None => {
+                    let end = SimpleTime::now();
+                    (shift(end, -(*minutes as i64)), end)
+                }
+            };
+            // an event ending just after midnight started the day before
+            let date = if start_time > end_time && previous_end.is_none() {
+                today - Days::new(1)
+            } else {
+                today
+            };
+            let names = get_description_tags(notes);
+            let tags: HashSet<_> = save_data.tags.iter().filter(|tag| names.contains(tag.inner())).cloned().collect();
+            Ok(vec![DeltaItem::AddEvent(Event {
+                id: EventId::NEW,
+                start_time,
+                end_time,
+                date,
+                category: category.clone(),
+                description: notes.clone(),
+                tags,
+                pinned: false,
+                source: EventSource::Macro(name.to_owned()),
+                external_id: None,
+                fields: BTreeMap::new(),
+            })])
+        }
+        MacroAction::Note(line) => {
+            let note = match save_data.daily_notes.get(&today) {
+                Some(note) if !note.is_empty() => format!("{note}\n{line}"),
+                _ => line.clone(),
+            };
+            Ok(vec![DeltaItem::SetDailyNote(today, note)])
+        }
+    }
+}
+
+/// `time` moved by `minutes`, wrapping around midnight
+fn shift(time: SimpleTime, minutes: i64) -> SimpleTime {
+    let total = (time.hour as i64 * 60 + time.minute as i64 + minutes).rem_euclid(24 * 60);
+    SimpleTime::try_new((total / 60) as u8, (total % 60) as u8).expect("wrapped to within a day")
+}
+
+/// The changes a macro makes, with each action seeing the changes of the ones before it
+pub fn macro_deltas(save_data: &SaveData, macro_: &Macro) -> TaskitResult<Vec<DeltaItem>> {
+    let mut save_data = save_data.clone();
+    let mut deltas = vec![];
+    for action in &macro_.actions {
+        let action_deltas = action_deltas(&save_data, &macro_.name, action)?;
+        save_data.apply(action_deltas.clone())?;
+        deltas.extend(action_deltas);
+    }
+    Ok(deltas)
+}
+
+/// Runs a macro from the config, or lists them if no name is given
+pub fn do_main(save_data: SaveData, name: Option<String>) -> TaskitResult<Vec<DeltaItem>> {
+    let Some(name) = name else {
+        if CONFIG.macros.is_empty() {
+            println!("No macros are defined; add some under [[macros]] in the config.");
+        }
+        for macro_ in &CONFIG.macros {
+            println!("{}", macro_.name);
+        }
+        return Ok(vec![]);
+    };
+    let Some(macro_) = CONFIG.macro_named(&name) else {
+        return Err(Kind::NoSuchMacro(name).with(Source::RunningMacro));
+    };
+    let deltas = macro_deltas(&save_data, macro_)?;
+    let changes = describe_changes(&deltas);
+    if !changes.is_empty() {
+        println!("{name}: {}", changes.join(", "));
+    }
+    Ok(deltas)
+}
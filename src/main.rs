@@ -1,20 +1,40 @@
+mod audit;
+mod backup;
+#[cfg(feature = "certify")]
+mod certify;
+#[cfg(feature = "charts")]
+mod chart;
 mod common;
+mod diff;
+mod export;
+mod history;
+mod import;
 mod input;
+mod journal;
+mod macros;
+mod migrate;
+mod profile;
+#[cfg(feature = "push")]
+mod push;
+mod report;
+mod share;
+#[cfg(feature = "tui")]
 mod tui;
 mod util;
 
 use std::{
-    fs::{File, create_dir_all, rename},
-    io::{Read, Write},
+    fs::{self, File, create_dir_all, rename},
+    io::Read,
     path::{Path, PathBuf},
     process::ExitCode,
 };
 
-use clap::{Parser, Subcommand};
-use common::{Apply, SaveData, UnverifiedSaveDataVersioned};
+use chrono::{NaiveDate, NaiveTime};
+use clap::{CommandFactory, FromArgMatches, Subcommand, parser::ValueSource};
+use common::{Apply, DeltaItem, SaveData, StorageKind, UnverifiedSaveDataVersioned, save_in, storage};
 use directories::ProjectDirs;
 
-use crate::common::{UnverifiedSaveDataLatest, config::CONFIG_WRITE};
+use crate::common::{UnverifiedSaveDataLatest, config::{Config, set_config}, error::{Kind, Source, TaskitResult, With}, index::TextIndex};
 
 #[derive(clap::Parser, Debug)]
 struct CliArgs {
@@ -25,45 +45,450 @@ struct CliArgs {
     #[arg(long)]
     config: Option<PathBuf>,
     
-    /// Use a different save file than the one at ~/.local/share/taskit/save.json
-    #[arg(long)]
+    /// Use a different save file than the one at ~/.local/share/taskit/save.json, e.g. to keep
+    /// separate logs or one in a synced folder
+    #[arg(long, visible_alias = "data-file", env = "TASKIT_DATA")]
     save: Option<PathBuf>,
+
+    /// Use the save file and config of a profile (see `taskit profile`). `--save` and `--config`
+    /// still take precedence
+    #[arg(long)]
+    profile: Option<String>,
 }
 
 #[derive(Subcommand, Debug)]
 enum CliSubcommands {
     /// (alias: taskit add) Add a new event, manually inputting all of its fields.
     #[clap(alias = "add")]
-    Record,
+    Record {
+        /// Keep recording events one after another, each starting when the previous one ended,
+        /// until you choose to stop
+        #[arg(long)]
+        batch: bool,
+    },
     /// (alias: taskit time, taskit start) Start a timer and add it as an event once it's done.
     #[clap(alias = "time", alias = "start")]
-    Stopwatch,
+    Stopwatch {
+        /// Choose the category up front instead of once the stopwatch is stopped
+        #[arg(long)]
+        category: Option<String>,
+        /// Pick up a stopwatch whose event never got saved (e.g. because the terminal was closed)
+        /// without asking. Without this, you're asked whether to resume it.
+        #[arg(long, conflicts_with = "category")]
+        resume: bool,
+    },
+    #[cfg(feature = "tui")]
     /// (alias: taskit list) Open the dashboard that displays all tracked time and allows you to filter events.
     #[clap(alias = "list")]
-    Show,
-    /// Modify a previously added event.
+    Show {
+        /// Start with a filter for events whose description matches this regular expression
+        #[arg(long)]
+        contains_regex: Option<String>,
+        /// List the events as plain text instead, and write a report of any that look malformed,
+        /// for when the dashboard keeps crashing
+        #[arg(long)]
+        safe: bool,
+    },
+    /// Run a macro from the config, like logging a 15-minute break that ends now, or list them
+    Do {
+        /// The macro's name, as given under [[macros]] in the config
+        name: Option<String>,
+    },
+    #[cfg(feature = "tui")]
+    /// Correct a single day in a worksheet, with its events editable in place, the gaps between
+    /// them, and its daily note
+    Day {
+        /// The day to open (YYYY-MM-DD), today if left out
+        date: Option<NaiveDate>,
+    },
+    /// (alias: taskit edit) Modify a previously added event, picked from a list that can be
+    /// narrowed down with the options below or by typing.
+    #[clap(alias = "edit")]
     Amend {
         /// Amend the most recently added event.
-        #[arg(long)]
+        #[arg(long, conflicts_with_all = ["category", "range", "contains"])]
         latest: bool,
+        /// Only list events in this category
+        #[arg(long)]
+        category: Option<String>,
+        /// Only list events in this range (see `taskit share --help` for the accepted forms)
+        #[arg(long)]
+        range: Option<util::DateRange>,
+        /// Only list events whose description contains this text (case-insensitive)
+        #[arg(long)]
+        contains: Option<String>,
     },
     /// Mark a category as archived, so no new events will be added to it.
     Archive { category: String },
+    /// Mark a category as private, so its events are left out of shared output like `taskit share`
+    Private {
+        category: String,
+        /// Make the category public again
+        #[arg(long)]
+        unset: bool,
+    },
+    /// Give a category a color, used for it in the TUI, and a description, shown when choosing a
+    /// category. Without either option, both are prompted for.
+    DescribeCategory {
+        category: String,
+        /// A color name like red or light-blue, or a hex code like #ff8800 (empty to remove it)
+        #[arg(long, value_parser = input::parse_color)]
+        color: Option<String>,
+        /// Empty to remove it
+        #[arg(long)]
+        description: Option<String>,
+    },
+    /// Set the hourly rate a category is billed at by `taskit invoice`, or stop billing it
+    Rate {
+        category: String,
+        /// Prompted for if not given
+        #[arg(value_parser = input::parse_rate, conflicts_with = "unset")]
+        rate: Option<f64>,
+        #[arg(long)]
+        unset: bool,
+    },
+    /// Set a weekly time goal for a category or tag, like 10 hours a week of study, or a limit like
+    /// at most 5 hours a week of games (see `taskit goals`)
+    Goal {
+        /// The category, or the tag with --tag
+        name: String,
+        /// Hours per week, prompted for if not given
+        #[arg(value_parser = input::parse_hours, conflicts_with = "unset")]
+        hours: Option<f64>,
+        /// Make the goal a limit to stay under
+        #[arg(long, conflicts_with = "unset")]
+        max: bool,
+        /// Set the goal of a tag rather than a category
+        #[arg(long)]
+        tag: bool,
+        /// Remove the goal
+        #[arg(long)]
+        unset: bool,
+    },
+    /// Show how far along each weekly goal is
+    Goals {
+        /// Which week to show: 0 for this week, -1 for last week, and so on
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        offset: i64,
+    },
+    /// Mark days as intentionally untracked (e.g. sick days or vacation), so they don't drag down
+    /// averages, break streaks, or get flagged by `taskit audit`
+    Skip {
+        /// A date or range of dates (see `taskit share --help` for the accepted forms)
+        days: util::DateRange,
+        /// Why the days are skipped, e.g. `vacation`
+        #[arg(long)]
+        reason: Option<String>,
+        /// Unmark the days instead
+        #[arg(long, conflicts_with = "reason")]
+        unset: bool,
+    },
     /// Add a tag to a category for larger aggregation.
     Tag,
     /// Add a note to a day
     Note,
-    /// Change the name of a category
-    RenameCategory,
-    /// Delete a previously recorded event
+    /// Change the name of a category, everywhere it's used. Prompts for either name if it's left
+    /// out.
+    RenameCategory {
+        /// The category's current name
+        old: Option<String>,
+        /// The name to give it
+        new: Option<String>,
+    },
+    /// (alias: taskit delete) Delete a previously recorded event.
+    #[clap(alias = "delete")]
     DeleteEvent,
     /// Delete a category that has no events in it
     DeleteCategory,
     /// Delete any tag
     DeleteTag,
+    #[cfg(feature = "tui")]
     /// Open a TUI to view and edit associations between categories and tags
     #[clap(alias = "edit-tags")]
     ManageTags,
+    /// Append a timestamped line to a previously recorded event's description
+    Annotate,
+    /// Pin a previously recorded event (e.g. a milestone) so it's easy to find later, or unpin it
+    Pin,
+    /// List all pinned events
+    Pins,
+    /// Print statistics about tracked time, such as 7-day and 30-day rolling averages, and how
+    /// many events each category has, how long they usually are, and what time of day they're at
+    Stats,
+    /// Show how tracked time falls across the hours of the day, to find when you actually get
+    /// things done
+    Heat {
+        /// Only include events in this range (see `taskit share --help` for the accepted forms)
+        #[arg(long)]
+        range: Option<util::DateRange>,
+        /// Show a shaded column per category instead of bars for the total
+        #[arg(long)]
+        by_category: bool,
+    },
+    /// Summarize time by category, tag, day, week, or month, optionally filtered and saved to run
+    /// again later
+    Report {
+        #[command(subcommand)]
+        kind: Option<report::ReportKind>,
+        #[command(flatten)]
+        options: report::ReportOptions,
+        /// Write to this file instead of standard output (for formats other than table)
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Choose the range, grouping, filters, and format with prompts, then optionally save the report
+        #[arg(short, long)]
+        interactive: bool,
+        /// Run a saved report
+        #[arg(long)]
+        saved: Option<String>,
+        /// Save the report under this name
+        #[arg(long)]
+        save_as: Option<String>,
+        /// Delete a saved report
+        #[arg(long, exclusive = true)]
+        delete_saved: Option<String>,
+        /// Run any saved reports that are due according to their schedules, writing them to the
+        /// directory set by `output_dir` under `[reports]` in the config
+        #[arg(long, exclusive = true)]
+        run_scheduled: bool,
+    },
+    /// Summarize a custom field: time spent per value, or averages for number fields
+    FieldReport {
+        /// The name of the field, as declared in the config
+        name: String,
+        /// Only include events in this range (see `taskit share --help` for the accepted forms)
+        #[arg(long)]
+        range: Option<util::DateRange>,
+    },
+    /// List the expenses recorded against events, with their receipts, and total what's reimbursable
+    /// per category (see `[expenses]` in the config)
+    Expenses {
+        /// Only include events in this range (see `taskit share --help` for the accepted forms)
+        #[arg(long)]
+        range: Option<util::DateRange>,
+    },
+    /// Total billable hours and amounts per category over a period (see `taskit rate`)
+    Invoice {
+        /// The first day to bill (YYYY-MM-DD)
+        #[arg(long)]
+        from: NaiveDate,
+        /// The last day to bill (YYYY-MM-DD)
+        #[arg(long)]
+        to: NaiveDate,
+        /// Round each event's time up to a multiple of this many minutes (defaults to
+        /// `round_to_minutes` under `[invoice]` in the config; 0 to not round)
+        #[arg(long)]
+        round: Option<u32>,
+    },
+    /// Compare the time tracked each week against the weekly hours committed to categories (see
+    /// `[capacity]` in the config), with the running over- or under-delivery
+    Capacity {
+        /// How many weeks to show, ending with this one
+        #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..))]
+        weeks: u32,
+        /// Start from the week containing this day instead (YYYY-MM-DD), e.g. when a retainer began
+        #[arg(long, conflicts_with = "weeks")]
+        since: Option<NaiveDate>,
+    },
+    #[cfg(feature = "push")]
+    /// List the events that couldn't be pushed (see `[push]` in the config) and are waiting to be
+    /// tried again, which happens whenever a later command changes the save
+    Push {
+        /// Try the waiting pushes again now
+        #[arg(long, conflicts_with = "clear")]
+        retry: bool,
+        /// Drop the waiting pushes without sending them
+        #[arg(long)]
+        clear: bool,
+    },
+    #[cfg(feature = "charts")]
+    /// Render a chart of tracked time to an SVG or PNG file
+    Chart {
+        #[arg(long = "type", value_enum, default_value = "pie")]
+        chart_type: chart::ChartType,
+        /// Where to write the chart (must end in .svg or .png)
+        #[arg(long)]
+        out: PathBuf,
+        /// Include events in private categories
+        #[arg(long)]
+        include_private: bool,
+        /// Only include events on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Only include events on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        to: Option<NaiveDate>,
+    },
+    /// Write a self-contained HTML snapshot of the events in a date range, e.g. for sending to a client
+    Share {
+        /// today, yesterday, this-week, last-week, this-month, last-month, this-year, last-year, a
+        /// year (YYYY), a month (YYYY-MM), a date (YYYY-MM-DD), or a range of dates
+        /// (YYYY-MM-DD..YYYY-MM-DD)
+        #[arg(long, default_value = "this-week")]
+        range: util::DateRange,
+        /// Where to write the snapshot (must end in .html)
+        #[arg(long)]
+        out: PathBuf,
+        /// Include events in private categories
+        #[arg(long)]
+        include_private: bool,
+        /// Ask for a passphrase and encrypt the snapshot with it; the page asks for it again
+        /// before showing anything
+        #[arg(long)]
+        passphrase: bool,
+    },
+    /// Export events as CSV, a Markdown table, or JSON Lines
+    Export {
+        #[command(subcommand)]
+        kind: Option<export::ExportKind>,
+        #[arg(long, value_enum, default_value = "csv")]
+        format: export::ExportFormat,
+        /// Comma-separated list of fields to include, in order: date, start, end, duration,
+        /// category, description, tags, pinned, source, or the name of a custom field. By default,
+        /// CSV and Markdown leave out descriptions, tags, and custom fields, and JSON Lines
+        /// includes everything.
+        #[arg(long, value_delimiter = ',')]
+        fields: Vec<export::Column>,
+        /// Only export events in this range (see `taskit share --help` for the accepted forms)
+        #[arg(long)]
+        range: Option<util::DateRange>,
+        /// Write to this file instead of standard output
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Include events in private categories
+        #[arg(long)]
+        include_private: bool,
+    },
+    /// List events as they were at the end of a past day, by undoing the journaled changes made
+    /// since, e.g. to check what was reported to a client at the time
+    View {
+        /// The day to view the data as of (YYYY-MM-DD)
+        #[arg(long)]
+        as_of: NaiveDate,
+        /// Only list events in this range (see `taskit share --help` for the accepted forms)
+        #[arg(long)]
+        range: Option<util::DateRange>,
+        /// Also print what has changed since
+        #[arg(long)]
+        changes: bool,
+    },
+    /// Print what differs between two save files, or between one (like a backup) and the current
+    /// save: categories, tags, events, and daily notes
+    Diff {
+        /// The older save file
+        old: PathBuf,
+        /// The newer save file (defaults to the current save)
+        new: Option<PathBuf>,
+    },
+    #[cfg(feature = "certify")]
+    /// Check that a certified export (`taskit export certified`) hasn't been altered: its hash
+    /// chain, and its signature if it has one
+    VerifyExport {
+        file: PathBuf,
+        /// The minisign public key to check the signature against. Defaults to `public_key` under
+        /// `[certify]` in the config
+        #[arg(long)]
+        public_key: Option<PathBuf>,
+        /// Pass an export that has no signature, or no public key to check it with, on its hash
+        /// chain alone
+        #[arg(long)]
+        unsigned: bool,
+    },
+    /// Compare tracked time against git commits and calendar events, flagging days with activity
+    /// but no tracked time
+    Audit {
+        /// A git repository to read commits from (can be given multiple times)
+        #[arg(long)]
+        git: Vec<PathBuf>,
+        /// An iCalendar (.ics) file to read events from (can be given multiple times)
+        #[arg(long)]
+        calendar: Vec<PathBuf>,
+        /// Only audit this range of days (defaults to the last 30 days; see `taskit share --help`
+        /// for the accepted forms)
+        #[arg(long)]
+        range: Option<util::DateRange>,
+    },
+    /// Backfill a forgotten day by going through it one block of time at a time
+    Reconstruct {
+        /// The day to fill in (YYYY-MM-DD)
+        date: NaiveDate,
+        /// When the day started (HH:MM)
+        #[arg(long, default_value = "09:00")]
+        from: NaiveTime,
+        /// When the day ended (HH:MM)
+        #[arg(long, default_value = "17:00")]
+        until: NaiveTime,
+        /// Length of each block of time, in minutes
+        #[arg(long, default_value_t = 60)]
+        step: u32,
+        /// A git repository whose commits are shown as hints (can be given multiple times)
+        #[arg(long)]
+        git: Vec<PathBuf>,
+        /// An iCalendar (.ics) file whose events are shown as hints (can be given multiple times)
+        #[arg(long)]
+        calendar: Vec<PathBuf>,
+    },
+    /// Import events from a CSV or JSON Lines file with the same fields as `taskit export`. Rows
+    /// with an `id` field update the event imported with that id last time instead of being added
+    /// again; rows without one are always added.
+    Import {
+        file: PathBuf,
+        /// Name of the system the events come from (e.g. toggl); ids are only matched within it
+        #[arg(long)]
+        source: String,
+    },
+    /// Check over a month, go through the month-end checklist from the config, and lock the month
+    /// (and everything before it) against further changes
+    CloseMonth {
+        /// The month to close (YYYY-MM); defaults to last month
+        month: Option<util::DateRange>,
+        /// Unlock the month (and everything after it) again instead
+        #[arg(long)]
+        reopen: bool,
+    },
+    /// Go through very short events, like stopwatch fragments, merging each into the event before
+    /// or after it, deleting it, or keeping it (see `[tidy]` in the config)
+    Tidy {
+        /// Minutes an event has to be shorter than to be offered up; defaults to `min_minutes` in
+        /// the config, or 2
+        #[arg(long)]
+        under: Option<u32>,
+    },
+    /// Search event descriptions and daily notes for some text (case-insensitive)
+    Search {
+        query: String,
+        /// Match fuzzily instead of requiring an exact substring, best matches first
+        #[arg(long)]
+        fuzzy: bool,
+    },
+    /// List, create, or delete profiles, each with its own save file, config, and optional passphrase
+    Profile {
+        #[command(subcommand)]
+        action: profile::ProfileAction,
+    },
+    /// Replace the save file with one of its backups (see `[backups]` in the config), after showing
+    /// what would change
+    Restore,
+    /// Move the save to another kind of storage, like a SQLite database once the JSON file has grown
+    /// large. The old save file is kept next to the new one
+    MigrateStorage {
+        #[arg(long, value_enum, default_value = "sqlite")]
+        to: StorageKind,
+    },
+    /// Reverse the most recent change to the save file. Can be repeated to go further back.
+    Undo,
+    /// List recently run commands, most recent first
+    History {
+        /// How many commands to list
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Run a recent command again, with the same arguments
+    Redo {
+        /// Which command to run, counting back from the most recent (as numbered by `taskit history`)
+        #[arg(default_value_t = 1)]
+        n: usize,
+    },
 }
 
 fn main() -> ExitCode {
@@ -78,54 +503,191 @@ fn main() -> ExitCode {
     )
     .expect("assume that there is a home directory");
 
-    let cli_args = CliArgs::parse();
-
-    let config_file_path = cli_args.config.unwrap_or_else(|| {
-        let mut path = project_dirs.config_local_dir().to_path_buf();
-        path.push("config.toml");
-        path
-    });
-    let config = File::open(config_file_path).and_then(|mut text| {
-        let mut s = String::new(); 
-        text.read_to_string(&mut s)?; 
-        Ok(toml::from_str(&s).unwrap_or_default())
-    }).unwrap_or_default();
-    CONFIG_WRITE.set(config).expect("this should be the only set call in the program");
-
-    let save_data_file_path = cli_args.save.unwrap_or_else(|| {
-        let mut path = project_dirs.data_dir().to_path_buf();
-        if !path.exists() {
-            println!("data directory does not exist. creating...");
-            create_dir_all(&path)
-                .expect("assume we have access to data directory - can't run without");
-        }
-        path.push("save.json");
-        path
-    });
-    let (save_data, upgraded) = read_save_data(&save_data_file_path).extract();
-    let save_data = save_data.fix_and_verify().expect("save file must be well-formed");
-    if upgraded {
-        rename(
-            &save_data_file_path,
-            save_data_file_path.with_extension(".upgrade_bak"),
-        )
-        .expect("assume file rename is possible");
-        write_save_data(save_data.clone(), &save_data_file_path);
+    let matches = CliArgs::command().get_matches();
+    let cli_args = CliArgs::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Some(name) = &cli_args.profile
+        && !profile::valid_name(name)
+    {
+        eprintln!("{}", Kind::InvalidProfileName(name.clone()).with(Source::OpeningProfile));
+        return ExitCode::FAILURE;
+    }
+    let data_dir = project_dirs.data_dir();
+    let default_config = project_dirs.config_local_dir().join("config.toml");
+    // the save file and config of a profile, or of no profile, which the dashboard can switch between
+    let locate = |profile: Option<&str>| match profile {
+        Some(name) => {
+            let dir = profile::profile_dir(data_dir, name);
+            (profile::save_path(&dir), profile::config_path(&dir))
+        }
+        None => {
+            let save = cli_args.save.clone().unwrap_or_else(|| {
+                if !data_dir.exists() {
+                    println!("data directory does not exist. creating...");
+                    create_dir_all(data_dir)
+                        .expect("assume we have access to data directory - can't run without");
+                }
+                save_in(data_dir)
+            });
+            (save, cli_args.config.clone().unwrap_or_else(|| default_config.clone()))
+        }
+    };
+    if let Some(name) = &cli_args.profile {
+        let dir = profile::profile_dir(data_dir, name);
+        if !dir.is_dir() {
+            eprintln!("{}", Kind::NoSuchProfile(name.clone()).with(Source::ManagingProfiles));
+            return ExitCode::FAILURE;
+        }
+        if let Err(e) = profile::unlock(&dir, name) {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
     }
+
+    let (save_data_file_path, config_file_path) = match cli_args.profile.as_deref() {
+        Some(name) => {
+            let (profile_save, profile_config) = locate(Some(name));
+            // a profile asked for on the command line wins over a save file set for every command
+            let save = cli_args.save.clone().filter(|_| matches.value_source("save") != Some(ValueSource::EnvVariable));
+            (save.unwrap_or(profile_save), cli_args.config.clone().unwrap_or(profile_config))
+        }
+        None => locate(None),
+    };
+    set_config(load_config(&config_file_path));
+    // commands that only look at a range of days don't need the events outside it
+    let (from, to) = match &cli_args.command {
+        CliSubcommands::Invoice { from, to, .. } => (Some(*from), Some(*to)),
+        #[cfg(feature = "charts")]
+        CliSubcommands::Chart { from, to, .. } => (*from, *to),
+        CliSubcommands::Share { range, .. } => (Some(range.start), Some(range.end)),
+        _ => (None, None),
+    };
+    let save_data = load_save_data(&save_data_file_path, from, to);
+    let journal_path = journal::journal_path(&save_data_file_path);
+    let undoing = matches!(cli_args.command, CliSubcommands::Undo);
+    // going back through the history shouldn't push what's being gone back to out of it
+    let remembered = !matches!(
+        cli_args.command,
+        CliSubcommands::Undo | CliSubcommands::History { .. } | CliSubcommands::Redo { .. }
+    );
+    let history_path = history::history_path(&save_data_file_path);
     let save_delta = match cli_args.command {
-        CliSubcommands::Record => input::record_main(save_data),
-        CliSubcommands::Stopwatch => input::stopwatch_main(save_data),
-        CliSubcommands::Amend { latest: true } => input::amend_main(save_data, 0),
-        CliSubcommands::Amend { latest: false } => input::dispatch_amend(save_data),
-        CliSubcommands::Show => tui::filter_main(save_data),
+        CliSubcommands::Record { batch } => input::record_main(save_data, batch),
+        CliSubcommands::Stopwatch { category, resume } => {
+            input::stopwatch_main(save_data, category, resume, &save_data_file_path.with_extension("stopwatch.json"))
+        }
+        CliSubcommands::Amend { latest: true, .. } => input::amend_main(save_data, 0),
+        CliSubcommands::Amend { latest: false, category, range, contains } => {
+            input::dispatch_amend(save_data, category, range, contains)
+        }
+        #[cfg(feature = "tui")]
+        CliSubcommands::Show { contains_regex, safe } => {
+            show_main(save_data, &save_data_file_path, contains_regex, safe, data_dir, cli_args.profile.as_deref(), locate)
+        }
+        #[cfg(feature = "tui")]
+        CliSubcommands::Day { date } => tui::day_main(save_data, date),
+        CliSubcommands::Do { name } => macros::do_main(save_data, name),
         CliSubcommands::Archive { category } => input::archive_main(save_data, category),
+        CliSubcommands::Private { category, unset } => input::private_main(save_data, category, unset),
+        CliSubcommands::DescribeCategory { category, color, description } => {
+            input::describe_category_main(save_data, category, color, description)
+        }
+        CliSubcommands::Rate { category, rate, unset } => input::rate_main(save_data, category, rate, unset),
+        CliSubcommands::Goal { name, hours, max, tag, unset } => input::goal_main(save_data, name, hours, max, tag, unset),
+        CliSubcommands::Goals { offset } => report::goals_main(save_data, offset),
+        CliSubcommands::Skip { days, reason, unset } => input::skip_main(save_data, days, reason, unset),
         CliSubcommands::Tag => input::tag_main(save_data),
         CliSubcommands::Note => input::note_main(save_data),
-        CliSubcommands::RenameCategory => input::rename_category_main(save_data),
+        CliSubcommands::RenameCategory { old, new } => input::rename_category_main(save_data, old, new),
         CliSubcommands::DeleteEvent => input::delete_event_main(save_data),
         CliSubcommands::DeleteCategory => input::delete_category_main(save_data),
         CliSubcommands::DeleteTag => input::delete_tag_main(save_data),
+        #[cfg(feature = "tui")]
         CliSubcommands::ManageTags => tui::tagedit_main(save_data),
+        CliSubcommands::Annotate => input::annotate_main(save_data),
+        CliSubcommands::Pin => input::pin_main(save_data),
+        CliSubcommands::Pins => input::pins_main(save_data),
+        CliSubcommands::Stats => report::stats_main(save_data),
+        CliSubcommands::Heat { range, by_category } => report::heat_main(save_data, range, by_category),
+        CliSubcommands::Report { kind: Some(report::ReportKind::Week { offset, include_private }), .. } => {
+            report::week_main(save_data, offset, include_private)
+        }
+        CliSubcommands::Report { kind: Some(report::ReportKind::Month { month, include_private }), .. } => {
+            report::month_main(save_data, month, include_private)
+        }
+        CliSubcommands::Report { delete_saved: Some(name), .. } => report::delete_report_main(save_data, name),
+        CliSubcommands::Report { run_scheduled: true, .. } => {
+            report::run_scheduled_main(save_data, &report::schedule_path(&save_data_file_path))
+        }
+        CliSubcommands::Report {
+            kind: None,
+            options,
+            out,
+            interactive,
+            saved,
+            save_as,
+            delete_saved: None,
+            run_scheduled: false,
+        } => {
+            report::report_main(save_data, options, out, interactive, saved, save_as)
+        }
+        CliSubcommands::FieldReport { name, range } => report::field_report_main(save_data, name, range),
+        CliSubcommands::Expenses { range } => report::expenses_main(save_data, range),
+        CliSubcommands::Invoice { from, to, round } => report::invoice_main(save_data, from, to, round),
+        CliSubcommands::Capacity { weeks, since } => report::capacity_main(save_data, weeks, since),
+        #[cfg(feature = "push")]
+        CliSubcommands::Push { retry, clear } => {
+            push::push_main(save_data, &push::outbox_path(&save_data_file_path), retry, clear)
+        }
+        #[cfg(feature = "charts")]
+        CliSubcommands::Chart { chart_type, out, include_private, from, to } => {
+            chart::chart_main(save_data, chart_type, out, include_private, from, to)
+        }
+        CliSubcommands::Export {
+            kind: Some(export::ExportKind::Workdays { year, range, columns, format, out, include_private }),
+            ..
+        } => export::export_workdays_main(save_data, year, range, columns, format, out, include_private),
+        CliSubcommands::Export { kind: Some(export::ExportKind::Ics { range, out, include_private }), .. } => {
+            export::export_ics_main(save_data, range, out, include_private)
+        }
+        #[cfg(feature = "certify")]
+        CliSubcommands::Export {
+            kind: Some(export::ExportKind::Certified { range, out, unsigned, include_private }),
+            ..
+        } => certify::export_certified_main(save_data, range, out, unsigned, include_private),
+        CliSubcommands::Export { kind: None, format, fields, range, out, include_private } => {
+            export::export_main(save_data, format, fields, range, out, include_private)
+        }
+        CliSubcommands::View { as_of, range, changes } => {
+            journal::view_main(save_data, &journal_path, as_of, range, changes)
+        }
+        CliSubcommands::Diff { old, new } => diff::diff_main(save_data, &old, new.as_deref()),
+        #[cfg(feature = "certify")]
+        CliSubcommands::VerifyExport { file, public_key, unsigned } => {
+            certify::verify_export_main(save_data, &file, public_key, unsigned)
+        }
+        CliSubcommands::Audit { git, calendar, range } => audit::audit_main(save_data, git, calendar, range),
+        CliSubcommands::Reconstruct { date, from, until, step, git, calendar } => {
+            input::reconstruct_main(save_data, date, from, until, step, git, calendar)
+        }
+        CliSubcommands::Import { file, source } => import::import_main(save_data, file, source),
+        CliSubcommands::CloseMonth { month, reopen } => input::close_month_main(save_data, month, reopen),
+        CliSubcommands::Share { range, out, include_private, passphrase } => {
+            share::share_main(save_data, range, out, include_private, passphrase)
+        }
+        CliSubcommands::Tidy { under } => input::tidy_main(save_data, under),
+        CliSubcommands::Search { query, fuzzy } => input::search_main(save_data, &save_data_file_path, query, fuzzy),
+        CliSubcommands::Profile { action } => profile::profile_main(
+            save_data,
+            action,
+            data_dir,
+            &config_file_path,
+            cli_args.profile.as_deref(),
+        ),
+        CliSubcommands::Restore => backup::restore_main(save_data, &save_data_file_path),
+        CliSubcommands::MigrateStorage { to } => migrate::migrate_storage_main(save_data, &save_data_file_path, to),
+        CliSubcommands::Undo => journal::undo_main(save_data, &journal_path),
+        CliSubcommands::History { limit } => history::history_main(save_data, &history_path, limit),
+        CliSubcommands::Redo { n } => history::redo_main(save_data, &history_path, n),
     };
     let save_delta = match save_delta {
         Ok(d) => d,
@@ -134,45 +696,134 @@ fn main() -> ExitCode {
             return ExitCode::FAILURE;
         }
     };
-    if !save_delta.is_empty() {
-        let mut save_data = read_save_data(&save_data_file_path).extract().0.fix_and_verify().expect("save data from file should still be well-formed");
-        save_data
-            .apply(save_delta)
-            .expect("save_delta doesn't actually return an error ever");
-        write_save_data(save_data, &save_data_file_path);
+    if let Err(e) = save_changes(save_delta, &save_data_file_path, undoing) {
+        eprintln!("{e} No modifications made.");
+        return ExitCode::FAILURE;
+    }
+    if remembered && let Err(e) = history::record(&history_path) {
+        eprintln!("Warning: the command history couldn't be updated: {e}");
     }
     ExitCode::SUCCESS
 }
 
-fn read_save_data(path: impl AsRef<Path>) -> UnverifiedSaveDataVersioned {
-    let mut save_data = String::new();
-    if let Ok(mut save_data_file) = File::open(path) {
-        save_data_file
-            .read_to_string(&mut save_data)
-            .expect("save data file should be readable and utf-8");
-        // TODO perhaps indicate the error in more detail in the case that deserialization fails?
-        serde_json::from_str::<UnverifiedSaveDataVersioned>(&save_data)
-            .expect("save data file should be valid JSON in the save data format")
+/// Reads the save file at `path`, upgrading it in place if it's from an older version of taskit.
+/// Events and daily notes outside `from` to `to` may be left out (see `Storage::read_between`).
+fn load_save_data(path: &Path, from: Option<NaiveDate>, to: Option<NaiveDate>) -> SaveData {
+    let (save_data, upgraded) = read_save_data(path, from, to).extract();
+    let save_data = save_data.fix_and_verify().expect("save file must be well-formed");
+    if upgraded {
+        rename(path, path.with_extension(".upgrade_bak")).expect("assume file rename is possible");
+        write_save_data(save_data.clone(), path, None);
+        if let Err(e) = journal::retire(&journal::journal_path(path)) {
+            eprintln!("Warning: the undo journal couldn't be set aside after upgrading the save file: {e}");
+        }
+    }
+    save_data
+}
+
+/// Applies a command's changes to the save file at `path`, and records them in the undo journal
+/// next to it (or, when `undoing`, takes the undone change out of it)
+fn save_changes(save_delta: Vec<DeltaItem>, path: &Path, undoing: bool) -> TaskitResult<()> {
+    if save_delta.is_empty() {
+        return Ok(());
+    }
+    let mut save_data = read_save_data(path, None, None)
+        .extract()
+        .0
+        .fix_and_verify()
+        .expect("save data from file should still be well-formed");
+    let changes = save_delta.clone();
+    let undo = save_data.apply_reversibly(save_delta)?;
+    // events added by this command are the ones its undo deletes
+    #[cfg(feature = "push")]
+    let pushed = if undoing { vec![] } else { push::added_events(&save_data, &undo) };
+    write_save_data(save_data, path, Some(&changes));
+    #[cfg(feature = "push")]
+    push::push_events(&pushed, &push::outbox_path(path));
+    // undoing doesn't get journaled itself, so repeating `taskit undo` keeps going back
+    let journal_path = journal::journal_path(path);
+    let journaled = if undoing {
+        journal::pop(&journal_path).map_err(|e| e.to_string())
     } else {
-        Default::default()
+        journal::record(&journal_path, undo).map_err(|e| e.to_string())
+    };
+    if let Err(e) = journaled {
+        eprintln!("Warning: the change was saved, but the undo journal couldn't be updated: {e}");
     }
+    Ok(())
 }
 
-fn write_save_data(data: SaveData, path: impl AsRef<Path>) {
-    let save_data_temp_path = path.as_ref().with_extension("tmp");
-    let unverified = UnverifiedSaveDataLatest::from(data);
-    {
-        let mut save_data_temp_file = File::create(&save_data_temp_path)
-            .expect("path should be known to be valid and file creation should be allowed");
-        save_data_temp_file
-            .write_all(
-                &serde_json::to_vec(&UnverifiedSaveDataVersioned::from(unverified.clone()))
-                    .expect("the file we just created should be writable"),
-            )
-            .expect("we should be able to write to the save file");
+/// Runs the dashboard, opening it again on another profile's save, with its config, each time it's
+/// asked to switch. `locate` gives the save file and config of each profile, or of no profile. Changes
+/// are saved as their profile is left; the ones made to the save the dashboard started on, if it's
+/// left last, are returned to be saved like any other command's.
+#[cfg(feature = "tui")]
+fn show_main(
+    mut save_data: SaveData,
+    save_path: &Path,
+    contains_regex: Option<String>,
+    safe: bool,
+    data_dir: &Path,
+    profile: Option<&str>,
+    locate: impl Fn(Option<&str>) -> (PathBuf, PathBuf),
+) -> TaskitResult<Vec<DeltaItem>> {
+    let mut path = save_path.to_path_buf();
+    let mut profile = profile.map(str::to_owned);
+    loop {
+        let crash_log = tui::crash_log_path(&path);
+        let (staged, switch_to) =
+            tui::filter_main(save_data, &path, contains_regex.clone(), safe, &crash_log, data_dir, profile.as_deref())?;
+        if switch_to.is_none() && path == save_path {
+            return Ok(staged);
+        }
+        save_changes(staged, &path, false)?;
+        let Some(name) = switch_to else {
+            return Ok(vec![]);
+        };
+        let config;
+        (path, config) = locate(name.as_deref());
+        set_config(load_config(&config));
+        save_data = load_save_data(&path, None, None);
+        profile = name;
     }
-    if let Err(e) = unverified.verify() {
+}
+
+/// Reads the config file at `path`, falling back to the defaults if it's missing or invalid
+fn load_config(path: &Path) -> Config {
+    File::open(path).and_then(|mut text| {
+        let mut s = String::new(); 
+        text.read_to_string(&mut s)?; 
+        Ok(toml::from_str(&s).unwrap_or_default())
+    }).unwrap_or_default()
+}
+
+fn read_save_data(path: impl AsRef<Path>, from: Option<NaiveDate>, to: Option<NaiveDate>) -> UnverifiedSaveDataVersioned {
+    storage(path.as_ref())
+        .and_then(|storage| storage.read_between(from, to))
+        .unwrap_or_else(|e| panic!("the save file should be readable: {e}"))
+        .unwrap_or_default()
+}
+
+/// Writes `data` to the save file at `path`. `changes`, if given, are the ones just applied to the
+/// save as it was read, so that storage that can write only what changed does.
+fn write_save_data(data: SaveData, path: impl AsRef<Path>, changes: Option<&[DeltaItem]>) {
+    let mut index = TextIndex::new(&data);
+    let unverified = UnverifiedSaveDataLatest::from(data);
+    if let Err(e) = unverified.clone().verify() {
         eprintln!("Warning: newly saved data doesn't verify properly. Please report this issue on the Taskit GitHub: \n {e:?}");
     }
-    rename(save_data_temp_path, &path).expect("we should be able to rename files");
+    if let Err(e) = backup::back_up(path.as_ref()) {
+        eprintln!("Warning: the previous save file couldn't be backed up: {e}");
+    }
+    storage(path.as_ref())
+        .and_then(|storage| match changes {
+            Some(changes) => storage.write_changes(unverified, changes),
+            None => storage.write(unverified),
+        })
+        .unwrap_or_else(|e| panic!("we should be able to write the save file: {e}"));
+    // hashed as written, which for a database isn't the JSON it was made from
+    let indexed = fs::read(path.as_ref()).and_then(|save| index.write(path.as_ref(), &save));
+    if let Err(e) = indexed {
+        eprintln!("Warning: the change was saved, but the search index couldn't be updated: {e}");
+    }
 }
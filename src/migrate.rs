@@ -0,0 +1,48 @@
+//! Moving the save between kinds of storage, like from the JSON file it starts out as to a SQLite
+//! database once it's grown large
+
+use std::{fs, io, path::Path};
+
+use crate::common::{
+    DeltaItem, SaveData, StorageKind, UnverifiedSaveDataLatest, UnverifiedSaveDataVersioned,
+    error::{Kind, Source, TaskitResult, With},
+    save_in, storage,
+};
+
+/// Writes the save to a file of the kind `to` next to it, checks that it reads back the same, and
+/// moves the old file aside
+pub fn migrate_storage_main(save_data: SaveData, save_path: &Path, to: StorageKind) -> TaskitResult<Vec<DeltaItem>> {
+    if StorageKind::of(save_path) == to {
+        return Err(Kind::AlreadyStoredAs(to.extension()).with(Source::MigratingStorage));
+    }
+    let new_path = save_path.with_extension(to.extension());
+    if new_path.exists() {
+        let e = io::Error::other(format!("{} already exists", new_path.display()));
+        return Err(e.with(Source::MigratingStorage).into());
+    }
+    let save = UnverifiedSaveDataLatest::from(save_data);
+    let stored = storage(&new_path).and_then(|storage| {
+        storage.write(save.clone())?;
+        storage.read()
+    });
+    // a save as JSON has everything in it, in a stable order
+    let expected = serde_json::to_value(UnverifiedSaveDataVersioned::from(save)).ok();
+    match stored {
+        Ok(Some(read)) if serde_json::to_value(&read).ok() == expected => {}
+        stored => {
+            // what was written can't be trusted, and would be picked over the old save
+            let _ = fs::remove_file(&new_path);
+            stored?;
+            let e = io::Error::other(format!("{} didn't read back the same as the save", new_path.display()));
+            return Err(e.with(Source::MigratingStorage).into());
+        }
+    }
+    let ext = save_path.extension().unwrap_or_default().to_string_lossy();
+    let old_path = save_path.with_extension(format!("{ext}.migrated"));
+    fs::rename(save_path, &old_path).with(Source::MigratingStorage)?;
+    println!("Moved the save to {}. The old one is kept as {}.", new_path.display(), old_path.display());
+    if save_in(save_path.parent().unwrap_or(Path::new(""))) != new_path {
+        println!("Point --save or TASKIT_DATA at the new file to keep using it.");
+    }
+    Ok(vec![])
+}
@@ -0,0 +1,240 @@
+//! Named profiles, each with its own save file and config in the data directory, for keeping logs
+//! like work and personal apart without passing `--save` and `--config` every time
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use aes_gcm::aead::{OsRng, rand_core::RngCore};
+use inquire::{Confirm, Password};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{
+    common::{
+        DeltaItem, SaveData,
+        error::{Kind, Source, TaskitResult, With},
+        save_in,
+    },
+    util::hex,
+};
+
+#[derive(clap::Subcommand, Debug)]
+pub enum ProfileAction {
+    /// List the profiles, marking the one in use
+    List,
+    /// Create a profile with an empty save file, starting from a copy of the config in use
+    Create { name: String },
+    /// Delete a profile along with its save file, config, and history
+    Delete { name: String },
+    /// Set or change the passphrase asked for before a profile is used with `--profile`. This is
+    /// not protection: the profile's files aren't encrypted, and pointing `--save` or `TASKIT_DATA`
+    /// at its save file skips the passphrase altogether
+    SetPassphrase { name: String },
+    /// Stop asking for a profile's passphrase
+    RemovePassphrase { name: String },
+}
+
+/// PBKDF2-HMAC-SHA256 rounds for hashing a profile's passphrase
+const ITERATIONS: u32 = 600_000;
+
+/// What's kept of a profile's passphrase: a salted hash to check it against. It only keeps taskit
+/// from opening the profile by name; the save file itself isn't encrypted.
+#[derive(Serialize, Deserialize)]
+struct Lock {
+    salt: String,
+    iterations: u32,
+    hash: String,
+}
+
+/// Where the profile named `name` keeps its files, whether or not it exists
+pub fn profile_dir(data_dir: &Path, name: &str) -> PathBuf {
+    data_dir.join("profiles").join(name)
+}
+
+pub fn save_path(profile_dir: &Path) -> PathBuf {
+    save_in(profile_dir)
+}
+
+pub fn config_path(profile_dir: &Path) -> PathBuf {
+    profile_dir.join("config.toml")
+}
+
+fn lock_path(profile_dir: &Path) -> PathBuf {
+    profile_dir.join("passphrase.json")
+}
+
+fn hash(passphrase: &str, salt: &str, iterations: u32) -> String {
+    let mut hash = [0; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt.as_bytes(), iterations, &mut hash);
+    hex(&hash)
+}
+
+pub fn has_passphrase(profile_dir: &Path) -> bool {
+    lock_path(profile_dir).exists()
+}
+
+fn set_passphrase(profile_dir: &Path, passphrase: &str, iterations: u32) -> TaskitResult<()> {
+    let mut salt = [0; 16];
+    OsRng.fill_bytes(&mut salt);
+    let salt = hex(&salt);
+    let lock = Lock { hash: hash(passphrase, &salt, iterations), salt, iterations };
+    let json = serde_json::to_vec(&lock).expect("a lock always serializes");
+    fs::write(lock_path(profile_dir), json).with(Source::ManagingProfiles)?;
+    Ok(())
+}
+
+/// Whether `passphrase` is the profile's. Profiles without a passphrase take any.
+pub fn check_passphrase(profile_dir: &Path, passphrase: &str) -> TaskitResult<bool> {
+    if !has_passphrase(profile_dir) {
+        return Ok(true);
+    }
+    let lock = fs::read(lock_path(profile_dir)).with(Source::OpeningProfile)?;
+    let lock: Lock = serde_json::from_slice(&lock).map_err(|e| Kind::Other(Box::new(e)).with(Source::OpeningProfile))?;
+    Ok(hash(passphrase, &lock.salt, lock.iterations) == lock.hash)
+}
+
+/// Asks for the passphrase of the profile named `name`, if it has one, and fails if it's wrong
+pub fn unlock(profile_dir: &Path, name: &str) -> TaskitResult<()> {
+    if !has_passphrase(profile_dir) {
+        return Ok(());
+    }
+    let passphrase = Password::new(&format!("Passphrase for profile {name}:"))
+        .without_confirmation()
+        .prompt()
+        .with(Source::OpeningProfile)?;
+    if !check_passphrase(profile_dir, &passphrase)? {
+        return Err(Kind::WrongPassphrase(name.to_owned()).with(Source::OpeningProfile));
+    }
+    Ok(())
+}
+
+/// The names of all profiles, sorted
+pub fn names(data_dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = match fs::read_dir(data_dir.join("profiles")) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect(),
+        Err(_) => vec![],
+    };
+    names.sort();
+    names
+}
+
+/// Names become directory names, so they're kept to characters that are safe in one
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// `config` is the config file in use, which new profiles start from, and `active` the profile in
+/// use, if any
+pub fn profile_main(
+    _save_data: SaveData,
+    action: ProfileAction,
+    data_dir: &Path,
+    config: &Path,
+    active: Option<&str>,
+) -> TaskitResult<Vec<DeltaItem>> {
+    match action {
+        ProfileAction::List => {
+            let names = names(data_dir);
+            if names.is_empty() {
+                println!("No profiles yet. Create one with `taskit profile create <name>`.");
+            }
+            for name in names {
+                let marker = if active == Some(name.as_str()) { "*" } else { " " };
+                let locked = if has_passphrase(&profile_dir(data_dir, &name)) { " (passphrase)" } else { "" };
+                println!("{marker} {name}{locked}");
+            }
+        }
+        ProfileAction::Create { name } => {
+            if !valid_name(&name) {
+                return Err(Kind::InvalidProfileName(name).with(Source::ManagingProfiles));
+            }
+            let dir = profile_dir(data_dir, &name);
+            if dir.exists() {
+                return Err(Kind::DuplicateProfile(name).with(Source::ManagingProfiles));
+            }
+            fs::create_dir_all(&dir).with(Source::ManagingProfiles)?;
+            if config.exists() {
+                fs::copy(config, config_path(&dir)).with(Source::ManagingProfiles)?;
+            }
+            println!("Created profile {name}, with its config at {}.", config_path(&dir).display());
+            println!("Use it with `taskit --profile {name} <command>`.");
+        }
+        ProfileAction::Delete { name } => {
+            let dir = profile_dir(data_dir, &name);
+            if !valid_name(&name) || !dir.is_dir() {
+                return Err(Kind::NoSuchProfile(name).with(Source::ManagingProfiles));
+            }
+            if active == Some(name.as_str()) {
+                return Err(Kind::ProfileInUse(name).with(Source::ManagingProfiles));
+            }
+            unlock(&dir, &name)?;
+            if !Confirm::new(&format!("Delete profile {name} with its save file, config, and history?"))
+                .with_default(false)
+                .prompt()
+                .with(Source::ManagingProfiles)?
+            {
+                return Err(Kind::Cancelled.with(Source::ManagingProfiles));
+            }
+            fs::remove_dir_all(&dir).with(Source::ManagingProfiles)?;
+            println!("Deleted profile {name}.");
+        }
+        ProfileAction::SetPassphrase { name } => {
+            let dir = profile_dir(data_dir, &name);
+            if !valid_name(&name) || !dir.is_dir() {
+                return Err(Kind::NoSuchProfile(name).with(Source::ManagingProfiles));
+            }
+            // the profile in use was unlocked on the way in
+            if active != Some(name.as_str()) {
+                unlock(&dir, &name)?;
+            }
+            let passphrase = Password::new("New passphrase:")
+                .with_validator(inquire::required!("The passphrase can't be empty."))
+                .prompt()
+                .with(Source::ManagingProfiles)?;
+            set_passphrase(&dir, &passphrase, ITERATIONS)?;
+            println!("Profile {name} now asks for its passphrase before it's used.");
+        }
+        ProfileAction::RemovePassphrase { name } => {
+            let dir = profile_dir(data_dir, &name);
+            if !valid_name(&name) || !dir.is_dir() {
+                return Err(Kind::NoSuchProfile(name).with(Source::ManagingProfiles));
+            }
+            if !has_passphrase(&dir) {
+                println!("Profile {name} has no passphrase.");
+                return Ok(vec![]);
+            }
+            if active != Some(name.as_str()) {
+                unlock(&dir, &name)?;
+            }
+            fs::remove_file(lock_path(&dir)).with(Source::ManagingProfiles)?;
+            println!("Profile {name} no longer asks for a passphrase.");
+        }
+    }
+    Ok(vec![])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    #[test]
+    fn passphrase_is_checked() {
+        let dir = env::temp_dir().join(format!("taskit-profile-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert!(check_passphrase(&dir, "anything").unwrap());
+        // few rounds, so the test doesn't take seconds
+        set_passphrase(&dir, "correct horse", 10).unwrap();
+        assert!(has_passphrase(&dir));
+        assert!(check_passphrase(&dir, "correct horse").unwrap());
+        assert!(!check_passphrase(&dir, "Correct horse").unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,345 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    iter,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::LazyLock,
+    thread,
+    time::Duration,
+};
+
+use chrono::{Local, NaiveDateTime};
+use inquire::Confirm;
+use itertools::Itertools;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{
+        DeltaItem, Event, SaveData,
+        config::CONFIG,
+        error::{Kind, Source, TaskitResult, With},
+    },
+    export::clock_time,
+    report::print_table,
+};
+
+/// Used when `body` isn't set under `[push]`
+const DEFAULT_BODY: &str = r#"{"id": {id}, "date": "{date}", "start": "{start}", "end": "{end}", "minutes": {minutes}, "category": "{category}", "description": "{description}", "tags": "{tags}"}"#;
+
+/// Used when `retries` isn't set under `[push]`
+const DEFAULT_RETRIES: u32 = 2;
+
+/// A `{name}` or `{fields.name}` placeholder. Anything else in braces, like the braces of a JSON
+/// body, is left alone.
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{([a-z_]+(?:\.[^{}\s]+)?)\}").expect("the placeholder pattern is valid"));
+
+/// A push that failed, kept in the outbox until it goes through
+#[derive(Serialize, Deserialize)]
+struct Queued {
+    /// The event as it was when it was added, for `taskit push` to list
+    event: String,
+    /// The value of each placeholder for the event, apart from `{token}`, which is filled in from
+    /// the config when the push is tried so that it isn't written to disk
+    values: BTreeMap<String, String>,
+    queued: NaiveDateTime,
+    attempts: u32,
+    /// Why the last attempt failed
+    error: String,
+}
+
+/// Where pushes that failed wait to be tried again. It sits next to the save file, rather than in
+/// it, so that undoing a change can't bring back a push that has since gone through.
+pub fn outbox_path(save_path: &Path) -> PathBuf {
+    save_path.with_extension("outbox.json")
+}
+
+fn read_outbox(path: &Path) -> TaskitResult<Vec<Queued>> {
+    match fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json).map_err(|e| Kind::Other(e.into()).with(Source::Pushing)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e.with(Source::Pushing).into()),
+    }
+}
+
+/// Writes what's left in the outbox, removing the file once it's empty
+fn write_outbox(path: &Path, queue: &[Queued]) -> TaskitResult<()> {
+    if queue.is_empty() {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.with(Source::Pushing).into()),
+            _ => Ok(()),
+        };
+    }
+    let json = serde_json::to_string(queue).map_err(|e| Kind::Other(e.into()).with(Source::Pushing))?;
+    fs::write(path, json).with(Source::Pushing)?;
+    Ok(())
+}
+
+/// The value of every placeholder for an event, apart from `{token}`
+fn values(ev: &Event) -> BTreeMap<String, String> {
+    [
+        ("id", ev.id.to_string()),
+        ("date", ev.date.to_string()),
+        ("start", clock_time(ev.start_time)),
+        ("end", clock_time(ev.end_time)),
+        ("minutes", ev.duration().num_minutes().to_string()),
+        ("hours", format!("{:.2}", ev.duration().num_seconds() as f64 / 3600.0)),
+        ("category", ev.category.own()),
+        ("description", ev.description.clone()),
+        ("tags", ev.tags.iter().map(|t| t.inner()).sorted().join(",")),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_owned(), value))
+    .chain(ev.fields.iter().map(|(name, value)| (format!("fields.{name}"), value.to_string())))
+    .collect()
+}
+
+/// The value of a placeholder, or None if there's no such placeholder. Custom fields the event
+/// has no value for are empty.
+fn placeholder(name: &str, values: &BTreeMap<String, String>) -> Option<String> {
+    match name {
+        "token" => Some(CONFIG.push.token.clone().unwrap_or_default()),
+        _ if name.starts_with("fields.") => Some(values.get(name).cloned().unwrap_or_default()),
+        _ => values.get(name).cloned(),
+    }
+}
+
+/// Fills in a template's placeholders, each passed through `escape` so it can't break the
+/// surrounding URL or JSON
+fn render(template: &str, values: &BTreeMap<String, String>, escape: impl Fn(&str) -> String) -> TaskitResult<String> {
+    if let Some(unknown) = PLACEHOLDER.captures_iter(template).find(|c| placeholder(&c[1], values).is_none()) {
+        return Err(Kind::InvalidTemplate(unknown[0].to_owned()).with(Source::Pushing));
+    }
+    Ok(PLACEHOLDER
+        .replace_all(template, |c: &Captures| escape(&placeholder(&c[1], values).expect("checked above")))
+        .into_owned())
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Escapes text to go inside a JSON string, without the quotes
+fn json_escape(s: &str) -> String {
+    let quoted = serde_json::to_string(s).expect("strings always serialize");
+    quoted[1..quoted.len() - 1].to_owned()
+}
+
+/// Whether an event should be pushed, going by `categories` under `[push]`. Events in private
+/// categories are never pushed.
+fn should_push(ev: &Event, private: bool) -> bool {
+    let categories = &CONFIG.push.categories;
+    CONFIG.push.url.is_some() && !private && (categories.is_empty() || categories.iter().any(|c| c == ev.category.inner()))
+}
+
+/// The events that `undo` deletes, which are the ones the change it undoes added, that should be
+/// pushed, oldest first
+pub fn added_events(save_data: &SaveData, undo: &[DeltaItem]) -> Vec<Event> {
+    let mut added: Vec<Event> = undo
+        .iter()
+        .filter_map(|item| match item {
+            DeltaItem::DeleteEvent(id) => save_data.events.iter().find(|ev| ev.id == *id),
+            _ => None,
+        })
+        .filter(|ev| should_push(ev, save_data.private_categories.contains(&ev.category)))
+        .cloned()
+        .collect();
+    added.sort_by_key(|ev| ev.id);
+    added
+}
+
+/// Sends one request for an event with curl, trying again after a pause if it fails, up to
+/// `retries` more times
+fn send(values: &BTreeMap<String, String>, retries: u32) -> TaskitResult<()> {
+    let settings = &CONFIG.push;
+    let Some(url) = &settings.url else {
+        return Err(Kind::PushNotConfigured.with(Source::Pushing));
+    };
+    let url = render(url, values, percent_encode)?;
+    let body = render(settings.body.as_deref().unwrap_or(DEFAULT_BODY), values, json_escape)?;
+    let mut headers = settings
+        .headers
+        .iter()
+        .map(|(name, value)| {
+            let header = format!("{name}: {}", render(value, values, str::to_owned)?);
+            if header.chars().any(char::is_control) {
+                return Err(Kind::InvalidHeader(name.clone()).with(Source::Pushing));
+            }
+            Ok(header)
+        })
+        .collect::<TaskitResult<Vec<String>>>()?;
+    if !settings.headers.keys().any(|name| name.eq_ignore_ascii_case("content-type")) {
+        headers.push("Content-Type: application/json".to_owned());
+    }
+
+    let mut attempt = 0;
+    loop {
+        match curl(&url, settings.method.as_deref().unwrap_or("POST"), &headers, &body) {
+            Ok(()) => return Ok(()),
+            Err(_) if attempt < retries => {
+                attempt += 1;
+                thread::sleep(Duration::from_secs(attempt.into()));
+            }
+            Err(e) => return Err(e.with(Source::Pushing).into()),
+        }
+    }
+}
+
+/// Runs curl with the request's URL, headers, and body in a config file read from its stdin, since
+/// anything in its arguments can be read by other users through `ps`, and they can hold the token
+fn curl(url: &str, method: &str, headers: &[String], body: &str) -> io::Result<()> {
+    let mut config = format!("request = {}\nurl = {}\n", curl_quote(method), curl_quote(url));
+    for header in headers {
+        config += &format!("header = {}\n", curl_quote(header));
+    }
+    // unlike data-binary, data-raw doesn't read a body starting with @ as a file name
+    config += &format!("data-raw = {}\n", curl_quote(body));
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail-with-body", "--connect-timeout", "10", "--max-time", "30"])
+        .args(["--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("stdin was piped")
+        .write_all(config.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_owned()))
+    }
+}
+
+/// Quotes a value for a curl config file, where backslashes escape the next character
+fn curl_quote(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Tries the queued pushes again in order, each up to `retries` more times, returning how many went
+/// through. Stops at the first one that fails, as the endpoint is most likely still unreachable,
+/// leaving the rest queued.
+fn retry_queue(queue: &mut Vec<Queued>, retries: u32) -> usize {
+    let mut pushed = 0;
+    for queued in queue.iter_mut() {
+        match send(&queued.values, retries) {
+            Ok(()) => pushed += 1,
+            Err(e) => {
+                queued.attempts += 1;
+                queued.error = e.to_string();
+                break;
+            }
+        }
+    }
+    queue.drain(..pushed);
+    pushed
+}
+
+/// Pushes newly added events, after trying anything left in the outbox by earlier runs once each,
+/// without the pauses between retries, so an endpoint that's down doesn't hold up every command.
+/// Pushes that fail are queued in the outbox, so problems are only ever warned about. New events
+/// wait behind queued ones that still fail, so that they're sent in the order they were added.
+pub fn push_events(events: &[Event], outbox: &Path) {
+    let mut queue = match read_outbox(outbox) {
+        Ok(queue) => queue,
+        Err(e) => {
+            eprintln!("Warning: the push outbox couldn't be read, so no events were pushed: {e}");
+            return;
+        }
+    };
+    if CONFIG.push.url.is_none() || (queue.is_empty() && events.is_empty()) {
+        return;
+    }
+    let queued = queue.len();
+    let pushed = retry_queue(&mut queue, 0);
+    if pushed > 0 {
+        println!("Pushed {pushed} of {queued} queued event(s).");
+    }
+    let now = Local::now().naive_local();
+    let mut failed = 0;
+    for ev in events {
+        let values = values(ev);
+        let error = if queue.is_empty() {
+            match send(&values, CONFIG.push.retries.unwrap_or(DEFAULT_RETRIES)) {
+                Ok(()) => continue,
+                Err(e) => e.to_string(),
+            }
+        } else {
+            "waiting for earlier pushes to go through".to_owned()
+        };
+        let attempts = if queue.is_empty() { 1 } else { 0 };
+        queue.push(Queued { event: ev.to_string(), values, queued: now, attempts, error });
+        failed += 1;
+    }
+    if let Err(e) = write_outbox(outbox, &queue) {
+        eprintln!("Warning: the push outbox couldn't be updated: {e}");
+    }
+    if failed > 0 {
+        eprintln!("Warning: {failed} event(s) couldn't be pushed and will be tried again next time; see `taskit push`.");
+    }
+}
+
+/// Lists the pushes waiting in the outbox, after trying them again with `retry`, or drops them
+/// with `clear`
+pub fn push_main(_save_data: SaveData, outbox: &Path, retry: bool, clear: bool) -> TaskitResult<Vec<DeltaItem>> {
+    let mut queue = read_outbox(outbox)?;
+    if queue.is_empty() {
+        println!("Nothing is waiting to be pushed.");
+        return Ok(vec![]);
+    }
+    if clear {
+        let message = format!("Drop {} queued push(es)? Their events won't be sent.", queue.len());
+        if Confirm::new(&message).with_default(false).prompt().with(Source::Pushing)? {
+            write_outbox(outbox, &[])?;
+        }
+        return Ok(vec![]);
+    }
+    if retry {
+        if CONFIG.push.url.is_none() {
+            return Err(Kind::PushNotConfigured.with(Source::Pushing));
+        }
+        let queued = queue.len();
+        let pushed = retry_queue(&mut queue, CONFIG.push.retries.unwrap_or(DEFAULT_RETRIES));
+        write_outbox(outbox, &queue)?;
+        println!("Pushed {pushed} of {queued} queued event(s).");
+        if queue.is_empty() {
+            return Ok(vec![]);
+        }
+        println!();
+    }
+    let rows: Vec<Vec<String>> = iter::once(["Queued", "Event", "Attempts", "Last error"].map(str::to_owned).to_vec())
+        .chain(queue.iter().map(|queued| {
+            vec![
+                queued.queued.format("%Y-%m-%d %H:%M").to_string(),
+                queued.event.clone(),
+                queued.attempts.to_string(),
+                queued.error.clone(),
+            ]
+        }))
+        .collect();
+    print_table(&rows);
+    Ok(vec![])
+}
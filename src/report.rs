@@ -0,0 +1,1375 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs, io, iter,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::{Datelike, Days, Local, NaiveDate, NaiveDateTime, TimeDelta};
+use clap::ValueEnum;
+use inquire::{Confirm, MultiSelect, Select, Text, validator::Validation};
+use itertools::Itertools;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    common::{
+        DeltaItem, Duration, Event, FieldValue, Goal, ReportFormat, ReportGrouping, ReportSchedule, ReportSpec, SaveData,
+        config::{CONFIG, FieldKind},
+        error::{Kind, Source, TaskitResult, With},
+        invariants::{Category, Tag},
+    },
+    export::{ExportFormat, render_report, write_document},
+    util::{DateRange, display_date, pad_to_width},
+};
+
+/// Like Duration's Display, but shows zero durations as `0m` so table cells are never blank
+pub fn format_duration(duration: &Duration) -> String {
+    let s = duration.to_string();
+    if s.is_empty() { "0m".to_owned() } else { s }
+}
+
+/// Like format_duration, but always with a sign, for differences from a target
+pub fn format_signed_duration(duration: &Duration) -> String {
+    if *duration < Duration::ZERO {
+        format!("-{}", format_duration(&-*duration))
+    } else {
+        format!("+{}", format_duration(duration))
+    }
+}
+
+/// A bar `width` characters wide, filled in proportion to `fraction` (which is capped at 1)
+pub fn progress_bar(fraction: f64, width: usize) -> String {
+    let filled = ((fraction.clamp(0.0, 1.0) * width as f64).round() as usize).min(width);
+    format!("{}{}", "\u{2588}".repeat(filled), "\u{2591}".repeat(width - filled))
+}
+
+/// Every goal with the time put towards it during `week`, categories first, each sorted by name.
+/// Tags count the time of events that have them directly or through their category.
+pub fn goal_progress<'a>(
+    events: impl Iterator<Item = &'a Event> + Clone,
+    tag_map: &HashMap<Category, HashSet<Tag>>,
+    category_goals: &HashMap<Category, Goal>,
+    tag_goals: &HashMap<Tag, Goal>,
+    week: DateRange,
+) -> Vec<(String, Goal, Duration)> {
+    let events = events.filter(|ev| week.contains(ev.date));
+    let categories = category_goals.iter().sorted_by_key(|(category, _)| category.inner()).map(|(category, goal)| {
+        let time = events.clone().filter(|ev| &ev.category == category).map(Event::duration).sum();
+        (category.own(), *goal, time)
+    });
+    let tags = tag_goals.iter().sorted_by_key(|(tag, _)| tag.inner()).map(|(tag, goal)| {
+        let time = events
+            .clone()
+            .filter(|ev| ev.tags.contains(tag) || tag_map.get(&ev.category).is_some_and(|tags| tags.contains(tag)))
+            .map(Event::duration)
+            .sum();
+        (format!("#{}", tag.inner()), *goal, time)
+    });
+    categories.chain(tags).collect()
+}
+
+/// Prints rows as a table with left-aligned columns separated by two spaces
+pub fn print_table(rows: &[Vec<String>]) {
+    print!("{}", format_table(rows));
+}
+
+/// Lays rows out like print_table, one line each
+pub fn format_table(rows: &[Vec<String>]) -> String {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| {
+            rows.iter()
+                .filter_map(|row| row.get(i))
+                .map(|cell| cell.width())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut table = String::new();
+    for row in rows {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| pad_to_width(cell, width))
+            .collect();
+        table.push_str(line.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+/// The pinned events among `events`, in the order they happened, as a section to follow a report's
+/// tables. Empty if none of them are pinned.
+fn pinned_section(events: &[&Event]) -> String {
+    let pinned: Vec<String> = events
+        .iter()
+        .filter(|ev| ev.pinned)
+        .sorted_by_key(|ev| (ev.date, ev.start_time))
+        .map(|ev| format!("  {ev}\n"))
+        .collect();
+    if pinned.is_empty() {
+        return String::new();
+    }
+    format!("\nPinned:\n{}", pinned.concat())
+}
+
+/// Total tracked time per day
+pub fn daily_totals<'a>(events: impl Iterator<Item = &'a Event>) -> BTreeMap<NaiveDate, Duration> {
+    events.fold(BTreeMap::new(), |mut map, ev| {
+        *map.entry(ev.date).or_insert(Duration::ZERO) += ev.duration();
+        map
+    })
+}
+
+/// Total tracked time per category, largest first
+pub fn category_totals<'a>(events: impl Iterator<Item = &'a Event>) -> Vec<(String, Duration)> {
+    let totals = events.fold(BTreeMap::new(), |mut map, ev| {
+        *map.entry(ev.category.own()).or_insert(Duration::ZERO) += ev.duration();
+        map
+    });
+    let mut totals: Vec<_> = totals.into_iter().collect();
+    totals.sort_by_key(|(_, d)| -*d);
+    totals
+}
+
+/// Finds events that are probably mistakes: zero-length or implausibly long events, and events that
+/// overlap an earlier one on the same day. Returns a description of each problem, in date order.
+pub fn event_problems<'a>(events: impl Iterator<Item = &'a Event>) -> Vec<(NaiveDate, String)> {
+    let mut events: Vec<&Event> = events.collect();
+    events.sort_by_key(|ev| (ev.date, ev.start_time));
+    let mut problems = vec![];
+    for (i, ev) in events.iter().enumerate() {
+        if ev.duration().is_zero() {
+            problems.push((ev.date, format!("zero-length event: {ev}")));
+        } else if ev.duration() > Duration::hours(12) {
+            problems.push((ev.date, format!("{} long event: {ev}", format_duration(&ev.duration()))));
+        }
+        if let Some(earlier) = events[..i]
+            .iter()
+            .rev()
+            .take_while(|other| other.date == ev.date)
+            .find(|other| other.covers(ev.start_time))
+        {
+            problems.push((ev.date, format!("overlaps an earlier event: {ev} / {earlier}")));
+        }
+    }
+    problems
+}
+
+/// Tracked minutes in each hour of the day. Events running past midnight carry on from hour 0.
+pub fn minutes_by_hour<'a>(events: impl Iterator<Item = &'a Event>) -> [i64; 24] {
+    let mut hours = [0; 24];
+    for ev in events {
+        let mut minute = ev.start_time.hour as i64 * 60 + ev.start_time.minute as i64;
+        let end = minute + ev.duration().num_minutes();
+        while minute < end {
+            let chunk = ((minute / 60 + 1) * 60).min(end) - minute;
+            hours[(minute / 60 % 24) as usize] += chunk;
+            minute += chunk;
+        }
+    }
+    hours
+}
+
+/// Average time per day over the `days` days ending on `end` (inclusive). Skipped days without any
+/// tracked time don't count towards the number of days.
+pub fn rolling_average(
+    totals: &BTreeMap<NaiveDate, Duration>,
+    skipped: &BTreeMap<NaiveDate, String>,
+    end: NaiveDate,
+    days: u32,
+) -> Duration {
+    let start = end - Days::new(days as u64 - 1);
+    let skipped = skipped.keys().filter(|date| (start..=end).contains(*date) && !totals.contains_key(*date));
+    let days = days as i64 - skipped.count() as i64;
+    if days <= 0 {
+        return Duration::ZERO;
+    }
+    totals.range(start..=end).map(|(_, d)| *d).sum::<Duration>() / days
+}
+
+/// The number of consecutive days with tracked time up to today (or up to yesterday, if nothing has
+/// been tracked today yet). Skipped days don't count, but don't break the streak either.
+pub fn streak(
+    totals: &BTreeMap<NaiveDate, Duration>,
+    skipped: &BTreeMap<NaiveDate, String>,
+    today: NaiveDate,
+) -> u32 {
+    let mut date = if totals.contains_key(&today) { today } else { today - Days::new(1) };
+    let mut streak = 0;
+    loop {
+        if totals.contains_key(&date) {
+            streak += 1;
+        } else if !skipped.contains_key(&date) {
+            return streak;
+        }
+        date = date - Days::new(1);
+    }
+}
+
+pub fn stats_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
+    let today = Local::now().date_naive();
+    let windows = [7, 30];
+    let aggregation = &CONFIG.aggregation;
+
+    let mut rows: Vec<Vec<String>> = vec![
+        iter::once("Average per day".to_owned())
+            .chain(windows.iter().map(|w| format!("{w} days")))
+            .collect(),
+    ];
+    let all = daily_totals(
+        save_data
+            .events
+            .iter()
+            .filter(|ev| !aggregation.excludes(ev, save_data.tag_map.get(&ev.category))),
+    );
+    rows.push(
+        iter::once("all".to_owned())
+            .chain(
+                windows
+                    .iter()
+                    .map(|&w| format_duration(&rolling_average(&all, &save_data.skipped_days, today, w))),
+            )
+            .collect(),
+    );
+    for category in save_data.categories.iter().chain(save_data.archived_categories.iter()) {
+        let totals = daily_totals(save_data.events.iter().filter(|ev| &ev.category == category));
+        let averages: Vec<Duration> = windows
+            .iter()
+            .map(|&w| rolling_average(&totals, &save_data.skipped_days, today, w))
+            .collect();
+        // archived categories are only worth listing if they were used recently
+        if save_data.archived_categories.contains(category)
+            && averages.iter().all(|d| d.is_zero())
+        {
+            continue;
+        }
+        rows.push(
+            iter::once(category.own())
+                .chain(averages.iter().map(format_duration))
+                .collect(),
+        );
+    }
+    print_table(&rows);
+    let streak = streak(&all, &save_data.skipped_days, today);
+    println!("\nCurrent streak: {streak} day{}", if streak == 1 { "" } else { "s" });
+
+    // when in the day each category's time goes, in quarters of the day
+    let quarters = ["00-06", "06-12", "12-18", "18-24"];
+    let mut rows: Vec<Vec<String>> = vec![
+        ["Category", "Events", "Average length"]
+            .into_iter()
+            .chain(quarters)
+            .map(str::to_owned)
+            .collect(),
+    ];
+    for category in save_data.categories.iter().chain(save_data.archived_categories.iter()) {
+        let events: Vec<&Event> = save_data.events.iter().filter(|ev| &ev.category == category).collect();
+        if events.is_empty() {
+            continue;
+        }
+        let total: Duration = events.iter().map(|ev| ev.duration()).sum();
+        let hours = minutes_by_hour(events.iter().copied());
+        let minutes: i64 = hours.iter().sum();
+        rows.push(
+            [category.own(), events.len().to_string(), format_duration(&(total / events.len() as i64))]
+                .into_iter()
+                .chain(hours.chunks(6).map(|quarter| {
+                    let share = if minutes == 0 { 0 } else { quarter.iter().sum::<i64>() * 100 / minutes };
+                    format!("{share}%")
+                }))
+                .collect(),
+        );
+    }
+    if rows.len() > 1 {
+        println!();
+        print_table(&rows);
+    }
+    Ok(vec![])
+}
+
+/// Tracked time by hour of the day, as a bar per hour. With `by_category`, shades each category's
+/// hours instead, relative to that category's busiest hour, so they can be compared side by side.
+pub fn heat_main(save_data: SaveData, range: Option<DateRange>, by_category: bool) -> TaskitResult<Vec<DeltaItem>> {
+    let events: Vec<&Event> = save_data
+        .events
+        .iter()
+        .filter(|ev| range.is_none_or(|range| range.contains(ev.date)))
+        .collect();
+    if events.is_empty() {
+        println!("No events to profile.");
+        return Ok(vec![]);
+    }
+    let mut rows = vec![];
+    if by_category {
+        const SHADES: [char; 5] = [' ', '\u{2591}', '\u{2592}', '\u{2593}', '\u{2588}'];
+        let profiles: Vec<(String, [i64; 24])> = category_totals(events.iter().copied())
+            .into_iter()
+            .map(|(category, _)| {
+                let hours = minutes_by_hour(events.iter().copied().filter(|ev| ev.category.inner() == category));
+                (category, hours)
+            })
+            .collect();
+        rows.push(iter::once("Hour".to_owned()).chain(profiles.iter().map(|(c, _)| c.clone())).collect());
+        for hour in 0..24 {
+            let cells = profiles.iter().map(|(category, hours)| {
+                let busiest = hours.iter().max().copied().unwrap_or(0).max(1);
+                // anything at all gets at least the lightest shade
+                let shade = ((hours[hour] * 4 + busiest - 1) / busiest) as usize;
+                SHADES[shade].to_string().repeat(category.width().min(8))
+            });
+            rows.push(iter::once(format!("{hour:02}")).chain(cells).collect());
+        }
+    } else {
+        let aggregation = &CONFIG.aggregation;
+        let hours = minutes_by_hour(
+            events
+                .iter()
+                .copied()
+                .filter(|ev| !aggregation.excludes(ev, save_data.tag_map.get(&ev.category))),
+        );
+        let busiest = hours.iter().max().copied().unwrap_or(0).max(1);
+        rows.push(vec!["Hour".to_owned(), "Time".to_owned(), String::new()]);
+        for (hour, minutes) in hours.iter().enumerate() {
+            rows.push(vec![
+                format!("{hour:02}"),
+                format_duration(&Duration::minutes(*minutes)),
+                "\u{2588}".repeat((minutes * 40 / busiest) as usize),
+            ]);
+        }
+    }
+    print_table(&rows);
+    Ok(vec![])
+}
+
+/// Time spent per value of a custom field. Number fields are summarized instead, since they rarely
+/// repeat exactly.
+pub fn field_report_main(
+    save_data: SaveData,
+    name: String,
+    range: Option<DateRange>,
+) -> TaskitResult<Vec<DeltaItem>> {
+    let Some(field) = CONFIG.field(&name) else {
+        return Err(Kind::NoSuchField(name).with(Source::Reporting));
+    };
+    let events: Vec<&Event> = save_data
+        .events
+        .iter()
+        .filter(|ev| range.is_none_or(|range| range.contains(ev.date)))
+        .collect();
+    let values: Vec<(&Event, &FieldValue)> = events
+        .iter()
+        .filter_map(|ev| ev.fields.get(&name).map(|v| (*ev, v)))
+        .collect();
+    if values.is_empty() {
+        println!("No events have {name} set.");
+        return Ok(vec![]);
+    }
+
+    if field.kind == FieldKind::Number {
+        let numbers: Vec<(String, f64, Duration)> = values
+            .iter()
+            .filter_map(|(ev, v)| match v {
+                FieldValue::Number(n) => Some((ev.category.own(), *n, ev.duration())),
+                _ => None,
+            })
+            .collect();
+        let mut rows = vec![vec![
+            String::new(),
+            "Events".to_owned(),
+            "Average".to_owned(),
+            "Weighted by time".to_owned(),
+            "Min".to_owned(),
+            "Max".to_owned(),
+        ]];
+        rows.push(number_summary("all", numbers.iter().map(|(_, n, d)| (*n, *d))));
+        // comparing categories is the point for things like mood, e.g. meetings against coding
+        let mut by_category: BTreeMap<&str, Vec<(f64, Duration)>> = BTreeMap::new();
+        for (category, n, d) in &numbers {
+            by_category.entry(category).or_default().push((*n, *d));
+        }
+        for (category, numbers) in by_category {
+            rows.push(number_summary(category, numbers.into_iter()));
+        }
+        print_table(&rows);
+    } else {
+        #[derive(Default)]
+        struct ValueTotal {
+            events: usize,
+            days: HashSet<NaiveDate>,
+            time: Duration,
+        }
+        let mut totals: BTreeMap<String, ValueTotal> = BTreeMap::new();
+        for (ev, value) in &values {
+            let total = totals.entry(value.to_string()).or_default();
+            total.events += 1;
+            total.days.insert(ev.date);
+            total.time += ev.duration();
+        }
+        let unset: Duration = events
+            .iter()
+            .filter(|ev| !ev.fields.contains_key(&name))
+            .map(|ev| ev.duration())
+            .sum();
+        // a day counts towards every value it has, so days can add up to more than the range
+        let mut rows = vec![vec![name.clone(), "Events".to_owned(), "Days".to_owned(), "Time".to_owned()]];
+        for (value, total) in totals.iter().sorted_by_key(|(_, total)| -total.time) {
+            rows.push(vec![
+                value.clone(),
+                total.events.to_string(),
+                total.days.len().to_string(),
+                format_duration(&total.time),
+            ]);
+        }
+        if !unset.is_zero() {
+            rows.push(vec!["(not set)".to_owned(), String::new(), String::new(), format_duration(&unset)]);
+        }
+        print_table(&rows);
+    }
+    Ok(vec![])
+}
+
+/// Money spent alongside an event, recorded in the custom fields named under `[expenses]`
+pub struct Expense<'a> {
+    pub event: &'a Event,
+    pub amount: f64,
+    pub receipt: Option<&'a str>,
+}
+
+/// The expenses recorded against these events, in the same order. Errors if the config doesn't say
+/// which fields hold them.
+pub fn event_expenses<'a>(events: impl IntoIterator<Item = &'a Event>) -> TaskitResult<Vec<Expense<'a>>> {
+    let settings = &CONFIG.expenses;
+    let Some(amount_field) = &settings.amount_field else {
+        return Err(Kind::ExpensesNotConfigured.with(Source::Reporting));
+    };
+    for name in iter::once(amount_field).chain(&settings.receipt_field) {
+        if CONFIG.field(name).is_none() {
+            return Err(Kind::NoSuchField(name.clone()).with(Source::Reporting));
+        }
+    }
+    Ok(events
+        .into_iter()
+        .filter_map(|event| match event.fields.get(amount_field) {
+            Some(FieldValue::Number(amount)) if *amount != 0.0 => Some(Expense {
+                event,
+                amount: *amount,
+                receipt: settings.receipt_field.as_ref().and_then(|field| match event.fields.get(field) {
+                    Some(FieldValue::String(path)) if !path.is_empty() => Some(path.as_str()),
+                    _ => None,
+                }),
+            }),
+            _ => None,
+        })
+        .collect())
+}
+
+pub fn expenses_main(save_data: SaveData, range: Option<DateRange>) -> TaskitResult<Vec<DeltaItem>> {
+    let events: Vec<&Event> = save_data
+        .events
+        .iter()
+        .filter(|ev| range.is_none_or(|range| range.contains(ev.date)))
+        .sorted_by_key(|ev| (ev.date, ev.start_time))
+        .collect();
+    let expenses = event_expenses(events)?;
+    if expenses.is_empty() {
+        println!("No events have an expense recorded.");
+        return Ok(vec![]);
+    }
+    let mut rows = vec![vec![
+        "Date".to_owned(),
+        "Category".to_owned(),
+        "Notes".to_owned(),
+        "Amount".to_owned(),
+        "Receipt".to_owned(),
+    ]];
+    let mut by_category: BTreeMap<&str, (usize, f64)> = BTreeMap::new();
+    let mut missing_receipts = 0;
+    for expense in &expenses {
+        let receipt = match expense.receipt {
+            Some(path) if Path::new(path).exists() => path.to_owned(),
+            Some(path) => format!("{path} (not found)"),
+            None => {
+                missing_receipts += 1;
+                "-".to_owned()
+            }
+        };
+        rows.push(vec![
+            expense.event.date.to_string(),
+            expense.event.category.own(),
+            expense.event.description.lines().next().unwrap_or_default().to_owned(),
+            format!("{:.2}", expense.amount),
+            receipt,
+        ]);
+        let total = by_category.entry(expense.event.category.inner()).or_default();
+        total.0 += 1;
+        total.1 += expense.amount;
+    }
+    print_table(&rows);
+
+    println!();
+    let mut rows = vec![vec!["Category".to_owned(), "Expenses".to_owned(), "Reimbursable".to_owned()]];
+    for (category, (count, amount)) in &by_category {
+        rows.push(vec![category.to_string(), count.to_string(), format!("{amount:.2}")]);
+    }
+    rows.push(vec![
+        "total".to_owned(),
+        expenses.len().to_string(),
+        format!("{:.2}", expenses.iter().map(|e| e.amount).sum::<f64>()),
+    ]);
+    print_table(&rows);
+    if missing_receipts > 0 && CONFIG.expenses.receipt_field.is_some() {
+        println!("\n{missing_receipts} expense(s) have no receipt.");
+    }
+    Ok(vec![])
+}
+
+/// Choices for `taskit report` that can also be saved, given on the command line. Any that are
+/// given override the ones in a saved report.
+#[derive(clap::Args, Debug)]
+pub struct ReportOptions {
+    /// Only include events in this range (see `taskit share --help` for the accepted forms)
+    #[arg(long, value_parser = parse_range)]
+    range: Option<String>,
+    /// What to total time by [default: category]
+    #[arg(long, value_enum)]
+    group_by: Option<ReportGrouping>,
+    /// Only include events in this category (can be given multiple times)
+    #[arg(long = "category")]
+    categories: Vec<String>,
+    /// Only include events with this tag, directly or through their category (can be given
+    /// multiple times)
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+    /// Only include events whose description contains this text, ignoring case
+    #[arg(long)]
+    contains: Option<String>,
+    /// Include events in private categories
+    #[arg(long)]
+    include_private: bool,
+    /// [default: table]
+    #[arg(long, value_enum)]
+    format: Option<ReportFormat>,
+    /// Run the report by itself when saved, like `daily 18:00`, `weekly mon 08:00`, or
+    /// `monthly 1 09:00` (see --run-scheduled)
+    #[arg(long)]
+    schedule: Option<ReportSchedule>,
+    /// Stop running a saved report by itself
+    #[arg(long, conflicts_with = "schedule")]
+    unschedule: bool,
+}
+
+/// Checks that a range can be parsed, but keeps it as typed so that saved reports with relative
+/// ranges like `this-week` stay relative
+fn parse_range(s: &str) -> Result<String, String> {
+    DateRange::from_str(s).map(|_| s.trim().to_owned())
+}
+
+impl ReportOptions {
+    fn apply_to(self, spec: &mut ReportSpec) {
+        if self.range.is_some() {
+            spec.range = self.range;
+        }
+        if let Some(group_by) = self.group_by {
+            spec.group_by = group_by;
+        }
+        if !self.categories.is_empty() {
+            spec.categories = self.categories;
+        }
+        if !self.tags.is_empty() {
+            spec.tags = self.tags.into_iter().map(|t| t.trim_start_matches('#').to_owned()).collect();
+        }
+        if self.contains.is_some() {
+            spec.contains = self.contains;
+        }
+        spec.include_private |= self.include_private;
+        if let Some(format) = self.format {
+            spec.format = format;
+        }
+        if self.schedule.is_some() || self.unschedule {
+            spec.schedule = self.schedule;
+        }
+    }
+}
+
+/// Fixed reports that don't need any options, run with `taskit report <kind>`
+#[derive(clap::Subcommand, Debug)]
+pub enum ReportKind {
+    /// A week's time per day and category, with totals per category, tag, and day
+    Week {
+        /// Which week to show: 0 for this week, -1 for last week, and so on
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        offset: i64,
+        /// Include events in private categories
+        #[arg(long)]
+        include_private: bool,
+    },
+    /// A month's total time, busiest days, and top categories and tags
+    Month {
+        /// The month to summarize (YYYY-MM)
+        #[arg(value_parser = parse_month)]
+        month: NaiveDate,
+        /// Include events in private categories
+        #[arg(long)]
+        include_private: bool,
+    },
+}
+
+/// Parses a `YYYY-MM` month into its first day
+fn parse_month(s: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(&format!("{}-01", s.trim()), "%Y-%m-%d").map_err(|e| format!("invalid month {s:?}: {e}"))
+}
+
+/// One group of events in a `taskit report`
+pub struct ReportRow {
+    pub group: String,
+    pub time: Duration,
+    /// Percentage of the time of all events in the report. Events can have several tags, so shares
+    /// of tags can add up to more than 100.
+    pub share: f64,
+    pub events: usize,
+}
+
+/// The events a report covers
+fn report_events<'a>(save_data: &'a SaveData, spec: &ReportSpec) -> TaskitResult<Vec<&'a Event>> {
+    let range = spec
+        .range
+        .as_deref()
+        .map(DateRange::from_str)
+        .transpose()
+        .map_err(|e| Kind::Other(e.into()).with(Source::Reporting))?;
+    let contains = spec.contains.as_ref().map(|s| s.to_lowercase());
+    Ok(save_data
+        .events
+        .iter()
+        .filter(|ev| range.is_none_or(|range| range.contains(ev.date)))
+        .filter(|ev| spec.include_private || !save_data.private_categories.contains(&ev.category))
+        .filter(|ev| spec.categories.is_empty() || spec.categories.iter().any(|c| c == ev.category.inner()))
+        .filter(|ev| {
+            spec.tags.is_empty()
+                || event_tags(save_data, ev).iter().any(|tag| spec.tags.iter().any(|t| t == tag.inner()))
+        })
+        .filter(|ev| contains.as_ref().is_none_or(|c| ev.description.to_lowercase().contains(c)))
+        .collect())
+}
+
+/// An event's own tags and its category's tags, in order
+fn event_tags<'a>(save_data: &'a SaveData, ev: &'a Event) -> BTreeSet<&'a Tag> {
+    ev.tags.iter().chain(save_data.tag_map.get(&ev.category).into_iter().flatten()).collect()
+}
+
+/// Totals events into rows by the report's grouping. Categories and tags are largest first, and
+/// periods are in chronological order.
+fn report_rows(save_data: &SaveData, events: &[&Event], group_by: ReportGrouping) -> Vec<ReportRow> {
+    let total: Duration = events.iter().map(|ev| ev.duration()).sum();
+    let mut groups: BTreeMap<String, (Duration, usize)> = BTreeMap::new();
+    for ev in events {
+        let keys = match group_by {
+            ReportGrouping::Category => vec![ev.category.own()],
+            ReportGrouping::Tag => {
+                let tags: Vec<String> = event_tags(save_data, ev).into_iter().map(|tag| tag.to_string()).collect();
+                if tags.is_empty() { vec!["(untagged)".to_owned()] } else { tags }
+            }
+            ReportGrouping::Day => vec![ev.date.format("%Y-%m-%d").to_string()],
+            ReportGrouping::Week => {
+                let week = ev.date.iso_week();
+                vec![format!("{}-W{:02}", week.year(), week.week())]
+            }
+            ReportGrouping::Month => vec![ev.date.format("%Y-%m").to_string()],
+        };
+        for key in keys {
+            let group = groups.entry(key).or_default();
+            group.0 += ev.duration();
+            group.1 += 1;
+        }
+    }
+    let mut rows: Vec<ReportRow> = groups
+        .into_iter()
+        .map(|(group, (time, events))| ReportRow { group, time, share: time.fraction_of(total) * 100.0, events })
+        .collect();
+    if matches!(group_by, ReportGrouping::Category | ReportGrouping::Tag) {
+        rows.sort_by_key(|row| -row.time);
+    }
+    rows
+}
+
+/// Runs a report, printing it or writing it to `out`
+fn run_report(save_data: &SaveData, spec: &ReportSpec, out: Option<PathBuf>) -> TaskitResult<()> {
+    match report_document(save_data, spec)? {
+        None => println!("No events match this report."),
+        Some(document) if spec.format == ReportFormat::Table => print!("{document}"),
+        Some(document) => write_document(&document, out, "the report")?,
+    }
+    Ok(())
+}
+
+/// The report in its format, or None if no events match it
+fn report_document(save_data: &SaveData, spec: &ReportSpec) -> TaskitResult<Option<String>> {
+    let events = report_events(save_data, spec)?;
+    if events.is_empty() {
+        return Ok(None);
+    }
+    let rows = report_rows(save_data, &events, spec.group_by);
+    let format = match spec.format {
+        ReportFormat::Table => {
+            let mut table = vec![vec![
+                capitalize(&spec.group_by.to_string()),
+                "Time".to_owned(),
+                "Share".to_owned(),
+                "Events".to_owned(),
+            ]];
+            table.extend(rows.iter().map(|row| {
+                // days are grouped by their ISO date so they sort, but read better with the weekday
+                let group = match NaiveDate::parse_from_str(&row.group, "%Y-%m-%d") {
+                    Ok(date) if spec.group_by == ReportGrouping::Day => display_date(date),
+                    _ => row.group.clone(),
+                };
+                vec![
+                    group,
+                    format_duration(&row.time),
+                    format!("{:.0}%", row.share),
+                    row.events.to_string(),
+                ]
+            }));
+            table.push(vec![
+                "total".to_owned(),
+                format_duration(&events.iter().map(|ev| ev.duration()).sum()),
+                String::new(),
+                events.len().to_string(),
+            ]);
+            return Ok(Some(format_table(&table) + &pinned_section(&events)));
+        }
+        ReportFormat::Csv => ExportFormat::Csv,
+        ReportFormat::Markdown => ExportFormat::Markdown,
+        ReportFormat::Jsonl => ExportFormat::Jsonl,
+    };
+    Ok(Some(render_report(&rows, spec.group_by, format)))
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+/// Asks for each of the report's choices in turn, starting from the ones in `spec`
+fn build_report(save_data: &SaveData, spec: &mut ReportSpec) -> TaskitResult<()> {
+    let range = Text::new("Range (leave empty for all time):")
+        .with_initial_value(spec.range.as_deref().unwrap_or_default())
+        .with_help_message("e.g. this-week, last-month, 2024, 2024-03, or 2024-03-01..2024-03-15")
+        .with_validator(|s: &str| {
+            Ok(match s.trim() {
+                "" => Validation::Valid,
+                s => match DateRange::from_str(s) {
+                    Ok(_) => Validation::Valid,
+                    Err(e) => Validation::Invalid(e.into()),
+                },
+            })
+        })
+        .prompt()
+        .with(Source::Reporting)?;
+    spec.range = Some(range.trim().to_owned()).filter(|r| !r.is_empty());
+
+    let groupings = ReportGrouping::value_variants();
+    spec.group_by = Select::new("Group by:", groupings.to_vec())
+        .with_starting_cursor(groupings.iter().position(|g| *g == spec.group_by).unwrap_or(0))
+        .prompt()
+        .with(Source::Reporting)?;
+
+    let categories: Vec<String> = save_data
+        .categories
+        .iter()
+        .chain(save_data.archived_categories.iter())
+        .map(Category::own)
+        .collect();
+    let selected: Vec<usize> =
+        (0..categories.len()).filter(|&i| spec.categories.contains(&categories[i])).collect();
+    spec.categories = MultiSelect::new("Only these categories (select none for all):", categories)
+        .with_default(&selected)
+        .prompt()
+        .with(Source::Reporting)?;
+
+    if !save_data.tags.is_empty() {
+        let tags: Vec<String> = save_data.tags.iter().map(|t| t.inner().to_owned()).collect();
+        let selected: Vec<usize> = (0..tags.len()).filter(|&i| spec.tags.contains(&tags[i])).collect();
+        spec.tags = MultiSelect::new("Only these tags (select none for all):", tags)
+            .with_default(&selected)
+            .prompt()
+            .with(Source::Reporting)?;
+    }
+
+    let contains = Text::new("Only descriptions containing (leave empty for all):")
+        .with_initial_value(spec.contains.as_deref().unwrap_or_default())
+        .prompt()
+        .with(Source::Reporting)?;
+    spec.contains = Some(contains).filter(|c| !c.is_empty());
+
+    spec.include_private = Confirm::new("Include private categories?")
+        .with_default(spec.include_private)
+        .prompt()
+        .with(Source::Reporting)?;
+
+    let formats = ReportFormat::value_variants();
+    spec.format = Select::new("Format:", formats.to_vec())
+        .with_starting_cursor(formats.iter().position(|f| *f == spec.format).unwrap_or(0))
+        .prompt()
+        .with(Source::Reporting)?;
+
+    let schedule = Text::new("Run by itself (leave empty to only run it yourself):")
+        .with_initial_value(&spec.schedule.map(|s| s.to_string()).unwrap_or_default())
+        .with_help_message("e.g. daily 18:00, weekly mon 08:00, or monthly 1 09:00, once saved")
+        .with_validator(|s: &str| {
+            Ok(match s.trim() {
+                "" => Validation::Valid,
+                s => match ReportSchedule::from_str(s) {
+                    Ok(_) => Validation::Valid,
+                    Err(e) => Validation::Invalid(e.into()),
+                },
+            })
+        })
+        .prompt()
+        .with(Source::Reporting)?;
+    spec.schedule = match schedule.trim() {
+        "" => None,
+        s => Some(s.parse().expect("validated above")),
+    };
+    Ok(())
+}
+
+/// Totals time by category, tag, or period. The report can come from a saved one (`saved`), be
+/// built with prompts (`interactive`), and be saved for later (`save_as`, or a prompt when
+/// interactive).
+pub fn report_main(
+    save_data: SaveData,
+    options: ReportOptions,
+    out: Option<PathBuf>,
+    interactive: bool,
+    saved: Option<String>,
+    save_as: Option<String>,
+) -> TaskitResult<Vec<DeltaItem>> {
+    let mut spec = match &saved {
+        Some(name) => save_data
+            .reports
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Kind::NoSuchReport(name.clone()).with(Source::Reporting))?,
+        None => ReportSpec::default(),
+    };
+    options.apply_to(&mut spec);
+    if interactive {
+        build_report(&save_data, &mut spec)?;
+    }
+    run_report(&save_data, &spec, out)?;
+
+    let name = match save_as {
+        Some(name) => name,
+        None if interactive => {
+            let name = Text::new("Save this report as (leave empty to not save):")
+                .with_initial_value(saved.as_deref().unwrap_or_default())
+                .prompt()
+                .with(Source::Reporting)?;
+            let name = name.trim().to_owned();
+            if name.is_empty() {
+                return Ok(vec![]);
+            }
+            if saved.as_ref() != Some(&name)
+                && save_data.reports.contains_key(&name)
+                && !Confirm::new(&format!("A report named {name} already exists. Replace it?"))
+                    .with_default(false)
+                    .prompt()
+                    .with(Source::Reporting)?
+            {
+                return Ok(vec![]);
+            }
+            name
+        }
+        None => return Ok(vec![]),
+    };
+    if save_data.reports.get(&name) == Some(&spec) {
+        return Ok(vec![]);
+    }
+    println!("Saved as {name}; run it again with `taskit report --saved {name}`.");
+    if let Some(schedule) = spec.schedule {
+        println!("It will also run {schedule} whenever `taskit report --run-scheduled` runs.");
+    }
+    Ok(vec![DeltaItem::SetReport(name, Some(spec))])
+}
+
+/// Where the last time each scheduled report ran is kept. It sits next to the save file, rather than
+/// in it, so that running reports can't be undone.
+pub fn schedule_path(save_path: &Path) -> PathBuf {
+    save_path.with_extension("scheduled.json")
+}
+
+/// Runs every saved report whose scheduled time has passed since it last ran, writing each to a
+/// file in the configured directory, named after the report and the day it was due. Meant to be run
+/// regularly, e.g. from cron. A report that has never run catches up on its latest scheduled time.
+pub fn run_scheduled_main(save_data: SaveData, last_runs: &Path) -> TaskitResult<Vec<DeltaItem>> {
+    let Some(dir) = &CONFIG.reports.output_dir else {
+        return Err(Kind::ReportsNotConfigured.with(Source::Reporting));
+    };
+    let mut runs: BTreeMap<String, NaiveDateTime> = match fs::read_to_string(last_runs) {
+        Ok(json) => serde_json::from_str(&json).map_err(|e| Kind::Other(e.into()).with(Source::Reporting))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => return Err(e.with(Source::Reporting).into()),
+    };
+    let now = Local::now().naive_local();
+    let due: Vec<(&String, &ReportSpec, NaiveDateTime)> = save_data
+        .reports
+        .iter()
+        .filter_map(|(name, spec)| spec.schedule.map(|schedule| (name, spec, schedule.latest(now))))
+        .filter(|(name, _, due)| runs.get(*name).is_none_or(|last| last < due))
+        .collect();
+    if due.is_empty() {
+        println!("No scheduled reports are due.");
+        return Ok(vec![]);
+    }
+    fs::create_dir_all(dir).with(Source::Reporting)?;
+    for (name, spec, due) in due {
+        match report_document(&save_data, spec)? {
+            Some(document) => {
+                let extension = match spec.format {
+                    ReportFormat::Table => "txt",
+                    ReportFormat::Csv => "csv",
+                    ReportFormat::Markdown => "md",
+                    ReportFormat::Jsonl => "jsonl",
+                };
+                let path = dir.join(format!("{name}-{}.{extension}", due.format("%Y-%m-%d")));
+                fs::write(&path, document).with(Source::Reporting)?;
+                println!("Wrote {name} to {}", path.display());
+            }
+            None => println!("No events match {name}, so nothing was written."),
+        }
+        runs.insert(name.clone(), now);
+    }
+    let json = serde_json::to_string(&runs).map_err(|e| Kind::Other(e.into()).with(Source::Reporting))?;
+    fs::write(last_runs, json).with(Source::Reporting)?;
+    Ok(vec![])
+}
+
+/// Deletes a saved report
+pub fn delete_report_main(save_data: SaveData, name: String) -> TaskitResult<Vec<DeltaItem>> {
+    if !save_data.reports.contains_key(&name) {
+        return Err(Kind::NoSuchReport(name).with(Source::Reporting));
+    }
+    Ok(vec![DeltaItem::SetReport(name, None)])
+}
+
+/// Prints a week as a table of categories by weekday, followed by the time per tag. Weeks start on
+/// Monday, and events excluded by `[aggregation]` in the config aren't counted.
+pub fn week_main(save_data: SaveData, offset: i64, include_private: bool) -> TaskitResult<Vec<DeltaItem>> {
+    let today = Local::now().date_naive();
+    let week = TimeDelta::try_weeks(offset)
+        .and_then(|delta| today.checked_add_signed(delta))
+        .map(DateRange::week_of)
+        .ok_or_else(|| Kind::Other(format!("a week {offset} weeks away is out of range").into()).with(Source::Reporting))?;
+    let days: Vec<NaiveDate> = week.start.iter_days().take(7).collect();
+    let events: Vec<&Event> = save_data
+        .events
+        .iter()
+        .filter(|ev| week.contains(ev.date))
+        .filter(|ev| include_private || !save_data.private_categories.contains(&ev.category))
+        .filter(|ev| !CONFIG.aggregation.excludes(ev, save_data.tag_map.get(&ev.category)))
+        .collect();
+
+    println!("Week {} ({} to {})\n", week.start.iso_week().week(), week.start, week.end);
+    if events.is_empty() {
+        println!("Nothing was tracked this week.");
+        return Ok(vec![]);
+    }
+
+    let mut rows: Vec<Vec<String>> = vec![
+        iter::once("Category".to_owned())
+            .chain(days.iter().map(|day| day.format("%a %d").to_string()))
+            .chain(iter::once("Total".to_owned()))
+            .collect(),
+    ];
+    for (category, total) in category_totals(events.iter().copied()) {
+        let per_day = daily_totals(events.iter().copied().filter(|ev| ev.category.inner() == category));
+        rows.push(
+            iter::once(category)
+                .chain(days.iter().map(|day| per_day.get(day).map(format_duration).unwrap_or_default()))
+                .chain(iter::once(format_duration(&total)))
+                .collect(),
+        );
+    }
+    let per_day = daily_totals(events.iter().copied());
+    let total: Duration = per_day.values().sum();
+    rows.push(
+        iter::once("Total".to_owned())
+            .chain(days.iter().map(|day| format_duration(per_day.get(day).unwrap_or(&Duration::ZERO))))
+            .chain(iter::once(format_duration(&total)))
+            .collect(),
+    );
+    print_table(&rows);
+
+    let tags = tag_totals(&save_data, &events);
+    if !tags.is_empty() {
+        println!();
+        let rows: Vec<Vec<String>> = iter::once(vec!["Tag".to_owned(), "Total".to_owned()])
+            .chain(tags.into_iter().map(|(tag, time)| vec![format!("#{}", tag.inner()), format_duration(&time)]))
+            .collect();
+        print_table(&rows);
+    }
+    print!("{}", pinned_section(&events));
+    Ok(vec![])
+}
+
+/// Total time per tag, counting the tags events get from their categories, largest first
+fn tag_totals<'a>(save_data: &'a SaveData, events: &[&'a Event]) -> Vec<(&'a Tag, Duration)> {
+    let mut tags: BTreeMap<&Tag, Duration> = BTreeMap::new();
+    for ev in events {
+        for tag in event_tags(save_data, ev) {
+            *tags.entry(tag).or_insert(Duration::ZERO) += ev.duration();
+        }
+    }
+    tags.into_iter().sorted_by_key(|(_, time)| -*time).collect()
+}
+
+/// How many of the busiest days, categories, and tags a month summary lists
+const MONTH_TOP: usize = 5;
+
+/// Prints a summary of a calendar month: the total, the busiest days, and the categories and tags
+/// that took the most time, each with their share of the month
+pub fn month_main(save_data: SaveData, month: NaiveDate, include_private: bool) -> TaskitResult<Vec<DeltaItem>> {
+    let range = DateRange::month_of(month);
+    let events: Vec<&Event> = save_data
+        .events
+        .iter()
+        .filter(|ev| range.contains(ev.date))
+        .filter(|ev| include_private || !save_data.private_categories.contains(&ev.category))
+        .filter(|ev| !CONFIG.aggregation.excludes(ev, save_data.tag_map.get(&ev.category)))
+        .collect();
+
+    println!("{} ({} to {})\n", range.start.format("%B %Y"), range.start, range.end);
+    if events.is_empty() {
+        println!("Nothing was tracked this month.");
+        return Ok(vec![]);
+    }
+    let per_day = daily_totals(events.iter().copied());
+    let total: Duration = per_day.values().sum();
+    let share = |time: &Duration| format!("{:.0}%", time.fraction_of(total) * 100.0);
+    print_table(&[
+        vec!["Total".to_owned(), format_duration(&total)],
+        vec!["Days tracked".to_owned(), per_day.len().to_string()],
+        vec!["Average per tracked day".to_owned(), format_duration(&(total / per_day.len() as i64))],
+        vec!["Events".to_owned(), events.len().to_string()],
+    ]);
+
+    println!();
+    let rows: Vec<Vec<String>> = iter::once(vec!["Busiest days".to_owned(), "Time".to_owned(), "Share".to_owned()])
+        .chain(
+            per_day
+                .iter()
+                .sorted_by_key(|(date, time)| (-**time, **date))
+                .take(MONTH_TOP)
+                .map(|(date, time)| vec![display_date(*date), format_duration(time), share(time)]),
+        )
+        .collect();
+    print_table(&rows);
+
+    println!();
+    let rows: Vec<Vec<String>> = iter::once(vec!["Top categories".to_owned(), "Time".to_owned(), "Share".to_owned()])
+        .chain(
+            category_totals(events.iter().copied())
+                .into_iter()
+                .take(MONTH_TOP)
+                .map(|(category, time)| vec![category, format_duration(&time), share(&time)]),
+        )
+        .collect();
+    print_table(&rows);
+
+    let tags = tag_totals(&save_data, &events);
+    if !tags.is_empty() {
+        println!();
+        let rows: Vec<Vec<String>> = iter::once(vec!["Top tags".to_owned(), "Time".to_owned(), "Share".to_owned()])
+            .chain(
+                tags.into_iter()
+                    .take(MONTH_TOP)
+                    .map(|(tag, time)| vec![format!("#{}", tag.inner()), format_duration(&time), share(&time)]),
+            )
+            .collect();
+        print_table(&rows);
+    }
+    print!("{}", pinned_section(&events));
+    Ok(vec![])
+}
+
+/// Compares the time tracked in each committed category against its weekly commitment, week by
+/// week, with the running balance since the first week shown. The current week is included even
+/// though it isn't over, so its balance is only final on Sunday.
+pub fn capacity_main(save_data: SaveData, weeks: u32, since: Option<NaiveDate>) -> TaskitResult<Vec<DeltaItem>> {
+    let commitments: Vec<(&String, Duration)> = CONFIG
+        .capacity
+        .weekly_hours
+        .iter()
+        .map(|(name, &hours)| (name, Duration::seconds((hours * 60.0 * 60.0).round() as i64)))
+        .sorted()
+        .collect();
+    if commitments.is_empty() {
+        return Err(Kind::CapacityNotConfigured.with(Source::Reporting));
+    }
+    for (name, _) in &commitments {
+        if !save_data.categories.iter().chain(save_data.archived_categories.iter()).any(|c| c.inner() == *name) {
+            return Err(Kind::NoSuchCategory(name.to_string()).with(Source::Reporting));
+        }
+    }
+
+    let this_week = DateRange::week_of(Local::now().date_naive());
+    let first = match since {
+        Some(date) => DateRange::week_of(date).start,
+        None => this_week.start - Days::new(7 * u64::from(weeks.max(1) - 1)),
+    };
+    let weeks: Vec<DateRange> = first
+        .iter_days()
+        .step_by(7)
+        .take_while(|start| *start <= this_week.start)
+        .map(DateRange::week_of)
+        .collect();
+
+    let mut rows: Vec<Vec<String>> = vec![
+        iter::once("Week".to_owned())
+            .chain(commitments.iter().flat_map(|(name, hours)| {
+                [format!("{name} ({})", format_duration(hours)), "Balance".to_owned()]
+            }))
+            .collect(),
+    ];
+    let mut totals = vec![Duration::ZERO; commitments.len()];
+    let mut balances = vec![Duration::ZERO; commitments.len()];
+    for week in &weeks {
+        let mut row = vec![format!(
+            "{} ({}){}",
+            week.start.format("%G-W%V"),
+            week.start,
+            if week.start == this_week.start { " so far" } else { "" }
+        )];
+        for (i, (name, committed)) in commitments.iter().enumerate() {
+            let actual: Duration = save_data
+                .events
+                .iter()
+                .filter(|ev| week.contains(ev.date) && ev.category.inner() == *name)
+                .filter(|ev| !CONFIG.aggregation.excludes(ev, save_data.tag_map.get(&ev.category)))
+                .map(Event::duration)
+                .sum();
+            totals[i] += actual;
+            balances[i] += actual - *committed;
+            row.push(format!("{} ({})", format_duration(&actual), format_signed_duration(&(actual - *committed))));
+            row.push(format_signed_duration(&balances[i]));
+        }
+        rows.push(row);
+    }
+    rows.push(
+        iter::once(format!("Total ({} weeks)", weeks.len()))
+            .chain(totals.iter().zip(&balances).flat_map(|(total, balance)| {
+                [format_duration(total), format_signed_duration(balance)]
+            }))
+            .collect(),
+    );
+    print_table(&rows);
+    Ok(vec![])
+}
+
+/// Prints each goal with the time put towards it in a week and how far it is from being met
+pub fn goals_main(save_data: SaveData, offset: i64) -> TaskitResult<Vec<DeltaItem>> {
+    if save_data.category_goals.is_empty() && save_data.tag_goals.is_empty() {
+        println!("There are no goals yet; set one with `taskit goal`.");
+        return Ok(vec![]);
+    }
+    let week = TimeDelta::try_weeks(offset)
+        .and_then(|delta| Local::now().date_naive().checked_add_signed(delta))
+        .map(DateRange::week_of)
+        .ok_or_else(|| Kind::Other(format!("a week {offset} weeks away is out of range").into()).with(Source::Reporting))?;
+    println!("Week {} ({} to {})\n", week.start.iso_week().week(), week.start, week.end);
+
+    let progress = goal_progress(
+        save_data.events.iter(),
+        &save_data.tag_map,
+        &save_data.category_goals,
+        &save_data.tag_goals,
+        week,
+    );
+    let rows: Vec<Vec<String>> = iter::once(["Goal", "Target", "Time", "Progress", ""].map(str::to_owned).to_vec())
+        .chain(progress.into_iter().map(|(name, goal, time)| {
+            let fraction = time.fraction_of(goal.weekly());
+            let status = match (goal.max, goal.met(time)) {
+                (false, true) => "met".to_owned(),
+                (false, false) => format!("{} to go", format_duration(&(goal.weekly() - time))),
+                (true, true) => format!("{} left", format_duration(&(goal.weekly() - time))),
+                (true, false) => format!("{} over", format_duration(&(time - goal.weekly()))),
+            };
+            vec![
+                name,
+                goal.to_string(),
+                format_duration(&time),
+                format!("{} {:>3.0}%", progress_bar(fraction, 20), fraction * 100.0),
+                status,
+            ]
+        }))
+        .collect();
+    print_table(&rows);
+    Ok(vec![])
+}
+
+/// Rounds a duration up to a multiple of `minutes`, if it's not zero
+fn round_up(duration: Duration, minutes: u32) -> Duration {
+    let increment = i64::from(minutes) * 60;
+    if increment == 0 || duration.num_seconds() % increment == 0 {
+        return duration;
+    }
+    Duration::seconds((duration.num_seconds() / increment + 1) * increment)
+}
+
+/// Totals billable time and what it's worth per category, for events from `from` to `to`. Events are
+/// billable if their category has a rate (see `taskit rate`), unless the configured billable field
+/// is false for them. Each event's time is rounded up to a multiple of `round` minutes, or of
+/// `round_to_minutes` from the config if not given.
+pub fn invoice_main(
+    save_data: SaveData,
+    from: NaiveDate,
+    to: NaiveDate,
+    round: Option<u32>,
+) -> TaskitResult<Vec<DeltaItem>> {
+    let settings = &CONFIG.invoice;
+    if let Some(field) = &settings.billable_field
+        && CONFIG.field(field).is_none()
+    {
+        return Err(Kind::NoSuchField(field.clone()).with(Source::Reporting));
+    }
+    let round = round.or(settings.round_to_minutes).unwrap_or(0);
+    let mut by_category: BTreeMap<&str, (usize, Duration, f64)> = BTreeMap::new();
+    for ev in save_data.events.iter().filter(|ev| from <= ev.date && ev.date <= to) {
+        let Some(rate) = save_data.category_rates.get(&ev.category) else {
+            continue;
+        };
+        if settings.billable_field.as_ref().is_some_and(|field| ev.fields.get(field) == Some(&FieldValue::Bool(false))) {
+            continue;
+        }
+        let total = by_category.entry(ev.category.inner()).or_insert((0, Duration::ZERO, *rate));
+        total.0 += 1;
+        total.1 += round_up(ev.duration(), round);
+    }
+    if by_category.is_empty() {
+        if save_data.category_rates.is_empty() {
+            println!("No categories have a rate yet; set one with `taskit rate <category> <rate>`.");
+        } else {
+            println!("No billable time from {from} to {to}.");
+        }
+        return Ok(vec![]);
+    }
+    let hours = |duration: Duration| duration.num_seconds() as f64 / 3600.0;
+    let mut rows = vec![vec![
+        "Category".to_owned(),
+        "Events".to_owned(),
+        "Hours".to_owned(),
+        "Rate".to_owned(),
+        "Amount".to_owned(),
+    ]];
+    for (category, (count, time, rate)) in &by_category {
+        rows.push(vec![
+            category.to_string(),
+            count.to_string(),
+            format!("{:.2}", hours(*time)),
+            format!("{rate:.2}"),
+            format!("{:.2}", hours(*time) * rate),
+        ]);
+    }
+    rows.push(vec![
+        "total".to_owned(),
+        by_category.values().map(|(count, _, _)| count).sum::<usize>().to_string(),
+        format!("{:.2}", hours(by_category.values().map(|(_, time, _)| *time).sum())),
+        String::new(),
+        format!("{:.2}", by_category.values().map(|(_, time, rate)| hours(*time) * rate).sum::<f64>()),
+    ]);
+    println!("Billable time from {from} to {to}{}:", if round > 0 { format!(", rounded up to {round} minutes per event") } else { String::new() });
+    print_table(&rows);
+    Ok(vec![])
+}
+
+/// A row of count, average, time-weighted average, minimum, and maximum for some values of a
+/// number field, each paired with the duration of its event
+fn number_summary(label: &str, numbers: impl Iterator<Item = (f64, Duration)>) -> Vec<String> {
+    let numbers: Vec<(f64, Duration)> = numbers.collect();
+    let average = numbers.iter().map(|(n, _)| n).sum::<f64>() / numbers.len() as f64;
+    // weighting by duration means a long block counts for more than a quick check-in
+    let minutes: f64 = numbers.iter().map(|(_, d)| d.num_minutes() as f64).sum();
+    let weighted = if minutes > 0.0 {
+        let total: f64 = numbers.iter().map(|(n, d)| n * d.num_minutes() as f64).sum();
+        format!("{:.1}", total / minutes)
+    } else {
+        "-".to_owned()
+    };
+    let min = numbers.iter().map(|(n, _)| *n).fold(f64::INFINITY, f64::min);
+    let max = numbers.iter().map(|(n, _)| *n).fold(f64::NEG_INFINITY, f64::max);
+    vec![
+        label.to_owned(),
+        numbers.len().to_string(),
+        format!("{average:.1}"),
+        weighted,
+        min.to_string(),
+        max.to_string(),
+    ]
+}
+
+/// Describes a batch of changes, e.g. "edited 1 event" and "made 2 other changes", leaving out
+/// kinds of change that weren't made
+pub fn describe_changes(delta: &[DeltaItem]) -> Vec<String> {
+    let (mut added, mut edited, mut deleted, mut annotated, mut notes, mut other) = (0, 0, 0, 0, 0, 0);
+    for item in delta {
+        match item {
+            DeltaItem::AddEvent(_) => added += 1,
+            DeltaItem::ChangeEvent { .. } => edited += 1,
+            DeltaItem::DeleteEvent(_) => deleted += 1,
+            DeltaItem::AnnotateEvent { .. } => annotated += 1,
+            DeltaItem::SetDailyNote(..) => notes += 1,
+            _ => other += 1,
+        }
+    }
+    [
+        (added, "added"),
+        (edited, "edited"),
+        (deleted, "deleted"),
+        (annotated, "annotated"),
+    ]
+    .into_iter()
+    .filter(|(count, _)| *count > 0)
+    .map(|(count, what)| format!("{what} {count} event{}", if count == 1 { "" } else { "s" }))
+    .chain((notes > 0).then(|| format!("wrote {notes} daily note{}", if notes == 1 { "" } else { "s" })))
+    .chain((other > 0).then(|| format!("made {other} other change{}", if other == 1 { "" } else { "s" })))
+    .collect()
+}
+
+/// Prints what a session (e.g. of the viewer or a stopwatch) is about to save, along with the time
+/// tracked today once it's saved. `save_data` is the save from before the session's changes.
+pub fn print_session_summary(save_data: &SaveData, delta: &[DeltaItem], filters_used: &[String]) {
+    let mut events = save_data.events.clone();
+    for item in delta {
+        match item {
+            DeltaItem::AddEvent(ev) => events.push(ev.clone()),
+            DeltaItem::ChangeEvent { id, new_event } => {
+                if let Some(event) = events.iter_mut().find(|ev| ev.id == *id) {
+                    *event = new_event.clone();
+                }
+            }
+            DeltaItem::DeleteEvent(id) => events.retain(|ev| ev.id != *id),
+            _ => {}
+        }
+    }
+    let changes = describe_changes(delta);
+
+    println!();
+    if changes.is_empty() {
+        println!("No changes this session.");
+    } else {
+        println!("This session: {}.", changes.join(", "));
+    }
+    if !filters_used.is_empty() {
+        println!("Filters used: {}", filters_used.join("; "));
+    }
+    let today = Local::now().date_naive();
+    let tracked: Duration = events
+        .iter()
+        .filter(|ev| ev.date == today)
+        .filter(|ev| !CONFIG.aggregation.excludes(ev, save_data.tag_map.get(&ev.category)))
+        .map(|ev| ev.duration())
+        .sum();
+    println!("Tracked today: {}", format_duration(&tracked));
+}
@@ -0,0 +1,244 @@
+use std::{fmt::Write as _, fs, path::PathBuf};
+
+use aes_gcm::{
+    Aes256Gcm, Key, KeyInit,
+    aead::{Aead, AeadCore, OsRng, rand_core::RngCore},
+};
+use inquire::Password;
+use itertools::Itertools;
+use sha2::Sha256;
+
+use crate::{
+    common::{
+        DeltaItem, Duration, Event, SaveData,
+        config::CONFIG,
+        error::{Kind, Source, TaskitResult, With},
+    },
+    report::{category_totals, format_duration},
+    util::{DateRange, escape_html, hex},
+};
+
+const STYLE: &str = "\
+body { font-family: sans-serif; max-width: 50em; margin: 2em auto; padding: 0 1em; color: #222; }
+table { border-collapse: collapse; width: 100%; margin-bottom: 1.5em; }
+th, td { text-align: left; padding: 0.3em 0.6em; border-bottom: 1px solid #ddd; vertical-align: top; }
+td.num, th.num { text-align: right; white-space: nowrap; }
+td.time { white-space: nowrap; }
+h2 { font-size: 1.1em; margin-top: 2em; }
+.muted { color: #777; }";
+
+/// PBKDF2-HMAC-SHA256 rounds for deriving the key of a passphrase-protected snapshot
+const ITERATIONS: u32 = 600_000;
+
+/// Asks for the passphrase and decrypts the report in place, with the same key derivation and
+/// cipher as `seal`
+const UNLOCK_SCRIPT: &str = "\
+const hex = s => Uint8Array.from(s.match(/../g), b => parseInt(b, 16));
+document.getElementById('unlock').addEventListener('submit', async e => {
+  e.preventDefault();
+  const sealed = JSON.parse(document.getElementById('sealed').textContent);
+  const passphrase = new TextEncoder().encode(document.getElementById('passphrase').value);
+  const base = await crypto.subtle.importKey('raw', passphrase, 'PBKDF2', false, ['deriveKey']);
+  const key = await crypto.subtle.deriveKey(
+    { name: 'PBKDF2', salt: hex(sealed.salt), iterations: sealed.iterations, hash: 'SHA-256' },
+    base, { name: 'AES-GCM', length: 256 }, false, ['decrypt']);
+  try {
+    const report = await crypto.subtle.decrypt({ name: 'AES-GCM', iv: hex(sealed.nonce) }, key, hex(sealed.data));
+    document.getElementById('report').innerHTML = new TextDecoder().decode(report);
+  } catch {
+    document.getElementById('wrong').hidden = false;
+  }
+});";
+
+/// The parts of a passphrase-protected snapshot, hex-encoded for the page to read back
+struct Sealed {
+    salt: String,
+    nonce: String,
+    data: String,
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> Key<Aes256Gcm> {
+    let mut key = Key::<Aes256Gcm>::default();
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, &mut key);
+    key
+}
+
+/// Encrypts `html` with AES-256-GCM, under a key derived from the passphrase and a random salt
+fn seal(html: &str, passphrase: &str, iterations: u32) -> Sealed {
+    let mut salt = [0; 16];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let data = Aes256Gcm::new(&derive_key(passphrase, &salt, iterations))
+        .encrypt(&nonce, html.as_bytes())
+        .expect("AES-GCM encryption only fails on inputs far larger than a report");
+    Sealed {
+        salt: hex(&salt),
+        nonce: hex(&nonce),
+        data: hex(&data),
+    }
+}
+
+/// The body of a passphrase-protected snapshot: a form for the passphrase, with the report filled
+/// in below it once it's decrypted
+fn locked_body(report: &str, passphrase: &str, iterations: u32) -> String {
+    let sealed = seal(report, passphrase, iterations);
+    format!(
+        concat!(
+            "<div id=\"report\">\n<h1>Time report</h1>\n",
+            "<form id=\"unlock\"><p>This report is protected with a passphrase.</p>\n",
+            "<input id=\"passphrase\" type=\"password\" autofocus> <button>Open</button>\n",
+            "<p id=\"wrong\" class=\"muted\" hidden>That passphrase didn't work.</p></form>\n</div>\n",
+            "<script id=\"sealed\" type=\"application/json\">",
+            "{{\"salt\":\"{salt}\",\"nonce\":\"{nonce}\",\"iterations\":{iterations},\"data\":\"{data}\"}}",
+            "</script>\n<script>\n{script}\n</script>\n"
+        ),
+        salt = sealed.salt,
+        nonce = sealed.nonce,
+        iterations = iterations,
+        data = sealed.data,
+        script = UNLOCK_SCRIPT,
+    )
+}
+
+fn summary_table(events: &[&Event], total: Duration) -> String {
+    let mut out = String::from(
+        "<table>\n<tr><th>Category</th><th class=\"num\">Time</th><th class=\"num\">Share</th></tr>\n",
+    );
+    for (category, duration) in category_totals(events.iter().copied()) {
+        let share = duration.fraction_of(total) * 100.0;
+        let _ = writeln!(
+            out,
+            "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{share:.0}%</td></tr>",
+            escape_html(&CONFIG.icons.label(&category)),
+            format_duration(&duration),
+        );
+    }
+    out.push_str("</table>\n");
+    out
+}
+
+fn day_sections(events: &[&Event]) -> String {
+    let mut out = String::new();
+    for (date, day) in &events.iter().chunk_by(|ev| ev.date) {
+        let day: Vec<&Event> = day.copied().collect();
+        let day_total: Duration = day.iter().map(|ev| ev.duration()).sum();
+        let _ = writeln!(
+            out,
+            "<h2>{} <span class=\"muted\">({})</span></h2>\n<table>",
+            date.format("%A, %Y-%m-%d"),
+            format_duration(&day_total)
+        );
+        for ev in day {
+            let description = ev.description.lines().map(escape_html).join("<br>");
+            let _ = writeln!(
+                out,
+                "<tr><td class=\"time\">{}&ndash;{}</td><td>{}</td><td>{description}</td><td class=\"num\">{}</td></tr>",
+                ev.start_time,
+                ev.end_time,
+                escape_html(&CONFIG.icons.label(&ev.category)),
+                format_duration(&ev.duration()),
+            );
+        }
+        out.push_str("</table>\n");
+    }
+    out
+}
+
+pub fn share_main(
+    save_data: SaveData,
+    range: DateRange,
+    out: PathBuf,
+    include_private: bool,
+    passphrase: bool,
+) -> TaskitResult<Vec<DeltaItem>> {
+    if out
+        .extension()
+        .is_none_or(|ext| !ext.eq_ignore_ascii_case("html") && !ext.eq_ignore_ascii_case("htm"))
+    {
+        return Err(Kind::UnsupportedFormat(out.display().to_string()).with(Source::Exporting));
+    }
+    let passphrase = if passphrase {
+        Some(
+            Password::new("Passphrase:")
+                .with_validator(inquire::required!("The passphrase can't be empty."))
+                .prompt()
+                .with(Source::Exporting)?,
+        )
+    } else {
+        None
+    };
+    let mut events: Vec<&Event> = save_data
+        .events
+        .iter()
+        .filter(|ev| range.contains(ev.date))
+        .filter(|ev| include_private || !save_data.private_categories.contains(&ev.category))
+        .collect();
+    events.sort_by_key(|ev| (ev.date, ev.start_time));
+    let total: Duration = events.iter().map(|ev| ev.duration()).sum();
+
+    let body = if events.is_empty() {
+        "<p class=\"muted\">No time was tracked in this period.</p>\n".to_owned()
+    } else {
+        format!("{}{}", summary_table(&events, total), day_sections(&events))
+    };
+    let report = format!(
+        "<h1>Time report</h1>\n<p>{range} &middot; {total} in total</p>\n{body}",
+        total = format_duration(&total),
+    );
+    // the range stays out of the title of a protected snapshot, like everything else
+    let (title, body) = match passphrase {
+        Some(passphrase) => ("Time report".to_owned(), locked_body(&report, &passphrase, ITERATIONS)),
+        None => (format!("Time report: {range}"), report),
+    };
+    let document = format!(
+        concat!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n",
+            "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n",
+            "<title>{title}</title>\n<style>\n{style}\n</style>\n</head>\n<body>\n{body}</body>\n</html>\n"
+        ),
+        title = title,
+        style = STYLE,
+        body = body,
+    );
+    fs::write(&out, document).with(Source::Exporting)?;
+    println!("Wrote {} events to {}", events.len(), out.display());
+    Ok(vec![])
+}
+
+#[cfg(test)]
+mod tests {
+    use aes_gcm::Nonce;
+
+    use super::*;
+
+    fn unhex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    /// Decrypts like the page's script does
+    fn open(sealed: &Sealed, passphrase: &str, iterations: u32) -> Option<String> {
+        let key = derive_key(passphrase, &unhex(&sealed.salt), iterations);
+        let report = Aes256Gcm::new(&key).decrypt(Nonce::from_slice(&unhex(&sealed.nonce)), unhex(&sealed.data).as_slice()).ok()?;
+        Some(String::from_utf8(report).unwrap())
+    }
+
+    #[test]
+    fn sealed_report_opens_only_with_its_passphrase() {
+        let report = "<h1>Time report</h1>\n<p>2024-05-06 &middot; 1h 30m in total</p>";
+        let sealed = seal(report, "correct horse", 1000);
+        assert_eq!(sealed.nonce.len(), 24);
+        assert!(!sealed.data.contains(&hex(report.as_bytes())));
+        assert_eq!(open(&sealed, "correct horse", 1000).as_deref(), Some(report));
+        assert_eq!(open(&sealed, "wrong horse", 1000), None);
+        // every snapshot gets its own salt and nonce
+        let again = seal(report, "correct horse", 1000);
+        assert_ne!((&again.salt, &again.nonce), (&sealed.salt, &sealed.nonce));
+    }
+
+    #[test]
+    fn locked_body_leaves_the_report_out() {
+        let body = locked_body("<p>secret client work</p>", "pass", 1000);
+        assert!(!body.contains("secret client work"));
+        assert!(body.contains("\"iterations\":1000"));
+    }
+}
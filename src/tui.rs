@@ -1,7 +1,14 @@
+mod calendar;
+mod day;
 mod framework;
+mod keys;
+mod safe;
 
 mod show;
 mod tagedit;
+mod theme;
 
+pub use day::day_main;
+pub use safe::crash_log_path;
 pub use show::filter_main;
 pub use tagedit::tagedit_main;
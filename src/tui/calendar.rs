@@ -0,0 +1,81 @@
+//! A month calendar for picking a date without leaving the TUI
+
+use chrono::{Datelike, Days, Local, Months, NaiveDate};
+use ratatui::{
+    style::{Style, Stylize},
+    text::{Line, Span},
+};
+
+use crate::tui::theme::THEME;
+
+/// Width of the lines from `Calendar::lines`, seven days of three columns each
+pub const WIDTH: u16 = 20;
+
+pub struct Calendar {
+    /// The date that Enter would pick
+    pub cursor: NaiveDate,
+}
+
+impl Calendar {
+    pub fn new(cursor: NaiveDate) -> Self {
+        Self { cursor }
+    }
+
+    /// Moves the cursor by `days`, staying put at the ends of the calendar
+    pub fn move_days(&mut self, days: i64) {
+        let moved = if days >= 0 {
+            self.cursor.checked_add_days(Days::new(days as u64))
+        } else {
+            self.cursor.checked_sub_days(Days::new(days.unsigned_abs()))
+        };
+        self.cursor = moved.unwrap_or(self.cursor);
+    }
+
+    /// Moves the cursor by `months`, onto the last day of the month if it's shorter
+    pub fn move_months(&mut self, months: i32) {
+        let moved = if months >= 0 {
+            self.cursor.checked_add_months(Months::new(months as u32))
+        } else {
+            self.cursor.checked_sub_months(Months::new(months.unsigned_abs()))
+        };
+        self.cursor = moved.unwrap_or(self.cursor);
+    }
+
+    pub fn today(&mut self) {
+        self.cursor = Local::now().date_naive();
+    }
+
+    /// The month and year, the weekdays from Monday, and a line per week of the cursor's month,
+    /// with the cursor reversed and today underlined
+    pub fn lines(&self) -> Vec<Line<'static>> {
+        let today = Local::now().date_naive();
+        let first = self.cursor.with_day(1).expect("every month has a first day");
+        let mut lines = vec![
+            Line::raw(format!("{:^width$}", first.format("%B %Y").to_string(), width = WIDTH as usize)),
+            Line::styled("Mo Tu We Th Fr Sa Su", THEME.dim(Style::new())),
+        ];
+        // the days before the first are blank, less the space that comes before the first
+        let blank = (3 * first.weekday().num_days_from_monday() as usize).saturating_sub(1);
+        let mut week = vec![Span::raw(" ".repeat(blank))];
+        for date in first.iter_days().take_while(|date| date.month() == first.month()) {
+            let mut style = Style::new();
+            if date == self.cursor {
+                style = style.patch(THEME.selected);
+            }
+            if date == today {
+                style = style.underlined();
+            }
+            if date.weekday().num_days_from_monday() > 0 {
+                week.push(Span::raw(" "));
+            }
+            week.push(Span::styled(format!("{:>2}", date.day()), style));
+            if date.weekday().num_days_from_monday() == 6 {
+                lines.push(Line::default().spans(week.drain(..)));
+            }
+        }
+        if !week.is_empty() {
+            lines.push(Line::default().spans(week));
+        }
+        lines
+    }
+}
@@ -0,0 +1,465 @@
+use std::{collections::HashSet, io::stdout};
+
+use chrono::NaiveDate;
+use crossterm::{
+    cursor::MoveTo,
+    event::{Event as CEvent, KeyModifiers},
+    execute,
+    terminal::{Clear, ClearType, disable_raw_mode, enable_raw_mode},
+};
+use inquire::{InquireError, error::InquireResult};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout},
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Paragraph, Row, Table, TableState, Wrap},
+};
+use smallvec::SmallVec;
+
+use crate::{
+    common::{
+        DeltaItem, Duration, Event, EventId, SaveData, SimpleTime,
+        error::{Source, TaskitResult, With},
+        get_description_tags,
+        invariants::Tag,
+    },
+    report::{describe_changes, format_duration, print_session_summary},
+    tui::{framework::{self, TuiState, sync::ExternalFunction}, theme::THEME},
+    util::display_date,
+};
+
+type Extrinsic<'a> = framework::Extrinsic<State<'a>>;
+
+enum Message {
+    Exit,
+    Save,
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Starts editing the selected cell, or finishes editing it
+    Enter,
+    CancelEdit,
+    KeyTyped(char),
+    Backspace,
+    EditNote,
+}
+
+/// The columns of the table that can be edited
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Start,
+    End,
+    Category,
+    Notes,
+}
+
+/// Text to edit in an editor, outside of the TUI
+struct EditorRequest {
+    message: String,
+    text: String,
+}
+
+struct State<'a> {
+    save_data: &'a SaveData,
+    date: NaiveDate,
+    /// The day's events with any changes made so far, in order of their start times
+    events: Vec<Event>,
+    changed: HashSet<EventId>,
+    note: String,
+    selected: usize,
+    column: Column,
+    /// What's been typed into the selected cell, while it's being edited
+    editing: Option<String>,
+    /// Shown in place of the key hints until the next keypress, e.g. when a time can't be read
+    status: Option<String>,
+    /// Set once quitting with unsaved changes has been asked about, so that quitting again discards them
+    confirm_discard: bool,
+    saved: bool,
+    table_state: TableState,
+}
+
+impl framework::Message for Message {}
+
+impl Column {
+    const ALL: [Self; 4] = [Self::Start, Self::End, Self::Category, Self::Notes];
+
+    /// The column's index in the table, which has a duration column between the end and category
+    fn table_index(self) -> usize {
+        match self {
+            Column::Start => 0,
+            Column::End => 1,
+            Column::Category => 3,
+            Column::Notes => 4,
+        }
+    }
+
+    fn step(self, by: isize) -> Self {
+        let index = Self::ALL.iter().position(|&c| c == self).expect("every column is in ALL") as isize;
+        Self::ALL[(index + by).clamp(0, Self::ALL.len() as isize - 1) as usize]
+    }
+}
+
+impl<'a> State<'a> {
+    fn note_changed(&self) -> bool {
+        self.note != self.save_data.daily_notes.get(&self.date).map(String::as_str).unwrap_or("")
+    }
+
+    fn deltas(&self) -> Vec<DeltaItem> {
+        let mut deltas: Vec<DeltaItem> = self
+            .events
+            .iter()
+            .filter(|ev| self.changed.contains(&ev.id))
+            .map(|ev| DeltaItem::ChangeEvent { id: ev.id, new_event: ev.clone() })
+            .collect();
+        if self.note_changed() {
+            deltas.push(DeltaItem::SetDailyNote(self.date, self.note.clone()));
+        }
+        deltas
+    }
+
+    fn cell_text(&self, ev: &Event, column: Column) -> String {
+        match column {
+            Column::Start => ev.start_time.to_string(),
+            Column::End => ev.end_time.to_string(),
+            Column::Category => ev.category.own(),
+            Column::Notes => ev.description.clone(),
+        }
+    }
+
+    fn start_editing(&mut self, call: &ExternalFunction<EditorRequest, InquireResult<String>>) -> TaskitResult<()> {
+        let Some(ev) = self.events.get(self.selected) else {
+            return Ok(());
+        };
+        // notes over several lines don't fit in a cell, so they're edited in an editor instead
+        if self.column == Column::Notes && ev.description.contains('\n') {
+            let request = EditorRequest {
+                message: format!("Notes for {ev}:"),
+                text: ev.description.clone(),
+            };
+            if let Some(text) = edit_in_editor(call, request)? {
+                self.set_notes(text.trim_end().to_owned());
+            }
+            return Ok(());
+        }
+        self.editing = Some(self.cell_text(ev, self.column));
+        Ok(())
+    }
+
+    /// Sets the selected event's notes, and the tags they mention, unless they mention tags that
+    /// don't exist. Returns whether the notes were set.
+    fn set_notes(&mut self, notes: String) -> bool {
+        let names = get_description_tags(&notes);
+        let tags: HashSet<Tag> = self.save_data.tags.iter().filter(|tag| names.contains(tag.inner())).cloned().collect();
+        let missing: Vec<String> = names
+            .iter()
+            .filter(|name| !tags.iter().any(|tag| tag.inner() == name.as_str()))
+            .map(|name| format!("#{name}"))
+            .collect();
+        if !missing.is_empty() {
+            self.status = Some(format!("No such tags: {}. Create them first with `taskit tag`.", missing.join(", ")));
+            return false;
+        }
+        let ev = &mut self.events[self.selected];
+        ev.description = notes;
+        ev.tags = tags;
+        self.changed.insert(ev.id);
+        true
+    }
+
+    /// Applies what was typed into the selected cell, or explains why it can't be
+    fn finish_editing(&mut self) {
+        let Some(text) = self.editing.take() else {
+            return;
+        };
+        let text = text.trim();
+        let ev = &mut self.events[self.selected];
+        match self.column {
+            Column::Start | Column::End => {
+                let Ok(time) = text.parse::<SimpleTime>() else {
+                    self.status = Some(format!("Couldn't read {text:?} as a time; enter one like 9:30."));
+                    self.editing = Some(text.to_owned());
+                    return;
+                };
+                if self.column == Column::Start {
+                    ev.start_time = time;
+                } else {
+                    ev.end_time = time;
+                }
+            }
+            Column::Category => {
+                if self.save_data.archived_categories.contains_match(text) {
+                    self.status = Some(format!("Category {text} is archived."));
+                    self.editing = Some(text.to_owned());
+                    return;
+                }
+                let Some(category) = self.save_data.categories.find(text) else {
+                    self.status = Some(format!("There's no category named {text}."));
+                    self.editing = Some(text.to_owned());
+                    return;
+                };
+                ev.category = category.clone();
+            }
+            Column::Notes => {
+                if !self.set_notes(text.to_owned()) {
+                    self.editing = Some(text.to_owned());
+                }
+                return;
+            }
+        }
+        self.changed.insert(ev.id);
+        self.sort_events();
+    }
+
+    /// Keeps events in order of their start times, with the same one still selected
+    fn sort_events(&mut self) {
+        let id = self.events.get(self.selected).map(|ev| ev.id);
+        self.events.sort_by_key(|ev| (ev.start_time, ev.id));
+        self.selected = self.events.iter().position(|ev| Some(ev.id) == id).unwrap_or_default();
+    }
+}
+
+/// Breaks out of the TUI to edit text in an editor, returning None if that was cancelled
+fn edit_in_editor(
+    call: &ExternalFunction<EditorRequest, InquireResult<String>>,
+    request: EditorRequest,
+) -> TaskitResult<Option<String>> {
+    execute!(stdout(), Clear(ClearType::All), MoveTo(0, 0)).with(Source::DrawingTui)?;
+    disable_raw_mode().with(Source::DrawingTui)?;
+    let text = call.call(request);
+    enable_raw_mode().with(Source::DrawingTui)?;
+    match text {
+        Ok(text) => Ok(Some(text)),
+        Err(InquireError::OperationCanceled | InquireError::OperationInterrupted) => Ok(None),
+        Err(e) => Err((e, Source::EditingEntry))?,
+    }
+}
+
+impl<'a> TuiState for State<'a> {
+    type Message = Message;
+    type Call = EditorRequest;
+    type Response = InquireResult<String>;
+    type Output = Vec<DeltaItem>;
+
+    fn handle_message(
+        &mut self,
+        message: Self::Message,
+        call: &ExternalFunction<Self::Call, Self::Response>,
+    ) -> TaskitResult<Option<Extrinsic<'a>>> {
+        let confirm_discard = std::mem::take(&mut self.confirm_discard);
+        self.status = None;
+        match message {
+            Message::Exit => {
+                if self.deltas().is_empty() || confirm_discard {
+                    return Ok(Some(Extrinsic::Halt));
+                }
+                self.confirm_discard = true;
+                self.status = Some("There are unsaved changes: s saves them, q again discards them.".to_owned());
+            }
+            Message::Save => {
+                self.saved = true;
+                return Ok(Some(Extrinsic::Halt));
+            }
+            Message::Up => self.selected = self.selected.saturating_sub(1),
+            Message::Down => self.selected = (self.selected + 1).min(self.events.len().saturating_sub(1)),
+            Message::Left => self.column = self.column.step(-1),
+            Message::Right => self.column = self.column.step(1),
+            Message::Enter if self.editing.is_some() => self.finish_editing(),
+            Message::Enter => {
+                let multiline = self.column == Column::Notes
+                    && self.events.get(self.selected).is_some_and(|ev| ev.description.contains('\n'));
+                self.start_editing(call)?;
+                if multiline {
+                    return Ok(Some(Extrinsic::ResetRatatui));
+                }
+            }
+            Message::CancelEdit => self.editing = None,
+            Message::KeyTyped(c) => {
+                if let Some(text) = &mut self.editing {
+                    text.push(c);
+                }
+            }
+            Message::Backspace => {
+                if let Some(text) = &mut self.editing {
+                    text.pop();
+                }
+            }
+            Message::EditNote => {
+                let request = EditorRequest {
+                    message: format!("Daily note for {}:", display_date(self.date)),
+                    text: self.note.clone(),
+                };
+                if let Some(note) = edit_in_editor(call, request)? {
+                    self.note = note;
+                }
+                return Ok(Some(Extrinsic::ResetRatatui));
+            }
+        }
+        Ok(None)
+    }
+
+    fn handle_keypresses(&self, event: CEvent) -> SmallVec<[Self::Message; 1]> {
+        match event {
+            CEvent::Key(k) if k.is_press() && k.code.is_char('c') && k.modifiers == KeyModifiers::CONTROL => {
+                [Message::Exit].into()
+            }
+            _ if self.editing.is_some() => match event {
+                CEvent::Key(k) if k.is_press() && k.code.is_enter() => [Message::Enter].into(),
+                CEvent::Key(k) if k.is_press() && k.code.is_esc() => [Message::CancelEdit].into(),
+                CEvent::Key(k) if k.is_press() && k.code.is_backspace() => [Message::Backspace].into(),
+                CEvent::Key(k) if k.is_press() && k.code.as_char().is_some() && !k.modifiers.contains(KeyModifiers::CONTROL) => {
+                    [Message::KeyTyped(k.code.as_char().expect("verified is_some() in condition"))].into()
+                }
+                _ => SmallVec::new(),
+            },
+            CEvent::Key(k) if k.is_press() && k.code.is_char('q') => [Message::Exit].into(),
+            CEvent::Key(k) if k.is_press() && k.code.is_char('s') => [Message::Save].into(),
+            CEvent::Key(k) if k.is_press() && (k.code.is_char('k') || k.code.is_up()) => [Message::Up].into(),
+            CEvent::Key(k) if k.is_press() && (k.code.is_char('j') || k.code.is_down()) => [Message::Down].into(),
+            CEvent::Key(k) if k.is_press() && (k.code.is_char('h') || k.code.is_left()) => [Message::Left].into(),
+            CEvent::Key(k) if k.is_press() && (k.code.is_char('l') || k.code.is_right()) => [Message::Right].into(),
+            CEvent::Key(k) if k.is_press() && k.code.is_enter() => [Message::Enter].into(),
+            CEvent::Key(k) if k.is_press() && k.code.is_char('n') => [Message::EditNote].into(),
+            _ => SmallVec::new(),
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let outer = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Fill(1), Constraint::Length(1)])
+            .split(frame.area());
+        let panels = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Fill(3), Constraint::Fill(1)])
+            .split(outer[1]);
+
+        let total: Duration = self.events.iter().map(Event::duration).sum();
+        frame.render_widget(
+            Line::default().spans([
+                Span::styled(display_date(self.date), THEME.date),
+                Span::raw(format!(" - {} tracked in {} events", format_duration(&total), self.events.len())),
+            ]),
+            outer[0],
+        );
+
+        // gaps between events get rows of their own, so the selected event's row has to be worked out
+        let mut rows = vec![];
+        let mut selected_row = None;
+        let mut previous: Option<&Event> = None;
+        for (i, ev) in self.events.iter().enumerate() {
+            // events running past midnight end the next day, so nothing can come after them
+            let previous_end = previous.filter(|p| p.start_time <= p.end_time).map(|p| p.end_time);
+            let overlaps = previous_end.is_some_and(|end| ev.start_time < end);
+            if let Some(end) = previous_end
+                && end < ev.start_time
+            {
+                rows.push(
+                    Row::new(["".to_owned(), "".to_owned(), format_duration(&(ev.start_time - end)), "(gap)".to_owned()])
+                        .style(THEME.dim(Style::new().italic())),
+                );
+            }
+            if i == self.selected {
+                selected_row = Some(rows.len());
+            }
+            let cells = Column::ALL.map(|column| {
+                let editing = (i == self.selected && column == self.column).then_some(()).and(self.editing.as_ref());
+                match editing {
+                    Some(text) => format!("{text}_"),
+                    None if column == Column::Notes => ev.description.lines().collect::<Vec<_>>().join(" | "),
+                    None => self.cell_text(ev, column),
+                }
+            });
+            let [start, end, category, notes] = cells;
+            let mut style = Style::new();
+            if overlaps {
+                style = style.patch(THEME.error);
+            }
+            if self.changed.contains(&ev.id) {
+                style = style.bold();
+            }
+            rows.push(Row::new([start, end, format_duration(&ev.duration()), category, notes]).style(style));
+            previous = Some(ev);
+        }
+        self.table_state.select(selected_row);
+        self.table_state.select_column(Some(self.column.table_index()));
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(8),
+                Constraint::Length(8),
+                Constraint::Length(8),
+                Constraint::Length(16),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(Row::new(["Start", "End", "Length", "Category", "Notes"]).style(THEME.heading))
+        .row_highlight_style(THEME.selected)
+        .cell_highlight_style(THEME.highlight.not_reversed())
+        .block(Block::bordered().title("Events"));
+        frame.render_stateful_widget(table, panels[0], &mut self.table_state);
+
+        let note = if self.note.is_empty() {
+            Paragraph::new(Span::styled("(none)", THEME.dim(Style::new())))
+        } else {
+            Paragraph::new(self.note.as_str())
+        };
+        frame.render_widget(
+            note.wrap(Wrap { trim: false }).block(Block::bordered().title("Daily note")),
+            panels[1],
+        );
+
+        let hints = match &self.status {
+            Some(status) => Span::styled(status.as_str(), THEME.status),
+            None if self.editing.is_some() => Span::raw("enter: done - esc: cancel"),
+            None => Span::raw(
+                "arrows/hjkl: move - enter: edit - n: edit daily note - s: save and quit - q: quit",
+            ),
+        };
+        let deltas = self.deltas();
+        let unsaved = if deltas.is_empty() {
+            Span::raw("")
+        } else {
+            Span::styled(format!("Unsaved: {} | ", describe_changes(&deltas).join(", ")), THEME.status.bold())
+        };
+        frame.render_widget(Line::default().spans([unsaved, hints]), outer[2]);
+    }
+
+    fn external_function(request: Self::Call) -> Self::Response {
+        inquire::Editor::new(&request.message).with_predefined_text(&request.text).prompt()
+    }
+
+    fn get_output(self) -> Self::Output {
+        if self.saved { self.deltas() } else { vec![] }
+    }
+}
+
+/// A worksheet for correcting a single day: its events in a table that can be edited in place, the
+/// gaps between them, and its daily note. Changes are only made once they're saved.
+pub fn day_main(save_data: SaveData, date: Option<NaiveDate>) -> TaskitResult<Vec<DeltaItem>> {
+    let date = date.unwrap_or_else(|| chrono::Local::now().date_naive());
+    if save_data.locked_until.is_some_and(|locked| locked >= date) {
+        println!("{} is in a closed month, so it can't be changed.", display_date(date));
+        return Ok(vec![]);
+    }
+    let mut events: Vec<Event> = save_data.events.iter().filter(|ev| ev.date == date).cloned().collect();
+    events.sort_by_key(|ev| (ev.start_time, ev.id));
+    let state = State {
+        save_data: &save_data,
+        date,
+        events,
+        changed: HashSet::new(),
+        note: save_data.daily_notes.get(&date).cloned().unwrap_or_default(),
+        selected: 0,
+        column: Column::Start,
+        editing: None,
+        status: None,
+        confirm_discard: false,
+        saved: false,
+        table_state: TableState::default(),
+    };
+    let deltas = state.run()?;
+    print_session_summary(&save_data, &deltas, &[]);
+    Ok(deltas)
+}
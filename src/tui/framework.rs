@@ -1,6 +1,9 @@
-use std::{mem, sync::mpsc, thread, time::Duration};
+use std::{io::stdout, mem, sync::mpsc, thread, time::Duration};
 
-use crossterm::event::{self, Event as CEvent};
+use crossterm::{
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event as CEvent},
+    execute,
+};
 use ratatui::Frame;
 use smallvec::SmallVec;
 
@@ -97,6 +100,9 @@ pub trait TuiState: Sized {
     type Call: Send;
     type Response: Send;
     type Output;
+    /// Whether the terminal should report mouse events. States that break out of the TUI have to
+    /// turn this off themselves while they're out, and it's turned back on by `ResetRatatui`.
+    const MOUSE_CAPTURE: bool = false;
 
     fn handle_message(
         &mut self,
@@ -110,10 +116,13 @@ pub trait TuiState: Sized {
 
     fn run(mut self) -> TaskitResult<Self::Output> {
         let mut terminal = ratatui::init();
+        if Self::MOUSE_CAPTURE {
+            execute!(stdout(), EnableMouseCapture).with(Source::DrawingTui)?;
+        }
 
         let mut messages = vec![];
 
-        thread::scope(|s| {
+        let output = thread::scope(|s| {
             let (keypress_tx, keypress_rx) = mpsc::channel();
             let (inquire_function, inquire_listener) = sync::function(Self::external_function);
             // let (inquire_request_tx, inquire_request_rx) = mpsc::channel::<InquireRequest>();
@@ -159,6 +168,9 @@ pub trait TuiState: Sized {
                             halt = true;
                         }
                         Some(Extrinsic::ResetRatatui) => {
+                            if Self::MOUSE_CAPTURE {
+                                execute!(stdout(), EnableMouseCapture).with(Source::DrawingTui)?;
+                            }
                             terminal.clear().with(Source::DrawingTui)?;
                         }
                         Some(Extrinsic::ResolveAfter(duration, res)) => {
@@ -174,6 +186,11 @@ pub trait TuiState: Sized {
             }
             ratatui::restore();
             Ok(self.get_output())
-        })
+        });
+        // the mouse is also let go of after errors, so the shell isn't left receiving mouse events
+        if Self::MOUSE_CAPTURE {
+            execute!(stdout(), DisableMouseCapture).with(Source::DrawingTui)?;
+        }
+        output
     }
 }
@@ -0,0 +1,56 @@
+//! Matching keypresses against the keys bound in `[keys]` in the config
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Parses a key like "j", "down", or "ctrl+d". Shift is left out, since it's already part of
+/// characters like "E".
+fn parse(key: &str) -> Option<(KeyModifiers, KeyCode)> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = key;
+    // "+" on its own is a key, not a separator
+    while let Some((modifier, after)) = rest.split_once('+').filter(|(_, after)| !after.is_empty()) {
+        modifiers |= match modifier.to_lowercase().as_str() {
+            "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            _ => return None,
+        };
+        rest = after;
+    }
+    let code = match rest.to_lowercase().as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "enter" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "esc" => KeyCode::Esc,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "space" => KeyCode::Char(' '),
+        _ => {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return None,
+            }
+        }
+    };
+    Some((modifiers, code))
+}
+
+/// Whether `event` is one of `keys`. Keys that can't be parsed never match.
+pub fn pressed(keys: &[String], event: &KeyEvent) -> bool {
+    let modifiers = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+    keys.iter()
+        .filter_map(|key| parse(key))
+        .any(|(key_modifiers, code)| key_modifiers == modifiers && code == event.code)
+}
+
+/// The first of `keys`, to show in key hints
+pub fn label(keys: &[String]) -> &str {
+    keys.first().map_or("(unbound)", String::as_str)
+}
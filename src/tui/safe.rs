@@ -0,0 +1,182 @@
+//! Keeping count of the times the dashboard didn't exit cleanly, and the plain-text safe mode
+//! offered once it keeps happening, so that one malformed event can't lock the user out of it
+
+use std::{
+    any::Any,
+    cmp::Reverse,
+    fmt::Write as _,
+    fs, io,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use chrono::{Local, NaiveTime};
+use inquire::Confirm;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{
+        Event, SaveData,
+        error::{Kind, Source, TaskitResult, With},
+    },
+    util::display_date,
+};
+
+/// How many times in a row the dashboard has to go without exiting cleanly before safe mode is
+/// offered
+const CRASHES_BEFORE_SAFE_MODE: u32 = 2;
+
+#[derive(Default, Serialize, Deserialize)]
+struct CrashLog {
+    /// Times in a row that the dashboard was started and didn't exit cleanly
+    unclean_exits: u32,
+    /// Where and why it last panicked, if it did
+    last_panic: Option<String>,
+}
+
+/// Where the dashboard keeps count of its unclean exits, next to the save file
+pub fn crash_log_path(save_path: &Path) -> PathBuf {
+    save_path.with_extension("crashes.json")
+}
+
+/// Where safe mode writes what it found, for troubleshooting or attaching to a bug report
+fn report_path(crash_log: &Path) -> PathBuf {
+    crash_log.with_extension("txt")
+}
+
+/// The crash log, or an empty one if it's missing or unreadable, since it's only ever a hint
+fn read_log(path: &Path) -> CrashLog {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn write_log(path: &Path, log: &CrashLog) -> TaskitResult<()> {
+    let json = serde_json::to_string(log).map_err(|e| Kind::Other(e.into()).with(Source::DrawingTui))?;
+    fs::write(path, json).with(Source::DrawingTui)?;
+    Ok(())
+}
+
+/// Counts the dashboard as having crashed until `finished` is called, and keeps the message of
+/// any panic in the meantime
+pub fn starting(crash_log: &Path) -> TaskitResult<()> {
+    let mut log = read_log(crash_log);
+    log.unclean_exits += 1;
+    write_log(crash_log, &log)?;
+    let path = crash_log.to_owned();
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let mut log = read_log(&path);
+        log.last_panic = Some(info.to_string());
+        // nothing more can be done about it while panicking
+        let _ = write_log(&path, &log);
+        previous(info);
+    }));
+    Ok(())
+}
+
+/// Clears the count of unclean exits, since the dashboard has just exited cleanly
+pub fn finished(crash_log: &Path) -> TaskitResult<()> {
+    match fs::remove_file(crash_log) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.with(Source::DrawingTui).into()),
+        _ => Ok(()),
+    }
+}
+
+/// Whether to start in safe mode, asking first if the dashboard keeps crashing
+pub fn offer(crash_log: &Path) -> TaskitResult<bool> {
+    let log = read_log(crash_log);
+    if log.unclean_exits < CRASHES_BEFORE_SAFE_MODE {
+        return Ok(false);
+    }
+    println!("The dashboard didn't exit cleanly the last {} times it was opened.", log.unclean_exits);
+    if let Some(panic) = &log.last_panic {
+        println!("Last time, it {}", panic.lines().next().unwrap_or_default());
+    }
+    Ok(Confirm::new("Start in safe mode, listing events as plain text?")
+        .with_default(true)
+        .with_help_message("this also writes a report of anything that looks wrong with the save file")
+        .prompt()
+        .with(Source::DrawingTui)?)
+}
+
+/// The message a panic was started with, if it was given one
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "no message".to_owned())
+}
+
+/// What looks wrong with an event without needing to show it
+fn problems(ev: &Event) -> Vec<String> {
+    [("start", ev.start_time), ("end", ev.end_time)]
+        .into_iter()
+        .filter(|(_, time)| NaiveTime::try_from(*time).is_err())
+        .map(|(which, time)| format!("its {which} time isn't on a clock ({}:{:02})", time.hour, time.minute))
+        .collect()
+}
+
+/// Lists every event as plain text, newest first, without any of the dashboard's drawing, and
+/// writes a report of the events that couldn't be shown or look malformed
+pub fn safe_main(save_data: &SaveData, crash_log: &Path) -> TaskitResult<()> {
+    let log = read_log(crash_log);
+    let mut events: Vec<&Event> = save_data.events.iter().collect();
+    events.sort_by_key(|ev| Reverse((ev.date, ev.start_time)));
+
+    // panics while showing an event are reported with it instead of on the terminal
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let mut broken = vec![];
+    for (date, day) in &events.into_iter().chunk_by(|ev| ev.date) {
+        println!("{}", display_date(date));
+        for ev in day {
+            let mut found = problems(ev);
+            match panic::catch_unwind(AssertUnwindSafe(|| ev.to_string())) {
+                Ok(line) => println!("  {line}"),
+                Err(payload) => {
+                    println!("  (event {} couldn't be shown)", ev.id);
+                    found.push(format!("showing it panicked: {}", panic_message(payload.as_ref())));
+                }
+            }
+            if !found.is_empty() {
+                broken.push((ev, found));
+            }
+        }
+    }
+    panic::set_hook(previous);
+
+    let mut report = format!("taskit {} safe mode report, {}\n\n", env!("CARGO_PKG_VERSION"), Local::now().format("%Y-%m-%d %H:%M"));
+    let _ = writeln!(report, "Unclean exits in a row: {}", log.unclean_exits);
+    let _ = writeln!(report, "Last panic: {}", log.last_panic.as_deref().unwrap_or("none recorded"));
+    let _ = writeln!(
+        report,
+        "Events: {}, categories: {}, archived categories: {}, tags: {}\n",
+        save_data.events.len(),
+        save_data.categories.len(),
+        save_data.archived_categories.len(),
+        save_data.tags.len()
+    );
+    if broken.is_empty() {
+        let _ = writeln!(report, "No events look malformed.");
+    }
+    for (ev, found) in &broken {
+        let _ = writeln!(report, "Event {} on {}:", ev.id, ev.date);
+        for problem in found {
+            let _ = writeln!(report, "  - {problem}");
+        }
+        let _ = writeln!(report, "  {ev:?}");
+    }
+    let path = report_path(crash_log);
+    fs::write(&path, report).with(Source::DrawingTui)?;
+    println!();
+    match broken.len() {
+        0 => println!("No events look malformed."),
+        n => println!("{n} event(s) look malformed, and can be fixed with `taskit amend` or `taskit delete-event`."),
+    }
+    println!("A report for troubleshooting was written to {}", path.display());
+    Ok(())
+}
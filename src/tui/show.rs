@@ -2,35 +2,47 @@ use std::{
     cmp::min,
     collections::{BTreeMap, HashMap, HashSet},
     fmt::Display,
+    fs,
     io::stdout,
     iter,
+    ops::Range,
+    path::Path,
     sync::LazyLock,
-    time::{Duration, Instant},
+    time::{self, Instant},
 };
 
-use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+use chrono::{Datelike, Days, Local, NaiveDate, NaiveDateTime, Weekday};
+use clap::ValueEnum;
 use crossterm::{
     cursor::MoveTo,
-    event::{Event as CEvent, KeyModifiers},
+    event::{DisableMouseCapture, Event as CEvent, KeyCode, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
     execute,
     terminal::{Clear, ClearType, disable_raw_mode, enable_raw_mode},
 };
 use inquire::{InquireError, error::InquireResult};
 use itertools::Itertools;
+use regex::Regex;
 use ratatui::{
     Frame,
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Style, Stylize},
     text::{Line, Span, Text},
-    widgets::{Block, Paragraph},
+    widgets::{Bar, BarChart, BarGroup, Block, Clear as ClearWidget, Padding, Paragraph, Sparkline, Tabs},
 };
 use smallvec::SmallVec;
+use unicode_width::UnicodeWidthStr;
 
 use crate::{
     common::{
-        CategoriesPair, DeltaItem, Event, SaveData, error::{Source, TaskitResult, With}, invariants::{Category, Tag}
+        Apply, CategoriesPair, CategoryDetails, DeltaItem, Duration, Event, EventId, EventSource, Goal, SaveData, category_answer, config::CONFIG, get_description_tags, error::{Kind, Source, TaskitError, TaskitResult, With}, index::{TextIndex, fuzzy_match}, invariants::{Category, Tag, add_tag, annotate_event}
     },
-    tui::framework::{self, TuiState, sync::ExternalFunction},
+    export::{ExportFormat, copy_to_clipboard, render_events},
+    input::{DescriptionTagsAutocomplete, amend_event},
+    macros::macro_deltas,
+    profile,
+    report::{category_totals, daily_totals, describe_changes, format_duration, goal_progress, print_session_summary, progress_bar, rolling_average},
+    tui::{calendar::{self, Calendar}, framework::{self, TuiState, sync::ExternalFunction}, keys, safe, theme::THEME},
+    util::{DateRange, display_date, highlight_graphemes, pop_grapheme},
 };
 
 type Extrinsic<'a> = framework::Extrinsic<State<'a>>;
@@ -38,10 +50,17 @@ type Extrinsic<'a> = framework::Extrinsic<State<'a>>;
 // 1 is a bit stupid but it might be what we want
 type MessageVec = SmallVec<[Message; 1]>;
 
+/// Whether `ev` is a press of one of `keys`, from `[keys]` in the config
+fn bound(keys: &[String], ev: &CEvent) -> bool {
+    matches!(ev, CEvent::Key(key_event) if key_event.is_press() && keys::pressed(keys, key_event))
+}
+
 enum Message {
     Exit,
     ScrollDown,
     ScrollUp,
+    /// Scrolls to the next day's header, or to the previous one if negative
+    JumpDay(isize),
     TabLeft,
     TabRight,
     Enter,
@@ -49,7 +68,51 @@ enum Message {
     Backspace,
     FinishFilter,
     CancelFilter,
+    /// Moves the cursor onto the applied filters, or off them
+    FocusFilters,
+    MoveFilterCursor(isize),
+    /// Removes the applied filter under the cursor
+    RemoveFilter,
+    /// Negates the applied filter under the cursor
+    NegateFilter,
+    /// Combines the applied filter under the cursor with the one after it, so that events matching
+    /// either pass
+    OrFilter,
+    ClearFilters,
     BlinkCursor(bool),
+    SelectNext,
+    SelectPrevious,
+    Annotate,
+    Edit,
+    Delete,
+    TogglePercentages,
+    ToggleSparkline,
+    ToggleChart,
+    ToggleLargeLayout,
+    ToggleWeekGrouping,
+    /// Runs the macro at this index in the config
+    RunMacro(usize),
+    /// Switches between the events and the totals in the compact layout
+    SwitchCompactTab,
+    /// Toggles a filter for the category at this position in `quick_categories`
+    QuickFilter(usize),
+    JumpToDate,
+    ExportView,
+    /// Acts like pressing Enter on the header button at this index
+    PressHeader(usize),
+    OpenPalette,
+    ClosePalette,
+    MovePaletteHighlight(isize),
+    RunPaletteCommand,
+    /// Moves the calendar's cursor by this many days
+    MoveCalendar(i64),
+    /// Moves the calendar's cursor by this many months
+    MoveCalendarMonth(i32),
+    CalendarToday,
+    PickDate,
+    CloseDatePicker,
+    /// Closes the dashboard to open it again on another profile's save
+    SwitchProfile,
 }
 
 impl framework::Message for Message {
@@ -59,26 +122,231 @@ impl framework::Message for Message {
 }
 
 struct State<'a> {
+    save_data: &'a SaveData,
     categories: &'a [Category],
     archived_categories: &'a [Category],
+    category_details: &'a HashMap<Category, CategoryDetails>,
+    category_goals: &'a HashMap<Category, Goal>,
     tags: &'a [Tag],
     tag_map: &'a HashMap<Category, HashSet<Tag>>,
+    tag_goals: &'a HashMap<Tag, Goal>,
     daily_notes: &'a HashMap<NaiveDate, String>,
+    skipped_days: &'a BTreeMap<NaiveDate, String>,
     events: Vec<Event>,
+    /// The save's text index, kept up to date with the changes staged to `events`
+    index: TextIndex,
+    /// Position in `events` of the selected event, if any
+    selected_event: Option<usize>,
+    /// Changes made from within the dashboard, returned once it exits
+    staged: Vec<DeltaItem>,
+    /// Tags created by staged changes, which don't exist in the save file yet
+    new_tags: Vec<Tag>,
+    /// Categories created by staged changes, which don't exist in the save file yet
+    new_categories: Vec<Category>,
+    /// Show each aggregated duration as a percentage of the total as well
+    show_percentages: bool,
+    /// Show a sparkline of daily totals above the aggregated durations
+    show_sparkline: bool,
+    /// Show the aggregated durations as a bar chart rather than as text
+    show_chart: bool,
+    /// Show only the events in one full-width panel, with extra spacing and no dimmed text
+    large_layout: bool,
+    /// Group the events by ISO week rather than by day
+    group_by_week: bool,
+    /// Show the aggregated durations rather than the events when the terminal is too narrow for
+    /// both (see `COMPACT_WIDTH`)
+    compact_totals: bool,
     scroll_position: u16,
+    /// The line each day's header was on when the events were last drawn, with the position in
+    /// `events` of the day's first event
+    day_starts: Vec<(u16, usize)>,
+    /// Where each part of the header was when last drawn, so it can be clicked
+    header_areas: Vec<(Rect, HeaderClick)>,
     header_highlight: usize,
     applied_filters: Vec<Filter>,
+    /// Position in `applied_filters` of the filter under the cursor, while it's on them
+    filter_cursor: Option<usize>,
     editing_filter: Option<Filter>,
+    /// The categories with the most tracked time, which the number keys filter for
+    quick_categories: Vec<Category>,
+    palette: Option<Palette>,
+    /// The calendar open for picking a date, and what the date is for
+    date_picker: Option<(DatePurpose, Calendar)>,
+    /// Shown in place of the key hints until the next keypress, e.g. to confirm an export
+    status: Option<String>,
+    /// Every filter applied during the session, for the summary printed on exit
+    filters_used: Vec<String>,
+    /// Where the profiles are, for switching to another one
+    data_dir: &'a Path,
+    /// The profile whose save is open, if any
+    profile: Option<&'a str>,
+    /// The profile picked to switch to, which the dashboard is closed to open: `Some(None)` for
+    /// the save used without a profile
+    switch_to: Option<Option<String>>,
     cursor_blink: bool,
     last_cursor_show_time: Instant,
 }
 
+/// The command palette opened with `:`, which runs actions picked by fuzzy-matching their names
+#[derive(Default)]
+struct Palette {
+    query: String,
+    /// Position of the highlighted command among the matches
+    highlight: usize,
+}
+
+/// What the date picked in the calendar is for
+#[derive(Clone, Copy)]
+enum DatePurpose {
+    StartFilter,
+    EndFilter,
+    Jump,
+}
+
+/// What clicking on part of the header does
+#[derive(Clone, Copy)]
+enum HeaderClick {
+    Button(usize),
+    /// The arrows either side of the one button shown in the compact layout
+    Previous,
+    Next,
+}
+
+/// An action that can be run from the command palette
+#[derive(Clone, Copy)]
+enum Command {
+    Header(usize),
+    SelectNext,
+    SelectPrevious,
+    NextDay,
+    PreviousDay,
+    FocusFilters,
+    JumpToDate,
+    Annotate,
+    Edit,
+    Delete,
+    ExportView,
+    TogglePercentages,
+    ToggleSparkline,
+    ToggleChart,
+    ToggleLargeLayout,
+    ToggleWeekGrouping,
+    Macro(usize),
+    SwitchProfile,
+    Quit,
+}
+
+impl Command {
+    fn all() -> impl Iterator<Item = Command> {
+        (0..HEADER.len()).map(Command::Header).chain([
+            Command::SelectNext,
+            Command::SelectPrevious,
+            Command::NextDay,
+            Command::PreviousDay,
+            Command::FocusFilters,
+            Command::JumpToDate,
+            Command::Annotate,
+            Command::Edit,
+            Command::Delete,
+            Command::ExportView,
+            Command::TogglePercentages,
+            Command::ToggleSparkline,
+            Command::ToggleChart,
+            Command::ToggleLargeLayout,
+            Command::ToggleWeekGrouping,
+        ])
+        .chain((0..CONFIG.macros.len()).map(Command::Macro))
+        .chain([Command::SwitchProfile, Command::Quit])
+    }
+
+    fn name(self) -> String {
+        match self {
+            Command::Header(i) => match &HEADER[i] {
+                HeaderButton::Filter(_) => format!("Add filter: {}", HEADER[i]),
+                HeaderButton::NegateLastFilter => "Negate last filter".to_owned(),
+                HeaderButton::OrLastFilters => "OR the last two filters".to_owned(),
+                HeaderButton::DeleteLastFilter => "Delete last filter".to_owned(),
+                HeaderButton::ClearFilters => "Clear all filters".to_owned(),
+            },
+            Command::SelectNext => "Select next event".to_owned(),
+            Command::SelectPrevious => "Select previous event".to_owned(),
+            Command::NextDay => "Scroll to next day".to_owned(),
+            Command::PreviousDay => "Scroll to previous day".to_owned(),
+            Command::FocusFilters => "Pick a filter to remove".to_owned(),
+            Command::JumpToDate => "Jump to date".to_owned(),
+            Command::Annotate => "Annotate selected event".to_owned(),
+            Command::Edit => "Edit selected event".to_owned(),
+            Command::Delete => "Delete selected event".to_owned(),
+            Command::ExportView => "Export filtered events".to_owned(),
+            Command::TogglePercentages => "Toggle percentages".to_owned(),
+            Command::ToggleSparkline => "Toggle sparkline panel".to_owned(),
+            Command::ToggleChart => "Toggle bar chart".to_owned(),
+            Command::ToggleLargeLayout => "Toggle large layout".to_owned(),
+            Command::ToggleWeekGrouping => "Toggle grouping by week".to_owned(),
+            Command::Macro(i) => format!("Run macro: {}", CONFIG.macros[i].name),
+            Command::SwitchProfile => "Switch profile".to_owned(),
+            Command::Quit => "Quit".to_owned(),
+        }
+    }
+
+    fn message(self) -> Message {
+        match self {
+            Command::Header(i) => Message::PressHeader(i),
+            Command::SelectNext => Message::SelectNext,
+            Command::SelectPrevious => Message::SelectPrevious,
+            Command::NextDay => Message::JumpDay(1),
+            Command::PreviousDay => Message::JumpDay(-1),
+            Command::FocusFilters => Message::FocusFilters,
+            Command::JumpToDate => Message::JumpToDate,
+            Command::Annotate => Message::Annotate,
+            Command::Edit => Message::Edit,
+            Command::Delete => Message::Delete,
+            Command::ExportView => Message::ExportView,
+            Command::TogglePercentages => Message::TogglePercentages,
+            Command::ToggleSparkline => Message::ToggleSparkline,
+            Command::ToggleChart => Message::ToggleChart,
+            Command::ToggleLargeLayout => Message::ToggleLargeLayout,
+            Command::ToggleWeekGrouping => Message::ToggleWeekGrouping,
+            Command::Macro(i) => Message::RunMacro(i),
+            Command::SwitchProfile => Message::SwitchProfile,
+            Command::Quit => Message::Exit,
+        }
+    }
+}
+
+impl Palette {
+    /// Commands matching the query, best first, with the char indices of their names that matched
+    fn matches(&self) -> Vec<(Command, Vec<usize>)> {
+        if self.query.is_empty() {
+            return Command::all().map(|c| (c, vec![])).collect();
+        }
+        Command::all()
+            .filter_map(|c| fuzzy_match(&c.name(), &self.query).map(|(score, indices)| (score, c, indices)))
+            .sorted_by_key(|(score, ..)| -score)
+            .map(|(_, c, indices)| (c, indices))
+            .collect()
+    }
+}
+
+/// Stands for the save used without a profile among the profiles to switch to. Profile names can't
+/// have parentheses, so it can't be taken by one.
+const NO_PROFILE: &str = "(no profile)";
+
+/// Terminals narrower than this get the compact layout, showing one panel at a time
+const COMPACT_WIDTH: u16 = 100;
+
 static HEADER: LazyLock<&[HeaderButton]> = LazyLock::new(|| {
     vec![
         HeaderButton::Filter(FilterKind::StartDate),
         HeaderButton::Filter(FilterKind::EndDate),
         HeaderButton::Filter(FilterKind::Category),
+        HeaderButton::Filter(FilterKind::Source),
         HeaderButton::Filter(FilterKind::Description),
+        HeaderButton::Filter(FilterKind::FuzzyDescription),
+        HeaderButton::Filter(FilterKind::DescriptionRegex),
+        HeaderButton::Filter(FilterKind::Field),
+        HeaderButton::NegateLastFilter,
+        HeaderButton::OrLastFilters,
         HeaderButton::DeleteLastFilter,
         HeaderButton::ClearFilters,
     ]
@@ -89,7 +357,24 @@ enum Filter {
     StartDate(NaiveDate),
     EndDate(NaiveDate),
     Category(Category),
+    Source(EventSource),
     Description(String),
+    FuzzyDescription(String),
+    DescriptionRegex(RegexFilter),
+    /// A custom field, typed as `name=value` to match a value or just `name` to match any event
+    /// that has the field set
+    Field(String),
+    /// Matches the events the inner filter doesn't
+    Not(Box<Filter>),
+    /// Matches the events any of the inner filters do
+    Any(Vec<Filter>),
+}
+
+/// A description filter interpreted as a regular expression. The pattern text is kept separately
+/// so that it can still be edited while it doesn't compile.
+struct RegexFilter {
+    pattern: String,
+    compiled: Result<Regex, regex::Error>,
 }
 
 // TODO reduce duplication between Filter and FilterKind
@@ -97,39 +382,80 @@ enum FilterKind {
     StartDate,
     EndDate,
     Category,
-    Description
+    Source,
+    Description,
+    FuzzyDescription,
+    DescriptionRegex,
+    Field,
 }
 
 enum HeaderButton {
     Filter(FilterKind),
+    NegateLastFilter,
+    OrLastFilters,
     DeleteLastFilter,
     ClearFilters,
 }
 
-enum InquireRequest<'a, 'b, 'c> {
-    DateSelect(&'a str),
+enum InquireRequest<'b, 'c> {
     CategoryFilter {
         categories: &'b [Category],
         archived_categories: &'c [Category],
+        category_details: &'b HashMap<Category, CategoryDetails>,
+    },
+    Description {
+        message: String,
+        tags: Vec<Tag>,
+    },
+    Confirm(String),
+    Text {
+        message: &'static str,
+        help: &'static str,
+    },
+    Select {
+        message: &'static str,
+        options: Vec<String>,
+    },
+    Password(String),
+    /// The prompts of `taskit amend` for the event at `index`
+    EditEvent {
+        save_data: Box<SaveData>,
+        index: usize,
     },
 }
 
 enum InquireResponse {
-    Date(InquireResult<NaiveDate>),
     Category(InquireResult<Category>),
+    Text(InquireResult<String>),
+    Confirm(InquireResult<bool>),
+    Deltas(TaskitResult<Vec<DeltaItem>>),
 }
 
 impl InquireResponse {
-    fn date(self) -> Option<InquireResult<NaiveDate>> {
+    fn category(self) -> Option<InquireResult<Category>> {
         match self {
-            Self::Date(d) => Some(d),
+            Self::Category(c) => Some(c),
             _ => None,
         }
     }
 
-    fn category(self) -> Option<InquireResult<Category>> {
+    fn text(self) -> Option<InquireResult<String>> {
         match self {
-            Self::Category(c) => Some(c),
+            Self::Text(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    fn confirm(self) -> Option<InquireResult<bool>> {
+        match self {
+            Self::Confirm(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    fn deltas(self) -> Option<TaskitResult<Vec<DeltaItem>>> {
+        match self {
+            Self::Deltas(d) => Some(d),
             _ => None,
         }
     }
@@ -141,7 +467,13 @@ impl Display for HeaderButton {
             HeaderButton::Filter(FilterKind::StartDate) => write!(f, "Start Date"),
             HeaderButton::Filter(FilterKind::EndDate) => write!(f, "End Date"),
             HeaderButton::Filter(FilterKind::Category) => write!(f, "Category"),
+            HeaderButton::Filter(FilterKind::Source) => write!(f, "Source"),
             HeaderButton::Filter(FilterKind::Description) => write!(f, "Description"),
+            HeaderButton::Filter(FilterKind::FuzzyDescription) => write!(f, "Fuzzy Search"),
+            HeaderButton::Filter(FilterKind::DescriptionRegex) => write!(f, "Regex"),
+            HeaderButton::Filter(FilterKind::Field) => write!(f, "Field"),
+            HeaderButton::NegateLastFilter => write!(f, "(negate last)"),
+            HeaderButton::OrLastFilters => write!(f, "(or last two)"),
             HeaderButton::DeleteLastFilter => write!(f, "(delete last)"),
             HeaderButton::ClearFilters => write!(f, "(reset)"),
         }
@@ -154,8 +486,79 @@ impl Display for Filter {
             Filter::StartDate(date) => write!(f, "At/After: {date}"),
             Filter::EndDate(date) => write!(f, "At/Before: {date}"),
             Filter::Category(category) => write!(f, "Category: {category}"),
+            Filter::Source(source) => write!(f, "Source: {source}"),
             Filter::Description(description) => write!(f, "Description contains: {description}"),
+            Filter::FuzzyDescription(pattern) => write!(f, "Description fuzzy matches: {pattern}"),
+            Filter::DescriptionRegex(RegexFilter { pattern, compiled: Ok(_) }) => {
+                write!(f, "Description matches regex: {pattern}")
+            }
+            Filter::DescriptionRegex(RegexFilter { pattern, compiled: Err(_) }) => {
+                write!(f, "Description matches regex (invalid): {pattern}")
+            }
+            Filter::Field(text) => write!(f, "Field: {text}"),
+            Filter::Not(filter) => write!(f, "NOT ({filter})"),
+            Filter::Any(filters) => write!(f, "{}", filters.iter().join(" OR ")),
+        }
+    }
+}
+
+impl RegexFilter {
+    fn new(pattern: String) -> Self {
+        let compiled = Regex::new(&pattern);
+        Self { pattern, compiled }
+    }
+}
+
+impl Filter {
+    /// Whether this filter is entered by typing text into the filter panel
+    fn is_typed(&self) -> bool {
+        matches!(
+            self,
+            Filter::Description(_)
+                | Filter::FuzzyDescription(_)
+                | Filter::DescriptionRegex(_)
+                | Filter::Field(_)
+        )
+    }
+
+    /// Applies an edit to the text of a typed filter. Returns false (and does nothing) if the filter
+    /// isn't typed.
+    fn edit_text(&mut self, edit: impl FnOnce(&mut String)) -> bool {
+        match self {
+            Filter::Description(text) | Filter::FuzzyDescription(text) | Filter::Field(text) => edit(text),
+            Filter::DescriptionRegex(regex) => {
+                edit(&mut regex.pattern);
+                regex.compiled = Regex::new(&regex.pattern);
+            }
+            _ => return false,
         }
+        true
+    }
+
+    /// The chars of `description`, by index, that this filter matched, so they can be highlighted
+    fn matched_chars(&self, description: &str) -> Vec<usize> {
+        let byte_ranges: Vec<Range<usize>> = match self {
+            Filter::Description(text) if !text.is_empty() => description
+                .match_indices(text.as_str())
+                .map(|(start, matched)| start..start + matched.len())
+                .collect(),
+            Filter::DescriptionRegex(RegexFilter { compiled: Ok(regex), .. }) => {
+                regex.find_iter(description).map(|matched| matched.range()).collect()
+            }
+            Filter::FuzzyDescription(pattern) => {
+                return fuzzy_match(description, pattern).map(|(_, indices)| indices).unwrap_or_default();
+            }
+            Filter::Any(filters) => return filters.iter().flat_map(|f| f.matched_chars(description)).collect(),
+            // the events left by a negated filter are the ones it didn't match, so there's nothing
+            // to show
+            _ => return vec![],
+        };
+        description
+            .char_indices()
+            .enumerate()
+            .filter(|(_, (byte, _))| byte_ranges.iter().any(|range| range.contains(byte)))
+            .map(|(i, _)| i)
+            .collect()
     }
 }
 
@@ -169,7 +572,17 @@ impl CanFilter for Filter {
             Filter::StartDate(date) => ev.date >= *date,
             Filter::EndDate(date) => ev.date <= *date,
             Filter::Category(category) => ev.category == *category,
+            Filter::Source(source) => ev.source == *source,
             Filter::Description(description) => ev.description.contains(description),
+            Filter::FuzzyDescription(pattern) => fuzzy_match(&ev.description, pattern).is_some(),
+            // an invalid pattern is still being typed, so it shouldn't hide everything meanwhile
+            Filter::DescriptionRegex(regex) => regex
+                .compiled
+                .as_ref()
+                .map_or(true, |re| re.is_match(&ev.description)),
+            Filter::Field(text) => ev.matches_field_filter(text),
+            Filter::Not(filter) => !filter.filter(ev),
+            Filter::Any(filters) => filters.iter().any(|f| f.filter(ev)),
         }
     }
 }
@@ -192,37 +605,404 @@ impl<T: CanFilter> CanFilter for Option<T> {
     }
 }
 
-fn duration_to_string(duration: &TimeDelta) -> String {
-    let mut duration_string = String::new();
-    if duration.num_hours() != 0 {
-        duration_string.push_str(&format!("{}h", duration.num_hours()));
+impl State<'_> {
+    /// Category names are shown in the category's color (see `taskit describe-category`), or in
+    /// the theme's style for categories if it doesn't have one
+    fn category_style(&self, category: &str) -> Style {
+        let color = self
+            .category_details
+            .iter()
+            .find(|(c, _)| c.inner() == category)
+            .and_then(|(_, details)| details.color());
+        THEME.category(color)
+    }
+
+    /// Positions in `events` of the events passing every applied filter (and the one being
+    /// edited), in display order. The text index is used to skip events that can't match a
+    /// description filter.
+    fn filtered_positions(&self) -> Vec<usize> {
+        let candidates = self
+            .applied_filters
+            .iter()
+            .chain(self.editing_filter.iter())
+            .filter_map(|f| match f {
+                Filter::Description(description) => self.index.events.candidates(description),
+                _ => None,
+            })
+            .reduce(|a, b| a.intersection(&b).copied().collect());
+        let filters = (&self.applied_filters, &self.editing_filter);
+        (0..self.events.len())
+            .filter(|&i| candidates.as_ref().is_none_or(|ids| ids.contains(&self.events[i].id)))
+            .filter(|&i| filters.filter(&self.events[i]))
+            .collect()
+    }
+
+    /// Keeps the filter cursor on a filter after some are removed, taking it off them once there
+    /// are none left
+    fn clamp_filter_cursor(&mut self) {
+        self.filter_cursor = self
+            .filter_cursor
+            .filter(|_| !self.applied_filters.is_empty())
+            .map(|cursor| cursor.min(self.applied_filters.len() - 1));
+    }
+
+    /// Negates the applied filter at `i`, or removes its negation if it's already negated
+    fn negate_filter(&mut self, i: usize) {
+        let filter = match self.applied_filters.remove(i) {
+            Filter::Not(filter) => *filter,
+            filter => Filter::Not(Box::new(filter)),
+        };
+        self.filters_used.push(filter.to_string());
+        self.applied_filters.insert(i, filter);
+    }
+
+    /// Combines the applied filter at `i` with the one after it, if there is one, into a filter
+    /// matching events either of them do. Filters that are already combined are merged rather
+    /// than nested.
+    fn or_filters(&mut self, i: usize) {
+        if i + 1 >= self.applied_filters.len() {
+            return;
+        }
+        let filters: Vec<Filter> = self
+            .applied_filters
+            .drain(i..=i + 1)
+            .flat_map(|filter| match filter {
+                Filter::Any(filters) => filters,
+                filter => vec![filter],
+            })
+            .collect();
+        let filter = Filter::Any(filters);
+        self.filters_used.push(filter.to_string());
+        self.applied_filters.insert(i, filter);
+        self.clamp_filter_cursor();
     }
-    if duration.num_minutes() % 60 != 0 {
-        duration_string.push_str(&format!("{}m", duration.num_minutes() % 60));
+
+    fn apply_filter(&mut self, filter: Filter) {
+        self.filters_used.push(filter.to_string());
+        self.applied_filters.push(filter);
+    }
+
+    /// Moves the selection `by` events through the filtered list, selecting the first event if
+    /// nothing is selected yet
+    fn move_selection(&mut self, by: isize) {
+        let positions = self.filtered_positions();
+        let current = self
+            .selected_event
+            .and_then(|selected| positions.iter().position(|&p| p == selected));
+        let next = match current {
+            Some(n) => n.saturating_add_signed(by).min(positions.len().saturating_sub(1)),
+            None => 0,
+        };
+        self.selected_event = positions.get(next).copied();
+    }
+
+    /// Selects the latest filtered event on or before `date`, or the earliest one if there are none
+    fn jump_to_date(&mut self, date: NaiveDate) {
+        // events are sorted newest first
+        let positions = self.filtered_positions();
+        self.selected_event = positions
+            .iter()
+            .find(|&&p| self.events[p].date <= date)
+            .or(positions.last())
+            .copied();
+    }
+
+    /// Asks the user for an annotation to the selected event (outside of ratatui) and stages it
+    fn annotate_selected(
+        &mut self,
+        call: &ExternalFunction<InquireRequest<'_, '_>, InquireResponse>,
+    ) -> TaskitResult<()> {
+        let Some(position) = self.selected_event else {
+            return Ok(());
+        };
+        let text = call
+            .call(InquireRequest::Description {
+                message: format!("Annotation for {}:", self.events[position]),
+                tags: self.tags.iter().chain(&self.new_tags).cloned().collect(),
+            })
+            .text()
+            .expect("requested text");
+        let text = match text {
+            Ok(t) => t,
+            Err(InquireError::OperationCanceled | InquireError::OperationInterrupted) => return Ok(()),
+            Err(e) => Err((e, Source::EditingEntry))?,
+        };
+        let mut deltas = vec![];
+        let mut tags = HashSet::new();
+        let mut created = vec![];
+        for name in get_description_tags(&text) {
+            if let Some(tag) = self.tags.iter().chain(&self.new_tags).find(|t| t.inner() == name) {
+                tags.insert(tag.clone());
+                continue;
+            }
+            let create = call
+                .call(InquireRequest::Confirm(format!(
+                    "Tag #{name} does not currently exist. Create it?"
+                )))
+                .confirm()
+                .expect("requested a confirmation");
+            match create {
+                Ok(true) => {
+                    let (delta, tag) = add_tag(name);
+                    deltas.push(delta);
+                    created.push(tag.clone());
+                    tags.insert(tag);
+                }
+                Ok(false)
+                | Err(InquireError::OperationCanceled | InquireError::OperationInterrupted) => {
+                    return Ok(());
+                }
+                Err(e) => Err((e, Source::CreatingTag))?,
+            }
+        }
+        let delta = annotate_event(self.events[position].id, &text, tags);
+        if let DeltaItem::AnnotateEvent { annotation, tags, .. } = &delta {
+            self.index.remove_event(&self.events[position]);
+            self.events[position].annotate(annotation, tags.clone());
+            self.index.insert_event(&self.events[position]);
+        }
+        self.new_tags.extend(created);
+        self.staged.extend(deltas);
+        self.staged.push(delta);
+        Ok(())
+    }
+
+    /// Asks for new values for the selected event's date, times, category, description, and custom
+    /// fields (outside of ratatui), the same way `taskit amend` does, and stages the changes
+    fn edit_selected(
+        &mut self,
+        call: &ExternalFunction<InquireRequest<'_, '_>, InquireResponse>,
+    ) -> TaskitResult<()> {
+        let Some(position) = self.selected_event else {
+            return Ok(());
+        };
+        // the prompts should start from the event as it is with the changes staged so far
+        let mut save_data = self.save_data.clone();
+        save_data.apply(self.staged.clone())?;
+        let id = self.events[position].id;
+        let index = save_data
+            .events
+            .iter()
+            .position(|ev| ev.id == id)
+            .expect("events shown are in the save");
+        let deltas = call
+            .call(InquireRequest::EditEvent { save_data: Box::new(save_data), index })
+            .deltas()
+            .expect("requested changes");
+        let deltas = match deltas {
+            Ok(deltas) => deltas,
+            Err(TaskitError { kind: Kind::Cancelled, .. }) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for delta in &deltas {
+            if let DeltaItem::ChangeEvent { new_event, .. } = delta {
+                self.index.remove_event(&self.events[position]);
+                self.index.insert_event(new_event);
+                self.events[position] = new_event.clone();
+            }
+        }
+        self.new_tags.extend(deltas.iter().filter_map(DeltaItem::added_tag));
+        self.new_categories.extend(deltas.iter().filter_map(DeltaItem::added_category));
+        // the event may have moved to another day
+        sort_events(&mut self.events);
+        self.selected_event = self.events.iter().position(|ev| ev.id == id);
+        self.staged.extend(deltas);
+        Ok(())
+    }
+
+    /// Stages a macro's changes, showing the events it logs straight away
+    fn run_macro(&mut self, index: usize) -> TaskitResult<()> {
+        let macro_ = &CONFIG.macros[index];
+        let mut save_data = self.save_data.clone();
+        save_data.apply(self.staged.clone())?;
+        let deltas = match macro_deltas(&save_data, macro_) {
+            Ok(deltas) => deltas,
+            Err(e) => {
+                self.status = Some(e.to_string());
+                return Ok(());
+            }
+        };
+        // new events are shown with the ids they'll get once saved, so they can be edited or
+        // deleted like the others
+        let known: HashSet<EventId> = save_data.events.iter().map(|ev| ev.id).collect();
+        save_data.apply(deltas.clone())?;
+        for ev in save_data.events.into_iter().filter(|ev| !known.contains(&ev.id)) {
+            self.index.insert_event(&ev);
+            self.events.push(ev);
+        }
+        let selected = self.selected_event.map(|position| self.events[position].id);
+        sort_events(&mut self.events);
+        self.selected_event = selected.and_then(|id| self.events.iter().position(|ev| ev.id == id));
+        self.status = Some(format!("{}: {}", macro_.name, describe_changes(&deltas).join(", ")));
+        self.staged.extend(deltas);
+        Ok(())
+    }
+
+    /// Asks for confirmation (outside of ratatui), then stages deleting the selected event and
+    /// selects the one after it
+    fn delete_selected(
+        &mut self,
+        call: &ExternalFunction<InquireRequest<'_, '_>, InquireResponse>,
+    ) -> TaskitResult<()> {
+        let Some(position) = self.selected_event else {
+            return Ok(());
+        };
+        let confirm = call
+            .call(InquireRequest::Confirm(format!("Delete this event? {}", self.events[position])))
+            .confirm()
+            .expect("requested a confirmation");
+        match confirm {
+            Ok(true) => {}
+            Ok(false) | Err(InquireError::OperationCanceled | InquireError::OperationInterrupted) => return Ok(()),
+            Err(e) => Err((e, Source::ConfirmingDelete))?,
+        }
+        let shown = self.filtered_positions().iter().position(|&p| p == position);
+        let event = self.events.remove(position);
+        self.staged.push(DeltaItem::DeleteEvent(event.id));
+        self.index.remove_event(&event);
+        let positions = self.filtered_positions();
+        self.selected_event = shown.and_then(|n| positions.get(n).or(positions.last())).copied();
+        Ok(())
+    }
+
+    /// Asks which of `profiles` to switch to, and for its passphrase if it has one. Returns a
+    /// status message if the switch didn't happen for any reason but being cancelled.
+    fn pick_profile(
+        &mut self,
+        call: &ExternalFunction<InquireRequest<'_, '_>, InquireResponse>,
+        profiles: Vec<String>,
+    ) -> TaskitResult<Option<String>> {
+        let name = call
+            .call(InquireRequest::Select { message: "Switch to profile:", options: profiles })
+            .text()
+            .expect("requested a selection");
+        let name = match name {
+            Ok(name) => name,
+            Err(InquireError::OperationCanceled | InquireError::OperationInterrupted) => return Ok(None),
+            Err(e) => Err((e, Source::OpeningProfile))?,
+        };
+        if name == NO_PROFILE {
+            self.switch_to = Some(None);
+            return Ok(None);
+        }
+        let dir = profile::profile_dir(self.data_dir, &name);
+        if profile::has_passphrase(&dir) {
+            let passphrase = call
+                .call(InquireRequest::Password(format!("Passphrase for profile {name}:")))
+                .text()
+                .expect("requested a passphrase");
+            let passphrase = match passphrase {
+                Ok(passphrase) => passphrase,
+                Err(InquireError::OperationCanceled | InquireError::OperationInterrupted) => return Ok(None),
+                Err(e) => Err((e, Source::OpeningProfile))?,
+            };
+            if !profile::check_passphrase(&dir, &passphrase)? {
+                return Ok(Some(format!("Wrong passphrase for profile {name}")));
+            }
+        }
+        self.switch_to = Some(Some(name));
+        Ok(None)
+    }
+
+    /// Asks for a format and destination (outside of ratatui), then exports the filtered events.
+    /// Returns a message describing what happened, or None if the export was cancelled.
+    fn export_view(
+        &self,
+        call: &ExternalFunction<InquireRequest<'_, '_>, InquireResponse>,
+    ) -> TaskitResult<Option<String>> {
+        let format = call
+            .call(InquireRequest::Select {
+                message: "Export format:",
+                options: ExportFormat::value_variants().iter().map(|f| f.name().to_owned()).collect(),
+            })
+            .text()
+            .expect("requested a selection");
+        let format = match format {
+            Ok(name) => *ExportFormat::value_variants()
+                .iter()
+                .find(|f| f.name() == name)
+                .expect("selected from the format names"),
+            Err(InquireError::OperationCanceled | InquireError::OperationInterrupted) => return Ok(None),
+            Err(e) => Err((e, Source::Exporting))?,
+        };
+        let path = call
+            .call(InquireRequest::Text {
+                message: "Write to file:",
+                help: "leave empty to copy to the clipboard",
+            })
+            .text()
+            .expect("requested text");
+        let path = match path {
+            Ok(p) => p,
+            Err(InquireError::OperationCanceled | InquireError::OperationInterrupted) => return Ok(None),
+            Err(e) => Err((e, Source::Exporting))?,
+        };
+        let events: Vec<&Event> = self.filtered_positions().iter().map(|&i| &self.events[i]).collect();
+        let count = events.len();
+        let document = render_events(events, format);
+        let status = if path.trim().is_empty() {
+            match copy_to_clipboard(&document) {
+                Ok(()) => format!("Copied {count} events to the clipboard as {}", format.name()),
+                Err(e) => format!("Couldn't copy to the clipboard: {e}"),
+            }
+        } else {
+            match fs::write(path.trim(), document) {
+                Ok(()) => format!("Wrote {count} events to {}", path.trim()),
+                Err(e) => format!("Couldn't write to {}: {e}", path.trim()),
+            }
+        };
+        Ok(Some(status))
+    }
+
+    /// Splits a description into lines of spans, highlighting the characters matched by description
+    /// filters
+    fn description_lines(&self, description: &str) -> Vec<Vec<Span<'static>>> {
+        let matched: HashSet<usize> = self
+            .applied_filters
+            .iter()
+            .chain(self.editing_filter.iter())
+            .flat_map(|f| f.matched_chars(description))
+            .collect();
+        let mut lines = vec![vec![]];
+        let chunks = highlight_graphemes(description, |i| matched.contains(&i))
+            .chunk_by(|&(g, highlighted)| (g == "\n" || g == "\r\n", highlighted));
+        for ((newline, highlighted), chunk) in &chunks {
+            if newline {
+                lines.extend(chunk.map(|_| vec![]));
+                continue;
+            }
+            let text: String = chunk.map(|(g, _)| g).collect();
+            let line = lines.last_mut().expect("starts nonempty");
+            if highlighted {
+                line.push(Span::styled(text, THEME.highlight));
+            } else {
+                line.push(Span::raw(text));
+            }
+        }
+        lines
     }
-    duration_string
 }
 
 impl<'a> framework::TuiState for State<'a> {
     type Message = Message;
-    type Call = InquireRequest<'static, 'a, 'a>;
+    type Call = InquireRequest<'a, 'a>;
     type Response = InquireResponse;
-    type Output = Vec<DeltaItem>;
+    /// Staged changes, descriptions of the filters used, and the profile to switch to
+    type Output = (Vec<DeltaItem>, Vec<String>, Option<Option<String>>);
+    const MOUSE_CAPTURE: bool = true;
 
     fn external_function(request: Self::Call) -> Self::Response {
         match request {
-            InquireRequest::DateSelect(s) => {
-                InquireResponse::Date(inquire::DateSelect::new(s).prompt())
-            }
-
             InquireRequest::CategoryFilter {
                 categories,
                 archived_categories,
+                category_details,
             } => InquireResponse::Category(
                 inquire::Text::new("Select a category:")
-                    .with_autocomplete(CategoriesPair(categories, archived_categories))
-                    .with_validator(CategoriesPair(categories, archived_categories))
+                    .with_autocomplete(CategoriesPair(categories, archived_categories, category_details))
+                    .with_validator(CategoriesPair(categories, archived_categories, category_details))
                     .prompt()
+                    .map(|answer| category_answer(answer, category_details))
                     .map(|category| categories.iter()
                         .chain(archived_categories.iter())
                         .find(|c| c.inner() == category)
@@ -230,6 +1010,32 @@ impl<'a> framework::TuiState for State<'a> {
                         .clone()
                     ),
             ),
+
+            InquireRequest::Description { message, tags } => InquireResponse::Text(
+                inquire::Text::new(&message)
+                    .with_autocomplete(DescriptionTagsAutocomplete(&tags))
+                    .prompt(),
+            ),
+
+            InquireRequest::Confirm(message) => {
+                InquireResponse::Confirm(inquire::Confirm::new(&message).prompt())
+            }
+
+            InquireRequest::Text { message, help } => {
+                InquireResponse::Text(inquire::Text::new(message).with_help_message(help).prompt())
+            }
+
+            InquireRequest::Select { message, options } => {
+                InquireResponse::Text(inquire::Select::new(message, options).prompt())
+            }
+
+            InquireRequest::Password(message) => {
+                InquireResponse::Text(inquire::Password::new(&message).without_confirmation().prompt())
+            }
+
+            InquireRequest::EditEvent { save_data, index } => {
+                InquireResponse::Deltas(amend_event(&save_data, index))
+            }
         }
     }
 
@@ -238,65 +1044,53 @@ impl<'a> framework::TuiState for State<'a> {
         message: Self::Message,
         call: &ExternalFunction<Self::Call, Self::Response>,
     ) -> TaskitResult<Option<framework::Extrinsic<Self>>> {
+        if !matches!(message, Message::BlinkCursor(_)) {
+            self.status = None;
+        }
         match message {
             Message::Exit => return Ok(Some(Extrinsic::Halt)),
             Message::ScrollDown => self.scroll_position = self.scroll_position.saturating_add(3),
             Message::ScrollUp => self.scroll_position = self.scroll_position.saturating_sub(3),
+            Message::JumpDay(by) => {
+                let target = if by > 0 {
+                    self.day_starts.iter().find(|(line, _)| *line > self.scroll_position)
+                } else {
+                    self.day_starts.iter().rev().find(|(line, _)| *line < self.scroll_position)
+                };
+                if let Some(&(line, position)) = target {
+                    self.scroll_position = line;
+                    // otherwise the selection would pull the view straight back to it
+                    if self.selected_event.is_some() {
+                        self.selected_event = Some(position);
+                    }
+                }
+            }
             Message::TabLeft => self.header_highlight = self.header_highlight.saturating_sub(1),
             Message::TabRight => {
                 self.header_highlight = min(self.header_highlight + 1, HEADER.len() - 1)
             }
+            Message::PressHeader(i) => {
+                self.header_highlight = i;
+                return self.handle_message(Message::Enter, call);
+            }
             Message::Enter => {
                 match HEADER[self.header_highlight] {
                     HeaderButton::Filter(FilterKind::StartDate) => {
-                        // temporarily breaking out of ratatui
-                        execute!(stdout(), Clear(ClearType::All), MoveTo(0, 0))
-                            .with(Source::DrawingTui)?;
-                        disable_raw_mode().with(Source::DrawingTui)?;
-                        let  date = call
-                            .call(InquireRequest::DateSelect("Start date filter:"))
-                            .date()
-                            .expect("requested a date");
-                        let date = match date {
-                            Ok(d) => Some(d),
-                            Err(InquireError::OperationCanceled) => None,
-                            Err(e) => Err((e, Source::SettingFilter))?,
-                        };
-                        enable_raw_mode().with(Source::DrawingTui)?;
-                        if let Some(date) = date {
-                            self.applied_filters.push(Filter::StartDate(date));
-                        }
-                        return Ok(Some(Extrinsic::ResetRatatui));
+                        self.date_picker = Some((DatePurpose::StartFilter, Calendar::new(Local::now().date_naive())));
                     }
                     HeaderButton::Filter(FilterKind::EndDate) => {
-                        // temporarily breaking out of ratatui
-                        execute!(stdout(), Clear(ClearType::All), MoveTo(0, 0))
-                            .with(Source::DrawingTui)?;
-                        disable_raw_mode().with(Source::DrawingTui)?;
-                        let date = call
-                            .call(InquireRequest::DateSelect("End date filter:"))
-                            .date()
-                            .expect("requested a date");
-                        let date = match date {
-                            Ok(d) => Some(d),
-                            Err(InquireError::OperationCanceled) => None,
-                            Err(e) => Err((e, Source::SettingFilter))?,
-                        };
-                        enable_raw_mode().with(Source::DrawingTui)?;
-                        if let Some(date) = date {
-                            self.applied_filters.push(Filter::EndDate(date));
-                        }
-                        return Ok(Some(Extrinsic::ResetRatatui));
+                        self.date_picker = Some((DatePurpose::EndFilter, Calendar::new(Local::now().date_naive())));
                     }
                     HeaderButton::Filter(FilterKind::Category) => {
                         // temporarily breaking out of ratatui
-                        execute!(stdout(), Clear(ClearType::All), MoveTo(0, 0))
+                        execute!(stdout(), DisableMouseCapture, Clear(ClearType::All), MoveTo(0, 0))
                             .with(Source::DrawingTui)?;
                         disable_raw_mode().with(Source::DrawingTui)?;
                         let category = call
                             .call(InquireRequest::CategoryFilter {
                                 categories: self.categories,
                                 archived_categories: self.archived_categories,
+                                category_details: self.category_details,
                             })
                             .category()
                             .expect("requested a category");
@@ -307,50 +1101,286 @@ impl<'a> framework::TuiState for State<'a> {
                         };
                         enable_raw_mode().with(Source::DrawingTui)?;
                         if let Some(category) = category {
-                            self.applied_filters.push(Filter::Category(category));
+                            self.apply_filter(Filter::Category(category));
+                        }
+                        return Ok(Some(Extrinsic::ResetRatatui));
+                    }
+                    HeaderButton::Filter(FilterKind::Source) => {
+                        let options: Vec<String> = self
+                            .events
+                            .iter()
+                            .map(|ev| ev.source.to_string())
+                            .unique()
+                            .sorted()
+                            .collect();
+                        // temporarily breaking out of ratatui
+                        execute!(stdout(), DisableMouseCapture, Clear(ClearType::All), MoveTo(0, 0))
+                            .with(Source::DrawingTui)?;
+                        disable_raw_mode().with(Source::DrawingTui)?;
+                        let source = call
+                            .call(InquireRequest::Select { message: "Select a source:", options })
+                            .text()
+                            .expect("requested a selection");
+                        let source = match source {
+                            Ok(s) => Some(s),
+                            Err(InquireError::OperationCanceled) => None,
+                            Err(e) => Err((e, Source::SettingFilter))?,
+                        };
+                        enable_raw_mode().with(Source::DrawingTui)?;
+                        if let Some(source) = source {
+                            self.apply_filter(Filter::Source(source.into()));
                         }
                         return Ok(Some(Extrinsic::ResetRatatui));
                     }
                     HeaderButton::Filter(FilterKind::Description) => {
                         self.editing_filter = Some(Filter::Description(String::new()))
                     }
-                    HeaderButton::ClearFilters => self.applied_filters.clear(),
+                    HeaderButton::Filter(FilterKind::FuzzyDescription) => {
+                        self.editing_filter = Some(Filter::FuzzyDescription(String::new()))
+                    }
+                    HeaderButton::Filter(FilterKind::DescriptionRegex) => {
+                        self.editing_filter = Some(Filter::DescriptionRegex(RegexFilter::new(String::new())))
+                    }
+                    HeaderButton::Filter(FilterKind::Field) => self.editing_filter = Some(Filter::Field(String::new())),
+                    HeaderButton::ClearFilters => return self.handle_message(Message::ClearFilters, call),
+                    HeaderButton::NegateLastFilter => {
+                        if let Some(last) = self.applied_filters.len().checked_sub(1) {
+                            self.negate_filter(last);
+                        }
+                    }
+                    HeaderButton::OrLastFilters => {
+                        if let Some(second_last) = self.applied_filters.len().checked_sub(2) {
+                            self.or_filters(second_last);
+                        }
+                    }
                     HeaderButton::DeleteLastFilter => {
                         self.applied_filters.pop();
+                        self.clamp_filter_cursor();
                     }
                 }
             }
+            Message::KeyTyped(c) if self.palette.is_some() => {
+                let palette = self.palette.as_mut().expect("checked in guard");
+                palette.query.push(c);
+                palette.highlight = 0;
+            }
+            Message::Backspace if self.palette.is_some() => {
+                let palette = self.palette.as_mut().expect("checked in guard");
+                pop_grapheme(&mut palette.query);
+                palette.highlight = 0;
+            }
             Message::KeyTyped(c) => {
-                if let Some(Filter::Description(ref mut cat)) = self.editing_filter {
+                if let Some(filter) = &mut self.editing_filter
+                    && filter.edit_text(|text| text.push(c))
+                {
                     self.last_cursor_show_time = Instant::now();
                     self.cursor_blink = true;
-                    cat.push(c);
                 }
             }
             Message::Backspace => {
-                if let Some(Filter::Description(ref mut cat)) = self.editing_filter {
+                if let Some(filter) = &mut self.editing_filter
+                    && filter.edit_text(pop_grapheme)
+                {
                     self.last_cursor_show_time = Instant::now();
                     self.cursor_blink = true;
-                    cat.pop();
                 }
             }
             Message::FinishFilter => {
-                if let Some(fil) = self.editing_filter.take() {
-                    self.applied_filters.push(fil);
+                // an invalid regex stays in the editor, where its error is displayed
+                if let Some(Filter::DescriptionRegex(RegexFilter { compiled: Err(_), .. })) =
+                    self.editing_filter
+                {
+                } else if let Some(fil) = self.editing_filter.take() {
+                    self.apply_filter(fil);
                 }
             }
             Message::CancelFilter => {
                 self.editing_filter = None;
             }
+            Message::FocusFilters => {
+                self.filter_cursor = match self.filter_cursor {
+                    None if !self.applied_filters.is_empty() => Some(0),
+                    _ => None,
+                };
+            }
+            Message::MoveFilterCursor(by) => {
+                if let Some(cursor) = &mut self.filter_cursor {
+                    *cursor = cursor.saturating_add_signed(by).min(self.applied_filters.len() - 1);
+                }
+            }
+            Message::RemoveFilter => {
+                if let Some(cursor) = self.filter_cursor {
+                    self.applied_filters.remove(cursor);
+                    self.clamp_filter_cursor();
+                }
+            }
+            Message::NegateFilter => {
+                if let Some(cursor) = self.filter_cursor {
+                    self.negate_filter(cursor);
+                }
+            }
+            Message::OrFilter => {
+                if let Some(cursor) = self.filter_cursor {
+                    self.or_filters(cursor);
+                }
+            }
+            Message::ClearFilters => {
+                self.applied_filters.clear();
+                self.filter_cursor = None;
+            }
+            Message::QuickFilter(n) => {
+                if let Some(category) = self.quick_categories.get(n).cloned() {
+                    let active = self.applied_filters.iter().any(|f| matches!(f, Filter::Category(c) if *c == category));
+                    // events only have one category, so any other category filter would hide everything
+                    self.applied_filters.retain(|f| !matches!(f, Filter::Category(_)));
+                    self.clamp_filter_cursor();
+                    if !active {
+                        self.apply_filter(Filter::Category(category));
+                    }
+                }
+            }
+            Message::TogglePercentages => self.show_percentages = !self.show_percentages,
+            Message::ToggleSparkline => self.show_sparkline = !self.show_sparkline,
+            Message::ToggleChart => self.show_chart = !self.show_chart,
+            Message::SwitchCompactTab => self.compact_totals = !self.compact_totals,
+            Message::ToggleLargeLayout => {
+                self.large_layout = !self.large_layout;
+                self.scroll_position = 0;
+            }
+            Message::ToggleWeekGrouping => {
+                self.group_by_week = !self.group_by_week;
+                self.scroll_position = 0;
+            }
+            Message::RunMacro(i) => self.run_macro(i)?,
+            Message::SelectNext => self.move_selection(1),
+            Message::SelectPrevious => self.move_selection(-1),
+            Message::OpenPalette => self.palette = Some(Palette::default()),
+            Message::ClosePalette => self.palette = None,
+            Message::MovePaletteHighlight(by) => {
+                if let Some(palette) = &mut self.palette {
+                    let last = palette.matches().len().saturating_sub(1);
+                    palette.highlight = palette.highlight.saturating_add_signed(by).min(last);
+                }
+            }
+            Message::RunPaletteCommand => {
+                let Some(palette) = self.palette.take() else {
+                    return Ok(None);
+                };
+                if let Some((command, _)) = palette.matches().into_iter().nth(palette.highlight) {
+                    return self.handle_message(command.message(), call);
+                }
+            }
+            Message::ExportView => {
+                // temporarily breaking out of ratatui
+                execute!(stdout(), DisableMouseCapture, Clear(ClearType::All), MoveTo(0, 0))
+                    .with(Source::DrawingTui)?;
+                disable_raw_mode().with(Source::DrawingTui)?;
+                let status = self.export_view(call);
+                enable_raw_mode().with(Source::DrawingTui)?;
+                self.status = status?;
+                return Ok(Some(Extrinsic::ResetRatatui));
+            }
+            Message::SwitchProfile => {
+                let others: Vec<String> = self
+                    .profile
+                    .map(|_| NO_PROFILE.to_owned())
+                    .into_iter()
+                    .chain(profile::names(self.data_dir).into_iter().filter(|name| self.profile != Some(name.as_str())))
+                    .collect();
+                if others.is_empty() {
+                    self.status = Some("No other profiles to switch to (see `taskit profile create`)".to_owned());
+                    return Ok(None);
+                }
+                // temporarily breaking out of ratatui
+                execute!(stdout(), DisableMouseCapture, Clear(ClearType::All), MoveTo(0, 0))
+                    .with(Source::DrawingTui)?;
+                disable_raw_mode().with(Source::DrawingTui)?;
+                let picked = self.pick_profile(call, others);
+                enable_raw_mode().with(Source::DrawingTui)?;
+                self.status = picked?;
+                if self.switch_to.is_some() {
+                    return Ok(Some(Extrinsic::Halt));
+                }
+                return Ok(Some(Extrinsic::ResetRatatui));
+            }
+            Message::JumpToDate => {
+                let date = self.selected_event.map_or_else(|| Local::now().date_naive(), |p| self.events[p].date);
+                self.date_picker = Some((DatePurpose::Jump, Calendar::new(date)));
+            }
+            Message::MoveCalendar(days) => {
+                if let Some((_, calendar)) = &mut self.date_picker {
+                    calendar.move_days(days);
+                }
+            }
+            Message::MoveCalendarMonth(months) => {
+                if let Some((_, calendar)) = &mut self.date_picker {
+                    calendar.move_months(months);
+                }
+            }
+            Message::CalendarToday => {
+                if let Some((_, calendar)) = &mut self.date_picker {
+                    calendar.today();
+                }
+            }
+            Message::CloseDatePicker => self.date_picker = None,
+            Message::PickDate => {
+                let Some((purpose, calendar)) = self.date_picker.take() else {
+                    return Ok(None);
+                };
+                match purpose {
+                    DatePurpose::StartFilter => self.apply_filter(Filter::StartDate(calendar.cursor)),
+                    DatePurpose::EndFilter => self.apply_filter(Filter::EndDate(calendar.cursor)),
+                    DatePurpose::Jump => self.jump_to_date(calendar.cursor),
+                }
+            }
+            Message::Edit => {
+                if self.selected_event.is_none() {
+                    return Ok(None);
+                }
+                // temporarily breaking out of ratatui
+                execute!(stdout(), DisableMouseCapture, Clear(ClearType::All), MoveTo(0, 0))
+                    .with(Source::DrawingTui)?;
+                disable_raw_mode().with(Source::DrawingTui)?;
+                let edited = self.edit_selected(call);
+                enable_raw_mode().with(Source::DrawingTui)?;
+                edited?;
+                return Ok(Some(Extrinsic::ResetRatatui));
+            }
+            Message::Delete => {
+                if self.selected_event.is_none() {
+                    return Ok(None);
+                }
+                // temporarily breaking out of ratatui
+                execute!(stdout(), DisableMouseCapture, Clear(ClearType::All), MoveTo(0, 0))
+                    .with(Source::DrawingTui)?;
+                disable_raw_mode().with(Source::DrawingTui)?;
+                let deleted = self.delete_selected(call);
+                enable_raw_mode().with(Source::DrawingTui)?;
+                deleted?;
+                return Ok(Some(Extrinsic::ResetRatatui));
+            }
+            Message::Annotate => {
+                if self.selected_event.is_none() {
+                    return Ok(None);
+                }
+                // temporarily breaking out of ratatui
+                execute!(stdout(), DisableMouseCapture, Clear(ClearType::All), MoveTo(0, 0))
+                    .with(Source::DrawingTui)?;
+                disable_raw_mode().with(Source::DrawingTui)?;
+                let annotated = self.annotate_selected(call);
+                enable_raw_mode().with(Source::DrawingTui)?;
+                annotated?;
+                return Ok(Some(Extrinsic::ResetRatatui));
+            }
             Message::BlinkCursor(real) => {
                 if real {
                     self.cursor_blink = !self.cursor_blink;
                 }
                 return Ok(Some(Extrinsic::ResolveAfter(
-                    Duration::from_millis(500),
+                    time::Duration::from_millis(500),
                     Box::new(|state| {
                         [Message::BlinkCursor(
-                            state.last_cursor_show_time.elapsed() > Duration::from_millis(500),
+                            state.last_cursor_show_time.elapsed() > time::Duration::from_millis(500),
                         )]
                         .into()
                     }),
@@ -361,6 +1391,7 @@ impl<'a> framework::TuiState for State<'a> {
     }
 
     fn handle_keypresses(&self, ev: CEvent) -> MessageVec {
+        let keys = &CONFIG.keys;
         match ev {
             CEvent::Key(key_event)
                 if key_event.is_press()
@@ -369,148 +1400,284 @@ impl<'a> framework::TuiState for State<'a> {
             {
                 [Message::Exit].into()
             }
-            CEvent::Key(key_event) if key_event.is_press() && key_event.code.is_down() => {
-                [Message::ScrollDown].into()
-            }
-            CEvent::Key(key_event) if key_event.is_press() && key_event.code.is_up() => {
-                [Message::ScrollUp].into()
-            }
-            _ => {
-                if let Some(Filter::Description(_)) = self.editing_filter {
-                    match ev {
-                        CEvent::Key(key_event)
-                            if key_event.is_press() && key_event.code.is_backspace() =>
-                        {
-                            [Message::Backspace].into()
-                        }
-                        CEvent::Key(key_event)
-                            if key_event.is_press() && key_event.code.is_enter() =>
-                        {
-                            [Message::FinishFilter].into()
-                        }
-                        CEvent::Key(key_event)
-                            if key_event.is_press() && key_event.code.is_esc() =>
-                        {
-                            [Message::CancelFilter].into()
-                        }
-                        CEvent::Key(key_event)
-                            if key_event.is_press() && key_event.code.as_char().is_some() =>
-                        {
-                            [Message::KeyTyped(
-                                key_event
-                                    .code
-                                    .as_char()
-                                    .expect("verified is_some() in condition"),
-                            )]
-                            .into()
-                        }
-                        _ => SmallVec::new(),
-                    }
-                } else {
-                    match ev {
-                        CEvent::Key(key_event)
-                            if key_event.is_press() && key_event.code.is_char('q') =>
-                        {
-                            [Message::Exit].into()
-                        }
-                        CEvent::Key(key_event)
-                            if key_event.is_press() && key_event.code.is_left() =>
-                        {
-                            [Message::TabLeft].into()
-                        }
-                        CEvent::Key(key_event)
-                            if key_event.is_press() && key_event.code.is_right() =>
-                        {
-                            [Message::TabRight].into()
-                        }
-                        CEvent::Key(key_event)
-                            if key_event.is_press() && key_event.code.is_enter() =>
-                        {
-                            [Message::Enter].into()
-                        }
-                        _ => SmallVec::new(),
-                    }
+            _ if self.date_picker.is_some() => match ev {
+                CEvent::Key(key_event) if key_event.is_press() => match key_event.code {
+                    KeyCode::Esc => [Message::CloseDatePicker].into(),
+                    KeyCode::Enter => [Message::PickDate].into(),
+                    KeyCode::Left | KeyCode::Char('h') => [Message::MoveCalendar(-1)].into(),
+                    KeyCode::Right | KeyCode::Char('l') => [Message::MoveCalendar(1)].into(),
+                    KeyCode::Up | KeyCode::Char('k') => [Message::MoveCalendar(-7)].into(),
+                    KeyCode::Down | KeyCode::Char('j') => [Message::MoveCalendar(7)].into(),
+                    KeyCode::PageUp | KeyCode::Char('[') => [Message::MoveCalendarMonth(-1)].into(),
+                    KeyCode::PageDown | KeyCode::Char(']') => [Message::MoveCalendarMonth(1)].into(),
+                    KeyCode::Char('t') => [Message::CalendarToday].into(),
+                    _ => SmallVec::new(),
+                },
+                _ => SmallVec::new(),
+            },
+            _ if self.palette.is_some() => match ev {
+                CEvent::Key(key_event) if key_event.is_press() && key_event.code.is_esc() => {
+                    [Message::ClosePalette].into()
+                }
+                CEvent::Key(key_event) if key_event.is_press() && key_event.code.is_enter() => {
+                    [Message::RunPaletteCommand].into()
+                }
+                CEvent::Key(key_event) if key_event.is_press() && key_event.code.is_down() => {
+                    [Message::MovePaletteHighlight(1)].into()
+                }
+                CEvent::Key(key_event) if key_event.is_press() && key_event.code.is_up() => {
+                    [Message::MovePaletteHighlight(-1)].into()
+                }
+                CEvent::Key(key_event) if key_event.is_press() && key_event.code.is_backspace() => {
+                    [Message::Backspace].into()
                 }
+                CEvent::Key(key_event) if key_event.is_press() && key_event.code.as_char().is_some() => {
+                    [Message::KeyTyped(key_event.code.as_char().expect("verified is_some() in condition"))].into()
+                }
+                _ => SmallVec::new(),
+            },
+            _ if self.filter_cursor.is_some() => match ev {
+                CEvent::Key(key_event) if key_event.is_press() && key_event.code.is_esc() => {
+                    [Message::FocusFilters].into()
+                }
+                _ if bound(&keys.filters, &ev) => [Message::FocusFilters].into(),
+                _ if bound(&keys.scroll_down, &ev) || bound(&keys.next_event, &ev) => {
+                    [Message::MoveFilterCursor(1)].into()
+                }
+                _ if bound(&keys.scroll_up, &ev) || bound(&keys.previous_event, &ev) => {
+                    [Message::MoveFilterCursor(-1)].into()
+                }
+                CEvent::Key(key_event)
+                    if key_event.is_press() && (key_event.code.is_backspace() || key_event.code.is_delete()) =>
+                {
+                    [Message::RemoveFilter].into()
+                }
+                _ if bound(&keys.delete, &ev) => [Message::RemoveFilter].into(),
+                _ if bound(&keys.negate_filter, &ev) => [Message::NegateFilter].into(),
+                _ if bound(&keys.or_filter, &ev) => [Message::OrFilter].into(),
+                _ if bound(&keys.clear_filters, &ev) => [Message::ClearFilters].into(),
+                _ if bound(&keys.quit, &ev) => [Message::Exit].into(),
+                _ => SmallVec::new(),
+            },
+            // typing into a filter comes before any keys bound to characters
+            _ if self.editing_filter.as_ref().is_some_and(Filter::is_typed) => match ev {
+                CEvent::Key(key_event) if key_event.is_press() && key_event.code.is_backspace() => {
+                    [Message::Backspace].into()
+                }
+                CEvent::Key(key_event) if key_event.is_press() && key_event.code.is_enter() => {
+                    [Message::FinishFilter].into()
+                }
+                CEvent::Key(key_event) if key_event.is_press() && key_event.code.is_esc() => {
+                    [Message::CancelFilter].into()
+                }
+                CEvent::Key(key_event) if key_event.is_press() && key_event.code.as_char().is_some() => {
+                    [Message::KeyTyped(key_event.code.as_char().expect("verified is_some() in condition"))].into()
+                }
+                _ if bound(&keys.scroll_down, &ev) => [Message::ScrollDown].into(),
+                _ if bound(&keys.scroll_up, &ev) => [Message::ScrollUp].into(),
+                _ if bound(&keys.switch_compact_tab, &ev) => [Message::SwitchCompactTab].into(),
+                _ => SmallVec::new(),
+            },
+            _ if bound(&keys.scroll_down, &ev) => [Message::ScrollDown].into(),
+            _ if bound(&keys.scroll_up, &ev) => [Message::ScrollUp].into(),
+            _ if bound(&keys.switch_compact_tab, &ev) => [Message::SwitchCompactTab].into(),
+            _ if bound(&keys.quit, &ev) => [Message::Exit].into(),
+            _ if bound(&keys.tab_left, &ev) => [Message::TabLeft].into(),
+            _ if bound(&keys.tab_right, &ev) => [Message::TabRight].into(),
+            _ if bound(&keys.select, &ev) => [Message::Enter].into(),
+            _ if bound(&keys.next_event, &ev) => [Message::SelectNext].into(),
+            _ if bound(&keys.previous_event, &ev) => [Message::SelectPrevious].into(),
+            _ if bound(&keys.annotate, &ev) => [Message::Annotate].into(),
+            _ if bound(&keys.edit, &ev) => [Message::Edit].into(),
+            _ if bound(&keys.delete, &ev) => [Message::Delete].into(),
+            _ if bound(&keys.filters, &ev) => [Message::FocusFilters].into(),
+            _ if bound(&keys.clear_filters, &ev) => [Message::ClearFilters].into(),
+            _ if bound(&keys.next_day, &ev) => [Message::JumpDay(1)].into(),
+            _ if bound(&keys.previous_day, &ev) => [Message::JumpDay(-1)].into(),
+            _ if bound(&keys.percentages, &ev) => [Message::TogglePercentages].into(),
+            _ if bound(&keys.sparkline, &ev) => [Message::ToggleSparkline].into(),
+            _ if bound(&keys.chart, &ev) => [Message::ToggleChart].into(),
+            _ if bound(&keys.large_layout, &ev) => [Message::ToggleLargeLayout].into(),
+            _ if bound(&keys.week_grouping, &ev) => [Message::ToggleWeekGrouping].into(),
+            _ if bound(&keys.export, &ev) => [Message::ExportView].into(),
+            _ if bound(&keys.palette, &ev) => [Message::OpenPalette].into(),
+            _ if bound(&keys.switch_profile, &ev) => [Message::SwitchProfile].into(),
+            CEvent::Mouse(MouseEvent { kind: MouseEventKind::ScrollDown, .. }) => [Message::ScrollDown].into(),
+            CEvent::Mouse(MouseEvent { kind: MouseEventKind::ScrollUp, .. }) => [Message::ScrollUp].into(),
+            CEvent::Mouse(MouseEvent { kind: MouseEventKind::Down(MouseButton::Left), column, row, .. }) => self
+                .header_areas
+                .iter()
+                .find(|(area, _)| area.contains((column, row).into()))
+                .map(|&(_, click)| match click {
+                    HeaderClick::Button(i) => Message::PressHeader(i),
+                    HeaderClick::Previous => Message::TabLeft,
+                    HeaderClick::Next => Message::TabRight,
+                })
+                .into_iter()
+                .collect(),
+            CEvent::Key(key_event)
+                if key_event.is_press()
+                    && key_event.code.as_char().is_some_and(|c| ('1'..='9').contains(&c)) =>
+            {
+                let c = key_event.code.as_char().expect("verified is_some_and() in condition");
+                [Message::QuickFilter(c as usize - '1' as usize)].into()
             }
+            _ => SmallVec::new(),
         }
     }
 
     fn render(&mut self, frame: &mut Frame) {
-        let events_chunked = self
-            .events
-            .iter()
-            .filter(|ev| (&self.applied_filters, &self.editing_filter).filter(ev))
-            .chunk_by(|ev| ev.date);
+        let filtered_positions = self.filtered_positions();
+        let filtered_events: Vec<&Event> =
+            filtered_positions.iter().map(|&i| &self.events[i]).collect();
+        // days, or the Monday of each week
+        let group_start = |date: NaiveDate| {
+            if self.group_by_week { date.week(Weekday::Mon).first_day() } else { date }
+        };
+        let events_chunked = filtered_positions.iter().chunk_by(|&&i| group_start(self.events[i].date));
+        let large = self.large_layout;
+        // dimmed text is hard to read on projectors and for low-vision users
+        let dim = |style: Style| if large { style } else { THEME.dim(style) };
 
-        let events_lines: Vec<Line> =
-            events_chunked
-                .into_iter()
-                .flat_map(|(date, group)| {
-                    let (group1, group2): (Vec<_>, Vec<_>) = group.map(|e| (e, e)).unzip();
-                    let duration: TimeDelta = group1
-                        .into_iter()
-                        .map(|ev| ev.end_time - ev.start_time)
-                        .sum();
-                    iter::once(Line::default().spans(vec![
-                        Span::raw("------ "),
-                        Span::styled(date.to_string(), Style::new().bold()),
-                        Span::raw(" ("),
-                        Span::styled(duration_to_string(&duration), Style::new().yellow()),
-                        Span::raw(") ------"),
-                    ]))
-                    .chain(self.daily_notes.get(&date).map(|s| {
-                        Line::styled(format!("[{s}]"), Style::new().cyan().dim().italic())
-                    }))
-                    .chain(group2.into_iter().flat_map(|ev| {
-                        let duration = ev.end_time - ev.start_time;
-                        [
-                            // Line::raw(format!("{}: {}-{}", ev.date, ev.start_time, ev.end_time)),
-                            Line::default().spans(vec![
-                                Span::styled(
-                                    format!("{}-{} ", ev.start_time, ev.end_time),
-                                    Style::new().bold(),
-                                ),
-                                Span::styled(duration_to_string(&duration), Style::new().dim()),
-                            ]),
-                            Line::default().spans(vec![
-                                Span::styled(ev.category.own(), Style::new().blue().bold()),
-                                Span::from(" - "),
-                                ev.description.clone().into(),
-                            ]),
-                            Line::raw(""),
-                        ]
-                    }))
-                })
-                .collect();
-
-        let events_widget = Paragraph::new(events_lines)
-            .block(Block::bordered())
-            .scroll((self.scroll_position, 0))
-            .wrap(Default::default());
+        let mut events_lines: Vec<Line> = vec![];
+        // line on which the selected event starts, so we can keep it in view
+        let mut selected_line = None;
+        // each day's (or week's) header and the line it's on, so the one for the day being
+        // scrolled through can stay at the top
+        let mut day_headers: Vec<(usize, Line)> = vec![];
+        let mut day_starts = vec![];
+        for (start, group) in &events_chunked {
+            let group: Vec<usize> = group.copied().collect();
+            let duration: Duration = group
+                .iter()
+                .map(|&i| self.events[i].end_time - self.events[i].start_time)
+                .sum();
+            let title = if self.group_by_week {
+                format!("Week {}, {}", start.iso_week().week(), display_date(start))
+            } else {
+                display_date(start)
+            };
+            let header = Line::default().spans(vec![
+                Span::raw("------ "),
+                Span::styled(title, THEME.date),
+                Span::raw(" ("),
+                Span::styled(duration.to_string(), THEME.duration),
+                Span::raw(") ------"),
+            ]);
+            day_headers.push((events_lines.len(), header.clone()));
+            day_starts.push((events_lines.len() as u16, group[0]));
+            events_lines.push(header);
+            if large {
+                events_lines.push(Line::raw(""));
+            }
+            let mut previous_date = None;
+            for i in group {
+                let ev = &self.events[i];
+                if previous_date != Some(ev.date) {
+                    // weeks are split into days by their dates alone
+                    if self.group_by_week {
+                        events_lines.push(Line::styled(display_date(ev.date), dim(THEME.date)));
+                    }
+                    events_lines.extend(self.daily_notes.get(&ev.date).map(|s| {
+                        Line::styled(format!("[{s}]"), dim(THEME.note))
+                    }));
+                    previous_date = Some(ev.date);
+                }
+                let duration = ev.end_time - ev.start_time;
+                let selected = self.selected_event == Some(i);
+                if selected {
+                    selected_line = Some(events_lines.len());
+                }
+                // under the styles of the event's parts, so its category and selection still show
+                let highlight = THEME.event(ev);
+                events_lines.push(Line::default().style(highlight).spans(vec![
+                    Span::styled(
+                        if ev.pinned { "\u{2605} " } else { "" },
+                        THEME.highlight,
+                    ),
+                    Span::styled(
+                        format!("{}-{} ", ev.start_time, ev.end_time),
+                        if selected {
+                            Style::new().bold().patch(THEME.selected)
+                        } else {
+                            Style::new().bold()
+                        },
+                    ),
+                    Span::styled(duration.to_string(), dim(Style::new())),
+                ]));
+                let mut description_lines = self.description_lines(&ev.description).into_iter();
+                events_lines.push(Line::default().style(highlight).spans(
+                    [
+                        Span::styled(CONFIG.icons.label(ev.category.inner()), self.category_style(&ev.category).bold()),
+                        Span::from(" - "),
+                    ]
+                    .into_iter()
+                    .chain(description_lines.next().into_iter().flatten()),
+                ));
+                events_lines.extend(description_lines.map(|spans| Line::from(spans).style(highlight)));
+                events_lines.push(Line::raw(""));
+                if large {
+                    events_lines.push(Line::raw(""));
+                }
+            }
+        }
 
-        let filters_lines: Vec<Line> = self
+        let filter_style = |i: usize| {
+            if self.filter_cursor == Some(i) {
+                THEME.selected
+            } else {
+                Style::new()
+            }
+        };
+        let mut filters_lines: Vec<Line> = self
             .applied_filters
             .iter()
-            .map(ToString::to_string)
-            .chain(self.editing_filter.iter().map(|f| {
-                let cursor = if self.cursor_blink { "\u{2588}" } else { "" };
-                format!("(*) {f}{cursor}")
-            }))
-            .map(Line::raw)
+            .enumerate()
+            .map(|(i, f)| Line::styled(f.to_string(), filter_style(i)))
             .collect();
+        if let Some(f) = &self.editing_filter {
+            let cursor = if self.cursor_blink { "\u{2588}" } else { "" };
+            filters_lines.push(Line::raw(format!("(*) {f}{cursor}")));
+            if let Filter::DescriptionRegex(RegexFilter { compiled: Err(e), .. }) = f {
+                filters_lines.extend(
+                    e.to_string()
+                        .lines()
+                        .map(|l| Line::styled(l.to_owned(), THEME.error)),
+                );
+            }
+        }
+        self.day_starts = day_starts;
+        let filters_height = filters_lines.len() as u16 + 2;
+        if !large && !self.quick_categories.is_empty() {
+            filters_lines.push(Line::raw(""));
+            filters_lines.push(Line::styled("Quick filters", THEME.heading));
+            for (n, category) in self.quick_categories.iter().enumerate() {
+                let active = self.applied_filters.iter().any(|f| matches!(f, Filter::Category(c) if c == category));
+                filters_lines.push(Line::default().spans([
+                    Span::styled(format!("{} ", n + 1), Style::new().bold()),
+                    Span::styled(
+                        CONFIG.icons.label(category.inner()),
+                        if active {
+                            self.category_style(category).bold().patch(THEME.selected)
+                        } else {
+                            self.category_style(category)
+                        },
+                    ),
+                ]));
+            }
+        }
         let filters_widget = Paragraph::new(filters_lines)
             .block(Block::bordered())
             .wrap(Default::default());
 
-        let category_sums = self
-            .events
+        let category_sums = filtered_events
             .iter()
-            .filter(|ev| (&self.applied_filters, &self.editing_filter).filter(ev))
             .fold(
                 self.categories
                     .iter()
-                    .map(|cat| (cat.inner(), TimeDelta::zero()))
-                    .collect::<BTreeMap<&str, TimeDelta>>(),
+                    .chain(&self.new_categories)
+                    .map(|cat| (cat.inner(), Duration::ZERO))
+                    .collect::<BTreeMap<&str, Duration>>(),
                 |mut map, ev| {
                     if let Some(t) = map.get_mut(ev.category.inner()) {
                         *t += ev.end_time - ev.start_time;
@@ -521,15 +1688,14 @@ impl<'a> framework::TuiState for State<'a> {
         // ...so similar in structure to category_sums code; we've gotta stop doing so much code duplication.
         // Also the loops should probably be merged so we don't end up re-iterating over the event
         // list a million times.
-        let tag_sums = self
-            .events
+        let tag_sums = filtered_events
             .iter()
-            .filter(|ev| (&self.applied_filters, &self.editing_filter).filter(ev))
             .fold(
                 self.tags
                     .iter()
-                    .map(|tag| (tag, TimeDelta::zero()))
-                    .collect::<BTreeMap<&Tag, TimeDelta>>(),
+                    .chain(&self.new_tags)
+                    .map(|tag| (tag, Duration::ZERO))
+                    .collect::<BTreeMap<&Tag, Duration>>(),
                 |mut map, ev| {
                     let tags = self
                         .tag_map
@@ -547,88 +1713,466 @@ impl<'a> framework::TuiState for State<'a> {
                 },
             );
 
+        let aggregation = &CONFIG.aggregation;
+        let total: Duration = filtered_events
+            .iter()
+            .filter(|ev| self.categories.contains(&ev.category) || self.new_categories.contains(&ev.category))
+            .filter(|ev| !aggregation.excludes(ev, self.tag_map.get(&ev.category)))
+            .map(|ev| ev.end_time - ev.start_time)
+            .sum();
+        let percentage = |duration: &Duration| {
+            if self.show_percentages && total > Duration::ZERO {
+                Span::styled(
+                    format!(" ({:.0}%)", duration.fraction_of(total) * 100.0),
+                    THEME.duration,
+                )
+            } else {
+                Span::raw("")
+            }
+        };
+        let excluded_marker = |excluded: bool| {
+            if excluded {
+                Span::styled(" (excluded)", dim(Style::new()))
+            } else {
+                Span::raw("")
+            }
+        };
+
+        // goals always cover this week, whatever the filters
+        let goals = goal_progress(
+            self.events.iter(),
+            self.tag_map,
+            self.category_goals,
+            self.tag_goals,
+            DateRange::week_of(Local::now().date_naive()),
+        );
+        let goal_lines = goals.iter().map(|(name, goal, time)| {
+            let name_style = if name.starts_with('#') {
+                THEME.tag.bold()
+            } else {
+                self.category_style(name).bold()
+            };
+            let bar_style = if goal.met(*time) {
+                THEME.good
+            } else if goal.max {
+                THEME.error
+            } else {
+                THEME.warning
+            };
+            Line::default().spans([
+                Span::styled(name.clone(), name_style),
+                Span::raw(" "),
+                Span::styled(progress_bar(time.fraction_of(goal.weekly()), 10), bar_style),
+                Span::raw(format!(
+                    " {} of {}{}",
+                    format_duration(time),
+                    if goal.max { "max " } else { "" },
+                    format_duration(&goal.weekly())
+                )),
+            ])
+        });
+        let goals_title = (!goals.is_empty())
+            .then(|| [Line::default(), Line::styled("Goals this week", THEME.heading)])
+            .into_iter()
+            .flatten();
+
         let aggregated_data_lines: Vec<Line> = iter::once(Line::styled(
             "Aggregated durations",
-            Style::new().bold().underlined(),
+            THEME.heading,
         ))
         .chain(iter::once(Line::default().spans([
-            Span::styled("all", Style::new().bold().green()),
+            Span::styled("all", THEME.total.bold()),
             Span::raw(": "),
-            Span::raw(duration_to_string(&category_sums.values().sum())),
+            Span::raw(total.to_string()),
         ])))
         .chain(category_sums.iter().map(|(cat, duration)| {
             // Line::raw(format!("{cat}: {duration_string}"))
             Line::default().spans([
-                Span::styled(cat.to_owned(), Style::new().bold().blue()),
+                Span::styled(CONFIG.icons.label(cat), self.category_style(cat).bold()),
                 Span::raw(": "),
-                Span::raw(duration_to_string(duration)),
+                Span::raw(duration.to_string()),
+                percentage(duration),
+                excluded_marker(aggregation.excludes_category(cat)),
             ])
         }))
         .chain(iter::once(Line::default()))
         .chain(tag_sums.iter().map(|(tag, dur)| {
             Line::default().spans([
-                Span::styled(tag.inner(), Style::new().bold().magenta()),
+                Span::styled(tag.inner(), THEME.tag.bold()),
                 Span::raw(": "),
-                Span::raw(duration_to_string(dur)),
+                Span::raw(dur.to_string()),
+                percentage(dur),
+                excluded_marker(aggregation.excludes_tag(tag)),
             ])
         }))
+        .chain(goals_title)
+        .chain(goal_lines)
         .collect();
         let aggregated_data_widget = Paragraph::new(aggregated_data_lines).block(Block::bordered());
 
+        // the same sums as bars, in minutes, which BarChart scales to the largest one
+        let bar = |label: String, style: Style, duration: &Duration, excluded: bool| {
+            Bar::default()
+                .label(Line::styled(label, if excluded { dim(style) } else { style }))
+                .value(duration.num_minutes().max(0) as u64)
+                .text_value(format!("{}{}", format_duration(duration), if excluded { " (excluded)" } else { "" }))
+                .style(if excluded { dim(style) } else { style })
+        };
+        let category_bars: Vec<Bar> = category_sums
+            .iter()
+            .map(|(cat, duration)| {
+                bar(CONFIG.icons.label(cat), self.category_style(cat), duration, aggregation.excludes_category(cat))
+            })
+            .collect();
+        let tag_bars: Vec<Bar> = tag_sums
+            .iter()
+            .map(|(tag, duration)| {
+                bar(tag.inner().to_owned(), THEME.tag, duration, aggregation.excludes_tag(tag))
+            })
+            .collect();
+        let chart_widget = BarChart::default()
+            .block(Block::bordered().title(format!("Aggregated durations (all: {total})")))
+            .direction(Direction::Horizontal)
+            .bar_width(1)
+            .bar_gap(0)
+            .group_gap(1)
+            .value_style(Style::new().bold())
+            .data(BarGroup::default().bars(&category_bars))
+            .data(BarGroup::default().bars(&tag_bars));
+
+        // daily totals over the 30 days up to the latest filtered event
+        let sparkline_end = filtered_events
+            .iter()
+            .map(|ev| ev.date)
+            .max()
+            .unwrap_or_else(|| Local::now().date_naive());
+        let totals = daily_totals(
+            filtered_events
+                .iter()
+                .copied()
+                .filter(|ev| !aggregation.excludes(ev, self.tag_map.get(&ev.category))),
+        );
+        let sparkline_data: Vec<u64> = (0..30)
+            .rev()
+            .map(|n| sparkline_end - Days::new(n))
+            .map(|date| totals.get(&date).map_or(0, |d| d.num_minutes().max(0) as u64))
+            .collect();
+        let sparkline_widget = Sparkline::default()
+            .block(Block::bordered().title(format!(
+                "30 days to {sparkline_end} (7-day avg {})",
+                rolling_average(&totals, self.skipped_days, sparkline_end, 7)
+            )))
+            .data(&sparkline_data)
+            .style(THEME.total);
+
+        // narrow terminals, like tmux splits, get one panel at a time instead of three squeezed ones
+        let compact = !large && frame.area().width < COMPACT_WIDTH;
         let outer_layout = Layout::default()
             .direction(Direction::Vertical)
-            .constraints(vec![
-                Constraint::Length(1),
-                Constraint::Fill(1),
-                Constraint::Length(1),
-            ])
+            .constraints(if compact {
+                vec![
+                    Constraint::Length(1),
+                    Constraint::Length(1),
+                    Constraint::Length(1),
+                    Constraint::Fill(1),
+                    Constraint::Length(1),
+                ]
+            } else {
+                vec![Constraint::Length(1), Constraint::Fill(1), Constraint::Length(1)]
+            })
             .split(frame.area());
-        let main_panel_layout = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([
-                Constraint::Fill(1),
-                Constraint::Fill(1),
-                Constraint::Fill(1),
-            ])
-            .split(outer_layout[1]);
-        let header_layout = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints(iter::repeat_n(Constraint::Length(15), HEADER.len() + 1))
-            .split(outer_layout[0]);
-        frame.render_widget(
-            "arrow keys for navigation - enter to select",
-            outer_layout[2],
-        );
+        let (main_area, hints_area) = (outer_layout[outer_layout.len() - 2], outer_layout[outer_layout.len() - 1]);
+        // where the filters, events, and aggregated durations go, if they're shown
+        let (filters_area, events_area, aggregation_area) = if compact {
+            if self.compact_totals {
+                (None, None, Some(main_area))
+            } else {
+                (None, Some(main_area), None)
+            }
+        } else if large {
+            // in the large layout, filters sit above the events (only while there are any) and the
+            // aggregated durations are left out, apart from the total in the events panel's title
+            let filters_height = if self.applied_filters.is_empty() && self.editing_filter.is_none() {
+                0
+            } else {
+                filters_height
+            };
+            let layout = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(filters_height), Constraint::Fill(1)])
+                .split(main_area);
+            (Some(layout[0]), Some(layout[1]), None)
+        } else {
+            let layout = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Fill(1),
+                    Constraint::Fill(1),
+                    Constraint::Fill(1),
+                ])
+                .split(main_area);
+            (Some(layout[0]), Some(layout[1]), Some(layout[2]))
+        };
+        // changes made here are only saved on quitting, so keep them in sight until then
+        let unsaved = if self.staged.is_empty() {
+            Span::raw("")
+        } else {
+            Span::styled(format!("Unsaved: {} | ", describe_changes(&self.staged).join(", ")), THEME.status.bold())
+        };
+        let (k, label) = (&CONFIG.keys, keys::label);
+        let navigate = [&k.scroll_up, &k.scroll_down, &k.tab_left, &k.tab_right]
+            .map(|keys| label(keys))
+            .join("/");
+        let navigate = if navigate == "up/down/left/right" { "arrows".to_owned() } else { navigate };
+        let hints = match &self.status {
+            Some(status) => Span::styled(status.as_str(), THEME.status),
+            None if self.filter_cursor.is_some() => Span::raw(format!(
+                "{}/{}: pick a filter - {}: remove it - {}: negate it - {}: OR it with the next - {}: remove all - esc: done",
+                label(&k.scroll_up),
+                label(&k.scroll_down),
+                label(&k.delete),
+                label(&k.negate_filter),
+                label(&k.or_filter),
+                label(&k.clear_filters),
+            )),
+            None if compact => Span::raw(format!(
+                "{}: events/totals - {navigate}: navigate - {}: select - {}/{}: select event - {}/{}: previous/next day - {}: edit - {}: delete - {}: commands",
+                label(&k.switch_compact_tab),
+                label(&k.select),
+                label(&k.next_event),
+                label(&k.previous_event),
+                label(&k.previous_day),
+                label(&k.next_day),
+                label(&k.edit),
+                label(&k.delete),
+                label(&k.palette),
+            )),
+            None => Span::raw(format!(
+                "{navigate}: navigate - {}: select - {}/{}: select event - {}/{}: previous/next day - {}/{}: remove filters - {}: annotate - {}: edit - {}: delete - {}: export - {}: percentages - {}: sparkline - {}: chart - 1-9: quick filters - {}: layout - {}: group by day/week - {}: switch profile - {}: commands",
+                label(&k.select),
+                label(&k.next_event),
+                label(&k.previous_event),
+                label(&k.previous_day),
+                label(&k.next_day),
+                label(&k.filters),
+                label(&k.clear_filters),
+                label(&k.annotate),
+                label(&k.edit),
+                label(&k.delete),
+                label(&k.export),
+                label(&k.percentages),
+                label(&k.sparkline),
+                label(&k.chart),
+                label(&k.large_layout),
+                label(&k.week_grouping),
+                label(&k.switch_profile),
+                label(&k.palette),
+            )),
+        };
+        frame.render_widget(Line::default().spans([unsaved, hints]), hints_area);
 
-        frame.render_widget(
-            Text::styled("Filters:", Style::new().bold()),
-            header_layout[0],
-        );
-        for (i, option) in HEADER.iter().enumerate() {
+        if let Some(line) = selected_line
+            && let Some(events_area) = events_area
+        {
+            // the event's time, category, and description lines should all be visible
+            let padding = if large { 2 } else { 0 };
+            let visible = events_area.height.saturating_sub(2 + padding);
+            let line = line as u16;
+            if line < self.scroll_position {
+                self.scroll_position = line;
+            } else if line + 3 > self.scroll_position + visible {
+                self.scroll_position = (line + 3).saturating_sub(visible);
+            }
+        }
+        let events_block = if large {
+            Block::bordered()
+                .padding(Padding::uniform(1))
+                .title(format!(" Total: {total} "))
+        } else {
+            Block::bordered()
+        };
+        let events_inner = events_area.map(|area| events_block.inner(area));
+        let events_widget = Paragraph::new(events_lines)
+            .block(events_block)
+            .scroll((self.scroll_position, 0))
+            .wrap(Default::default());
+
+        self.header_areas.clear();
+        if compact {
+            // only the highlighted header button fits, with the applied filters on one line below.
+            // Clicking the arrows either side of it moves between buttons.
+            let header = outer_layout[0];
+            let label_width = HEADER[self.header_highlight].to_string().width() as u16;
+            let at = |x: u16, width: u16| Rect { x: header.x + x, width, ..header }.intersection(header);
+            if self.header_highlight > 0 {
+                self.header_areas.push((at(9, 2), HeaderClick::Previous));
+            }
+            self.header_areas.push((at(11, label_width), HeaderClick::Button(self.header_highlight)));
+            if self.header_highlight + 1 < HEADER.len() {
+                self.header_areas.push((at(11 + label_width, 2), HeaderClick::Next));
+            }
             frame.render_widget(
-                Paragraph::new(Text::styled(
-                    option.to_string(),
-                    if self.header_highlight == i {
-                        Style::new().underlined()
-                    } else {
-                        Style::new()
-                    },
-                )),
-                header_layout[i + 1],
+                Line::default().spans([
+                    Span::styled("Filters: ", Style::new().bold()),
+                    Span::raw(if self.header_highlight > 0 { "< " } else { "  " }),
+                    Span::styled(HEADER[self.header_highlight].to_string(), Style::new().underlined()),
+                    Span::raw(if self.header_highlight + 1 < HEADER.len() { " >" } else { "" }),
+                ]),
+                outer_layout[0],
+            );
+            let cursor = if self.cursor_blink { "\u{2588}" } else { "" };
+            let spans = self
+                .applied_filters
+                .iter()
+                .enumerate()
+                .map(|(i, f)| Span::styled(f.to_string(), filter_style(i)))
+                .chain(self.editing_filter.iter().map(|f| Span::raw(format!("(*) {f}{cursor}"))));
+            let mut summary = Line::default().spans(Itertools::intersperse(spans, Span::raw(" | ")));
+            match &self.editing_filter {
+                Some(Filter::DescriptionRegex(RegexFilter { compiled: Err(_), .. })) => {
+                    summary.push_span(Span::styled(" (invalid regex)", THEME.error));
+                }
+                _ if summary.spans.is_empty() => summary = Line::styled("no filters", dim(Style::new())),
+                _ => {}
+            }
+            frame.render_widget(summary, outer_layout[1]);
+            frame.render_widget(
+                Tabs::new(["Events", "Totals"]).select(usize::from(self.compact_totals)),
+                outer_layout[2],
+            );
+        } else {
+            let header_layout = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(
+                    iter::once("Filters:".to_owned())
+                        .chain(HEADER.iter().map(ToString::to_string))
+                        .map(|label| Constraint::Length(label.width() as u16 + 2)),
+                )
+                .split(outer_layout[0]);
+            frame.render_widget(
+                Text::styled("Filters:", Style::new().bold()),
+                header_layout[0],
+            );
+            for (i, option) in HEADER.iter().enumerate() {
+                self.header_areas.push((header_layout[i + 1], HeaderClick::Button(i)));
+                frame.render_widget(
+                    Paragraph::new(Text::styled(
+                        option.to_string(),
+                        if self.header_highlight == i {
+                            Style::new().underlined()
+                        } else {
+                            Style::new()
+                        },
+                    )),
+                    header_layout[i + 1],
+                );
+            }
+        }
+        if let Some(area) = filters_area {
+            frame.render_widget(filters_widget, area);
+        }
+        if let Some(area) = events_area {
+            frame.render_widget(events_widget, area);
+        }
+        // pinned once the day's own header has scrolled out of view
+        let scroll = self.scroll_position as usize;
+        if let Some(inner) = events_inner
+            && let Some((line, header)) = day_headers.iter().rev().find(|(line, _)| *line <= scroll)
+            && *line < scroll
+        {
+            let sticky_area = Rect { height: inner.height.min(1), ..inner };
+            frame.render_widget(ClearWidget, sticky_area);
+            frame.render_widget(header.clone(), sticky_area);
+        }
+        if let Some(area) = aggregation_area {
+            let totals_area = if self.show_sparkline {
+                let aggregation_layout = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(6), Constraint::Fill(1)])
+                    .split(area);
+                frame.render_widget(sparkline_widget, aggregation_layout[0]);
+                aggregation_layout[1]
+            } else {
+                area
+            };
+            if self.show_chart {
+                frame.render_widget(chart_widget, totals_area);
+            } else {
+                frame.render_widget(aggregated_data_widget, totals_area);
+            }
+        }
+
+        if let Some(palette) = &self.palette {
+            let matches = palette.matches();
+            let cursor = if self.cursor_blink { "\u{2588}" } else { "" };
+            let palette_lines: Vec<Line> = iter::once(Line::raw(format!(": {}{cursor}", palette.query)))
+                .chain(matches.iter().enumerate().map(|(n, (command, matched))| {
+                    let style = if n == palette.highlight { THEME.selected } else { Style::new() };
+                    let name = command.name();
+                    let chunks = name.chars().enumerate().chunk_by(|(i, _)| matched.contains(i));
+                    let spans: Vec<Span> = chunks
+                        .into_iter()
+                        .map(|(highlighted, chunk)| {
+                            let text: String = chunk.map(|(_, c)| c).collect();
+                            Span::styled(text, if highlighted { style.patch(THEME.highlight) } else { style })
+                        })
+                        .collect();
+                    Line::default().spans(spans)
+                }))
+                .collect();
+            let area = frame.area();
+            let width = area.width.min(50);
+            let height = area.height.min(palette_lines.len() as u16 + 2);
+            let palette_area = Rect::new(
+                (area.width - width) / 2,
+                area.height.saturating_sub(height) / 3,
+                width,
+                height,
+            );
+            frame.render_widget(ClearWidget, palette_area);
+            frame.render_widget(
+                Paragraph::new(palette_lines).block(Block::bordered().title("Commands")),
+                palette_area,
+            );
+        }
+
+        if let Some((purpose, calendar)) = &self.date_picker {
+            let title = match purpose {
+                DatePurpose::StartFilter => "Start date filter",
+                DatePurpose::EndFilter => "End date filter",
+                DatePurpose::Jump => "Jump to date",
+            };
+            let lines = calendar.lines();
+            let area = frame.area();
+            // also just wide enough for the key hint
+            let width = area.width.min(calendar::WIDTH + 4);
+            let height = area.height.min(lines.len() as u16 + 2);
+            let calendar_area = Rect::new(
+                (area.width - width) / 2,
+                area.height.saturating_sub(height) / 3,
+                width,
+                height,
+            );
+            frame.render_widget(ClearWidget, calendar_area);
+            frame.render_widget(
+                Paragraph::new(lines).block(
+                    Block::bordered()
+                        .padding(Padding::horizontal(1))
+                        .title(title)
+                        .title_bottom(Line::styled(" [/]: month, t: today ", THEME.dim(Style::new()))),
+                ),
+                calendar_area,
             );
         }
-        frame.render_widget(filters_widget, main_panel_layout[0]);
-        frame.render_widget(events_widget, main_panel_layout[1]);
-        frame.render_widget(aggregated_data_widget, main_panel_layout[2]);
     }
 
     fn get_output(self) -> Self::Output {
-        vec![]
+        (self.staged, self.filters_used, self.switch_to)
     }
 }
 
-pub fn filter_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
-    let mut events = save_data.events.clone();
+/// Sorts events newest first, the order they're listed in
+fn sort_events(events: &mut [Event]) {
     events.sort_by_key(|e| {
         -NaiveDateTime::new(
             e.date,
@@ -639,19 +2183,82 @@ pub fn filter_main(save_data: SaveData) -> TaskitResult<Vec<DeltaItem>> {
         .and_utc()
         .timestamp()
     });
+}
+
+pub fn filter_main(
+    save_data: SaveData,
+    save_path: &Path,
+    contains_regex: Option<String>,
+    safe_mode: bool,
+    crash_log: &Path,
+    data_dir: &Path,
+    profile: Option<&str>,
+) -> TaskitResult<(Vec<DeltaItem>, Option<Option<String>>)> {
+    if safe_mode || safe::offer(crash_log)? {
+        safe::safe_main(&save_data, crash_log)?;
+        return Ok((vec![], None));
+    }
+    let mut applied_filters = vec![];
+    if let Some(pattern) = contains_regex {
+        let regex = RegexFilter::new(pattern);
+        if let Err(e) = &regex.compiled {
+            return Err(Kind::InvalidRegex(e.to_string()).with(Source::SettingFilter));
+        }
+        applied_filters.push(Filter::DescriptionRegex(regex));
+    }
+    safe::starting(crash_log)?;
+    let mut events = save_data.events.clone();
+    sort_events(&mut events);
+    let quick_categories = category_totals(events.iter())
+        .into_iter()
+        .filter_map(|(name, _)| save_data.categories.find(&name).cloned())
+        .take(9)
+        .collect();
+    let filters_used = applied_filters.iter().map(ToString::to_string).collect();
+    let index = TextIndex::load(save_path, &save_data);
     let state = State {
+        save_data: &save_data,
         categories: &save_data.categories,
         archived_categories: &save_data.archived_categories,
+        category_details: &save_data.category_details,
+        category_goals: &save_data.category_goals,
         events,
+        index,
+        selected_event: None,
+        staged: vec![],
+        new_tags: vec![],
+        new_categories: vec![],
+        show_percentages: false,
+        show_sparkline: false,
+        show_chart: false,
+        large_layout: CONFIG.prefs.large_layout,
+        group_by_week: false,
+        compact_totals: false,
         scroll_position: 0,
+        day_starts: vec![],
+        header_areas: vec![],
         header_highlight: 0,
-        applied_filters: vec![],
+        applied_filters,
+        filter_cursor: None,
         editing_filter: None,
+        quick_categories,
+        palette: None,
+        date_picker: None,
+        status: None,
+        filters_used,
         tags: &save_data.tags,
         tag_map: &save_data.tag_map,
+        tag_goals: &save_data.tag_goals,
         daily_notes: &save_data.daily_notes,
+        skipped_days: &save_data.skipped_days,
+        data_dir,
+        profile,
+        switch_to: None,
         cursor_blink: true,
         last_cursor_show_time: Instant::now(),
     };
-    state.run()
+    let (staged, filters_used, switch_to) = state.run()?;
+    safe::finished(crash_log)?;
+    print_session_summary(&save_data, &staged, &filters_used);
+    Ok((staged, switch_to))
 }
@@ -5,7 +5,7 @@ use std::{
 use crossterm::event::{Event as CEvent, KeyModifiers};
 use ratatui::{
     layout::{Constraint, Direction as LDirection, Layout},
-    style::{Color, Style, Stylize},
+    style::{Style, Stylize},
     text::Text,
     widgets::{Block, BorderType, List, ListState},
 };
@@ -13,7 +13,7 @@ use smallvec::SmallVec;
 
 use crate::{
     common::{DeltaItem, SaveData, error::TaskitResult, invariants::{Category, Tag}},
-    tui::framework::{self, TuiState}, util::SetVec,
+    tui::{framework::{self, TuiState}, theme::THEME}, util::SetVec,
 };
 
 type Extrinsic<'a> = framework::Extrinsic<State<'a>>;
@@ -71,9 +71,9 @@ impl Direction {
 impl Column {
     fn hl_style(&self, focused: Self) -> Style {
         if self == &focused {
-            Style::new().bg(Color::Blue)
+            THEME.selected
         } else {
-            Style::new().bg(Color::White).fg(Color::Black)
+            THEME.dim(THEME.selected)
         }
     }
 }
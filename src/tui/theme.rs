@@ -0,0 +1,193 @@
+//! The styles the TUIs draw with, from `[theme]` in the config
+
+use std::{str::FromStr, sync::LazyLock};
+
+use ratatui::style::{Color, Style, Stylize};
+use regex::Regex;
+
+use crate::common::{
+    Duration, Event,
+    config::{CONFIG, Highlight, Theme as ThemeConfig, ThemeBase},
+};
+
+pub static THEME: LazyLock<Theme> = LazyLock::new(|| Theme::from_config(&CONFIG.theme, &CONFIG.highlights));
+
+pub struct Theme {
+    pub category: Style,
+    pub tag: Style,
+    pub heading: Style,
+    pub date: Style,
+    pub duration: Style,
+    pub note: Style,
+    pub highlight: Style,
+    pub selected: Style,
+    pub total: Style,
+    pub good: Style,
+    pub warning: Style,
+    pub error: Style,
+    pub status: Style,
+    pub dim: bool,
+    pub category_colors: bool,
+    /// From `[[highlights]]`, leaving out the ones that can't be used
+    rules: Vec<Rule>,
+}
+
+/// A highlight rule with its regex compiled and its style parsed
+struct Rule {
+    contains: Option<String>,
+    regex: Option<Regex>,
+    category: Option<String>,
+    over: Option<Duration>,
+    style: Style,
+}
+
+impl Rule {
+    fn new(highlight: &Highlight) -> Option<Self> {
+        Some(Self {
+            contains: highlight.contains.clone(),
+            regex: match &highlight.regex {
+                Some(regex) => Some(Regex::new(regex).ok()?),
+                None => None,
+            },
+            category: highlight.category.clone(),
+            over: highlight.over_hours.map(|hours| Duration::seconds((hours * 60.0 * 60.0).round() as i64)),
+            style: parse_style(&highlight.style)?,
+        })
+    }
+
+    fn matches(&self, ev: &Event) -> bool {
+        self.contains.as_ref().is_none_or(|text| ev.description.contains(text.as_str()))
+            && self.regex.as_ref().is_none_or(|regex| regex.is_match(&ev.description))
+            && self.category.as_ref().is_none_or(|category| ev.category.inner() == category)
+            && self.over.is_none_or(|over| ev.end_time - ev.start_time > over)
+    }
+}
+
+impl Theme {
+    fn base(base: ThemeBase) -> Self {
+        match base {
+            ThemeBase::Default => Self {
+                category: Style::new().blue(),
+                tag: Style::new().magenta(),
+                heading: Style::new().bold().underlined(),
+                date: Style::new().bold(),
+                duration: Style::new().yellow(),
+                note: Style::new().cyan().italic(),
+                highlight: Style::new().yellow().bold(),
+                selected: Style::new().reversed(),
+                total: Style::new().green(),
+                good: Style::new().green(),
+                warning: Style::new().yellow(),
+                error: Style::new().red(),
+                status: Style::new().yellow(),
+                dim: true,
+                category_colors: true,
+                rules: vec![],
+            },
+            ThemeBase::HighContrast => Self {
+                category: Style::new().light_blue(),
+                tag: Style::new().light_magenta(),
+                heading: Style::new().bold().underlined(),
+                date: Style::new().bold(),
+                duration: Style::new().light_yellow(),
+                note: Style::new().light_cyan().italic(),
+                highlight: Style::new().black().on_light_yellow().bold(),
+                selected: Style::new().bold().reversed(),
+                total: Style::new().light_green(),
+                good: Style::new().light_green(),
+                warning: Style::new().light_yellow(),
+                error: Style::new().light_red().bold(),
+                status: Style::new().light_yellow().bold(),
+                dim: false,
+                category_colors: true,
+                rules: vec![],
+            },
+            ThemeBase::NoColor => Self {
+                category: Style::new(),
+                tag: Style::new().italic(),
+                heading: Style::new().bold().underlined(),
+                date: Style::new().bold(),
+                duration: Style::new(),
+                note: Style::new().italic(),
+                highlight: Style::new().bold().underlined(),
+                selected: Style::new().reversed(),
+                total: Style::new().bold(),
+                good: Style::new(),
+                warning: Style::new(),
+                error: Style::new().bold(),
+                status: Style::new().bold(),
+                dim: true,
+                category_colors: false,
+                rules: vec![],
+            },
+        }
+    }
+
+    fn from_config(config: &ThemeConfig, highlights: &[Highlight]) -> Self {
+        let mut theme = Self::base(config.base);
+        for (style, custom) in [
+            (&mut theme.category, &config.category),
+            (&mut theme.tag, &config.tag),
+            (&mut theme.heading, &config.heading),
+            (&mut theme.date, &config.date),
+            (&mut theme.duration, &config.duration),
+            (&mut theme.note, &config.note),
+            (&mut theme.highlight, &config.highlight),
+            (&mut theme.selected, &config.selected),
+            (&mut theme.total, &config.total),
+            (&mut theme.good, &config.good),
+            (&mut theme.warning, &config.warning),
+            (&mut theme.error, &config.error),
+            (&mut theme.status, &config.status),
+        ] {
+            if let Some(parsed) = custom.as_deref().and_then(parse_style) {
+                *style = parsed;
+            }
+        }
+        theme.dim = config.dim.unwrap_or(theme.dim);
+        theme.category_colors = config.category_colors.unwrap_or(theme.category_colors);
+        theme.rules = highlights.iter().filter_map(Rule::new).collect();
+        theme
+    }
+
+    /// A category in its own color if it has one and the theme shows them, and otherwise in the
+    /// theme's style for categories
+    pub fn category(&self, color: Option<Color>) -> Style {
+        match color {
+            Some(color) if self.category_colors => Style::new().fg(color),
+            _ => self.category,
+        }
+    }
+
+    /// The style of every highlight rule that `ev` matches, each over the ones before
+    pub fn event(&self, ev: &Event) -> Style {
+        self.rules
+            .iter()
+            .filter(|rule| rule.matches(ev))
+            .fold(Style::new(), |style, rule| style.patch(rule.style))
+    }
+
+    /// `style` dimmed, if the theme dims secondary text
+    pub fn dim(&self, style: Style) -> Style {
+        if self.dim { style.dim() } else { style }
+    }
+}
+
+/// Parses a style like "bold yellow" or "black on white". An empty one is plain text.
+fn parse_style(s: &str) -> Option<Style> {
+    let mut style = Style::new();
+    let mut words = s.split_whitespace();
+    while let Some(word) = words.next() {
+        style = match word.to_lowercase().as_str() {
+            "bold" => style.bold(),
+            "dim" => style.dim(),
+            "italic" => style.italic(),
+            "underlined" => style.underlined(),
+            "reversed" => style.reversed(),
+            "crossed-out" => style.crossed_out(),
+            "on" => style.bg(Color::from_str(words.next()?).ok()?),
+            color => style.fg(Color::from_str(color).ok()?),
+        };
+    }
+    Some(style)
+}
@@ -1,4 +1,10 @@
-use std::ops::Deref;
+use std::{fmt::{Display, Write as _}, ops::Deref, str::FromStr};
+
+use chrono::{Datelike, Days, Local, Months, NaiveDate};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::common::config::CONFIG;
 
 /// A SetVec is a Vec that enforces the guarantee that no elements will be duplicated. It doesn't
 /// need anything faster than O(n) for most operations because we don't expect it to ever have 
@@ -25,6 +31,17 @@ impl<T: PartialEq> SetVec<T> {
         }
     }
 
+    /// Insert an element at `index`, shifting the ones after it along, if it is not already
+    /// contained within. Return Ok(()) if it was inserted, Err(el) if it wasn't.
+    pub fn insert(&mut self, index: usize, el: T) -> Result<(), T> {
+        if self.0.contains(&el) {
+            Err(el)
+        } else {
+            self.0.insert(index, el);
+            Ok(())
+        }
+    }
+
     pub fn remove(&mut self, el: &T) -> Option<T> {
         self.index_of(el).map(|n| self.0.remove(n))
     }
@@ -57,7 +74,7 @@ impl<T: PartialEq> SetVec<T> {
         self.0.retain(pred)
     }
 
-    fn index_of(&self, el: &T) -> Option<usize> {
+    pub fn index_of(&self, el: &T) -> Option<usize> {
         self.0.iter().enumerate().find(|(_, t)| *t == el).map(|(n, _)| n)
     }
 }
@@ -100,3 +117,148 @@ impl<T> IntoIterator for SetVec<T> {
         self.0.into_iter()
     }
 }
+
+/// Escapes text for inclusion in HTML or SVG documents
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Lowercase hex, two digits per byte
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, b| {
+        let _ = write!(out, "{b:02x}");
+        out
+    })
+}
+
+/// Pads `s` with spaces to take up `width` terminal columns. Unlike `format!("{s:width$}")`, this
+/// counts wide characters (like most CJK characters and emoji) as two columns.
+pub fn pad_to_width(s: &str, width: usize) -> String {
+    format!("{s}{}", " ".repeat(width.saturating_sub(s.width())))
+}
+
+/// Removes the last user-perceived character of `s`, so that backspacing over an emoji or an
+/// accented letter removes all of it instead of leaving part of it behind
+#[cfg(feature = "tui")]
+pub fn pop_grapheme(s: &mut String) {
+    if let Some((i, _)) = s.grapheme_indices(true).next_back() {
+        s.truncate(i);
+    }
+}
+
+/// Splits `text` into user-perceived characters, each paired with whether any of its chars is
+/// `matched` (by char index, as returned by fuzzy matching). Highlighting whole graphemes keeps a
+/// match on part of one from splitting it.
+pub fn highlight_graphemes(text: &str, matched: impl Fn(usize) -> bool) -> impl Iterator<Item = (&str, bool)> {
+    let mut chars = 0;
+    text.graphemes(true).map(move |g| {
+        let start = chars;
+        chars += g.chars().count();
+        (g, (start..chars).any(&matched))
+    })
+}
+
+/// An inclusive range of dates. Parses from `today`, `yesterday`, `this-week`, `last-week`,
+/// `this-month`, `last-month`, `this-year`, `last-year`, a year (`YYYY`), a month (`YYYY-MM`), a
+/// single `YYYY-MM-DD` date, or `YYYY-MM-DD..YYYY-MM-DD`.
+/// Weeks start on Monday.
+#[derive(Clone, Copy, Debug)]
+pub struct DateRange {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl DateRange {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.start <= date && date <= self.end
+    }
+
+    pub fn week_of(date: NaiveDate) -> Self {
+        let start = date - Days::new(date.weekday().num_days_from_monday() as u64);
+        Self {
+            start,
+            end: start + Days::new(6),
+        }
+    }
+
+    pub fn month_of(date: NaiveDate) -> Self {
+        let start = date.with_day(1).expect("every month has a first day");
+        Self {
+            start,
+            end: start + Months::new(1) - Days::new(1),
+        }
+    }
+
+    /// The whole calendar year, or `None` if it's out of chrono's range
+    pub fn year(year: i32) -> Option<Self> {
+        Some(Self {
+            start: NaiveDate::from_ymd_opt(year, 1, 1)?,
+            end: NaiveDate::from_ymd_opt(year, 12, 31)?,
+        })
+    }
+}
+
+impl FromStr for DateRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let today = Local::now().date_naive();
+        let parse_date = |s: &str| {
+            NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d")
+                .map_err(|e| format!("invalid date {s:?}: {e}"))
+        };
+        Ok(match s.trim() {
+            "today" => Self { start: today, end: today },
+            "yesterday" => {
+                let yesterday = today - Days::new(1);
+                Self { start: yesterday, end: yesterday }
+            }
+            "this-week" => Self::week_of(today),
+            "last-week" => Self::week_of(today - Days::new(7)),
+            "this-month" => Self::month_of(today),
+            "last-month" => Self::month_of(today - Months::new(1)),
+            "this-year" => Self::year(today.year()).expect("the current year is in range"),
+            "last-year" => Self::year(today.year() - 1).expect("last year is in range"),
+            other if other.len() == 4 && other.chars().all(|c| c.is_ascii_digit()) => {
+                Self::year(other.parse().expect("four digits fit in an i32"))
+                    .ok_or_else(|| format!("invalid year {other:?}"))?
+            }
+            other if other.len() == 7 => {
+                let start = parse_date(&format!("{other}-01"))
+                    .map_err(|_| format!("invalid month {other:?} (expected YYYY-MM)"))?;
+                Self::month_of(start)
+            }
+            other => match other.split_once("..") {
+                Some((start, end)) => {
+                    let (start, end) = (parse_date(start)?, parse_date(end)?);
+                    if end < start {
+                        return Err(format!("range ends ({end}) before it starts ({start})"));
+                    }
+                    Self { start, end }
+                }
+                None => {
+                    let date = parse_date(other)?;
+                    Self { start: date, end: date }
+                }
+            },
+        })
+    }
+}
+
+impl Display for DateRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{} to {}", self.start, self.end)
+        }
+    }
+}
+
+/// A date in the format set by `date_format` under `[preferences]`, with its weekday by default
+pub fn display_date(date: NaiveDate) -> String {
+    date.format(CONFIG.prefs.date_format()).to_string()
+}
@@ -0,0 +1,54 @@
+//! Helpers shared by the integration tests
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command, Output},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A copy of the fixture save in its own temporary directory, removed when dropped. The copy keeps
+/// taskit from upgrading the fixture in place or writing its history next to it.
+pub struct Save {
+    pub dir: PathBuf,
+}
+
+impl Save {
+    pub fn new() -> Self {
+        static SAVES: AtomicUsize = AtomicUsize::new(0);
+        let dir = env::temp_dir().join(format!("taskit-tests-{}-{}", process::id(), SAVES.fetch_add(1, Ordering::Relaxed)));
+        fs::create_dir_all(&dir).expect("temporary directory should be writable");
+        fs::copy(fixtures().join("save.json"), dir.join("save.json")).expect("fixture save should be copyable");
+        Self { dir }
+    }
+
+    /// Runs taskit against this save and the fixture config
+    pub fn taskit(&self, args: &[&str]) -> Output {
+        self.taskit_on("save.json", args)
+    }
+
+    /// Runs taskit against the save file named `file` in this save's directory, e.g. after it was
+    /// moved to other storage
+    pub fn taskit_on(&self, file: &str, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_taskit-tracker"))
+            .arg("--save")
+            .arg(self.dir.join(file))
+            .arg("--config")
+            .arg(fixtures().join("config.toml"))
+            .args(args)
+            // icons fall back to ASCII depending on the locale
+            .env("LC_ALL", "C.UTF-8")
+            .output()
+            .expect("taskit should run")
+    }
+}
+
+impl Drop for Save {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
@@ -0,0 +1,69 @@
+//! Golden-file tests for `taskit export`. Each test exports the fixture save in one format and
+//! compares the result against a file in `tests/golden`. See "Export stability" in the README for
+//! what's allowed to change; to accept an intended change, run the tests with `UPDATE_GOLDEN=1`
+//! and review the diff of the golden files.
+
+mod common;
+
+use std::{env, fs, path::Path};
+
+use common::Save;
+
+/// Runs taskit against a copy of the fixture save and the fixture config, returning what it
+/// printed
+fn taskit(args: &[&str]) -> String {
+    let output = Save::new().taskit(args);
+    assert!(output.status.success(), "taskit {args:?} failed: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).expect("exports should be UTF-8")
+}
+
+fn check_golden(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).expect("golden file should be writable");
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|e| panic!("couldn't read {}: {e}", path.display()));
+    assert!(
+        actual == expected,
+        "export doesn't match {}; if the change is intended, rerun with UPDATE_GOLDEN=1\n--- expected\n{expected}\n--- actual\n{actual}",
+        path.display(),
+    );
+}
+
+#[test]
+fn csv() {
+    check_golden("events.csv", &taskit(&["export"]));
+}
+
+#[test]
+fn csv_all_fields() {
+    let fields = "date,start,end,duration,category,description,tags,pinned,source,location,billable";
+    check_golden("events_all_fields.csv", &taskit(&["export", "--include-private", "--fields", fields]));
+}
+
+#[test]
+fn markdown() {
+    check_golden("events.md", &taskit(&["export", "--format", "markdown"]));
+}
+
+#[test]
+fn jsonl() {
+    check_golden("events.jsonl", &taskit(&["export", "--format", "jsonl"]));
+}
+
+#[test]
+fn ics() {
+    // the timestamp of the export itself is the one thing allowed to differ between runs
+    let export = taskit(&["export", "ics"])
+        .split("\r\n")
+        .map(|line| if line.starts_with("DTSTAMP:") { "DTSTAMP:<export time>" } else { line })
+        .collect::<Vec<_>>()
+        .join("\r\n");
+    check_golden("events.ics", &export);
+}
+
+#[test]
+fn workdays() {
+    check_golden("workdays.csv", &taskit(&["export", "workdays", "--range", "2026-03"]));
+}
@@ -0,0 +1,87 @@
+//! Tests for `taskit import`, run against a copy of the fixture save: importing the same file again
+//! shouldn't duplicate events, and a file that can't be imported should leave the save alone.
+
+mod common;
+
+use std::{fs, process::Output};
+
+use common::Save;
+
+impl Save {
+    /// Writes `contents` to a file named `name` next to the save and imports it
+    fn import(&self, name: &str, contents: &str) -> Output {
+        let file = self.dir.join(name);
+        fs::write(&file, contents).expect("import file should be writable");
+        self.taskit(&["import", file.to_str().expect("temporary paths should be UTF-8"), "--source", "test"])
+    }
+
+    fn events(&self) -> String {
+        let output = self.taskit(&["export", "--format", "jsonl"]);
+        assert!(output.status.success(), "export failed: {}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).expect("exports should be UTF-8")
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "import failed: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn repeated_id_is_rejected() {
+    let save = Save::new();
+    let before = save.events();
+    let output = save.import(
+        "events.csv",
+        "id,date,start,end,category,description\n\
+         x1,2024-05-06,09:00,10:00,coding,first\n\
+         x1,2024-05-06,11:00,12:00,coding,second\n",
+    );
+    assert_eq!(output.status.code(), Some(1), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("line 3") && stderr.contains("line 2"), "unexpected error: {stderr}");
+    assert_eq!(save.events(), before);
+}
+
+#[test]
+fn reimporting_with_ids_changes_nothing() {
+    let save = Save::new();
+    let file = "id,date,start,end,category,description\n\
+                x1,2024-05-06,09:00,10:00,coding,first\n\
+                x2,2024-05-06,11:00,12:00,meetings,second\n";
+    assert!(stdout(&save.import("events.csv", file)).contains("Added 2 events, updated 0, and left 0 unchanged."));
+    let after_first = save.events();
+    assert!(stdout(&save.import("events.csv", file)).contains("Added 0 events, updated 0, and left 2 unchanged."));
+    assert_eq!(save.events(), after_first);
+}
+
+#[test]
+fn reimporting_without_ids_matches_by_time_and_category() {
+    let save = Save::new();
+    // the two identical rows are separate events, and stay two events on the next import
+    let file = "date,start,end,category,description\n\
+                2024-05-06,09:00,10:00,coding,first\n\
+                2024-05-06,09:00,10:00,coding,first\n\
+                2024-05-06,11:00,12:00,meetings,second\n";
+    assert!(stdout(&save.import("events.csv", file)).contains("Added 3 events"));
+    let after_first = save.events();
+    let changed = file.replace("second", "second renamed");
+    assert!(stdout(&save.import("events.csv", &changed)).contains("Added 0 events, updated 1, and left 2 unchanged."));
+    assert_eq!(save.events().lines().count(), after_first.lines().count());
+}
+
+#[test]
+fn updates_in_archived_categories_are_rejected() {
+    let save = Save::new();
+    let file = "id,date,start,end,category,description\n\
+                x1,2024-05-06,09:00,10:00,coding,first\n";
+    stdout(&save.import("events.csv", file));
+    let archived = save.taskit(&["archive", "coding"]);
+    assert!(archived.status.success(), "archive failed: {}", String::from_utf8_lossy(&archived.stderr));
+    // the same file again is fine, since nothing in it changes
+    assert!(stdout(&save.import("events.csv", file)).contains("left 1 unchanged."));
+    let before = save.events();
+    let output = save.import("events.csv", &file.replace("first", "first renamed"));
+    assert_eq!(output.status.code(), Some(1), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(save.events(), before);
+}
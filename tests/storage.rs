@@ -0,0 +1,35 @@
+//! Tests for `taskit migrate-storage`, run against a copy of the fixture save: a save moved into
+//! SQLite and back should read the same as the one it came from.
+
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use std::process::Output;
+
+use common::Save;
+
+fn stdout(output: Output) -> String {
+    assert!(output.status.success(), "taskit failed: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).expect("output should be UTF-8")
+}
+
+#[test]
+fn sqlite_round_trip() {
+    let save = Save::new();
+    let events = stdout(save.taskit(&["export", "--format", "jsonl"]));
+    stdout(save.taskit(&["migrate-storage"]));
+    assert!(save.dir.join("save.json.migrated").exists());
+    assert_eq!(stdout(save.taskit_on("save.sqlite", &["export", "--format", "jsonl"])), events);
+
+    stdout(save.taskit_on("save.sqlite", &["migrate-storage", "--to", "json"]));
+    assert_eq!(stdout(save.taskit(&["export", "--format", "jsonl"])), events);
+}
+
+#[test]
+fn migrating_to_the_same_storage_fails() {
+    let save = Save::new();
+    let output = save.taskit(&["migrate-storage", "--to", "json"]);
+    assert_eq!(output.status.code(), Some(1), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(save.dir.join("save.json").exists());
+}